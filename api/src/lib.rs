@@ -1,8 +1,10 @@
-use std::{fmt::Display, str::FromStr};
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
 
 use bitcoin::Transaction;
 use serde::{de::Visitor, Deserialize, Serialize};
 
+pub mod lnd;
+
 pub const API_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub mod routes {
@@ -19,6 +21,8 @@ pub mod routes {
     pub const ESTIMATE_CHANNEL_LIQUIDITY: &str = "/v1/estimateChannelLiquidity";
     /// Websocket
     pub const WEBSOCKET: &str = "/v1/ws";
+    /// The OpenAPI document of this API.
+    pub const OPENAPI: &str = "/v1/openapi.json";
 
     /// List on chain and channel funds
     pub const LIST_FUNDS: &str = "/v1/listFunds";
@@ -51,6 +55,12 @@ pub mod routes {
     pub const LIST_FORWARDS: &str = "/v1/channel/listForwards";
     /// Fetch our channel history.
     pub const LIST_CHANNEL_HISTORY: &str = "/v1/channel/history";
+    /// Routing stats of the forwards per channel and per peer.
+    pub const FORWARD_STATS: &str = "/v1/channel/forwardStats";
+    /// Get or update the policy for accepting inbound channels.
+    pub const CHANNEL_ACCEPTANCE_POLICY: &str = "/v1/channel/acceptancePolicy";
+    /// Export an encrypted static channel backup.
+    pub const CHANNEL_BACKUP: &str = "/v1/channel/backup";
 
     /// --- Network ---
     /// Look up a node on the network.
@@ -79,17 +89,120 @@ pub mod routes {
     pub const PAY_INVOICE: &str = "/v1/pay";
     /// List payments.
     pub const LIST_PAYMENTS: &str = "/v1/pay/listPayments";
+    /// Probe the route to a node or invoice without paying.
+    pub const PROBE: &str = "/v1/pay/probe";
+    /// Status of a payment by its ID.
+    pub const GET_PAYMENT: &str = "/v1/pay/:id";
+    /// Status of a payment and every path that was tried for it.
+    pub const PAYMENT_DETAIL: &str = "/v1/pay/:id/detail";
 
     /// --- Invoices ---
     /// Generate a bolt11 invoice.
     pub const GENERATE_INVOICE: &str = "/v1/invoice/genInvoice";
     /// List the invoices on the node
     pub const LIST_INVOICES: &str = "/v1/invoice/listInvoices";
+    /// Generate a bolt11 hold invoice for a payment hash.
+    pub const GENERATE_HOLD_INVOICE: &str = "/v1/invoice/genHoldInvoice";
+    /// Settle a held invoice with its preimage.
+    pub const SETTLE_HOLD_INVOICE: &str = "/v1/invoice/settleHoldInvoice";
+    /// Cancel a hold invoice and fail any held payment back.
+    pub const CANCEL_HOLD_INVOICE: &str = "/v1/invoice/cancelHoldInvoice";
     /// Decode invoice
     pub const DECODE_INVOICE: &str = "/v1/utility/decode/:invoice";
+
+    /// --- Offers ---
+    /// Create a bolt12 offer.
+    pub const CREATE_OFFER: &str = "/v1/offers/createOffer";
+    /// List the bolt12 offers and refunds that we have created or paid.
+    pub const LIST_OFFERS: &str = "/v1/offers/listOffers";
+    /// Pay a bolt12 offer.
+    pub const PAY_OFFER: &str = "/v1/offers/payOffer";
+    /// Create a bolt12 refund that we pay when the recipient sends an invoice for it.
+    pub const CREATE_REFUND: &str = "/v1/offers/createRefund";
+    /// Send an invoice for a bolt12 refund so that we get paid.
+    pub const REQUEST_REFUND_PAYMENT: &str = "/v1/offers/requestRefundPayment";
+    /// List the invoice requests sent for the offers that we pay.
+    pub const LIST_INVOICE_REQUESTS: &str = "/v1/offers/listInvoiceRequests";
+
+    /// --- Liquidity ---
+    /// The opening fee parameters offered to LSPS2 clients for just in time channels.
+    pub const JIT_CHANNEL_OFFER: &str = "/v1/lsps2/offer";
+    /// List the just in time channels sold to LSPS2 clients.
+    pub const LIST_JIT_CHANNELS: &str = "/v1/lsps2/listSessions";
+
+    /// --- Webhooks ---
+    /// List the registered webhooks or register a new one.
+    pub const WEBHOOKS: &str = "/v1/webhooks";
+    /// Remove a webhook, its pending deliveries are dropped.
+    pub const DELETE_WEBHOOK: &str = "/v1/webhooks/:id";
+
+    /// --- Macaroons ---
+    /// Bake a macaroon with fewer permissions than the one used for the request.
+    pub const BAKE_MACAROON: &str = "/v1/macaroon/bake";
+    /// List the root keys of the macaroons that are accepted.
+    pub const LIST_MACAROON_ROOT_KEYS: &str = "/v1/macaroon/rootKeys";
+    /// Delete a root key, which revokes every macaroon signed with it.
+    pub const DELETE_MACAROON_ROOT_KEY: &str = "/v1/macaroon/rootKeys/:id";
+    /// Replace the admin and readonly root keys and return the new macaroons.
+    pub const ROTATE_MACAROON_ROOT_KEYS: &str = "/v1/macaroon/rotate";
+
+    /// Routes of the LND compatible REST API, which is served on its own address because some of
+    /// the paths clash with the routes above.
+    pub mod lnd {
+        /// --- General ---
+        /// Get node information.
+        pub const GET_INFO: &str = "/v1/getinfo";
+        /// Decode a payment request.
+        pub const DECODE_PAY_REQ: &str = "/v1/payreq/:pay_req";
+
+        /// --- Wallet ---
+        /// Get the on chain balance.
+        pub const WALLET_BALANCE: &str = "/v1/balance/blockchain";
+        /// Generate a new address.
+        pub const NEW_ADDRESS: &str = "/v1/newaddress";
+        /// Send coins to an address.
+        pub const SEND_COINS: &str = "/v1/transactions";
+
+        /// --- Channels ---
+        /// Get the balance of the channels.
+        pub const CHANNEL_BALANCE: &str = "/v1/balance/channels";
+        /// List the open channels.
+        pub const LIST_CHANNELS: &str = "/v1/channels";
+
+        /// --- Peers ---
+        /// List the connected peers.
+        pub const LIST_PEERS: &str = "/v1/peers";
+
+        /// --- Invoices ---
+        /// List the invoices or add a new one.
+        pub const INVOICES: &str = "/v1/invoices";
+
+        /// --- Payments ---
+        /// List the outgoing payments.
+        pub const LIST_PAYMENTS: &str = "/v1/payments";
+        /// Pay an invoice or send a keysend payment.
+        pub const SEND_PAYMENT: &str = "/v2/router/send";
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Query parameters of the paged list routes. Lists are ordered from old to new and the next
+/// page starts after the cursor, which is the ID of the last item of the previous page.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Pagination {
+    /// UNIX timestamp, only items from this time on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<i64>,
+    /// UNIX timestamp, only items before this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// A cursor that is not the ID of a listed item is a bad request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Error {
     pub status: String,
     pub detail: String,
@@ -399,6 +512,73 @@ pub struct SetChannelFee {
 #[derive(Serialize, Deserialize)]
 pub struct SetChannelFeeResponse(pub Vec<SetChannelFee>);
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelTypeFeature {
+    StaticRemoteKey,
+    AnchorsZeroFeeHtlcTx,
+    ScidPrivacy,
+    ZeroConf,
+}
+
+impl Display for ChannelTypeFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelTypeFeature::StaticRemoteKey => write!(f, "static_remote_key"),
+            ChannelTypeFeature::AnchorsZeroFeeHtlcTx => write!(f, "anchors_zero_fee_htlc_tx"),
+            ChannelTypeFeature::ScidPrivacy => write!(f, "scid_privacy"),
+            ChannelTypeFeature::ZeroConf => write!(f, "zero_conf"),
+        }
+    }
+}
+
+impl FromStr for ChannelTypeFeature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "static_remote_key" => Ok(ChannelTypeFeature::StaticRemoteKey),
+            "anchors_zero_fee_htlc_tx" => Ok(ChannelTypeFeature::AnchorsZeroFeeHtlcTx),
+            "scid_privacy" => Ok(ChannelTypeFeature::ScidPrivacy),
+            "zero_conf" => Ok(ChannelTypeFeature::ZeroConf),
+            _ => Err(format!("unknown channel type feature {s}. Expecting one of static_remote_key/anchors_zero_fee_htlc_tx/scid_privacy/zero_conf")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelBackup {
+    /// Number of channels in the backup
+    pub channels: usize,
+    /// Time (seconds since epoch) when the backup was created
+    pub timestamp: u64,
+    /// Hex encoded backup, encrypted with the node's key
+    pub backup: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelAcceptancePolicy {
+    /// Smallest inbound channel (in satoshis) that we accept
+    pub min_channel_sats: Option<u64>,
+    /// Largest inbound channel (in satoshis) that we accept
+    pub max_channel_sats: Option<u64>,
+    /// Only these node IDs may open channels with us. Empty allows every node
+    #[serde(default)]
+    pub allow_list: Vec<String>,
+    /// These node IDs may never open channels with us
+    #[serde(default)]
+    pub deny_list: Vec<String>,
+    /// Maximum number of channels that a single peer may have with us
+    pub max_channels_per_peer: Option<usize>,
+    /// Channel type features that an inbound channel must negotiate
+    #[serde(default)]
+    pub required_features: Vec<ChannelTypeFeature>,
+    /// Reason sent to the peer when a channel is rejected
+    pub reject_reason: String,
+}
+
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct Peer {
     pub id: String,
@@ -444,21 +624,54 @@ pub struct KeysendRequest {
     pub maxdelay: Option<u64>,
     // Amount for which the maxfeepercent check is skipped
     pub exemptfee: Option<u64>,
+    // Return once the payment is sent instead of waiting for the result
+    pub nowait: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PaymentResponse {
+    // 32 byte, hex-encoded, ID to look up the payment with
+    pub id: String,
     pub destination: String,
     pub payment_hash: String,
     pub created_at: u64,
-    pub parts: u64,
     pub amount_msat: Option<u64>,
     pub amount_sent_msat: u64,
     pub payment_preimage: String,
     pub status: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeRequest {
+    // 33 byte, hex-encoded, pubkey of the node to probe (if no invoice is given)
+    pub pubkey: Option<String>,
+    // Bolt11 invoice to probe
+    pub invoice: Option<String>,
+    // Amount in milli satoshis (required unless the invoice has an amount)
+    pub amount_msat: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeStatus {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResponse {
+    pub status: ProbeStatus,
+    // Number of paths that were probed
+    pub paths: u64,
+    // Short channel ID of the channel that failed to forward the probe
+    pub failed_short_channel_id: Option<u64>,
+    // Node ID at the end of the channel that failed to forward the probe
+    pub failed_node_id: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct GenerateInvoice {
     // Amount in milli satoshis
@@ -477,11 +690,42 @@ pub struct GenerateInvoice {
     pub preimage: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateHoldInvoice {
+    // Amount in milli satoshis
+    pub amount: u64,
+    // Unique label for the invoice
+    pub label: String,
+    // Description for the invoice
+    pub description: String,
+    // Expiry time period for the invoice (seconds)
+    pub expiry: Option<u32>,
+    // 64-digit hex string of the payment hash. The preimage is only needed to settle the invoice.
+    pub payment_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SettleHoldInvoice {
+    // 64-digit hex string of the payment preimage
+    pub preimage: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelHoldInvoice {
+    // 64-digit hex string of the payment hash
+    pub payment_hash: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub enum InvoiceStatus {
     Unpaid,
     Paid,
     Expired,
+    // A hold invoice with a payment waiting to be settled or cancelled.
+    Held,
+    Cancelled,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -509,10 +753,645 @@ pub struct GenerateInvoiceResponse {
     pub bolt11: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct PayInvoice {
+    // BOLT11 invoice to pay
     pub invoice: String,
+    // Amount in milli satoshis, only for an invoice without an amount
+    pub amount_msat: Option<u64>,
+    // Label for the payment
     pub label: Option<String>,
+    // Highest routing fee as a percentage of the invoice amount (default from the node settings)
+    pub maxfeepercent: Option<f64>,
+    // Stop retrying the payment over other routes after this many seconds
+    pub retry_for: Option<u64>,
+    // Highest total CLTV expiry delta (blocks) that a route may add to the payment
+    pub maxdelay: Option<u64>,
+    // Routing fee (msat) that is always allowed, even when it is above maxfeepercent
+    pub exemptfee: Option<u64>,
+    // Return once the payment is sent instead of waiting for the result
+    pub nowait: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentStatusResponse {
+    pub id: String,
+    pub payment_hash: Option<String>,
+    pub payment_preimage: Option<String>,
+    pub status: String,
+    pub direction: String,
+    pub amount_msat: u64,
+    pub fee_msat: Option<u64>,
+    // Number of paths that were tried
+    pub attempts: u32,
+    // Number of parts the payment was split into
+    pub parts: u32,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentDetailResponse {
+    pub payment: PaymentStatusResponse,
+    pub attempts: Vec<PaymentAttempt>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentAttempt {
+    // succeeded or failed
+    pub status: String,
+    // The hops of the path, from our peer to the payee
+    pub hops: Vec<PaymentHop>,
+    // Amount delivered to the payee over this path
+    pub amount_msat: u64,
+    pub fee_msat: u64,
+    // The channel that failed the path, when known
+    pub failed_short_channel_id: Option<u64>,
+    // The payment is not retried after a permanent failure
+    pub failed_permanently: bool,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentHop {
+    pub node_id: String,
+    pub short_channel_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOffer {
+    pub description: String,
+    // Amount in milli satoshis. Any amount can be paid if empty.
+    pub amount_msat: Option<u64>,
+    // Expiry time period for the offer (seconds)
+    pub expiry: Option<u64>,
+    pub issuer: Option<String>,
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateRefund {
+    pub description: String,
+    // Amount in milli satoshis to refund
+    pub amount_msat: u64,
+    // Expiry time period for the refund (seconds)
+    pub expiry: Option<u64>,
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OfferResponse {
+    pub id: String,
+    pub bolt12: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OfferKind {
+    Offer,
+    Refund,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Offer {
+    pub id: String,
+    pub kind: OfferKind,
+    pub bolt12: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_msat: Option<u64>,
+    // inbound if we get paid, outbound if we pay
+    pub direction: String,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PayOffer {
+    pub offer: String,
+    // Amount in milli satoshis. Required if the offer has no amount.
+    pub amount_msat: Option<u64>,
+    pub quantity: Option<u64>,
+    pub payer_note: Option<String>,
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RequestRefundPayment {
+    pub refund: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceRequestStatus {
+    Pending,
+    Paid,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InvoiceRequest {
+    pub payment_id: String,
+    pub offer_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_msat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payer_note: Option<String>,
+    pub status: InvoiceRequestStatus,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JitChannelOffer {
+    // Minimum opening fee (msat)
+    pub min_fee_msat: u64,
+    // Proportional opening fee (parts per million of the payment size)
+    pub proportional: u32,
+    // ISO8601 timestamp after which the offer is no longer valid
+    pub valid_until: String,
+    // Number of blocks that the channel is kept open
+    pub min_lifetime: u32,
+    // Maximum to_self_delay that the client may have to wait for its funds
+    pub max_client_to_self_delay: u32,
+    pub min_payment_size_msat: u64,
+    pub max_payment_size_msat: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JitChannelState {
+    AwaitingPayment,
+    PendingChannelOpen,
+    ChannelReady,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JitChannelSession {
+    pub user_channel_id: u64,
+    pub counterparty_node_id: String,
+    // The SCID that the client puts in the route hint of its invoice
+    pub intercept_scid: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_size_msat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening_fee_msat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
+    pub state: JitChannelState,
+    pub created_at: u64,
+}
+
+/// Group of events that a websocket client can subscribe to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum EventTopic {
+    Invoices,
+    Payments,
+    Channels,
+    Forwards,
+    Outputs,
+    Wallet,
+}
+
+impl EventTopic {
+    pub const ALL: [EventTopic; 6] = [
+        EventTopic::Invoices,
+        EventTopic::Payments,
+        EventTopic::Channels,
+        EventTopic::Forwards,
+        EventTopic::Outputs,
+        EventTopic::Wallet,
+    ];
+}
+
+impl Display for EventTopic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let topic = match self {
+            EventTopic::Invoices => "invoices",
+            EventTopic::Payments => "payments",
+            EventTopic::Channels => "channels",
+            EventTopic::Forwards => "forwards",
+            EventTopic::Outputs => "outputs",
+            EventTopic::Wallet => "wallet",
+        };
+        write!(f, "{topic}")
+    }
+}
+
+impl FromStr for EventTopic {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EventTopic::ALL
+            .into_iter()
+            .find(|topic| topic.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown event topic {s}"))
+    }
+}
+
+/// Notification that the node pushes to websocket clients, tagged with its "type".
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum NodeEvent {
+    InvoicePaid {
+        payment_hash: String,
+        amount_msat: u64,
+    },
+    PaymentSent {
+        payment_id: String,
+        payment_hash: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fee_msat: Option<u64>,
+    },
+    PaymentFailed {
+        payment_id: String,
+        payment_hash: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    ChannelPending {
+        channel_id: String,
+        counterparty: String,
+        // Funding outpoint as txid:index
+        funding_txo: String,
+    },
+    ChannelReady {
+        channel_id: String,
+        counterparty: String,
+    },
+    ChannelClosed {
+        channel_id: String,
+        reason: String,
+        force_closed: bool,
+    },
+    ForwardSucceeded {
+        id: String,
+        inbound_channel_id: String,
+        outbound_channel_id: String,
+        amount_msat: u64,
+        fee_msat: u64,
+    },
+    ForwardFailed {
+        id: String,
+        inbound_channel_id: String,
+        reason: String,
+    },
+    SpendableOutput {
+        txid: String,
+        vout: u16,
+        value_sats: u64,
+    },
+    // The confirmed wallet balance dropped below the configured threshold.
+    LowWalletBalance {
+        balance_sats: u64,
+        threshold_sats: u64,
+    },
+}
+
+impl NodeEvent {
+    pub fn topic(&self) -> EventTopic {
+        match self {
+            NodeEvent::InvoicePaid { .. } => EventTopic::Invoices,
+            NodeEvent::PaymentSent { .. } | NodeEvent::PaymentFailed { .. } => EventTopic::Payments,
+            NodeEvent::ChannelPending { .. }
+            | NodeEvent::ChannelReady { .. }
+            | NodeEvent::ChannelClosed { .. } => EventTopic::Channels,
+            NodeEvent::ForwardSucceeded { .. } | NodeEvent::ForwardFailed { .. } => {
+                EventTopic::Forwards
+            }
+            NodeEvent::SpendableOutput { .. } => EventTopic::Outputs,
+            NodeEvent::LowWalletBalance { .. } => EventTopic::Wallet,
+        }
+    }
+
+    /// The webhook events that this event is delivered as.
+    pub fn webhook_events(&self) -> Vec<WebhookEvent> {
+        match self {
+            NodeEvent::InvoicePaid { .. } => vec![WebhookEvent::InvoiceSettled],
+            NodeEvent::ChannelClosed { force_closed, .. } => {
+                if *force_closed {
+                    vec![
+                        WebhookEvent::ChannelClosed,
+                        WebhookEvent::ChannelForceClosed,
+                    ]
+                } else {
+                    vec![WebhookEvent::ChannelClosed]
+                }
+            }
+            NodeEvent::LowWalletBalance { .. } => vec![WebhookEvent::LowWalletBalance],
+            _ => vec![],
+        }
+    }
+}
+
+/// Message that a websocket client sends to change the topics it receives.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum EventSubscription {
+    Subscribe(Vec<EventTopic>),
+    Unsubscribe(Vec<EventTopic>),
+}
+
+/// Event that a webhook can be registered for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookEvent {
+    InvoiceSettled,
+    ChannelClosed,
+    ChannelForceClosed,
+    LowWalletBalance,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::InvoiceSettled,
+        WebhookEvent::ChannelClosed,
+        WebhookEvent::ChannelForceClosed,
+        WebhookEvent::LowWalletBalance,
+    ];
+}
+
+impl Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let event = match self {
+            WebhookEvent::InvoiceSettled => "invoiceSettled",
+            WebhookEvent::ChannelClosed => "channelClosed",
+            WebhookEvent::ChannelForceClosed => "channelForceClosed",
+            WebhookEvent::LowWalletBalance => "lowWalletBalance",
+        };
+        write!(f, "{event}")
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WebhookEvent::ALL
+            .into_iter()
+            .find(|event| event.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown webhook event {s}"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWebhook {
+    /// HTTP(S) URL that the events are posted to
+    pub url: String,
+    /// Secret that the X-Kld-Signature header (HMAC-SHA256 of the body) is created with
+    pub secret: String,
+    /// Events to deliver
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: u64,
+}
+
+/// Body of a webhook delivery.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    // Seconds since epoch when the event happened
+    pub timestamp: u64,
+    pub data: NodeEvent,
+}
+
+/// Group of API routes that a macaroon can be restricted to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum MacaroonPermission {
+    Info,
+    Sign,
+    Onchain,
+    Peers,
+    Channels,
+    Network,
+    Payments,
+    Invoices,
+    Offers,
+    Liquidity,
+    Events,
+}
+
+impl MacaroonPermission {
+    pub const ALL: [MacaroonPermission; 11] = [
+        MacaroonPermission::Info,
+        MacaroonPermission::Sign,
+        MacaroonPermission::Onchain,
+        MacaroonPermission::Peers,
+        MacaroonPermission::Channels,
+        MacaroonPermission::Network,
+        MacaroonPermission::Payments,
+        MacaroonPermission::Invoices,
+        MacaroonPermission::Offers,
+        MacaroonPermission::Liquidity,
+        MacaroonPermission::Events,
+    ];
+}
+
+impl Display for MacaroonPermission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let permission = match self {
+            MacaroonPermission::Info => "info",
+            MacaroonPermission::Sign => "sign",
+            MacaroonPermission::Onchain => "onchain",
+            MacaroonPermission::Peers => "peers",
+            MacaroonPermission::Channels => "channels",
+            MacaroonPermission::Network => "network",
+            MacaroonPermission::Payments => "payments",
+            MacaroonPermission::Invoices => "invoices",
+            MacaroonPermission::Offers => "offers",
+            MacaroonPermission::Liquidity => "liquidity",
+            MacaroonPermission::Events => "events",
+        };
+        write!(f, "{permission}")
+    }
+}
+
+impl FromStr for MacaroonPermission {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MacaroonPermission::ALL
+            .into_iter()
+            .find(|permission| permission.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown macaroon permission {s}"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BakeMacaroon {
+    /// Only allow the read only routes
+    #[serde(default)]
+    pub readonly: bool,
+    /// Route groups that the macaroon can access. Empty allows every group
+    #[serde(default)]
+    pub permissions: Vec<MacaroonPermission>,
+    /// Largest amount (in satoshis) that a single payment or withdrawal can send
+    pub max_payment_sats: Option<u64>,
+    /// Seconds until the macaroon expires
+    pub expiry: Option<u64>,
+    /// IP addresses or CIDR ranges that the macaroon can be used from. Empty allows every address
+    #[serde(default)]
+    pub ip_ranges: Vec<String>,
+    /// Sign the macaroon with a new root key of this name, so that it can be revoked on its own
+    pub root_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BakeMacaroonResponse {
+    /// Base64 encoded macaroon, it can be written to a file and used like admin.macaroon
+    pub macaroon: String,
+    /// ID of the root key that the macaroon is signed with
+    pub root_key_id: String,
+    /// Seconds since epoch when the macaroon expires
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MacaroonRootKey {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateMacaroonRootKeysResponse {
+    /// Base64 encoded, the admin.macaroon file is replaced with it
+    pub admin_macaroon: String,
+    /// Base64 encoded, the readonly.macaroon file is replaced with it
+    pub readonly_macaroon: String,
+    /// Root keys of the macaroons that were baked with their own root key. Their macaroons are
+    /// not revoked by the rotation, delete the root keys to revoke them
+    pub remaining_root_keys: Vec<MacaroonRootKey>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardStats {
+    /// UNIX timestamp of the start of the time window, the first forward when not set
+    pub since: Option<i64>,
+    /// UNIX timestamp of the end of the time window, now when not set
+    pub until: Option<i64>,
+    pub total: ForwardCounts,
+    /// Ordered by the fees that the channels earned, highest first
+    pub channels: Vec<ChannelForwardStats>,
+    /// Ordered by the fees that the peers earned, highest first
+    pub peers: Vec<PeerForwardStats>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelForwardStats {
+    pub channel_id: String,
+    /// Not set when the channel is unknown
+    pub peer_id: Option<String>,
+    pub stats: ForwardCounts,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerForwardStats {
+    pub peer_id: String,
+    pub stats: ForwardCounts,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardCounts {
+    /// Succeeded forwards that came in through the channel
+    pub succeeded_in: u64,
+    /// Succeeded forwards that went out through the channel
+    pub succeeded_out: u64,
+    /// Amount received for the succeeded forwards
+    pub volume_in_msat: u64,
+    /// Amount sent for the succeeded forwards
+    pub volume_out_msat: u64,
+    /// Fees of the forwards that went out through the channel
+    pub fees_earned_msat: u64,
+    /// Fees earned per million msat that was sent, not set without volume out
+    pub effective_fee_rate_ppm: Option<u64>,
+    /// Failed forwards that came in through the channel by the reason that they failed
+    /// (next_hop_channel, unknown_next_hop, invalid_forward, failed_payment)
+    pub failed_in: BTreeMap<String, u64>,
+    /// Failed forwards that the channel was the next hop of
+    pub failed_out: u64,
+}
+
+#[test]
+fn test_node_event() {
+    let event = NodeEvent::PaymentSent {
+        payment_id: "01".to_string(),
+        payment_hash: "02".to_string(),
+        fee_msat: None,
+    };
+    let json = serde_json::to_string(&event).unwrap();
+    assert_eq!(
+        r#"{"type":"paymentSent","paymentId":"01","paymentHash":"02"}"#,
+        json
+    );
+    assert_eq!(event, serde_json::from_str(&json).unwrap());
+    assert_eq!(EventTopic::Payments, event.topic());
+
+    let subscription: EventSubscription =
+        serde_json::from_str(r#"{"subscribe":["channels","forwards"]}"#).unwrap();
+    assert_eq!(
+        EventSubscription::Subscribe(vec![EventTopic::Channels, EventTopic::Forwards]),
+        subscription
+    );
+    assert_eq!(
+        EventTopic::Outputs,
+        EventTopic::from_str("outputs").unwrap()
+    );
+    assert!(EventTopic::from_str("blocks").is_err());
+
+    let closed = NodeEvent::ChannelClosed {
+        channel_id: "03".to_string(),
+        reason: "CommitmentTxConfirmed".to_string(),
+        force_closed: true,
+    };
+    assert_eq!(
+        vec![
+            WebhookEvent::ChannelClosed,
+            WebhookEvent::ChannelForceClosed
+        ],
+        closed.webhook_events()
+    );
+    assert!(event.webhook_events().is_empty());
+    assert_eq!(
+        WebhookEvent::LowWalletBalance,
+        WebhookEvent::from_str("lowWalletBalance").unwrap()
+    );
 }
 
 #[test]
@@ -0,0 +1,328 @@
+//! Requests and responses of the LND compatible REST API. Field names follow the LND protobuf
+//! definitions, 64 bit integers are encoded as strings and byte fields as base64 like the LND
+//! REST proxy does.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GetInfo {
+    pub version: String,
+    pub identity_pubkey: String,
+    pub alias: String,
+    pub color: String,
+    pub num_pending_channels: u32,
+    pub num_active_channels: u32,
+    pub num_inactive_channels: u32,
+    pub num_peers: u32,
+    pub block_height: u32,
+    pub block_hash: String,
+    pub synced_to_chain: bool,
+    pub synced_to_graph: bool,
+    pub testnet: bool,
+    pub chains: Vec<Chain>,
+    pub uris: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Chain {
+    pub chain: String,
+    pub network: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct WalletBalance {
+    #[serde(with = "int64")]
+    pub total_balance: u64,
+    #[serde(with = "int64")]
+    pub confirmed_balance: u64,
+    #[serde(with = "int64")]
+    pub unconfirmed_balance: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Amount {
+    #[serde(with = "int64")]
+    pub sat: u64,
+    #[serde(with = "int64")]
+    pub msat: u64,
+}
+
+impl Amount {
+    pub fn from_msat(msat: u64) -> Amount {
+        Amount {
+            sat: msat / 1000,
+            msat,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ChannelBalance {
+    /// Deprecated by LND, the local balance in satoshis.
+    #[serde(with = "int64")]
+    pub balance: u64,
+    pub local_balance: Amount,
+    pub remote_balance: Amount,
+    pub pending_open_local_balance: Amount,
+    pub pending_open_remote_balance: Amount,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ListChannels {
+    pub channels: Vec<Channel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Channel {
+    pub active: bool,
+    pub remote_pubkey: String,
+    /// Funding outpoint as txid:index.
+    pub channel_point: String,
+    /// Short channel ID.
+    #[serde(with = "int64")]
+    pub chan_id: u64,
+    #[serde(with = "int64")]
+    pub capacity: u64,
+    #[serde(with = "int64")]
+    pub local_balance: u64,
+    #[serde(with = "int64")]
+    pub remote_balance: u64,
+    pub private: bool,
+    pub initiator: bool,
+    #[serde(with = "int64")]
+    pub local_chan_reserve_sat: u64,
+    #[serde(with = "int64")]
+    pub remote_chan_reserve_sat: u64,
+    pub peer_alias: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ListPeers {
+    pub peers: Vec<Peer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Peer {
+    pub pub_key: String,
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum InvoiceState {
+    Open,
+    Settled,
+    Canceled,
+    Accepted,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ListInvoices {
+    pub invoices: Vec<Invoice>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Invoice {
+    pub memo: String,
+    /// Base64, empty until the invoice is paid.
+    pub r_preimage: String,
+    /// Base64 payment hash.
+    pub r_hash: String,
+    #[serde(with = "int64")]
+    pub value: u64,
+    #[serde(with = "int64")]
+    pub value_msat: u64,
+    pub settled: bool,
+    #[serde(with = "int64")]
+    pub creation_date: u64,
+    #[serde(with = "int64")]
+    pub settle_date: u64,
+    pub payment_request: String,
+    #[serde(with = "int64")]
+    pub expiry: u64,
+    #[serde(with = "int64")]
+    pub amt_paid_sat: u64,
+    #[serde(with = "int64")]
+    pub amt_paid_msat: u64,
+    pub state: InvoiceState,
+    /// Base64 payment secret.
+    pub payment_addr: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct AddInvoice {
+    #[serde(default)]
+    pub memo: String,
+    /// Amount in satoshis, zero for an invoice without an amount.
+    #[serde(default, with = "int64")]
+    pub value: u64,
+    /// Amount in millisatoshis, takes precedence over value.
+    #[serde(default, with = "int64")]
+    pub value_msat: u64,
+    /// Seconds until the invoice expires, zero for the default.
+    #[serde(default, with = "int64")]
+    pub expiry: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AddInvoiceResponse {
+    /// Base64 payment hash.
+    pub r_hash: String,
+    pub payment_request: String,
+    /// Base64 payment secret.
+    pub payment_addr: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PayReq {
+    pub destination: String,
+    pub payment_hash: String,
+    #[serde(with = "int64")]
+    pub num_satoshis: u64,
+    #[serde(with = "int64")]
+    pub num_msat: u64,
+    #[serde(with = "int64")]
+    pub timestamp: u64,
+    #[serde(with = "int64")]
+    pub expiry: u64,
+    pub description: String,
+    pub description_hash: String,
+    #[serde(with = "int64")]
+    pub cltv_expiry: u64,
+    /// Base64 payment secret.
+    pub payment_addr: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PaymentStatus {
+    Unknown,
+    InFlight,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PaymentFailureReason {
+    FailureReasonNone,
+    FailureReasonTimeout,
+    FailureReasonNoRoute,
+    FailureReasonError,
+    FailureReasonIncorrectPaymentDetails,
+    FailureReasonInsufficientBalance,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ListPayments {
+    pub payments: Vec<Payment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Payment {
+    /// Hex payment hash.
+    pub payment_hash: String,
+    #[serde(with = "int64")]
+    pub value_sat: u64,
+    #[serde(with = "int64")]
+    pub value_msat: u64,
+    /// Hex preimage, empty until the payment succeeded.
+    pub payment_preimage: String,
+    pub payment_request: String,
+    pub status: PaymentStatus,
+    #[serde(with = "int64")]
+    pub fee_sat: u64,
+    #[serde(with = "int64")]
+    pub fee_msat: u64,
+    #[serde(with = "int64")]
+    pub creation_date: u64,
+    #[serde(with = "int64")]
+    pub creation_time_ns: u64,
+    pub failure_reason: PaymentFailureReason,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct SendPayment {
+    /// Invoice to pay, a keysend payment is sent to dest when it is empty.
+    #[serde(default)]
+    pub payment_request: String,
+    /// Base64 public key of the keysend recipient.
+    #[serde(default)]
+    pub dest: String,
+    /// Amount in satoshis, only for keysend payments and invoices without an amount.
+    #[serde(default, with = "int64")]
+    pub amt: u64,
+    /// Amount in millisatoshis, takes precedence over amt.
+    #[serde(default, with = "int64")]
+    pub amt_msat: u64,
+    /// Seconds to keep retrying the payment, zero for the default.
+    #[serde(default)]
+    pub timeout_seconds: u32,
+    /// Highest routing fee in satoshis, zero for the default.
+    #[serde(default, with = "int64")]
+    pub fee_limit_sat: u64,
+    /// Highest routing fee in millisatoshis, takes precedence over fee_limit_sat.
+    #[serde(default, with = "int64")]
+    pub fee_limit_msat: u64,
+}
+
+/// LND streams payment updates, we only send the final one.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PaymentUpdate {
+    pub result: Payment,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct NewAddressResponse {
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct SendCoins {
+    pub addr: String,
+    /// Amount in satoshis, ignored when send_all is set.
+    #[serde(default, with = "int64")]
+    pub amount: u64,
+    #[serde(default)]
+    pub send_all: bool,
+    /// Fee rate, zero for the default.
+    #[serde(default, with = "int64")]
+    pub sat_per_vbyte: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SendCoinsResponse {
+    pub txid: String,
+}
+
+/// LND encodes 64 bit integers as strings, requests may use numbers as well.
+mod int64 {
+    use std::{fmt::Display, str::FromStr};
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, T: Display>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr + Deserialize<'de>,
+        T::Err: Display,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum StringOrNumber<T> {
+            String(String),
+            Number(T),
+        }
+        match StringOrNumber::<T>::deserialize(deserializer)? {
+            StringOrNumber::String(s) => s.parse().map_err(de::Error::custom),
+            StringOrNumber::Number(n) => Ok(n),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use clap::Parser;
+use kld_client::cli::{run_command, KldCliCommand};
+
+#[tokio::main]
+async fn main() {
+    let args = KldCliCommand::parse();
+
+    match run_command(args).await {
+        Ok(output) => {
+            if !output.is_empty() {
+                println!("{output}");
+            }
+        }
+        Err(e) => {
+            eprintln!("Error executing command: {e}");
+            std::process::exit(1);
+        }
+    }
+}
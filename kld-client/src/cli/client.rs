@@ -0,0 +1,487 @@
+use std::{
+    fs::{self},
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use api::{
+    BakeMacaroon, CancelHoldInvoice, ChannelFee, CreateOffer, CreateRefund, FeeRate, FundChannel,
+    GenerateHoldInvoice, GenerateInvoice, KeysendRequest, MacaroonPermission, Pagination,
+    PayInvoice, PayOffer, ProbeRequest, RequestRefundPayment, SettleHoldInvoice, SignRequest,
+    WalletTransfer,
+};
+use serde::Serialize;
+use serde_json::to_string_pretty;
+
+use super::commands::PageArgs;
+use crate::{
+    codegen::{
+        get_v1_estimate_channel_liquidity_body::GetV1EstimateChannelLiquidityBody,
+        post_v1_peer_connect_body::PostV1PeerConnectBody,
+    },
+    Error, KldClient,
+};
+
+pub struct Api {
+    client: KldClient,
+}
+
+impl Api {
+    pub fn new(host: SocketAddr, cert_path: PathBuf, macaroon_path: PathBuf) -> Result<Api> {
+        let macaroon = fs::read(macaroon_path)?;
+        let cert = fs::read(cert_path)?;
+        Ok(Api {
+            client: KldClient::new(host, &cert, &macaroon)?,
+        })
+    }
+
+    pub async fn sign(&self, message: String) -> Result<String> {
+        output(self.client.sign(&SignRequest { message }).await)
+    }
+
+    pub async fn get_info(&self) -> Result<String> {
+        output(self.client.get_info().await)
+    }
+
+    pub async fn get_balance(&self) -> Result<String> {
+        output(self.client.get_balance().await)
+    }
+
+    pub async fn new_address(&self) -> Result<String> {
+        output(self.client.new_address().await)
+    }
+
+    pub async fn withdraw(
+        &self,
+        address: String,
+        satoshis: String,
+        fee_rate: Option<String>,
+    ) -> Result<String> {
+        let wallet_transfer = WalletTransfer {
+            address,
+            satoshis,
+            fee_rate: fee_rate.map(|f| FeeRate::from_str(&f)).transpose()?,
+            min_conf: None,
+            utxos: vec![],
+        };
+        output(self.client.withdraw(&wallet_transfer).await)
+    }
+
+    pub async fn list_funds(&self) -> Result<String> {
+        output(self.client.list_funds().await)
+    }
+
+    pub async fn list_peer_channels(&self) -> Result<String> {
+        output(self.client.list_peer_channels().await)
+    }
+
+    pub async fn list_peers(&self) -> Result<String> {
+        output(self.client.list_peers().await)
+    }
+
+    pub async fn connect_peer(&self, id: String) -> Result<String> {
+        let connect = PostV1PeerConnectBody { id };
+        output(self.client.connect_peer(&connect).await)
+    }
+
+    pub async fn disconnect_peer(&self, id: String) -> Result<String> {
+        output(self.client.disconnect_peer(&id).await)
+    }
+
+    pub async fn open_channel(
+        &self,
+        id: String,
+        satoshis: String,
+        push_msat: Option<String>,
+        announce: Option<bool>,
+        fee_rate: Option<String>,
+    ) -> Result<String> {
+        let open_channel = FundChannel {
+            id,
+            satoshis,
+            fee_rate: fee_rate.map(|f| FeeRate::from_str(&f)).transpose()?,
+            announce,
+            min_conf: None,
+            utxos: vec![],
+            push_msat,
+            close_to: None,
+            request_amt: None,
+            compact_lease: None,
+        };
+        output(self.client.open_channel(&open_channel).await)
+    }
+
+    pub async fn set_channel_fee(
+        &self,
+        id: String,
+        base: Option<u32>,
+        ppm: Option<u32>,
+    ) -> Result<String> {
+        let fee_request = ChannelFee { id, base, ppm };
+        output(self.client.set_channel_fee(&fee_request).await)
+    }
+
+    pub async fn close_channel(&self, id: String) -> Result<String> {
+        output(self.client.close_channel(&id).await)
+    }
+
+    pub async fn force_close_channel(&self, id: String, may_broadcast: bool) -> Result<String> {
+        output(self.client.force_close_channel(&id, may_broadcast).await)
+    }
+
+    pub async fn list_network_nodes(&self, id: Option<String>) -> Result<String> {
+        if let Some(id) = id {
+            output(self.client.get_network_node(&id).await)
+        } else {
+            output(self.client.list_network_nodes().await)
+        }
+    }
+
+    pub async fn list_network_channels(&self, id: Option<String>) -> Result<String> {
+        if let Some(id) = id {
+            output(self.client.get_network_channel(&id).await)
+        } else {
+            output(self.client.list_network_channels().await)
+        }
+    }
+
+    pub async fn fee_rates(&self, style: Option<String>) -> Result<String> {
+        let style = style.unwrap_or("perkb".to_string());
+        output(self.client.fee_rates(&style).await)
+    }
+
+    pub async fn keysend(&self, public_key: String, amount: u64, nowait: bool) -> Result<String> {
+        let body = KeysendRequest {
+            pubkey: public_key,
+            amount,
+            label: None,
+            maxfeepercent: None,
+            retry_for: None,
+            maxdelay: None,
+            exemptfee: None,
+            nowait: Some(nowait),
+        };
+        output(self.client.keysend(&body).await)
+    }
+
+    pub async fn generate_invoice(
+        &self,
+        amount: u64,
+        label: String,
+        description: String,
+        expiry: Option<u32>,
+    ) -> Result<String> {
+        let body = GenerateInvoice {
+            amount,
+            label,
+            description,
+            expiry,
+            ..Default::default()
+        };
+        output(self.client.generate_invoice(&body).await)
+    }
+
+    pub async fn generate_hold_invoice(
+        &self,
+        amount: u64,
+        label: String,
+        description: String,
+        payment_hash: String,
+        expiry: Option<u32>,
+    ) -> Result<String> {
+        let body = GenerateHoldInvoice {
+            amount,
+            label,
+            description,
+            expiry,
+            payment_hash,
+        };
+        output(self.client.generate_hold_invoice(&body).await)
+    }
+
+    pub async fn settle_hold_invoice(&self, preimage: String) -> Result<String> {
+        let body = SettleHoldInvoice { preimage };
+        output(self.client.settle_hold_invoice(&body).await)
+    }
+
+    pub async fn cancel_hold_invoice(&self, payment_hash: String) -> Result<String> {
+        let body = CancelHoldInvoice { payment_hash };
+        output(self.client.cancel_hold_invoice(&body).await)
+    }
+
+    pub async fn list_invoices(&self, label: Option<String>, page: PageArgs) -> Result<String> {
+        output(
+            self.client
+                .list_invoices(label.as_deref(), &pagination(page))
+                .await,
+        )
+    }
+
+    pub async fn pay_invoice(
+        &self,
+        bolt11: String,
+        amount: Option<u64>,
+        label: Option<String>,
+        nowait: bool,
+    ) -> Result<String> {
+        let body = PayInvoice {
+            invoice: bolt11,
+            amount_msat: amount,
+            label,
+            maxfeepercent: None,
+            retry_for: None,
+            maxdelay: None,
+            exemptfee: None,
+            nowait: Some(nowait),
+        };
+        output(self.client.pay_invoice(&body).await)
+    }
+
+    pub async fn probe(
+        &self,
+        pubkey: Option<String>,
+        invoice: Option<String>,
+        amount: Option<u64>,
+    ) -> Result<String> {
+        let body = ProbeRequest {
+            pubkey,
+            invoice,
+            amount_msat: amount,
+        };
+        output(self.client.probe(&body).await)
+    }
+
+    pub async fn list_payments(
+        &self,
+        bolt11: Option<String>,
+        direction: Option<String>,
+        status: Option<String>,
+        page: PageArgs,
+    ) -> Result<String> {
+        output(
+            self.client
+                .list_payments(
+                    bolt11.as_deref(),
+                    direction.as_deref(),
+                    status.as_deref(),
+                    &pagination(page),
+                )
+                .await,
+        )
+    }
+
+    pub async fn get_payment(&self, id: String) -> Result<String> {
+        output(self.client.get_payment(&id).await)
+    }
+
+    pub async fn payment_detail(&self, id: String) -> Result<String> {
+        output(self.client.payment_detail(&id).await)
+    }
+
+    pub async fn create_offer(
+        &self,
+        description: String,
+        amount: Option<u64>,
+        expiry: Option<u64>,
+        issuer: Option<String>,
+        label: Option<String>,
+    ) -> Result<String> {
+        let body = CreateOffer {
+            description,
+            amount_msat: amount,
+            expiry,
+            issuer,
+            label,
+        };
+        output(self.client.create_offer(&body).await)
+    }
+
+    pub async fn list_offers(&self, label: Option<String>) -> Result<String> {
+        output(self.client.list_offers(label.as_deref()).await)
+    }
+
+    pub async fn pay_offer(
+        &self,
+        offer: String,
+        amount: Option<u64>,
+        quantity: Option<u64>,
+        payer_note: Option<String>,
+        label: Option<String>,
+    ) -> Result<String> {
+        let body = PayOffer {
+            offer,
+            amount_msat: amount,
+            quantity,
+            payer_note,
+            label,
+        };
+        output(self.client.pay_offer(&body).await)
+    }
+
+    pub async fn create_refund(
+        &self,
+        amount: u64,
+        description: String,
+        expiry: Option<u64>,
+        label: Option<String>,
+    ) -> Result<String> {
+        let body = CreateRefund {
+            description,
+            amount_msat: amount,
+            expiry,
+            label,
+        };
+        output(self.client.create_refund(&body).await)
+    }
+
+    pub async fn request_refund_payment(&self, refund: String) -> Result<String> {
+        let body = RequestRefundPayment { refund };
+        output(self.client.request_refund_payment(&body).await)
+    }
+
+    pub async fn list_invoice_requests(&self, offer_id: Option<String>) -> Result<String> {
+        output(self.client.list_invoice_requests(offer_id.as_deref()).await)
+    }
+
+    pub async fn estimate_channel_liquidity(&self, scid: u64, target: String) -> Result<String> {
+        let body = GetV1EstimateChannelLiquidityBody {
+            scid: scid as i64,
+            target,
+        };
+        output(self.client.estimate_channel_liquidity(&body).await)
+    }
+
+    pub async fn local_remote_balance(&self) -> Result<String> {
+        output(self.client.local_remote_balance().await)
+    }
+
+    pub async fn get_fees(&self) -> Result<String> {
+        output(self.client.get_fees().await)
+    }
+
+    pub async fn list_forwards(
+        &self,
+        status: Option<String>,
+        channel: Option<String>,
+        peer: Option<String>,
+        page: PageArgs,
+    ) -> Result<String> {
+        output(
+            self.client
+                .list_forwards(
+                    status.as_deref(),
+                    channel.as_deref(),
+                    peer.as_deref(),
+                    &pagination(page),
+                )
+                .await,
+        )
+    }
+
+    pub async fn channel_history(
+        &self,
+        channel: Option<String>,
+        peer: Option<String>,
+        page: PageArgs,
+    ) -> Result<String> {
+        output(
+            self.client
+                .channel_history(channel.as_deref(), peer.as_deref(), &pagination(page))
+                .await,
+        )
+    }
+
+    pub async fn forward_stats(&self, since: Option<i64>, until: Option<i64>) -> Result<String> {
+        output(self.client.forward_stats(since, until).await)
+    }
+
+    pub async fn export_channel_backup(&self, path: PathBuf) -> Result<String> {
+        let backup = match self.client.channel_backup().await {
+            Err(Error::Api { error, .. }) => return Ok(to_string_pretty(&error)?),
+            result => result?,
+        };
+        fs::write(&path, &backup.backup)
+            .with_context(|| format!("Failed to write channel backup to {}", path.display()))?;
+        Ok(to_string_pretty(&backup)?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn bake_macaroon(
+        &self,
+        path: PathBuf,
+        readonly: bool,
+        permissions: Option<String>,
+        max_payment_sats: Option<u64>,
+        expiry: Option<u64>,
+        ip_ranges: Option<String>,
+        root_key: Option<String>,
+    ) -> Result<String> {
+        let body = BakeMacaroon {
+            readonly,
+            permissions: split_list(permissions)
+                .iter()
+                .map(|p| MacaroonPermission::from_str(p))
+                .collect::<Result<Vec<MacaroonPermission>>>()?,
+            max_payment_sats,
+            expiry,
+            ip_ranges: split_list(ip_ranges),
+            root_key,
+        };
+        let baked = match self.client.bake_macaroon(&body).await {
+            Err(Error::Api { error, .. }) => return Ok(to_string_pretty(&error)?),
+            result => result?,
+        };
+        fs::write(&path, &baked.macaroon)
+            .with_context(|| format!("Failed to write macaroon to {}", path.display()))?;
+        Ok(to_string_pretty(&baked)?)
+    }
+
+    pub async fn list_macaroon_root_keys(&self) -> Result<String> {
+        output(self.client.list_macaroon_root_keys().await)
+    }
+
+    pub async fn delete_macaroon_root_key(&self, id: String) -> Result<String> {
+        output(self.client.delete_macaroon_root_key(&id).await)
+    }
+
+    /// The macaroon that kld-cli was started with is revoked, so the new admin macaroon is
+    /// written to a file.
+    pub async fn rotate_macaroon_root_keys(&self, path: PathBuf) -> Result<String> {
+        let rotated = match self.client.rotate_macaroon_root_keys().await {
+            Err(Error::Api { error, .. }) => return Ok(to_string_pretty(&error)?),
+            result => result?,
+        };
+        fs::write(&path, &rotated.admin_macaroon)
+            .with_context(|| format!("Failed to write macaroon to {}", path.display()))?;
+        Ok(to_string_pretty(&rotated)?)
+    }
+
+    pub async fn decode(&self, invoice: String) -> Result<String> {
+        output(self.client.decode_invoice(&invoice).await)
+    }
+}
+
+/// Errors from the API are printed like any other response.
+fn output<T: Serialize>(result: Result<T, Error>) -> Result<String> {
+    match result {
+        Ok(response) => Ok(to_string_pretty(&response)?),
+        Err(Error::Api { error, .. }) => Ok(to_string_pretty(&error)?),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn split_list(list: Option<String>) -> Vec<String> {
+    list.map(|list| list.split(',').map(|s| s.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn pagination(page: PageArgs) -> Pagination {
+    Pagination {
+        since: page.since,
+        until: page.until,
+        limit: page.limit,
+        cursor: page.cursor,
+    }
+}
@@ -0,0 +1,423 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct KldCliCommand {
+    /// IP address the target machine.
+    #[arg(short, long, hide = true)]
+    pub target: SocketAddr,
+    /// Path to the TLS cert of the target API.
+    #[arg(short, long, hide = true)]
+    pub cert_path: PathBuf,
+    /// Path to the macaroon for authenticating with the API.
+    #[arg(short, long, hide = true)]
+    pub macaroon_path: PathBuf,
+    /// Command to run.
+    #[clap(subcommand)]
+    pub command: KldCliSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KldCliSubCommand {
+    /// Fetch information about this lightning node.
+    GetInfo,
+    /// Creates a signature of the message using node's secret key (message limit 65536 chars)
+    Sign {
+        /// Message to be signed (max 65536 chars)
+        #[arg()]
+        message: String,
+    },
+    /// Fetch confirmed and unconfirmed on-chain balance.
+    GetBalance,
+    /// Generates new on-chain address for receiving funds.
+    NewAddress,
+    /// Send on-chain funds out of the wallet.
+    Withdraw {
+        /// The address to withdraw to.
+        #[arg()]
+        address: String,
+        /// The amount to withdraw (in Satoshis). The string "all" will empty the wallet.
+        #[arg()]
+        amount: String,
+        /// Fee rate [urgent/normal/slow/<sats>perkw/<sats>perkb]
+        #[arg(short, long)]
+        fee_rate: Option<String>,
+    },
+    /// Show available funds from the internal wallet.
+    ListFunds,
+    /// Fetch a list of this nodes peers.
+    ListPeers,
+    /// Connect with a network peer.
+    ConnectPeer {
+        /// The public key (id) of the node to connect to. Optionally provide host and port [id@host:port].
+        #[arg()]
+        public_key: String,
+    },
+    /// Disconnect from a network peer.
+    DisconnectPeer {
+        /// The public key of the node to disconnect from.
+        #[arg()]
+        public_key: String,
+    },
+    /// Fetch a list of this nodes open channels.
+    ListPeerChannels,
+    /// Open a channel with another node.
+    OpenChannel {
+        /// The public key of the node to open a channel with. Optionally provide host and port [id@host:port].
+        #[arg()]
+        public_key: String,
+        /// Amount of satoshis to commit to the channel.
+        #[arg()]
+        sats: String,
+        /// The number of satoshis to push to the other node side of the channel.
+        #[arg(short, long)]
+        push_msat: Option<String>,
+        /// Whether to announce the channel to the rest of the network (public - default) or not (private).
+        #[arg(short, long)]
+        announce: Option<bool>,
+        /// Fee rate [urgent/normal/slow/<sats>perkw/<sats>perkb]
+        #[arg(short, long)]
+        fee_rate: Option<String>,
+    },
+    /// Set channel fees.
+    SetChannelFee {
+        /// Channel ID, short channel ID or "all" for all channels.
+        #[arg()]
+        id: String,
+        /// Optional value in msats added as base fee to any routed payment.
+        #[arg(short, long)]
+        base_fee: Option<u32>,
+        /// Optional value that is added proportionally per-millionths to any routed payment volume in satoshi
+        #[arg(short, long)]
+        ppm_fee: Option<u32>,
+    },
+    /// Close a channel.
+    CloseChannel {
+        /// Channel ID or short channel ID to close.
+        #[arg()]
+        id: String,
+
+        /// Force closes a channel with or without broadcasting the latest local transaction(s) .
+        /// If `broadcast-flag` is `broadcast`, it will immediately broadcasting the latest local transaction(s) and rejecting new HTLCs on the given channel.
+        /// If `broadcast-flag` is `no-broadcast`, it will rejecting new HTLCs on the given channel but skips broadcasting the latest local transaction(s).
+        #[arg(short, long, name = "broadcast-flag")]
+        force_close: Option<String>,
+    },
+    /// Get node information from the network graph.
+    NetworkNodes {
+        /// Provide Node ID to get info about a single node.
+        #[arg(short, long)]
+        id: Option<String>,
+    },
+    /// Get channel information from the network graph.
+    NetworkChannels {
+        /// Provide short channel ID to get info about a single channel.
+        #[arg(short, long)]
+        id: Option<String>,
+    },
+    /// Return feerate estimates, either satoshi-per-kw or satoshi-per-kb.
+    FeeRates {
+        /// perkb (default) or perkw
+        #[arg(short, long)]
+        style: Option<String>,
+    },
+    /// Pay a node without an invoice.
+    Keysend {
+        /// Node ID of the payee.
+        #[arg()]
+        public_key: String,
+        /// Amount to pay in millisats.
+        #[arg()]
+        amount: u64,
+        /// Return once the payment is sent instead of waiting for the result.
+        #[arg(long)]
+        nowait: bool,
+    },
+    /// Generate a bolt11 invoice for receiving a payment.
+    GenerateInvoice {
+        /// Amount in millisats
+        #[arg()]
+        amount: u64,
+        /// Unique label for the invoice
+        #[arg()]
+        label: String,
+        /// Description for the invoice
+        #[arg()]
+        description: String,
+        /// Expiry time period for the invoice (seconds)
+        #[arg(short, long)]
+        expiry: Option<u32>,
+    },
+    /// Generate a bolt11 hold invoice that is only settled when the preimage is provided.
+    GenerateHoldInvoice {
+        /// Amount in millisats
+        #[arg()]
+        amount: u64,
+        /// Unique label for the invoice
+        #[arg()]
+        label: String,
+        /// Description for the invoice
+        #[arg()]
+        description: String,
+        /// Payment hash (hex) of the invoice
+        #[arg()]
+        payment_hash: String,
+        /// Expiry time period for the invoice (seconds)
+        #[arg(short, long)]
+        expiry: Option<u32>,
+    },
+    /// Settle a held payment of a hold invoice
+    SettleHoldInvoice {
+        /// Preimage (hex) of the payment hash
+        #[arg()]
+        preimage: String,
+    },
+    /// Cancel a hold invoice and fail back any held payment
+    CancelHoldInvoice {
+        /// Payment hash (hex) of the invoice
+        #[arg()]
+        payment_hash: String,
+    },
+    /// List all invoices
+    ListInvoices {
+        /// Label of the invoice
+        #[arg(short, long)]
+        label: Option<String>,
+        #[command(flatten)]
+        page: PageArgs,
+    },
+    /// Pay an invoice
+    PayInvoice {
+        /// The invoice to pay
+        #[arg()]
+        bolt11: String,
+        /// Amount in millisats (only for an invoice without an amount)
+        #[arg(short, long)]
+        amount: Option<u64>,
+        /// Label for the payment
+        #[arg(short, long)]
+        label: Option<String>,
+        /// Return once the payment is sent instead of waiting for the result
+        #[arg(long)]
+        nowait: bool,
+    },
+    /// Probe the route to a node or invoice without paying
+    Probe {
+        /// Node ID to probe
+        #[arg(short, long)]
+        pubkey: Option<String>,
+        /// Bolt11 invoice to probe
+        #[arg(short, long)]
+        invoice: Option<String>,
+        /// Amount in millisats (required unless the invoice has an amount)
+        #[arg(short, long)]
+        amount: Option<u64>,
+    },
+    /// List all payments
+    ListPayments {
+        /// Bolt11 invoice of payment
+        #[arg(short, long)]
+        bolt11: Option<String>,
+        /// Direction (inbound/outbound)
+        #[arg(short, long)]
+        direction: Option<String>,
+        /// Status of the payment (pending/complete/failed)
+        #[arg(short, long)]
+        status: Option<String>,
+        #[command(flatten)]
+        page: PageArgs,
+    },
+    /// Get the status of a payment
+    GetPayment {
+        /// ID (hex) of the payment
+        #[arg()]
+        id: String,
+    },
+    /// Get the status of a payment and every path that was tried for it
+    PaymentDetail {
+        /// ID (hex) of the payment
+        #[arg()]
+        id: String,
+    },
+    /// Create a bolt12 offer for receiving payments.
+    CreateOffer {
+        /// Description for the offer
+        #[arg()]
+        description: String,
+        /// Amount in millisats (any amount if not set)
+        #[arg(short, long)]
+        amount: Option<u64>,
+        /// Expiry time period for the offer (seconds)
+        #[arg(short, long)]
+        expiry: Option<u64>,
+        /// Issuer of the offer
+        #[arg(short, long)]
+        issuer: Option<String>,
+        /// Label for the offer
+        #[arg(short, long)]
+        label: Option<String>,
+    },
+    /// List all bolt12 offers and refunds
+    ListOffers {
+        /// Label of the offer
+        #[arg(short, long)]
+        label: Option<String>,
+    },
+    /// Pay a bolt12 offer
+    PayOffer {
+        /// The offer to pay
+        #[arg()]
+        offer: String,
+        /// Amount in millisats (required if the offer has no amount)
+        #[arg(short, long)]
+        amount: Option<u64>,
+        /// Quantity of items
+        #[arg(short, long)]
+        quantity: Option<u64>,
+        /// Note to the payee
+        #[arg(short = 'n', long)]
+        payer_note: Option<String>,
+        /// Label for the payment
+        #[arg(short, long)]
+        label: Option<String>,
+    },
+    /// Create a bolt12 refund for someone to pay us
+    CreateRefund {
+        /// Amount in millisats
+        #[arg()]
+        amount: u64,
+        /// Description for the refund
+        #[arg()]
+        description: String,
+        /// Expiry time period for the refund (seconds)
+        #[arg(short, long)]
+        expiry: Option<u64>,
+        /// Label for the refund
+        #[arg(short, long)]
+        label: Option<String>,
+    },
+    /// Pay a bolt12 refund
+    RequestRefundPayment {
+        /// The refund to pay
+        #[arg()]
+        refund: String,
+    },
+    /// List the invoice requests sent to pay bolt12 offers
+    ListInvoiceRequests {
+        /// ID of the offer
+        #[arg(short, long)]
+        offer_id: Option<String>,
+    },
+    /// Estimate channel liquidity to a target node
+    EstimateChannelLiquidity {
+        /// Short channel ID
+        #[arg()]
+        scid: u64,
+        /// Bolt11 invoice of payment
+        #[arg()]
+        target: String,
+    },
+    /// Fetch the aggregate local and remote channel balances (msat) of the node
+    LocalRemoteBalance,
+    /// Get node routing fees.
+    GetFees,
+    /// Fetch a list of the forwarded htlcs.
+    ListForwards {
+        /// The status of the forwards (succeeded, failed)
+        #[arg(short, long)]
+        status: Option<String>,
+        /// Only forwards into or out of this channel (ID in hex)
+        #[arg(long)]
+        channel: Option<String>,
+        /// Only forwards through channels with this peer (public key)
+        #[arg(short, long)]
+        peer: Option<String>,
+        #[command(flatten)]
+        page: PageArgs,
+    },
+    /// Fetch a list of historic (closed) channels
+    ListChannelHistory {
+        /// Only the channel with this ID (hex)
+        #[arg(long)]
+        channel: Option<String>,
+        /// Only channels with this peer (public key)
+        #[arg(short, long)]
+        peer: Option<String>,
+        #[command(flatten)]
+        page: PageArgs,
+    },
+    /// Report the forwarded volume, fees and failures per channel and per peer
+    ForwardStats {
+        /// UNIX timestamp, only forwards from this time on
+        #[arg(long)]
+        since: Option<i64>,
+        /// UNIX timestamp, only forwards from before this time
+        #[arg(long)]
+        until: Option<i64>,
+    },
+    /// Write an encrypted static channel backup to a file, start kld with it in recovery mode to have our peers force close the channels
+    ExportChannelBackup {
+        /// File to write the backup to
+        #[arg()]
+        path: PathBuf,
+    },
+    /// Bake a macaroon with fewer permissions than the one used by kld-cli and write it to a file
+    BakeMacaroon {
+        /// File to write the macaroon to
+        #[arg()]
+        path: PathBuf,
+        /// Only allow the read only routes.
+        #[arg(long)]
+        readonly: bool,
+        /// Comma separated route groups that the macaroon can access [info/sign/onchain/peers/channels/network/payments/invoices/offers/liquidity/events]
+        #[arg(short, long)]
+        permissions: Option<String>,
+        /// Largest amount (in satoshis) that a single payment or withdrawal can send.
+        #[arg(long)]
+        max_payment_sats: Option<u64>,
+        /// Seconds until the macaroon expires.
+        #[arg(short, long)]
+        expiry: Option<u64>,
+        /// Comma separated IP addresses or CIDR ranges that the macaroon can be used from.
+        #[arg(long)]
+        ip_ranges: Option<String>,
+        /// Sign the macaroon with a new root key of this name, so that it can be revoked on its own.
+        #[arg(long)]
+        root_key: Option<String>,
+    },
+    /// List the root keys of the macaroons that are accepted.
+    ListMacaroonRootKeys,
+    /// Delete a macaroon root key, every macaroon signed with it is revoked.
+    DeleteMacaroonRootKey {
+        /// ID of the root key.
+        #[arg()]
+        id: String,
+    },
+    /// Replace the admin and readonly macaroon root keys, every macaroon of the old keys is revoked. Macaroons baked with their own root key are not, delete their root keys to revoke them.
+    RotateMacaroonRootKeys {
+        /// File to write the new admin macaroon to.
+        #[arg()]
+        path: PathBuf,
+    },
+    /// Decode invoice
+    Decode { invoice: String },
+}
+
+/// Time range and paging of the list commands. Lists are ordered from old to new.
+#[derive(Args, Debug)]
+pub struct PageArgs {
+    /// UNIX timestamp, only list items from this time on
+    #[arg(long)]
+    pub since: Option<i64>,
+    /// UNIX timestamp, only list items from before this time
+    #[arg(long)]
+    pub until: Option<i64>,
+    /// Maximum number of items to list
+    #[arg(long)]
+    pub limit: Option<u32>,
+    /// ID of the last item of the previous page, to list the items after it
+    #[arg(long)]
+    pub cursor: Option<String>,
+}
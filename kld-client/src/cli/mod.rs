@@ -0,0 +1,198 @@
+//! The commands of kld-cli. They live in the library so that they can be tested against a kld
+//! API server without spawning the binary.
+
+mod client;
+mod commands;
+
+use anyhow::{bail, Result};
+use client::Api;
+pub use commands::{KldCliCommand, KldCliSubCommand};
+
+/// Run the command and return what kld-cli prints, which is empty when the API returns nothing.
+pub async fn run_command(args: KldCliCommand) -> Result<String> {
+    let api = Api::new(args.target, args.cert_path, args.macaroon_path)?;
+
+    let output = match args.command {
+        KldCliSubCommand::Sign { message } => api.sign(message).await?,
+        KldCliSubCommand::GetInfo => api.get_info().await?,
+        KldCliSubCommand::GetBalance => api.get_balance().await?,
+        KldCliSubCommand::NewAddress => api.new_address().await?,
+        KldCliSubCommand::Withdraw {
+            address,
+            amount: satoshis,
+            fee_rate,
+        } => api.withdraw(address, satoshis, fee_rate).await?,
+        KldCliSubCommand::ListFunds => api.list_funds().await?,
+        KldCliSubCommand::ListPeerChannels => api.list_peer_channels().await?,
+        KldCliSubCommand::ListPeers => api.list_peers().await?,
+        KldCliSubCommand::ConnectPeer { public_key } => api.connect_peer(public_key).await?,
+        KldCliSubCommand::DisconnectPeer { public_key } => api.disconnect_peer(public_key).await?,
+        KldCliSubCommand::OpenChannel {
+            public_key,
+            sats: satoshis,
+            push_msat,
+            announce,
+            fee_rate,
+        } => {
+            api.open_channel(public_key, satoshis, push_msat, announce, fee_rate)
+                .await?
+        }
+        KldCliSubCommand::SetChannelFee {
+            id,
+            base_fee,
+            ppm_fee,
+        } => api.set_channel_fee(id, base_fee, ppm_fee).await?,
+        KldCliSubCommand::CloseChannel {
+            id,
+            force_close: None,
+        } => api.close_channel(id).await?,
+        KldCliSubCommand::CloseChannel {
+            id,
+            force_close: Some(broadcast_flag),
+        } => {
+            let need_broadcast = match broadcast_flag.as_str() {
+                "broadcast" => true,
+                "no-broadcast" => false,
+                _ => bail!("the broadcast-flag need to `broadcast` or `no-broadcast`"),
+            };
+            api.force_close_channel(id, need_broadcast).await?
+        }
+        KldCliSubCommand::NetworkNodes { id } => api.list_network_nodes(id).await?,
+        KldCliSubCommand::NetworkChannels { id } => api.list_network_channels(id).await?,
+        KldCliSubCommand::FeeRates { style } => api.fee_rates(style).await?,
+        KldCliSubCommand::Keysend {
+            public_key,
+            amount,
+            nowait,
+        } => api.keysend(public_key, amount, nowait).await?,
+        KldCliSubCommand::GenerateInvoice {
+            amount,
+            label,
+            description,
+            expiry,
+        } => {
+            api.generate_invoice(amount, label, description, expiry)
+                .await?
+        }
+        KldCliSubCommand::GenerateHoldInvoice {
+            amount,
+            label,
+            description,
+            payment_hash,
+            expiry,
+        } => {
+            api.generate_hold_invoice(amount, label, description, payment_hash, expiry)
+                .await?
+        }
+        KldCliSubCommand::SettleHoldInvoice { preimage } => {
+            api.settle_hold_invoice(preimage).await?
+        }
+        KldCliSubCommand::CancelHoldInvoice { payment_hash } => {
+            api.cancel_hold_invoice(payment_hash).await?
+        }
+        KldCliSubCommand::ListInvoices { label, page } => api.list_invoices(label, page).await?,
+        KldCliSubCommand::PayInvoice {
+            bolt11,
+            amount,
+            label,
+            nowait,
+        } => api.pay_invoice(bolt11, amount, label, nowait).await?,
+        KldCliSubCommand::Probe {
+            pubkey,
+            invoice,
+            amount,
+        } => api.probe(pubkey, invoice, amount).await?,
+        KldCliSubCommand::ListPayments {
+            bolt11,
+            direction,
+            status,
+            page,
+        } => api.list_payments(bolt11, direction, status, page).await?,
+        KldCliSubCommand::GetPayment { id } => api.get_payment(id).await?,
+        KldCliSubCommand::PaymentDetail { id } => api.payment_detail(id).await?,
+        KldCliSubCommand::CreateOffer {
+            description,
+            amount,
+            expiry,
+            issuer,
+            label,
+        } => {
+            api.create_offer(description, amount, expiry, issuer, label)
+                .await?
+        }
+        KldCliSubCommand::ListOffers { label } => api.list_offers(label).await?,
+        KldCliSubCommand::PayOffer {
+            offer,
+            amount,
+            quantity,
+            payer_note,
+            label,
+        } => {
+            api.pay_offer(offer, amount, quantity, payer_note, label)
+                .await?
+        }
+        KldCliSubCommand::CreateRefund {
+            amount,
+            description,
+            expiry,
+            label,
+        } => {
+            api.create_refund(amount, description, expiry, label)
+                .await?
+        }
+        KldCliSubCommand::RequestRefundPayment { refund } => {
+            api.request_refund_payment(refund).await?
+        }
+        KldCliSubCommand::ListInvoiceRequests { offer_id } => {
+            api.list_invoice_requests(offer_id).await?
+        }
+        KldCliSubCommand::EstimateChannelLiquidity { scid, target } => {
+            api.estimate_channel_liquidity(scid, target).await?
+        }
+        KldCliSubCommand::LocalRemoteBalance => api.local_remote_balance().await?,
+        KldCliSubCommand::GetFees => api.get_fees().await?,
+        KldCliSubCommand::ListForwards {
+            status,
+            channel,
+            peer,
+            page,
+        } => api.list_forwards(status, channel, peer, page).await?,
+        KldCliSubCommand::ListChannelHistory {
+            channel,
+            peer,
+            page,
+        } => api.channel_history(channel, peer, page).await?,
+        KldCliSubCommand::ForwardStats { since, until } => api.forward_stats(since, until).await?,
+        KldCliSubCommand::ExportChannelBackup { path } => api.export_channel_backup(path).await?,
+        KldCliSubCommand::BakeMacaroon {
+            path,
+            readonly,
+            permissions,
+            max_payment_sats,
+            expiry,
+            ip_ranges,
+            root_key,
+        } => {
+            api.bake_macaroon(
+                path,
+                readonly,
+                permissions,
+                max_payment_sats,
+                expiry,
+                ip_ranges,
+                root_key,
+            )
+            .await?
+        }
+        KldCliSubCommand::ListMacaroonRootKeys => api.list_macaroon_root_keys().await?,
+        KldCliSubCommand::DeleteMacaroonRootKey { id } => api.delete_macaroon_root_key(id).await?,
+        KldCliSubCommand::RotateMacaroonRootKeys { path } => {
+            api.rotate_macaroon_root_keys(path).await?
+        }
+        KldCliSubCommand::Decode { invoice } => api.decode(invoice).await?,
+    };
+    if output == "null" {
+        return Ok(String::new());
+    }
+    Ok(output)
+}
@@ -0,0 +1,456 @@
+//! Async client of the kld REST API. Requests and responses use the types of the `api` crate and
+//! the ones generated from the OpenAPI document that kld serves at `/v1/openapi.json`.
+
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use api::{
+    routes, BakeMacaroon, BakeMacaroonResponse, CancelHoldInvoice, ChannelBackup, ChannelFee,
+    CreateOffer, CreateRefund, FeeRatesResponse, ForwardStats, FundChannel, FundChannelResponse,
+    GenerateHoldInvoice, GenerateInvoice, GenerateInvoiceResponse, GetInfo, Invoice,
+    InvoiceRequest, KeysendRequest, ListFunds, MacaroonRootKey, NetworkChannel, NetworkNode, Offer,
+    OfferResponse, Pagination, PayInvoice, PayOffer, PaymentDetailResponse, PaymentResponse,
+    PaymentStatusResponse, Peer, ProbeRequest, ProbeResponse, RequestRefundPayment,
+    RotateMacaroonRootKeysResponse, SetChannelFeeResponse, SettleHoldInvoice, SignRequest,
+    SignResponse, WalletBalance, WalletTransfer, WalletTransferResponse,
+};
+use codegen::{
+    get_v1_channel_history_response::GetV1ChannelHistoryResponseItem,
+    get_v1_channel_list_forwards_response::GetV1ChannelListForwardsResponseItem,
+    get_v1_channel_list_peer_channels_response::GetV1ChannelListPeerChannelsResponseItem,
+    get_v1_channel_localremotebal_response::GetV1ChannelLocalremotebalResponse,
+    get_v1_estimate_channel_liquidity_body::GetV1EstimateChannelLiquidityBody,
+    get_v1_estimate_channel_liquidity_response::GetV1EstimateChannelLiquidityResponse,
+    get_v1_get_fees_response::GetV1GetFeesResponse, get_v1_newaddr_response::GetV1NewaddrResponse,
+    get_v1_pay_list_payments_response::GetV1PayListPaymentsResponse,
+    get_v1_utility_decode_invoice_string_response::GetV1UtilityDecodeInvoiceStringResponse,
+    post_v1_peer_connect_body::PostV1PeerConnectBody,
+    post_v1_peer_connect_response::PostV1PeerConnectResponse,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    Certificate, Client, ClientBuilder, Method, RequestBuilder, StatusCode,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub mod cli;
+
+// For api codegen
+#[macro_use]
+extern crate serde;
+
+#[allow(clippy::all)]
+pub mod codegen {
+    #![allow(dead_code)]
+    include!(concat!(env!("OUT_DIR"), "/mod.rs"));
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// kld handled the request and responded with an error.
+    #[error("{status}: {}", .error.detail)]
+    Api {
+        status: StatusCode,
+        error: api::Error,
+    },
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Clone)]
+pub struct KldClient {
+    base_url: String,
+    client: Client,
+}
+
+impl KldClient {
+    pub fn new(host: SocketAddr, cert_pem: &[u8], macaroon: &[u8]) -> Result<KldClient> {
+        let mut headers = HeaderMap::new();
+        headers.insert("macaroon", HeaderValue::from_bytes(macaroon)?);
+        // Rustls does not support IP addresses (hostnames only) so we need to use native tls (openssl)
+        let client = ClientBuilder::new()
+            .add_root_certificate(Certificate::from_pem(cert_pem)?)
+            .use_native_tls()
+            .default_headers(headers)
+            .build()?;
+        Ok(KldClient::with_client(format!("https://{host}"), client))
+    }
+
+    /// Use a client that already has the TLS configuration and the macaroon header.
+    pub fn with_client(base_url: impl Into<String>, client: Client) -> KldClient {
+        KldClient {
+            base_url: base_url.into(),
+            client,
+        }
+    }
+
+    /// Call any route of the API.
+    pub async fn call<T: DeserializeOwned, B: Serialize>(
+        &self,
+        method: Method,
+        route: &str,
+        body: &B,
+    ) -> Result<T, Error> {
+        send(self.request_with_body(method, route, body)?).await
+    }
+
+    pub async fn openapi(&self) -> Result<serde_json::Value, Error> {
+        send(self.request(Method::GET, routes::OPENAPI)).await
+    }
+
+    pub async fn sign(&self, body: &SignRequest) -> Result<SignResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::SIGN, body)?).await
+    }
+
+    pub async fn get_info(&self) -> Result<GetInfo, Error> {
+        send(self.request(Method::GET, routes::GET_INFO)).await
+    }
+
+    pub async fn get_balance(&self) -> Result<WalletBalance, Error> {
+        send(self.request(Method::GET, routes::GET_BALANCE)).await
+    }
+
+    pub async fn new_address(&self) -> Result<GetV1NewaddrResponse, Error> {
+        send(self.request(Method::GET, routes::NEW_ADDR)).await
+    }
+
+    pub async fn withdraw(&self, body: &WalletTransfer) -> Result<WalletTransferResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::WITHDRAW, body)?).await
+    }
+
+    pub async fn list_funds(&self) -> Result<ListFunds, Error> {
+        send(self.request(Method::GET, routes::LIST_FUNDS)).await
+    }
+
+    pub async fn list_peer_channels(
+        &self,
+    ) -> Result<Vec<GetV1ChannelListPeerChannelsResponseItem>, Error> {
+        send(self.request(Method::GET, routes::LIST_PEER_CHANNELS)).await
+    }
+
+    pub async fn list_peers(&self) -> Result<Vec<Peer>, Error> {
+        send(self.request(Method::GET, routes::LIST_PEERS)).await
+    }
+
+    pub async fn connect_peer(
+        &self,
+        body: &PostV1PeerConnectBody,
+    ) -> Result<PostV1PeerConnectResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::CONNECT_PEER, body)?).await
+    }
+
+    pub async fn disconnect_peer(&self, id: &str) -> Result<(), Error> {
+        let route = routes::DISCONNECT_PEER.replace(":id", id);
+        send(self.request(Method::DELETE, &route)).await
+    }
+
+    pub async fn open_channel(&self, body: &FundChannel) -> Result<FundChannelResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::OPEN_CHANNEL, body)?).await
+    }
+
+    pub async fn set_channel_fee(&self, body: &ChannelFee) -> Result<SetChannelFeeResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::SET_CHANNEL_FEE, body)?).await
+    }
+
+    pub async fn close_channel(&self, id: &str) -> Result<(), Error> {
+        let route = routes::CLOSE_CHANNEL.replace(":id", id);
+        send(self.request(Method::DELETE, &route)).await
+    }
+
+    pub async fn force_close_channel(&self, id: &str, may_broadcast: bool) -> Result<(), Error> {
+        let route = if may_broadcast {
+            routes::FORCE_CLOSE_CHANNEL_WITH_BROADCAST
+        } else {
+            routes::FORCE_CLOSE_CHANNEL_WITHOUT_BROADCAST
+        };
+        send(self.request(Method::DELETE, &route.replace(":id", id))).await
+    }
+
+    pub async fn list_network_nodes(&self) -> Result<Vec<NetworkNode>, Error> {
+        send(self.request(Method::GET, routes::LIST_NETWORK_NODES)).await
+    }
+
+    pub async fn get_network_node(&self, id: &str) -> Result<Vec<NetworkNode>, Error> {
+        let route = routes::LIST_NETWORK_NODE.replace(":id", id);
+        send(self.request(Method::GET, &route)).await
+    }
+
+    pub async fn list_network_channels(&self) -> Result<Vec<NetworkChannel>, Error> {
+        send(self.request(Method::GET, routes::LIST_NETWORK_CHANNELS)).await
+    }
+
+    pub async fn get_network_channel(&self, id: &str) -> Result<Vec<NetworkChannel>, Error> {
+        let route = routes::LIST_NETWORK_CHANNEL.replace(":id", id);
+        send(self.request(Method::GET, &route)).await
+    }
+
+    /// Style is perkb or perkw.
+    pub async fn fee_rates(&self, style: &str) -> Result<FeeRatesResponse, Error> {
+        let route = routes::FEE_RATES.replace(":style", style);
+        send(self.request(Method::GET, &route)).await
+    }
+
+    pub async fn keysend(&self, body: &KeysendRequest) -> Result<PaymentResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::KEYSEND, body)?).await
+    }
+
+    pub async fn generate_invoice(
+        &self,
+        body: &GenerateInvoice,
+    ) -> Result<GenerateInvoiceResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::GENERATE_INVOICE, body)?).await
+    }
+
+    pub async fn generate_hold_invoice(
+        &self,
+        body: &GenerateHoldInvoice,
+    ) -> Result<GenerateInvoiceResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::GENERATE_HOLD_INVOICE, body)?).await
+    }
+
+    pub async fn settle_hold_invoice(&self, body: &SettleHoldInvoice) -> Result<(), Error> {
+        send(self.request_with_body(Method::POST, routes::SETTLE_HOLD_INVOICE, body)?).await
+    }
+
+    pub async fn cancel_hold_invoice(&self, body: &CancelHoldInvoice) -> Result<(), Error> {
+        send(self.request_with_body(Method::POST, routes::CANCEL_HOLD_INVOICE, body)?).await
+    }
+
+    pub async fn list_invoices(
+        &self,
+        label: Option<&str>,
+        page: &Pagination,
+    ) -> Result<Vec<Invoice>, Error> {
+        let params: Vec<(&str, &str)> = label.map(|l| ("label", l)).into_iter().collect();
+        send(
+            self.request(Method::GET, routes::LIST_INVOICES)
+                .query(&params)
+                .query(page),
+        )
+        .await
+    }
+
+    pub async fn pay_invoice(&self, body: &PayInvoice) -> Result<PaymentResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::PAY_INVOICE, body)?).await
+    }
+
+    pub async fn probe(&self, body: &ProbeRequest) -> Result<ProbeResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::PROBE, body)?).await
+    }
+
+    pub async fn list_payments(
+        &self,
+        bolt11: Option<&str>,
+        direction: Option<&str>,
+        status: Option<&str>,
+        page: &Pagination,
+    ) -> Result<GetV1PayListPaymentsResponse, Error> {
+        let mut params = vec![];
+        if let Some(bolt11) = bolt11 {
+            params.push(("invoice", bolt11));
+        }
+        if let Some(direction) = direction {
+            params.push(("direction", direction));
+        }
+        if let Some(status) = status {
+            params.push(("status", status));
+        }
+        send(
+            self.request(Method::GET, routes::LIST_PAYMENTS)
+                .query(&params)
+                .query(page),
+        )
+        .await
+    }
+
+    pub async fn get_payment(&self, id: &str) -> Result<PaymentStatusResponse, Error> {
+        let route = routes::GET_PAYMENT.replace(":id", id);
+        send(self.request(Method::GET, &route)).await
+    }
+
+    pub async fn payment_detail(&self, id: &str) -> Result<PaymentDetailResponse, Error> {
+        let route = routes::PAYMENT_DETAIL.replace(":id", id);
+        send(self.request(Method::GET, &route)).await
+    }
+
+    pub async fn create_offer(&self, body: &CreateOffer) -> Result<OfferResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::CREATE_OFFER, body)?).await
+    }
+
+    pub async fn list_offers(&self, label: Option<&str>) -> Result<Vec<Offer>, Error> {
+        let params: Vec<(&str, &str)> = label.map(|l| ("label", l)).into_iter().collect();
+        send(
+            self.request(Method::GET, routes::LIST_OFFERS)
+                .query(&params),
+        )
+        .await
+    }
+
+    pub async fn pay_offer(&self, body: &PayOffer) -> Result<PaymentResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::PAY_OFFER, body)?).await
+    }
+
+    pub async fn create_refund(&self, body: &CreateRefund) -> Result<OfferResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::CREATE_REFUND, body)?).await
+    }
+
+    pub async fn request_refund_payment(&self, body: &RequestRefundPayment) -> Result<(), Error> {
+        send(self.request_with_body(Method::POST, routes::REQUEST_REFUND_PAYMENT, body)?).await
+    }
+
+    pub async fn list_invoice_requests(
+        &self,
+        offer_id: Option<&str>,
+    ) -> Result<Vec<InvoiceRequest>, Error> {
+        let params: Vec<(&str, &str)> = offer_id.map(|o| ("offerId", o)).into_iter().collect();
+        send(
+            self.request(Method::GET, routes::LIST_INVOICE_REQUESTS)
+                .query(&params),
+        )
+        .await
+    }
+
+    pub async fn estimate_channel_liquidity(
+        &self,
+        body: &GetV1EstimateChannelLiquidityBody,
+    ) -> Result<GetV1EstimateChannelLiquidityResponse, Error> {
+        send(self.request_with_body(Method::GET, routes::ESTIMATE_CHANNEL_LIQUIDITY, body)?).await
+    }
+
+    pub async fn local_remote_balance(&self) -> Result<GetV1ChannelLocalremotebalResponse, Error> {
+        send(self.request(Method::GET, routes::LOCAL_REMOTE_BALANCE)).await
+    }
+
+    pub async fn get_fees(&self) -> Result<GetV1GetFeesResponse, Error> {
+        send(self.request(Method::GET, routes::GET_FEES)).await
+    }
+
+    pub async fn list_forwards(
+        &self,
+        status: Option<&str>,
+        channel: Option<&str>,
+        peer: Option<&str>,
+        page: &Pagination,
+    ) -> Result<Vec<GetV1ChannelListForwardsResponseItem>, Error> {
+        let mut params = vec![];
+        if let Some(status) = status {
+            params.push(("status", status));
+        }
+        params.extend(channel_and_peer(channel, peer));
+        send(
+            self.request(Method::GET, routes::LIST_FORWARDS)
+                .query(&params)
+                .query(page),
+        )
+        .await
+    }
+
+    pub async fn channel_history(
+        &self,
+        channel: Option<&str>,
+        peer: Option<&str>,
+        page: &Pagination,
+    ) -> Result<Vec<GetV1ChannelHistoryResponseItem>, Error> {
+        send(
+            self.request(Method::GET, routes::LIST_CHANNEL_HISTORY)
+                .query(&channel_and_peer(channel, peer))
+                .query(page),
+        )
+        .await
+    }
+
+    pub async fn forward_stats(
+        &self,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> Result<ForwardStats, Error> {
+        let mut params = vec![];
+        if let Some(since) = since {
+            params.push(("since", since));
+        }
+        if let Some(until) = until {
+            params.push(("until", until));
+        }
+        send(
+            self.request(Method::GET, routes::FORWARD_STATS)
+                .query(&params),
+        )
+        .await
+    }
+
+    pub async fn channel_backup(&self) -> Result<ChannelBackup, Error> {
+        send(self.request(Method::GET, routes::CHANNEL_BACKUP)).await
+    }
+
+    pub async fn bake_macaroon(&self, body: &BakeMacaroon) -> Result<BakeMacaroonResponse, Error> {
+        send(self.request_with_body(Method::POST, routes::BAKE_MACAROON, body)?).await
+    }
+
+    pub async fn list_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>, Error> {
+        send(self.request(Method::GET, routes::LIST_MACAROON_ROOT_KEYS)).await
+    }
+
+    pub async fn delete_macaroon_root_key(&self, id: &str) -> Result<(), Error> {
+        let route = routes::DELETE_MACAROON_ROOT_KEY.replace(":id", id);
+        send(self.request(Method::DELETE, &route)).await
+    }
+
+    pub async fn rotate_macaroon_root_keys(&self) -> Result<RotateMacaroonRootKeysResponse, Error> {
+        send(self.request(Method::POST, routes::ROTATE_MACAROON_ROOT_KEYS)).await
+    }
+
+    pub async fn decode_invoice(
+        &self,
+        invoice: &str,
+    ) -> Result<GetV1UtilityDecodeInvoiceStringResponse, Error> {
+        let route = routes::DECODE_INVOICE.replace(":invoice", invoice);
+        send(self.request(Method::GET, &route)).await
+    }
+
+    fn request(&self, method: Method, route: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.base_url, route))
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+    }
+
+    fn request_with_body<B: Serialize>(
+        &self,
+        method: Method,
+        route: &str,
+        body: &B,
+    ) -> Result<RequestBuilder, Error> {
+        let body = serde_json::to_string(body)?;
+        Ok(self.request(method, route).body(body))
+    }
+}
+
+async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, Error> {
+    let response = request.send().await?;
+    let status = response.status();
+    let bytes = response.bytes().await?;
+    if status.is_success() {
+        return Ok(serde_json::from_slice(&bytes)?);
+    }
+    // Errors from outside of our handlers, like a proxy, are not JSON.
+    let error = serde_json::from_slice(&bytes).unwrap_or_else(|_| api::Error {
+        status: status.to_string(),
+        detail: String::from_utf8_lossy(&bytes).to_string(),
+    });
+    Err(Error::Api { status, error })
+}
+
+fn channel_and_peer<'a>(
+    channel: Option<&'a str>,
+    peer: Option<&'a str>,
+) -> Vec<(&'a str, &'a str)> {
+    let mut params = vec![];
+    if let Some(channel) = channel {
+        params.push(("channel", channel));
+    }
+    if let Some(peer) = peer {
+        params.push(("peer", peer));
+    }
+    params
+}
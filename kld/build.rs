@@ -1,4 +1,3 @@
-use clap::CommandFactory;
 use paperclip::v2::{
     self,
     codegen::{DefaultEmitter, Emitter, EmitterState},
@@ -8,21 +7,9 @@ use paperclip::v2::{
 use std::env;
 use std::fs::File;
 
-use clap_complete::{generate_to, shells::Bash};
-
-include!("src/cli/commands.rs");
-
 fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
-    generate_api(out_dir.clone());
-    generate_cli_completion(out_dir);
-}
-
-fn generate_cli_completion(out_dir: String) {
-    let mut cmd = KldCliCommand::command();
-    let _path = generate_to(Bash, &mut cmd, "kld-cli", out_dir).unwrap();
-
-    println!("cargo:rerun-if-changed=src/cli/commands.rs");
+    generate_api(out_dir);
 }
 
 fn generate_api(out_dir: String) {
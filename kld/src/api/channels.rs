@@ -3,12 +3,20 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::api::SocketAddress;
-use crate::database::forward::ForwardStatus;
+use crate::database::channel::ChannelFilter;
+use crate::database::forward::{ForwardFilter, ForwardStatus};
 use crate::ldk::htlc_destination_to_string;
 use anyhow::Context;
+use api::ChannelAcceptancePolicy;
+use api::ChannelBackup;
 use api::ChannelFee;
+use api::ChannelForwardStats;
+use api::ForwardCounts;
+use api::ForwardStats;
 use api::FundChannel;
 use api::FundChannelResponse;
+use api::Pagination;
+use api::PeerForwardStats;
 use api::SetChannelFee;
 use api::SetChannelFeeResponse;
 use axum::extract::Path;
@@ -20,9 +28,16 @@ use lightning::events::HTLCDestination;
 use lightning::ln::channelmanager::ChannelDetails;
 use lightning::ln::features::ChannelTypeFeatures;
 use lightning::ln::ChannelId;
+use lightning::routing::gossip::NodeId;
 use lightning::util::config::MaxDustHTLCExposure;
+use uuid::Uuid;
 
-use crate::api::bad_request;
+use crate::api::{
+    bad_request, empty_string_as_none, from_hex_id, list_error,
+    macaroon_auth::MacaroonRestrictions, to_page,
+};
+use crate::ldk::analytics;
+use crate::ldk::channel_acceptor::InboundChannelPolicy;
 use crate::ldk::LightningInterface;
 use crate::ldk::PeerStatus;
 use crate::to_string_empty;
@@ -31,9 +46,9 @@ use super::codegen::get_v1_channel_history_response::GetV1ChannelHistoryResponse
 use super::codegen::get_v1_channel_list_forwards_response::{
     GetV1ChannelListForwardsResponseItem, GetV1ChannelListForwardsResponseItemStatus,
 };
-use super::codegen::get_v1_channel_list_peer_channels_response::GetV1ChannelListPeerChannelsResponseState;
+use super::codegen::get_v1_channel_list_peer_channels_response::GetV1ChannelListPeerChannelsResponseItemState;
 use super::codegen::get_v1_channel_list_peer_channels_response::{
-    GetV1ChannelListPeerChannelsResponse, GetV1ChannelListPeerChannelsResponseOpener,
+    GetV1ChannelListPeerChannelsResponseItem, GetV1ChannelListPeerChannelsResponseItemOpener,
 };
 use super::codegen::get_v1_channel_localremotebal_response::GetV1ChannelLocalremotebalResponse;
 use super::internal_server;
@@ -55,7 +70,7 @@ pub(crate) async fn list_peer_channels(
             .context("expected channel config")
             .map_err(internal_server)?;
 
-        response.push(GetV1ChannelListPeerChannelsResponse {
+        response.push(GetV1ChannelListPeerChannelsResponseItem {
             alias: lightning_interface
                 .alias_of(&channel.counterparty.node_id)
                 .unwrap_or_default(),
@@ -74,9 +89,9 @@ pub(crate) async fn list_peer_channels(
             funding_txid: channel.funding_txo.map(|x| x.txid.to_string()),
             htlcs: None,
             opener: if channel.is_outbound {
-                GetV1ChannelListPeerChannelsResponseOpener::Local
+                GetV1ChannelListPeerChannelsResponseItemOpener::Local
             } else {
-                GetV1ChannelListPeerChannelsResponseOpener::Remote
+                GetV1ChannelListPeerChannelsResponseItemOpener::Remote
             },
             peer_connected: peers
                 .iter()
@@ -89,9 +104,9 @@ pub(crate) async fn list_peer_channels(
             short_channel_id: channel.short_channel_id.map(|x| x.to_string()),
             spendable_msat: channel.outbound_capacity_msat as i64,
             state: if channel.is_usable {
-                GetV1ChannelListPeerChannelsResponseState::ChanneldNormal
+                GetV1ChannelListPeerChannelsResponseItemState::ChanneldNormal
             } else {
-                GetV1ChannelListPeerChannelsResponseState::Openingd
+                GetV1ChannelListPeerChannelsResponseItemState::Openingd
             },
             our_reserve_msat: channel
                 .unspendable_punishment_reserve
@@ -111,6 +126,7 @@ pub(crate) async fn list_peer_channels(
 
 pub(crate) async fn open_channel(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(restrictions): Extension<MacaroonRestrictions>,
     Json(fund_channel): Json<FundChannel>,
 ) -> Result<impl IntoResponse, ApiError> {
     let (public_key, net_address) = match fund_channel.id.split_once('@') {
@@ -134,6 +150,12 @@ pub(crate) async fn open_channel(
         .map(|x| x.parse::<u64>())
         .transpose()
         .map_err(bad_request)?;
+    // Opening moves the funding out of the wallet and the push to the peer, both count against the limit.
+    restrictions.check_payment_msat(Some(
+        value
+            .saturating_mul(1000)
+            .saturating_add(push_msat.unwrap_or_default()),
+    ))?;
 
     let mut user_config = lightning_interface.user_config();
     if let Some(announce) = fund_channel.announce {
@@ -298,11 +320,16 @@ pub(crate) async fn local_remote_balance(
 #[derive(Serialize, Deserialize)]
 pub struct ListForwardsQueryParams {
     pub status: Option<GetV1ChannelListForwardsResponseItemStatus>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub channel: Option<String>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub peer: Option<String>,
 }
 
 pub(crate) async fn list_forwards(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
     Query(params): Query<ListForwardsQueryParams>,
+    Query(pagination): Query<Pagination>,
 ) -> Result<impl IntoResponse, ApiError> {
     let status = match params.status {
         None => None,
@@ -310,13 +337,30 @@ pub(crate) async fn list_forwards(
         Some(GetV1ChannelListForwardsResponseItemStatus::Offered) => Some(ForwardStatus::Succeeded),
         _ => Some(ForwardStatus::Failed),
     };
+    let filter = ForwardFilter {
+        status,
+        channel_id: params
+            .channel
+            .as_deref()
+            .map(parse_channel_id)
+            .transpose()?,
+        peer: params.peer.as_deref().map(parse_peer).transpose()?,
+        page: to_page(&pagination)?,
+        cursor: pagination
+            .cursor
+            .as_deref()
+            .map(Uuid::parse_str)
+            .transpose()
+            .map_err(bad_request)?,
+    };
     let mut response = vec![];
     for forward in lightning_interface
-        .fetch_forwards(status)
+        .fetch_forwards(filter)
         .await
-        .map_err(internal_server)?
+        .map_err(list_error)?
     {
         response.push(GetV1ChannelListForwardsResponseItem {
+            id: Some(forward.id.to_string()),
             failcode: match forward.htlc_destination {
                 Some(HTLCDestination::NextHopChannel {
                     node_id: _,
@@ -362,13 +406,107 @@ pub(crate) async fn list_forwards(
     Ok(Json(response))
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct ForwardStatsQueryParams {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+pub(crate) async fn forward_stats(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ForwardStatsQueryParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let window = to_page(&Pagination {
+        since: params.since,
+        until: params.until,
+        ..Default::default()
+    })?;
+    let stats = lightning_interface
+        .forward_stats(window)
+        .await
+        .map_err(internal_server)?;
+    let mut channels: Vec<ChannelForwardStats> = stats
+        .channels
+        .into_iter()
+        .map(|(channel_id, channel)| ChannelForwardStats {
+            channel_id: channel_id.to_hex(),
+            peer_id: channel.peer.map(|peer| peer.to_string()),
+            stats: forward_counts(channel.counts),
+        })
+        .collect();
+    channels.sort_by(|a, b| {
+        b.stats
+            .fees_earned_msat
+            .cmp(&a.stats.fees_earned_msat)
+            .then_with(|| a.channel_id.cmp(&b.channel_id))
+    });
+    let mut peers: Vec<PeerForwardStats> = stats
+        .peers
+        .into_iter()
+        .map(|(peer, counts)| PeerForwardStats {
+            peer_id: peer.to_string(),
+            stats: forward_counts(counts),
+        })
+        .collect();
+    peers.sort_by(|a, b| {
+        b.stats
+            .fees_earned_msat
+            .cmp(&a.stats.fees_earned_msat)
+            .then_with(|| a.peer_id.cmp(&b.peer_id))
+    });
+    Ok(Json(ForwardStats {
+        since: params.since,
+        until: params.until,
+        total: forward_counts(stats.total),
+        channels,
+        peers,
+    }))
+}
+
+fn forward_counts(counts: analytics::ForwardCounts) -> ForwardCounts {
+    ForwardCounts {
+        succeeded_in: counts.succeeded_in,
+        succeeded_out: counts.succeeded_out,
+        volume_in_msat: counts.volume_in,
+        volume_out_msat: counts.volume_out,
+        fees_earned_msat: counts.fees,
+        effective_fee_rate_ppm: counts.effective_fee_rate_ppm(),
+        failed_in: counts.failed_in,
+        failed_out: counts.failed_out,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChannelHistoryQueryParams {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub channel: Option<String>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub peer: Option<String>,
+}
+
 pub(crate) async fn channel_history(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ChannelHistoryQueryParams>,
+    Query(pagination): Query<Pagination>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let filter = ChannelFilter {
+        channel_id: params
+            .channel
+            .as_deref()
+            .map(parse_channel_id)
+            .transpose()?,
+        peer: params.peer.as_deref().map(parse_peer).transpose()?,
+        page: to_page(&pagination)?,
+        cursor: pagination
+            .cursor
+            .as_deref()
+            .map(parse_channel_id)
+            .transpose()?,
+    };
     let channel_history = lightning_interface
-        .channel_history()
+        .channel_history(filter)
         .await
-        .map_err(internal_server)?;
+        .map_err(list_error)?;
 
     let mut response = vec![];
 
@@ -399,6 +537,16 @@ pub(crate) async fn channel_history(
     Ok(Json(response))
 }
 
+fn parse_channel_id(hex: &str) -> Result<ChannelId, ApiError> {
+    Ok(ChannelId::from_bytes(from_hex_id(hex)?))
+}
+
+fn parse_peer(public_key: &str) -> Result<NodeId, ApiError> {
+    Ok(NodeId::from_pubkey(
+        &PublicKey::from_str(public_key).map_err(bad_request)?,
+    ))
+}
+
 fn format_features(channel_type: ChannelTypeFeatures) -> Vec<String> {
     channel_type
         .to_string()
@@ -413,3 +561,41 @@ fn format_features(channel_type: ChannelTypeFeatures) -> Vec<String> {
         })
         .collect()
 }
+
+pub(crate) async fn channel_backup(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let backup = lightning_interface
+        .channel_backup()
+        .await
+        .map_err(internal_server)?;
+    let encrypted = lightning_interface
+        .encrypt_channel_backup(&backup)
+        .map_err(internal_server)?;
+    Ok(Json(ChannelBackup {
+        channels: backup.channels.len(),
+        timestamp: backup.timestamp,
+        backup: hex::encode(encrypted),
+    }))
+}
+
+pub(crate) async fn get_channel_acceptance_policy(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let policy = lightning_interface
+        .inbound_channel_policy()
+        .map_err(internal_server)?;
+    Ok(Json(ChannelAcceptancePolicy::from(policy)))
+}
+
+pub(crate) async fn set_channel_acceptance_policy(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(policy): Json<ChannelAcceptancePolicy>,
+) -> Result<impl IntoResponse, ApiError> {
+    let policy = InboundChannelPolicy::try_from(policy).map_err(bad_request)?;
+    lightning_interface
+        .set_inbound_channel_policy(policy.clone())
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(ChannelAcceptancePolicy::from(policy)))
+}
@@ -5,22 +5,30 @@ use std::{
 };
 
 use anyhow::anyhow;
-use api::{GenerateInvoice, GenerateInvoiceResponse, Invoice, InvoiceStatus};
+use api::{
+    CancelHoldInvoice, GenerateHoldInvoice, GenerateInvoice, GenerateInvoiceResponse, Invoice,
+    InvoiceStatus, Pagination, SettleHoldInvoice,
+};
 use axum::{
     extract::{Path, Query},
     response::IntoResponse,
     Extension, Json,
 };
-use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use lightning::ln::{PaymentHash, PaymentPreimage};
 use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription};
 
 use super::{
     codegen::get_v1_utility_decode_invoice_string_response::{
         GetV1UtilityDecodeInvoiceStringResponse, GetV1UtilityDecodeInvoiceStringResponseType,
     },
-    empty_string_as_none,
+    empty_string_as_none, from_hex_id, list_error, to_page,
+};
+use crate::{
+    database::invoice::{HoldInvoiceStatus, InvoiceFilter},
+    ldk::LightningInterface,
+    MillisatAmount,
 };
-use crate::{ldk::LightningInterface, MillisatAmount};
 
 use super::{bad_request, internal_server, ApiError};
 
@@ -53,6 +61,64 @@ pub(crate) async fn generate_invoice(
     Ok(Json(response))
 }
 
+pub(crate) async fn generate_hold_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(invoice_request): Json<GenerateHoldInvoice>,
+) -> Result<impl IntoResponse, ApiError> {
+    if invoice_request.label.len() > 100 {
+        return Err(bad_request(anyhow!("Label max length is 100 chars")));
+    }
+    let payment_hash =
+        PaymentHash(<[u8; 32]>::from_hex(&invoice_request.payment_hash).map_err(bad_request)?);
+    let invoice = lightning_interface
+        .generate_hold_invoice(
+            invoice_request.label,
+            invoice_request.amount,
+            invoice_request.description,
+            invoice_request.expiry,
+            payment_hash,
+        )
+        .await
+        .map_err(internal_server)?;
+
+    let response = GenerateInvoiceResponse {
+        payment_hash: invoice.bolt11.payment_hash().to_hex(),
+        expires_at: invoice
+            .bolt11
+            .expires_at()
+            .ok_or_else(|| bad_request(anyhow!("expiry is too far in the future")))?
+            .as_secs() as u32,
+        bolt11: invoice.bolt11.to_string(),
+    };
+    Ok(Json(response))
+}
+
+pub(crate) async fn settle_hold_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(settle_request): Json<SettleHoldInvoice>,
+) -> Result<impl IntoResponse, ApiError> {
+    let preimage =
+        PaymentPreimage(<[u8; 32]>::from_hex(&settle_request.preimage).map_err(bad_request)?);
+    lightning_interface
+        .settle_hold_invoice(preimage)
+        .await
+        .map_err(bad_request)?;
+    Ok(Json(()))
+}
+
+pub(crate) async fn cancel_hold_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(cancel_request): Json<CancelHoldInvoice>,
+) -> Result<impl IntoResponse, ApiError> {
+    let payment_hash =
+        PaymentHash(<[u8; 32]>::from_hex(&cancel_request.payment_hash).map_err(bad_request)?);
+    lightning_interface
+        .cancel_hold_invoice(payment_hash)
+        .await
+        .map_err(bad_request)?;
+    Ok(Json(()))
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListInvoiceParams {
@@ -63,17 +129,28 @@ pub struct ListInvoiceParams {
 pub(crate) async fn list_invoices(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
     Query(params): Query<ListInvoiceParams>,
+    Query(pagination): Query<Pagination>,
 ) -> Result<impl IntoResponse, ApiError> {
     if let Some(label) = &params.label {
         if label.len() > 100 {
             return Err(bad_request(anyhow!("Label max length is 100 chars")));
         }
     }
+    let filter = InvoiceFilter {
+        label: params.label,
+        page: to_page(&pagination)?,
+        cursor: pagination
+            .cursor
+            .as_deref()
+            .map(from_hex_id)
+            .transpose()?
+            .map(PaymentHash),
+    };
     let mut response = vec![];
     let invoices = lightning_interface
-        .list_invoices(params.label)
+        .list_invoices(filter)
         .await
-        .map_err(internal_server)?;
+        .map_err(list_error)?;
     for invoice in invoices {
         let description = match invoice.bolt11.description() {
             lightning_invoice::Bolt11InvoiceDescription::Direct(d) => d.to_string(),
@@ -85,6 +162,10 @@ pub(crate) async fn list_invoices(
             .fold(MillisatAmount::default(), |sum, p| sum + p.amount);
         let status = if !invoice.payments.is_empty() {
             InvoiceStatus::Paid
+        } else if invoice.hold_status == Some(HoldInvoiceStatus::Accepted) {
+            InvoiceStatus::Held
+        } else if invoice.hold_status == Some(HoldInvoiceStatus::Cancelled) {
+            InvoiceStatus::Cancelled
         } else if invoice.bolt11.expiry_time()
             > SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use api::JitChannelSession;
+use axum::{response::IntoResponse, Extension, Json};
+
+use crate::ldk::LightningInterface;
+
+use super::{internal_server, ApiError};
+
+pub(crate) async fn get_jit_channel_offer(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let offer = lightning_interface
+        .jit_channel_offer()
+        .ok_or_else(|| ApiError::NotFound("LSPS2 is disabled".to_string()))?;
+    Ok(Json(offer))
+}
+
+pub(crate) async fn list_jit_channels(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let sessions: Vec<JitChannelSession> = lightning_interface
+        .list_jit_channels()
+        .map_err(internal_server)?
+        .into_iter()
+        .map(JitChannelSession::from)
+        .collect();
+    Ok(Json(sessions))
+}
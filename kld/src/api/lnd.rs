@@ -0,0 +1,465 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use api::{
+    lnd::{
+        AddInvoice, AddInvoiceResponse, Amount, Chain, Channel, ChannelBalance, GetInfo, Invoice,
+        InvoiceState, ListChannels, ListInvoices, ListPayments, ListPeers, NewAddressResponse,
+        PayReq, Payment, PaymentFailureReason, PaymentStatus, PaymentUpdate, Peer, SendCoins,
+        SendCoinsResponse, SendPayment, WalletBalance,
+    },
+    FeeRate,
+};
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
+use base64::{engine::general_purpose, Engine};
+use bitcoin::{hashes::hex::ToHex, secp256k1::PublicKey, Address, Network};
+use lightning::routing::gossip::NodeId;
+use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    bitcoind::bitcoind_interface::BitcoindInterface,
+    database::{
+        invoice::{HoldInvoiceStatus, Invoice as KldInvoice, InvoiceFilter},
+        payment::{self, PaymentDirection, PaymentFilter},
+    },
+    ldk::{payment_limits::PaymentLimits, LightningInterface, PeerStatus},
+    wallet::WalletInterface,
+    MillisatAmount, VERSION,
+};
+
+use super::{bad_request, internal_server, macaroon_auth::MacaroonRestrictions, ApiError};
+
+pub(crate) async fn get_info(
+    Extension(bitcoind_interface): Extension<Arc<dyn BitcoindInterface + Send + Sync>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let synced = lightning_interface
+        .synced()
+        .await
+        .map_err(internal_server)?;
+    let blockchain_info = bitcoind_interface
+        .get_blockchain_info()
+        .await
+        .map_err(internal_server)?;
+    let identity_pubkey = lightning_interface.identity_pubkey().to_string();
+    let network = lightning_interface.network();
+    let info = GetInfo {
+        version: VERSION.to_string(),
+        uris: lightning_interface
+            .public_addresses()
+            .into_iter()
+            .map(|address| format!("{identity_pubkey}@{address}"))
+            .collect(),
+        identity_pubkey,
+        alias: lightning_interface.alias(),
+        color: format!("#{}", lightning_interface.color()),
+        num_pending_channels: lightning_interface.num_pending_channels() as u32,
+        num_active_channels: lightning_interface.num_active_channels() as u32,
+        num_inactive_channels: lightning_interface.num_inactive_channels() as u32,
+        num_peers: lightning_interface.num_peers() as u32,
+        block_height: blockchain_info.blocks as u32,
+        block_hash: blockchain_info.best_block_hash.to_string(),
+        synced_to_chain: synced,
+        synced_to_graph: synced,
+        testnet: network != Network::Bitcoin,
+        chains: vec![Chain {
+            chain: "bitcoin".to_string(),
+            network: match network {
+                Network::Bitcoin => "mainnet".to_string(),
+                network => network.to_string(),
+            },
+        }],
+    };
+    Ok(Json(info))
+}
+
+pub(crate) async fn wallet_balance(
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let balance = wallet.balance().map_err(internal_server)?;
+    let unconfirmed_balance = balance.untrusted_pending + balance.trusted_pending;
+    Ok(Json(WalletBalance {
+        total_balance: balance.confirmed + unconfirmed_balance,
+        confirmed_balance: balance.confirmed,
+        unconfirmed_balance,
+    }))
+}
+
+pub(crate) async fn channel_balance(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (mut local, mut remote, mut pending_local, mut pending_remote) = (0, 0, 0, 0);
+    for channel in lightning_interface.list_channels() {
+        let remote_msat = channel.channel_value_satoshis * 1000 - channel.balance_msat;
+        if channel.is_channel_ready {
+            local += channel.balance_msat;
+            remote += remote_msat;
+        } else {
+            pending_local += channel.balance_msat;
+            pending_remote += remote_msat;
+        }
+    }
+    Ok(Json(ChannelBalance {
+        balance: local / 1000,
+        local_balance: Amount::from_msat(local),
+        remote_balance: Amount::from_msat(remote),
+        pending_open_local_balance: Amount::from_msat(pending_local),
+        pending_open_remote_balance: Amount::from_msat(pending_remote),
+    }))
+}
+
+pub(crate) async fn list_channels(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let channels = lightning_interface
+        .list_channels()
+        .into_iter()
+        .filter(|channel| channel.is_channel_ready)
+        .map(|channel| {
+            let local_balance = channel.balance_msat / 1000;
+            Channel {
+                active: channel.is_usable,
+                remote_pubkey: channel.counterparty.node_id.to_string(),
+                channel_point: channel
+                    .funding_txo
+                    .map(|txo| format!("{}:{}", txo.txid, txo.index))
+                    .unwrap_or_default(),
+                chan_id: channel.short_channel_id.unwrap_or_default(),
+                capacity: channel.channel_value_satoshis,
+                local_balance,
+                remote_balance: channel.channel_value_satoshis - local_balance,
+                private: !channel.is_public,
+                initiator: channel.is_outbound,
+                local_chan_reserve_sat: channel.unspendable_punishment_reserve.unwrap_or_default(),
+                remote_chan_reserve_sat: channel.counterparty.unspendable_punishment_reserve,
+                peer_alias: lightning_interface
+                    .alias_of(&channel.counterparty.node_id)
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+    Ok(Json(ListChannels { channels }))
+}
+
+pub(crate) async fn list_peers(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let peers = lightning_interface
+        .list_peers()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .filter(|peer| peer.status == PeerStatus::Connected)
+        .map(|peer| Peer {
+            pub_key: peer.public_key.to_string(),
+            address: peer
+                .net_address
+                .map(|address| address.to_string())
+                .unwrap_or_default(),
+        })
+        .collect();
+    Ok(Json(ListPeers { peers }))
+}
+
+pub(crate) async fn list_invoices(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let invoices = lightning_interface
+        .list_invoices(InvoiceFilter::default())
+        .await
+        .map_err(internal_server)?
+        .iter()
+        .map(to_lnd_invoice)
+        .collect();
+    Ok(Json(ListInvoices { invoices }))
+}
+
+fn to_lnd_invoice(invoice: &KldInvoice) -> Invoice {
+    let amount_paid: MillisatAmount = invoice.payments.iter().map(|p| p.amount).sum();
+    let state = if !invoice.payments.is_empty() {
+        InvoiceState::Settled
+    } else if invoice.hold_status == Some(HoldInvoiceStatus::Accepted) {
+        InvoiceState::Accepted
+    } else if invoice.hold_status == Some(HoldInvoiceStatus::Cancelled)
+        || invoice.bolt11.is_expired()
+    {
+        InvoiceState::Canceled
+    } else {
+        InvoiceState::Open
+    };
+    let value_msat = invoice.bolt11.amount_milli_satoshis().unwrap_or_default();
+    Invoice {
+        memo: match invoice.bolt11.description() {
+            Bolt11InvoiceDescription::Direct(description) => description.to_string(),
+            Bolt11InvoiceDescription::Hash(_) => String::new(),
+        },
+        r_preimage: invoice
+            .payments
+            .iter()
+            .find_map(|p| p.preimage)
+            .map(|preimage| general_purpose::STANDARD.encode(preimage.0))
+            .unwrap_or_default(),
+        r_hash: general_purpose::STANDARD.encode(invoice.payment_hash.0),
+        value: value_msat / 1000,
+        value_msat,
+        settled: state == InvoiceState::Settled,
+        creation_date: invoice.bolt11.duration_since_epoch().as_secs(),
+        settle_date: invoice
+            .payments
+            .first()
+            .map(|p| p.timestamp.unix_timestamp() as u64)
+            .unwrap_or_default(),
+        payment_request: invoice.bolt11.to_string(),
+        expiry: invoice.bolt11.expiry_time().as_secs(),
+        amt_paid_sat: amount_paid / 1000,
+        amt_paid_msat: amount_paid,
+        state,
+        payment_addr: general_purpose::STANDARD.encode(invoice.bolt11.payment_secret().0),
+    }
+}
+
+pub(crate) async fn add_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(add_invoice): Json<AddInvoice>,
+) -> Result<impl IntoResponse, ApiError> {
+    let amount = if add_invoice.value_msat > 0 {
+        Some(add_invoice.value_msat)
+    } else if add_invoice.value > 0 {
+        Some(add_invoice.value * 1000)
+    } else {
+        None
+    };
+    let expiry = match add_invoice.expiry {
+        0 => None,
+        expiry => Some(u32::try_from(expiry).map_err(bad_request)?),
+    };
+    // LND invoices have no label, but kld requires a unique one.
+    let invoice = lightning_interface
+        .generate_invoice(Uuid::new_v4().to_string(), amount, add_invoice.memo, expiry)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(AddInvoiceResponse {
+        r_hash: general_purpose::STANDARD.encode(invoice.payment_hash.0),
+        payment_request: invoice.bolt11.to_string(),
+        payment_addr: general_purpose::STANDARD.encode(invoice.bolt11.payment_secret().0),
+    }))
+}
+
+pub(crate) async fn decode_pay_req(
+    Path(pay_req): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let bolt11 = Bolt11Invoice::from_str(&pay_req).map_err(bad_request)?;
+    let amount = bolt11.amount_milli_satoshis().unwrap_or_default();
+    let (description, description_hash) = match bolt11.description() {
+        Bolt11InvoiceDescription::Direct(description) => (description.to_string(), String::new()),
+        Bolt11InvoiceDescription::Hash(hash) => (String::new(), hash.0.to_hex()),
+    };
+    Ok(Json(PayReq {
+        destination: bolt11
+            .payee_pub_key()
+            .cloned()
+            .unwrap_or_else(|| bolt11.recover_payee_pub_key())
+            .to_string(),
+        payment_hash: bolt11.payment_hash().to_hex(),
+        num_satoshis: amount / 1000,
+        num_msat: amount,
+        timestamp: bolt11.duration_since_epoch().as_secs(),
+        expiry: bolt11.expiry_time().as_secs(),
+        description,
+        description_hash,
+        cltv_expiry: bolt11.min_final_cltv_expiry_delta(),
+        payment_addr: general_purpose::STANDARD.encode(bolt11.payment_secret().0),
+    }))
+}
+
+pub(crate) async fn list_payments(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let payments = lightning_interface
+        .list_payments(PaymentFilter {
+            direction: Some(PaymentDirection::Outbound),
+            ..Default::default()
+        })
+        .await
+        .map_err(internal_server)?
+        .iter()
+        .map(to_lnd_payment)
+        .collect();
+    Ok(Json(ListPayments { payments }))
+}
+
+pub(crate) async fn send_payment(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(restrictions): Extension<MacaroonRestrictions>,
+    Json(send_payment): Json<SendPayment>,
+) -> Result<impl IntoResponse, ApiError> {
+    let amount = if send_payment.amt_msat > 0 {
+        Some(send_payment.amt_msat)
+    } else if send_payment.amt > 0 {
+        Some(send_payment.amt * 1000)
+    } else {
+        None
+    };
+    let fee_limit = if send_payment.fee_limit_msat > 0 {
+        Some(send_payment.fee_limit_msat)
+    } else if send_payment.fee_limit_sat > 0 {
+        Some(send_payment.fee_limit_sat * 1000)
+    } else {
+        None
+    };
+    // A fixed fee limit is a fee that is always allowed on top of a zero percentage.
+    let limits = PaymentLimits {
+        max_fee_percent: fee_limit.map(|_| 0.0),
+        exempt_fee_msat: fee_limit,
+        retry_for: (send_payment.timeout_seconds > 0)
+            .then(|| Duration::from_secs(send_payment.timeout_seconds as u64)),
+        max_cltv_expiry_delta: None,
+    };
+    let payment = if !send_payment.payment_request.is_empty() {
+        let mut invoice: KldInvoice = send_payment
+            .payment_request
+            .try_into()
+            .map_err(bad_request)?;
+        match (invoice.amount, amount) {
+            (Some(_), Some(_)) => {
+                return Err(bad_request(anyhow!(
+                    "amount must not be specified when paying an invoice with an amount"
+                )))
+            }
+            (None, None) => return Err(bad_request(anyhow!("amount must be specified"))),
+            (None, amount) => invoice.amount = amount,
+            (Some(_), None) => (),
+        }
+        restrictions.check_routed_payment_msat(invoice.amount, |amount| {
+            lightning_interface.max_fee_msat(&limits, amount)
+        })?;
+        lightning_interface
+            .pay_invoice(invoice, None, limits, true)
+            .await
+            .map_err(internal_server)?
+    } else {
+        let dest = general_purpose::STANDARD
+            .decode(&send_payment.dest)
+            .map_err(bad_request)?;
+        let payee = PublicKey::from_slice(&dest).map_err(bad_request)?;
+        let amount = amount
+            .ok_or_else(|| bad_request(anyhow!("amount is required for a keysend payment")))?;
+        restrictions.check_routed_payment_msat(Some(amount), |amount| {
+            lightning_interface.max_fee_msat(&limits, amount)
+        })?;
+        lightning_interface
+            .keysend_payment(NodeId::from_pubkey(&payee), amount, limits, true)
+            .await
+            .map_err(internal_server)?
+    };
+    Ok(Json(PaymentUpdate {
+        result: to_lnd_payment(&payment),
+    }))
+}
+
+fn to_lnd_payment(payment: &payment::Payment) -> Payment {
+    let (status, failure_reason) = match payment.status {
+        payment::PaymentStatus::Pending => (
+            PaymentStatus::InFlight,
+            PaymentFailureReason::FailureReasonNone,
+        ),
+        payment::PaymentStatus::Succeeded => (
+            PaymentStatus::Succeeded,
+            PaymentFailureReason::FailureReasonNone,
+        ),
+        payment::PaymentStatus::RecipientRejected => (
+            PaymentStatus::Failed,
+            PaymentFailureReason::FailureReasonIncorrectPaymentDetails,
+        ),
+        payment::PaymentStatus::RetriesExhausted | payment::PaymentStatus::Expired => (
+            PaymentStatus::Failed,
+            PaymentFailureReason::FailureReasonTimeout,
+        ),
+        payment::PaymentStatus::RouteNotFound => (
+            PaymentStatus::Failed,
+            PaymentFailureReason::FailureReasonNoRoute,
+        ),
+        payment::PaymentStatus::UserAbandoned | payment::PaymentStatus::Error => (
+            PaymentStatus::Failed,
+            PaymentFailureReason::FailureReasonError,
+        ),
+    };
+    let fee = payment.fee.unwrap_or_default();
+    Payment {
+        payment_hash: payment.hash.map(|h| h.0.to_hex()).unwrap_or_default(),
+        value_sat: payment.amount / 1000,
+        value_msat: payment.amount,
+        payment_preimage: payment.preimage.map(|p| p.0.to_hex()).unwrap_or_default(),
+        payment_request: payment
+            .bolt11
+            .as_ref()
+            .map(|bolt11| bolt11.to_string())
+            .unwrap_or_default(),
+        status,
+        fee_sat: fee / 1000,
+        fee_msat: fee,
+        creation_date: payment.timestamp.unix_timestamp() as u64,
+        creation_time_ns: payment.timestamp.unix_timestamp_nanos() as u64,
+        failure_reason,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewAddressParams {
+    #[serde(rename = "type")]
+    pub address_type: Option<String>,
+}
+
+pub(crate) async fn new_address(
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Query(params): Query<NewAddressParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Only native segwit addresses are supported.
+    if params
+        .address_type
+        .is_some_and(|t| t != "0" && t != "WITNESS_PUBKEY_HASH")
+    {
+        return Err(bad_request(anyhow!("Unsupported address type")));
+    }
+    let address_info = wallet.new_external_address().map_err(internal_server)?;
+    Ok(Json(NewAddressResponse {
+        address: address_info.address.to_string(),
+    }))
+}
+
+pub(crate) async fn send_coins(
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Extension(restrictions): Extension<MacaroonRestrictions>,
+    Json(send_coins): Json<SendCoins>,
+) -> Result<impl IntoResponse, ApiError> {
+    let address = Address::from_str(&send_coins.addr).map_err(bad_request)?;
+    let amount = if send_coins.send_all {
+        restrictions.check_payment_msat(None)?;
+        u64::MAX
+    } else if send_coins.amount > 0 {
+        restrictions.check_payment_msat(Some(send_coins.amount.saturating_mul(1000)))?;
+        send_coins.amount
+    } else {
+        return Err(bad_request(anyhow!("amount must be specified")));
+    };
+    // One virtual byte is four weight units.
+    let fee_rate = match send_coins.sat_per_vbyte {
+        0 => None,
+        sat_per_vbyte => Some(FeeRate::PerKw(
+            u32::try_from(sat_per_vbyte.saturating_mul(250)).map_err(bad_request)?,
+        )),
+    };
+    let (_, tx_details) = wallet
+        .transfer(address, amount, fee_rate, None, vec![])
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(SendCoinsResponse {
+        txid: tx_details.txid.to_string(),
+    }))
+}
@@ -3,34 +3,96 @@ use bitcoin::hashes::hex::FromHex;
 use hyper::header;
 #[cfg(not(test))]
 use std::fs;
-use std::sync::Arc;
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::SystemTime,
+};
 #[cfg(test)]
 use test_utils::fake_fs as fs;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use api::{
+    routes, BakeMacaroon, BakeMacaroonResponse, MacaroonPermission, RotateMacaroonRootKeysResponse,
+};
 use axum::{
     async_trait,
-    extract::FromRequestParts,
+    extract::{ConnectInfo, FromRequestParts, MatchedPath, Path},
     http::{request::Parts, Request, StatusCode},
     middleware::Next,
     response::IntoResponse,
-    Extension,
+    Extension, Json,
 };
-use macaroon::{ByteString, Macaroon, MacaroonKey, Verifier};
+use ipnet::IpNet;
+use macaroon::{ByteString, Caveat, Macaroon, MacaroonKey, Verifier};
+use uuid::Uuid;
+
+use crate::{database::macaroon_root_key::MacaroonRootKey, ldk::LightningInterface};
+
+use super::{bad_request, internal_server, unauthorized, ApiError};
+
+const ROLES: &str = "roles";
+const PERMISSIONS: &str = "permissions";
+const MAX_PAYMENT_SATS: &str = "max_payment_sats";
+const TIME_BEFORE: &str = "time-before";
+const IP: &str = "ip";
 
-use super::{unauthorized, ApiError};
+// Names of the root keys of the macaroons that are written to the data directory.
+const ADMIN: &str = "admin";
+const READONLY: &str = "readonly";
 
+// Macaroons from before there were root keys have their role as identifier and are signed with the
+// seed. They are accepted as macaroons of the first admin and readonly root keys, which get these
+// IDs, until those are rotated.
+const LEGACY_ADMIN_ID: Uuid = Uuid::from_u128(1);
+const LEGACY_READONLY_ID: Uuid = Uuid::from_u128(2);
+
+/// Creates and verifies macaroons. The root keys are read from the database for every request, so
+/// a root key that is revoked on one node is revoked on every node that shares the database.
 pub struct MacaroonAuth {
-    key: MacaroonKey,
+    seed: [u8; 32],
+    data_dir: String,
 }
 
 impl MacaroonAuth {
-    pub fn init(seed: &[u8; 32], data_dir: &str) -> Result<MacaroonAuth> {
+    /// Create the default admin and readonly root keys when they are missing and write their
+    /// macaroons to the data directory.
+    pub async fn init(
+        seed: &[u8; 32],
+        data_dir: &str,
+        lightning_interface: &(dyn LightningInterface + Send + Sync),
+    ) -> Result<MacaroonAuth> {
+        let mut root_keys = lightning_interface.list_macaroon_root_keys().await?;
+        for (name, id) in [(ADMIN, LEGACY_ADMIN_ID), (READONLY, LEGACY_READONLY_ID)] {
+            if !root_keys.iter().any(|root_key| root_key.name == name) {
+                let root_key = MacaroonRootKey {
+                    id,
+                    ..MacaroonRootKey::new(name.to_string())
+                };
+                lightning_interface
+                    .create_macaroon_root_key(root_key.clone())
+                    .await?;
+                root_keys.push(root_key);
+            }
+        }
+        let macaroon_auth = MacaroonAuth::new(seed, data_dir)?;
+        macaroon_auth.write_macaroons(&root_keys)?;
+        Ok(macaroon_auth)
+    }
+
+    fn new(seed: &[u8; 32], data_dir: &str) -> Result<MacaroonAuth> {
         macaroon::initialize()?;
-        let key = MacaroonKey::generate(seed);
+        Ok(MacaroonAuth {
+            seed: *seed,
+            data_dir: data_dir.to_string(),
+        })
+    }
 
-        let admin_macaroon = Self::admin_macaroon(&key)?;
-        let readonly_macaroon = Self::readonly_macaroon(&key)?;
+    fn write_macaroons(&self, root_keys: &[MacaroonRootKey]) -> Result<()> {
+        let data_dir = &self.data_dir;
+        let admin_macaroon = self.admin_macaroon(root_keys)?;
+        let readonly_macaroon = self.readonly_macaroon(root_keys)?;
 
         let mut buf = vec![];
         let base64 = admin_macaroon.serialize(macaroon::Format::V2)?;
@@ -48,35 +110,397 @@ impl MacaroonAuth {
             format!("{data_dir}/macaroons/readonly.macaroon"),
             readonly_macaroon.serialize(macaroon::Format::V2)?,
         )?;
+        Ok(())
+    }
+
+    /// The admin and readonly root keys can only be rotated, not deleted.
+    pub fn is_default(name: &str) -> bool {
+        name == ADMIN || name == READONLY
+    }
+
+    /// Sign the caveats of the macaroon with a new root key, so that the new macaroon can be
+    /// revoked without revoking the one it was baked from.
+    pub async fn rekey(
+        &self,
+        macaroon: &Macaroon,
+        name: String,
+        lightning_interface: &(dyn LightningInterface + Send + Sync),
+    ) -> Result<Macaroon> {
+        let root_key = MacaroonRootKey::new(name);
+        let mut rekeyed = self.create_macaroon(&root_key)?;
+        for caveat in macaroon.first_party_caveats() {
+            if let Caveat::FirstParty(caveat) = caveat {
+                rekeyed.add_first_party_caveat(caveat.predicate());
+            }
+        }
+        lightning_interface
+            .create_macaroon_root_key(root_key)
+            .await?;
+        Ok(rekeyed)
+    }
 
-        Ok(MacaroonAuth { key })
+    /// Replace the admin and readonly root keys and rewrite their macaroons. Every macaroon of
+    /// the old keys, including the ones baked from them, is revoked. Macaroons that were baked
+    /// with their own root key are not, so their root keys are returned with the new macaroons.
+    /// They have to be deleted through the root key route to revoke those macaroons.
+    pub async fn rotate(
+        &self,
+        lightning_interface: &(dyn LightningInterface + Send + Sync),
+    ) -> Result<(Macaroon, Macaroon, Vec<MacaroonRootKey>)> {
+        let (old_root_keys, remaining_root_keys): (Vec<MacaroonRootKey>, Vec<MacaroonRootKey>) =
+            lightning_interface
+                .list_macaroon_root_keys()
+                .await?
+                .into_iter()
+                .partition(|root_key| MacaroonAuth::is_default(&root_key.name));
+        let mut root_keys = vec![];
+        for name in [ADMIN, READONLY] {
+            let root_key = MacaroonRootKey::new(name.to_string());
+            lightning_interface
+                .create_macaroon_root_key(root_key.clone())
+                .await?;
+            root_keys.push(root_key);
+        }
+        self.write_macaroons(&root_keys)?;
+        for root_key in old_root_keys {
+            lightning_interface
+                .delete_macaroon_root_key(root_key.id)
+                .await?;
+        }
+        Ok((
+            self.admin_macaroon(&root_keys)?,
+            self.readonly_macaroon(&root_keys)?,
+            remaining_root_keys,
+        ))
+    }
+
+    // The newest root key with the name, there can briefly be two while they are rotated.
+    fn default_root_key(
+        &self,
+        root_keys: &[MacaroonRootKey],
+        name: &str,
+    ) -> Result<MacaroonRootKey> {
+        root_keys
+            .iter()
+            .filter(|root_key| root_key.name == name)
+            .max_by_key(|root_key| root_key.timestamp)
+            .cloned()
+            .with_context(|| format!("Missing {name} macaroon root key"))
+    }
+
+    fn create_macaroon(&self, root_key: &MacaroonRootKey) -> Result<Macaroon> {
+        Ok(Macaroon::create(
+            None,
+            &self.key(&root_key.id),
+            root_key.id.to_string().into(),
+        )?)
+    }
+
+    // Root keys are derived from the seed so that only their IDs need to be stored.
+    fn key(&self, id: &Uuid) -> MacaroonKey {
+        MacaroonKey::generate(&[&self.seed[..], &id.as_bytes()[..]].concat())
+    }
+
+    // The root key ID and the key of the macaroon.
+    fn root_key_of(&self, macaroon: &Macaroon) -> Result<(Uuid, MacaroonKey)> {
+        let identifier = String::from_utf8(macaroon.identifier().0)
+            .ok()
+            .context("Macaroon has no root key ID")?;
+        match identifier.as_str() {
+            ADMIN => Ok((LEGACY_ADMIN_ID, MacaroonKey::generate(&self.seed))),
+            READONLY => Ok((LEGACY_READONLY_ID, MacaroonKey::generate(&self.seed))),
+            id => {
+                let id = Uuid::parse_str(id)
+                    .ok()
+                    .context("Macaroon has no root key ID")?;
+                Ok((id, self.key(&id)))
+            }
+        }
     }
 
-    pub fn verify_admin_macaroon(&self, macaroon: &Macaroon) -> Result<()> {
+    /// The root keys are the ones in the database, a macaroon of any other root key is rejected.
+    pub fn verify_admin_macaroon(
+        &self,
+        macaroon: &Macaroon,
+        request: &MacaroonRequest,
+        root_keys: &[MacaroonRootKey],
+    ) -> Result<MacaroonRestrictions> {
         let mut verifier = Verifier::default();
         verifier.satisfy_general(|caveat| verify_role(caveat, "admin"));
-        Ok(verifier.verify(macaroon, &self.key, vec![])?)
+        self.verify(verifier, macaroon, request, root_keys)
     }
 
-    pub fn verify_readonly_macaroon(&self, macaroon: &Macaroon) -> Result<()> {
+    pub fn verify_readonly_macaroon(
+        &self,
+        macaroon: &Macaroon,
+        request: &MacaroonRequest,
+        root_keys: &[MacaroonRootKey],
+    ) -> Result<MacaroonRestrictions> {
         let mut verifier = Verifier::default();
         verifier.satisfy_general(|caveat| verify_role(caveat, "readonly"));
-        Ok(verifier.verify(macaroon, &self.key, vec![])?)
+        self.verify(verifier, macaroon, request, root_keys)
+    }
+
+    /// Add caveats to a macaroon. The baked macaroon keeps the caveats of the original one,
+    /// so it can never do more than the macaroon it was baked from.
+    pub fn bake(macaroon: &Macaroon, bake: &BakeMacaroon) -> Result<(Macaroon, Option<u64>)> {
+        let mut macaroon = macaroon.clone();
+        if bake.readonly {
+            macaroon.add_first_party_caveat(format!("{ROLES} = readonly").into());
+        }
+        if !bake.permissions.is_empty() {
+            let permissions: Vec<String> = bake.permissions.iter().map(|p| p.to_string()).collect();
+            macaroon.add_first_party_caveat(
+                format!("{PERMISSIONS} = {}", permissions.join("|")).into(),
+            );
+        }
+        if let Some(max_payment_sats) = bake.max_payment_sats {
+            macaroon
+                .add_first_party_caveat(format!("{MAX_PAYMENT_SATS} = {max_payment_sats}").into());
+        }
+        let expires_at = match bake.expiry {
+            Some(expiry) => Some(
+                unix_timestamp()?
+                    .checked_add(expiry)
+                    .context("Expiry is too large")?,
+            ),
+            None => None,
+        };
+        if let Some(expires_at) = expires_at {
+            macaroon.add_first_party_caveat(format!("{TIME_BEFORE} = {expires_at}").into());
+        }
+        if !bake.ip_ranges.is_empty() {
+            let ranges = bake
+                .ip_ranges
+                .iter()
+                .map(|range| parse_ip_range(range).map(|range| range.to_string()))
+                .collect::<Result<Vec<String>>>()?;
+            macaroon.add_first_party_caveat(format!("{IP} = {}", ranges.join("|")).into());
+        }
+        Ok((macaroon, expires_at))
+    }
+
+    // The verifier only takes plain functions so the caveats that depend on the request are
+    // accepted by it and checked afterwards.
+    fn verify(
+        &self,
+        mut verifier: Verifier,
+        macaroon: &Macaroon,
+        request: &MacaroonRequest,
+        root_keys: &[MacaroonRootKey],
+    ) -> Result<MacaroonRestrictions> {
+        verifier.satisfy_general(verify_time_before);
+        verifier.satisfy_general(|caveat| has_name(caveat, PERMISSIONS));
+        verifier.satisfy_general(|caveat| has_name(caveat, MAX_PAYMENT_SATS));
+        verifier.satisfy_general(|caveat| has_name(caveat, IP));
+        let (id, key) = self.root_key_of(macaroon)?;
+        if !root_keys.iter().any(|root_key| root_key.id == id) {
+            bail!("Macaroon root key {id} has been revoked");
+        }
+        verifier.verify(macaroon, &key, vec![])?;
+
+        let permission = route_permission(request.route);
+        for permissions in caveat_values(macaroon, PERMISSIONS) {
+            let permissions = permissions
+                .split('|')
+                .map(MacaroonPermission::from_str)
+                .collect::<Result<Vec<MacaroonPermission>>>()?;
+            if !permission.is_some_and(|p| permissions.contains(&p)) {
+                bail!("Macaroon has no permission for {}", request.route);
+            }
+        }
+        for ranges in caveat_values(macaroon, IP) {
+            let mut ranges = ranges.split('|').map(parse_ip_range);
+            if !ranges.any(|range| range.is_ok_and(|range| range.contains(&request.client))) {
+                bail!("Macaroon can not be used from {}", request.client);
+            }
+        }
+        let max_payment_sats = caveat_values(macaroon, MAX_PAYMENT_SATS)
+            .iter()
+            .map(|max| u64::from_str(max))
+            .collect::<Result<Vec<u64>, _>>()?
+            .into_iter()
+            .min();
+        Ok(MacaroonRestrictions { max_payment_sats })
     }
 
-    fn admin_macaroon(key: &MacaroonKey) -> Result<Macaroon> {
-        let mut macaroon = Macaroon::create(None, key, "admin".into())?;
-        macaroon.add_first_party_caveat("roles = admin|readonly".into());
+    fn admin_macaroon(&self, root_keys: &[MacaroonRootKey]) -> Result<Macaroon> {
+        let mut macaroon = self.create_macaroon(&self.default_root_key(root_keys, ADMIN)?)?;
+        macaroon.add_first_party_caveat(format!("{ROLES} = admin|readonly").into());
         Ok(macaroon)
     }
 
-    fn readonly_macaroon(key: &MacaroonKey) -> Result<Macaroon> {
-        let mut macaroon = Macaroon::create(None, key, "readonly".into())?;
-        macaroon.add_first_party_caveat("roles = readonly".into());
+    fn readonly_macaroon(&self, root_keys: &[MacaroonRootKey]) -> Result<Macaroon> {
+        let mut macaroon = self.create_macaroon(&self.default_root_key(root_keys, READONLY)?)?;
+        macaroon.add_first_party_caveat(format!("{ROLES} = readonly").into());
         Ok(macaroon)
     }
 }
 
+/// The route and client that a macaroon is presented for.
+pub struct MacaroonRequest<'a> {
+    pub route: &'a str,
+    pub client: IpAddr,
+}
+
+/// Limits from the caveats of the request's macaroon that the route handlers enforce.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MacaroonRestrictions {
+    pub max_payment_sats: Option<u64>,
+}
+
+impl MacaroonRestrictions {
+    /// Fails when the macaroon does not allow sending this amount. Payments without a known
+    /// amount are only allowed when there is no limit.
+    pub fn check_payment_msat(&self, amount_msat: Option<u64>) -> Result<(), ApiError> {
+        let Some(max_payment_sats) = self.max_payment_sats else {
+            return Ok(());
+        };
+        match amount_msat {
+            Some(amount) if amount <= max_payment_sats.saturating_mul(1000) => Ok(()),
+            Some(amount) => Err(unauthorized(anyhow!(
+                "Payment of {amount} msat is over the macaroon limit of {max_payment_sats} sats"
+            ))),
+            None => Err(unauthorized(anyhow!(
+                "Macaroon only allows payments with a known amount"
+            ))),
+        }
+    }
+
+    /// Like check_payment_msat for a lightning payment, which may pay up to the max fee on top of
+    /// the amount.
+    pub fn check_routed_payment_msat(
+        &self,
+        amount_msat: Option<u64>,
+        max_fee_msat: impl FnOnce(u64) -> u64,
+    ) -> Result<(), ApiError> {
+        self.check_payment_msat(
+            amount_msat.map(|amount| amount.saturating_add(max_fee_msat(amount))),
+        )
+    }
+}
+
+/// The group that a route belongs to. Routes without a group can only be used with macaroons
+/// that have no permissions caveat.
+fn route_permission(route: &str) -> Option<MacaroonPermission> {
+    match route {
+        routes::ROOT
+        | routes::GET_INFO
+        | routes::OPENAPI
+        | routes::GET_FEES
+        | routes::ESTIMATE_CHANNEL_LIQUIDITY
+        | routes::DECODE_INVOICE => Some(MacaroonPermission::Info),
+        routes::SIGN => Some(MacaroonPermission::Sign),
+        routes::LIST_FUNDS | routes::GET_BALANCE | routes::NEW_ADDR | routes::WITHDRAW => {
+            Some(MacaroonPermission::Onchain)
+        }
+        routes::CONNECT_PEER | routes::LIST_PEERS | routes::DISCONNECT_PEER => {
+            Some(MacaroonPermission::Peers)
+        }
+        routes::LIST_PEER_CHANNELS
+        | routes::OPEN_CHANNEL
+        | routes::SET_CHANNEL_FEE
+        | routes::CLOSE_CHANNEL
+        | routes::FORCE_CLOSE_CHANNEL_WITH_BROADCAST
+        | routes::FORCE_CLOSE_CHANNEL_WITHOUT_BROADCAST
+        | routes::LOCAL_REMOTE_BALANCE
+        | routes::LIST_FORWARDS
+        | routes::LIST_CHANNEL_HISTORY
+        | routes::FORWARD_STATS
+        | routes::CHANNEL_ACCEPTANCE_POLICY
+        | routes::CHANNEL_BACKUP => Some(MacaroonPermission::Channels),
+        routes::LIST_NETWORK_NODE
+        | routes::LIST_NETWORK_NODES
+        | routes::LIST_NETWORK_CHANNEL
+        | routes::LIST_NETWORK_CHANNELS
+        | routes::FEE_RATES => Some(MacaroonPermission::Network),
+        routes::KEYSEND
+        | routes::PAY_INVOICE
+        | routes::LIST_PAYMENTS
+        | routes::PROBE
+        | routes::GET_PAYMENT
+        | routes::PAYMENT_DETAIL => Some(MacaroonPermission::Payments),
+        routes::GENERATE_INVOICE
+        | routes::LIST_INVOICES
+        | routes::GENERATE_HOLD_INVOICE
+        | routes::SETTLE_HOLD_INVOICE
+        | routes::CANCEL_HOLD_INVOICE => Some(MacaroonPermission::Invoices),
+        routes::CREATE_OFFER
+        | routes::LIST_OFFERS
+        | routes::PAY_OFFER
+        | routes::CREATE_REFUND
+        | routes::REQUEST_REFUND_PAYMENT
+        | routes::LIST_INVOICE_REQUESTS => Some(MacaroonPermission::Offers),
+        routes::JIT_CHANNEL_OFFER | routes::LIST_JIT_CHANNELS => {
+            Some(MacaroonPermission::Liquidity)
+        }
+        routes::WEBSOCKET | routes::WEBHOOKS | routes::DELETE_WEBHOOK => {
+            Some(MacaroonPermission::Events)
+        }
+        // The LND routes, its getinfo path is the same as ours.
+        routes::lnd::DECODE_PAY_REQ => Some(MacaroonPermission::Info),
+        routes::lnd::WALLET_BALANCE | routes::lnd::NEW_ADDRESS | routes::lnd::SEND_COINS => {
+            Some(MacaroonPermission::Onchain)
+        }
+        routes::lnd::CHANNEL_BALANCE | routes::lnd::LIST_CHANNELS => {
+            Some(MacaroonPermission::Channels)
+        }
+        routes::lnd::LIST_PEERS => Some(MacaroonPermission::Peers),
+        routes::lnd::INVOICES => Some(MacaroonPermission::Invoices),
+        routes::lnd::LIST_PAYMENTS | routes::lnd::SEND_PAYMENT => {
+            Some(MacaroonPermission::Payments)
+        }
+        _ => None,
+    }
+}
+
+/// Values of the first party caveats with this name.
+fn caveat_values(macaroon: &Macaroon, name: &str) -> Vec<String> {
+    let prefix = format!("{name} = ");
+    macaroon
+        .first_party_caveats()
+        .into_iter()
+        .filter_map(|caveat| match caveat {
+            Caveat::FirstParty(caveat) => String::from_utf8(caveat.predicate().0).ok(),
+            _ => None,
+        })
+        .filter_map(|predicate| predicate.strip_prefix(&prefix).map(str::to_string))
+        .collect()
+}
+
+fn has_name(caveat: &ByteString, name: &str) -> bool {
+    caveat.0.starts_with(format!("{name} = ").as_bytes())
+}
+
+fn verify_time_before(caveat: &ByteString) -> bool {
+    let Some(value) = caveat
+        .0
+        .strip_prefix(format!("{TIME_BEFORE} = ").as_bytes())
+    else {
+        return false;
+    };
+    let expires_at = std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| u64::from_str(value).ok());
+    match (expires_at, unix_timestamp()) {
+        (Some(expires_at), Ok(now)) => now < expires_at,
+        _ => false,
+    }
+}
+
+/// An IP range in CIDR notation or a single address.
+fn parse_ip_range(range: &str) -> Result<IpNet> {
+    IpNet::from_str(range)
+        .or_else(|_| IpAddr::from_str(range).map(IpNet::from))
+        .map_err(|_| anyhow!("Invalid IP range {range}"))
+}
+
+fn unix_timestamp() -> Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs())
+}
+
 fn verify_role(caveat: &ByteString, expected_role: &str) -> bool {
     if !caveat.0.starts_with(b"roles = ") {
         return false;
@@ -92,27 +516,136 @@ fn verify_role(caveat: &ByteString, expected_role: &str) -> bool {
 pub async fn admin_auth<B>(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
-    request: Request<B>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request<B>,
     next: Next<B>,
 ) -> Result<impl IntoResponse, ApiError> {
-    macaroon_auth
-        .verify_admin_macaroon(&macaroon.0)
+    let root_keys = lightning_interface
+        .list_macaroon_root_keys()
+        .await
+        .map_err(internal_server)?;
+    let restrictions = macaroon_auth
+        .verify_admin_macaroon(&macaroon.0, &macaroon_request(&request, addr), &root_keys)
         .map_err(unauthorized)?;
+    request.extensions_mut().insert(restrictions);
     Ok(next.run(request).await)
 }
 
 pub async fn readonly_auth<B>(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
-    request: Request<B>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request<B>,
     next: Next<B>,
 ) -> Result<impl IntoResponse, ApiError> {
-    macaroon_auth
-        .verify_readonly_macaroon(&macaroon.0)
+    let root_keys = lightning_interface
+        .list_macaroon_root_keys()
+        .await
+        .map_err(internal_server)?;
+    let restrictions = macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0, &macaroon_request(&request, addr), &root_keys)
         .map_err(unauthorized)?;
+    request.extensions_mut().insert(restrictions);
     Ok(next.run(request).await)
 }
 
+fn macaroon_request<B>(request: &Request<B>, addr: SocketAddr) -> MacaroonRequest<'_> {
+    MacaroonRequest {
+        route: request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str())
+            .unwrap_or_default(),
+        client: addr.ip(),
+    }
+}
+
+pub(crate) async fn bake_macaroon(
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    macaroon: KldMacaroon,
+    Json(bake): Json<BakeMacaroon>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (mut macaroon, expires_at) = MacaroonAuth::bake(&macaroon.0, &bake).map_err(bad_request)?;
+    if let Some(name) = bake.root_key {
+        if name.is_empty() || MacaroonAuth::is_default(&name) {
+            return Err(bad_request(anyhow!("Invalid root key name {name}")));
+        }
+        macaroon = macaroon_auth
+            .rekey(&macaroon, name, lightning_interface.as_ref())
+            .await
+            .map_err(internal_server)?;
+    }
+    Ok(Json(BakeMacaroonResponse {
+        root_key_id: String::from_utf8_lossy(&macaroon.identifier().0).to_string(),
+        macaroon: macaroon
+            .serialize(macaroon::Format::V2)
+            .map_err(internal_server)?,
+        expires_at,
+    }))
+}
+
+pub(crate) async fn list_macaroon_root_keys(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let root_keys: Vec<api::MacaroonRootKey> = lightning_interface
+        .list_macaroon_root_keys()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(Json(root_keys))
+}
+
+pub(crate) async fn delete_macaroon_root_key(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let root_key_id = Uuid::parse_str(&id).map_err(bad_request)?;
+    let Some(root_key) = lightning_interface
+        .list_macaroon_root_keys()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .find(|root_key| root_key.id == root_key_id)
+    else {
+        return Err(ApiError::NotFound(id));
+    };
+    if MacaroonAuth::is_default(&root_key.name) {
+        return Err(bad_request(anyhow!(
+            "The {} root key can only be rotated",
+            root_key.name
+        )));
+    }
+    lightning_interface
+        .delete_macaroon_root_key(root_key_id)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(()))
+}
+
+pub(crate) async fn rotate_macaroon_root_keys(
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (admin_macaroon, readonly_macaroon, remaining_root_keys) = macaroon_auth
+        .rotate(lightning_interface.as_ref())
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(RotateMacaroonRootKeysResponse {
+        admin_macaroon: admin_macaroon
+            .serialize(macaroon::Format::V2)
+            .map_err(internal_server)?,
+        readonly_macaroon: readonly_macaroon
+            .serialize(macaroon::Format::V2)
+            .map_err(internal_server)?,
+        remaining_root_keys: remaining_root_keys.into_iter().map(Into::into).collect(),
+    }))
+}
+
 pub struct KldMacaroon(pub Macaroon);
 
 #[async_trait]
@@ -150,6 +683,13 @@ where
                 if let Ok(macaroon) = Macaroon::deserialize_binary(&bytes).map(KldMacaroon) {
                     return Ok(macaroon);
                 }
+                // LND clients send the hex encoded file, which contains a base64 macaroon.
+                if let Some(macaroon) = std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|s| Macaroon::deserialize(s).ok())
+                {
+                    return Ok(KldMacaroon(macaroon));
+                }
             }
         }
 
@@ -157,22 +697,197 @@ where
     }
 }
 
+#[cfg(test)]
+fn test_macaroon_auth() -> (MacaroonAuth, Vec<MacaroonRootKey>) {
+    let root_keys = vec![
+        MacaroonRootKey::new(ADMIN.to_string()),
+        MacaroonRootKey::new(READONLY.to_string()),
+    ];
+    let macaroon_auth = MacaroonAuth::new(&[3u8; 32], "").unwrap();
+    macaroon_auth.write_macaroons(&root_keys).unwrap();
+    (macaroon_auth, root_keys)
+}
+
+#[cfg(test)]
+fn test_request(route: &str) -> MacaroonRequest<'_> {
+    MacaroonRequest {
+        route,
+        client: IpAddr::from([127, 0, 0, 1]),
+    }
+}
+
 #[test]
 fn test_readonly_macaroon() {
-    let macaroon_auth = MacaroonAuth::init(&[3u8; 32], "").unwrap();
-    let readonly_macaroon = MacaroonAuth::readonly_macaroon(&macaroon_auth.key).unwrap();
+    let (macaroon_auth, root_keys) = test_macaroon_auth();
+    let readonly_macaroon = macaroon_auth.readonly_macaroon(&root_keys).unwrap();
 
     macaroon_auth
-        .verify_readonly_macaroon(&readonly_macaroon)
+        .verify_readonly_macaroon(
+            &readonly_macaroon,
+            &test_request(routes::GET_INFO),
+            &root_keys,
+        )
         .unwrap();
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&readonly_macaroon, &test_request(routes::SIGN), &root_keys)
+        .is_err());
 }
 
 #[test]
 fn test_admin_macaroon() {
-    let macaroon_auth = MacaroonAuth::init(&[3u8; 32], "").unwrap();
-    let admin_macaroon = MacaroonAuth::admin_macaroon(&macaroon_auth.key).unwrap();
+    let (macaroon_auth, root_keys) = test_macaroon_auth();
+    let admin_macaroon = macaroon_auth.admin_macaroon(&root_keys).unwrap();
+
+    let restrictions = macaroon_auth
+        .verify_admin_macaroon(&admin_macaroon, &test_request(routes::SIGN), &root_keys)
+        .unwrap();
+    assert_eq!(MacaroonRestrictions::default(), restrictions);
+}
+
+#[test]
+fn test_bake_macaroon() {
+    let (macaroon_auth, root_keys) = test_macaroon_auth();
+    let admin_macaroon = macaroon_auth.admin_macaroon(&root_keys).unwrap();
+
+    let bake = BakeMacaroon {
+        permissions: vec![MacaroonPermission::Invoices, MacaroonPermission::Payments],
+        max_payment_sats: Some(1000),
+        expiry: Some(60),
+        ip_ranges: vec!["127.0.0.0/8".to_string()],
+        ..Default::default()
+    };
+    let (macaroon, expires_at) = MacaroonAuth::bake(&admin_macaroon, &bake).unwrap();
+    assert!(expires_at.is_some());
+    let restrictions = macaroon_auth
+        .verify_admin_macaroon(&macaroon, &test_request(routes::PAY_INVOICE), &root_keys)
+        .unwrap();
+    assert_eq!(Some(1000), restrictions.max_payment_sats);
+    assert!(restrictions.check_payment_msat(Some(1_000_000)).is_ok());
+    assert!(restrictions.check_payment_msat(Some(1_000_001)).is_err());
+    assert!(restrictions.check_payment_msat(None).is_err());
+    assert!(restrictions
+        .check_routed_payment_msat(Some(900_000), |_| 100_000)
+        .is_ok());
+    assert!(restrictions
+        .check_routed_payment_msat(Some(900_000), |_| 100_001)
+        .is_err());
+
+    // Routes outside of the permissions.
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&macaroon, &test_request(routes::OPEN_CHANNEL), &root_keys)
+        .is_err());
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&macaroon, &test_request(routes::BAKE_MACAROON), &root_keys)
+        .is_err());
+
+    // Client outside of the IP range.
+    let request = MacaroonRequest {
+        route: routes::PAY_INVOICE,
+        client: IpAddr::from([10, 0, 0, 1]),
+    };
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&macaroon, &request, &root_keys)
+        .is_err());
+
+    // Baking again can only add restrictions.
+    let bake = BakeMacaroon {
+        readonly: true,
+        permissions: vec![MacaroonPermission::Invoices, MacaroonPermission::Channels],
+        max_payment_sats: Some(2000),
+        ..Default::default()
+    };
+    let (macaroon, expires_at) = MacaroonAuth::bake(&macaroon, &bake).unwrap();
+    assert!(expires_at.is_none());
+    let restrictions = macaroon_auth
+        .verify_readonly_macaroon(&macaroon, &test_request(routes::LIST_INVOICES), &root_keys)
+        .unwrap();
+    assert_eq!(Some(1000), restrictions.max_payment_sats);
+    assert!(macaroon_auth
+        .verify_admin_macaroon(
+            &macaroon,
+            &test_request(routes::GENERATE_INVOICE),
+            &root_keys
+        )
+        .is_err());
+    assert!(macaroon_auth
+        .verify_readonly_macaroon(
+            &macaroon,
+            &test_request(routes::LIST_PEER_CHANNELS),
+            &root_keys
+        )
+        .is_err());
+}
+
+#[test]
+fn test_expired_macaroon() {
+    let (macaroon_auth, root_keys) = test_macaroon_auth();
+    let mut macaroon = macaroon_auth.admin_macaroon(&root_keys).unwrap();
+    macaroon.add_first_party_caveat(format!("{TIME_BEFORE} = 1700000000").into());
+
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&macaroon, &test_request(routes::SIGN), &root_keys)
+        .is_err());
+}
+
+#[test]
+fn test_bake_invalid_ip_range() {
+    let (macaroon_auth, root_keys) = test_macaroon_auth();
+    let admin_macaroon = macaroon_auth.admin_macaroon(&root_keys).unwrap();
+    let bake = BakeMacaroon {
+        ip_ranges: vec!["localhost".to_string()],
+        ..Default::default()
+    };
+    assert!(MacaroonAuth::bake(&admin_macaroon, &bake).is_err());
+}
 
+#[test]
+fn test_revoked_macaroon() {
+    let (macaroon_auth, root_keys) = test_macaroon_auth();
+    let admin_macaroon = macaroon_auth.admin_macaroon(&root_keys).unwrap();
+
+    // A macaroon signed with a root key that was never created.
+    let unknown = MacaroonRootKey::new("unknown".to_string());
+    let mut macaroon = macaroon_auth.create_macaroon(&unknown).unwrap();
+    macaroon.add_first_party_caveat(format!("{ROLES} = admin").into());
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&macaroon, &test_request(routes::SIGN), &root_keys)
+        .is_err());
+
+    // The same root key ID with another seed.
+    let other_seed = MacaroonAuth::new(&[4u8; 32], "").unwrap();
+    assert!(other_seed
+        .verify_admin_macaroon(&admin_macaroon, &test_request(routes::SIGN), &root_keys)
+        .is_err());
+
+    // The root key was deleted from the database.
+    let root_key_id = admin_macaroon.identifier();
+    let remaining: Vec<MacaroonRootKey> = root_keys
+        .into_iter()
+        .filter(|root_key| root_key.id.to_string().as_bytes() != root_key_id.0)
+        .collect();
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&admin_macaroon, &test_request(routes::SIGN), &remaining)
+        .is_err());
+}
+
+#[test]
+fn test_legacy_macaroon() {
+    let (macaroon_auth, _) = test_macaroon_auth();
+    let mut macaroon =
+        Macaroon::create(None, &MacaroonKey::generate(&[3u8; 32]), ADMIN.into()).unwrap();
+    macaroon.add_first_party_caveat(format!("{ROLES} = admin|readonly").into());
+
+    let root_keys = vec![MacaroonRootKey {
+        id: LEGACY_ADMIN_ID,
+        ..MacaroonRootKey::new(ADMIN.to_string())
+    }];
     macaroon_auth
-        .verify_admin_macaroon(&admin_macaroon)
+        .verify_admin_macaroon(&macaroon, &test_request(routes::SIGN), &root_keys)
         .unwrap();
+
+    // Rotated away.
+    let root_keys = vec![MacaroonRootKey::new(ADMIN.to_string())];
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&macaroon, &test_request(routes::SIGN), &root_keys)
+        .is_err());
 }
@@ -1,14 +1,20 @@
 mod channels;
 mod invoices;
+mod liquidity;
+mod lnd;
 mod macaroon_auth;
 mod network;
+mod offers;
 mod payments;
 mod peers;
+mod rpc;
 mod skt_addr;
 mod utility;
 mod wallet;
+mod webhooks;
 mod ws;
 
+pub use rpc::{bind_rpc_server, RpcServer};
 pub use skt_addr::SocketAddress;
 
 pub use macaroon_auth::{KldMacaroon, MacaroonAuth};
@@ -19,28 +25,42 @@ use self::utility::get_info;
 use crate::{
     api::{
         channels::{
-            channel_history, close_channel, force_close_channel_with_broadcast,
-            force_close_channel_without_broadcast, list_forwards, list_peer_channels,
-            local_remote_balance, open_channel, set_channel_fee,
+            channel_backup, channel_history, close_channel, force_close_channel_with_broadcast,
+            force_close_channel_without_broadcast, forward_stats, get_channel_acceptance_policy,
+            list_forwards, list_peer_channels, local_remote_balance, open_channel,
+            set_channel_acceptance_policy, set_channel_fee,
+        },
+        invoices::{
+            cancel_hold_invoice, decode_invoice, generate_hold_invoice, generate_invoice,
+            list_invoices, settle_hold_invoice,
+        },
+        liquidity::{get_jit_channel_offer, list_jit_channels},
+        macaroon_auth::{
+            admin_auth, bake_macaroon, delete_macaroon_root_key, list_macaroon_root_keys,
+            readonly_auth, rotate_macaroon_root_keys,
         },
-        invoices::{decode_invoice, generate_invoice, list_invoices},
-        macaroon_auth::{admin_auth, readonly_auth},
         network::{
             fee_rates, get_network_channel, get_network_node, list_network_channels,
             list_network_nodes,
         },
-        payments::{keysend, list_payments, pay_invoice},
+        offers::{
+            create_offer, create_refund, list_invoice_requests, list_offers, pay_offer,
+            request_refund_payment,
+        },
+        payments::{get_payment, keysend, list_payments, pay_invoice, payment_detail, probe},
         peers::{connect_peer, disconnect_peer, list_peers},
-        utility::{estimate_channel_liquidity_range, get_fees, sign},
+        utility::{estimate_channel_liquidity_range, get_fees, openapi, sign},
         wallet::{get_balance, list_funds, new_address, transfer},
+        webhooks::{create_webhook, delete_webhook, list_webhooks},
         ws::ws_handler,
     },
     bitcoind::bitcoind_interface::BitcoindInterface,
+    database::{Page, UnknownCursor},
     ldk::LightningInterface,
     wallet::WalletInterface,
 };
-use anyhow::{Context, Result};
-use api::routes;
+use anyhow::{anyhow, Context, Result};
+use api::{routes, Pagination};
 use axum::{
     extract::Extension,
     middleware::from_fn,
@@ -52,10 +72,12 @@ use axum_server::{
     tls_rustls::{RustlsAcceptor, RustlsConfig},
     Handle, Server,
 };
+use bitcoin::hashes::hex::FromHex;
 use futures::{future::Shared, Future};
 use hyper::StatusCode;
 use log::{error, info, warn};
 use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+use time::OffsetDateTime;
 use tower_http::cors::CorsLayer;
 
 pub struct RestApi {
@@ -83,53 +105,48 @@ impl RestApi {
         quit_signal: Shared<impl Future<Output = ()>>,
     ) -> Result<()> {
         let cors = CorsLayer::permissive();
-        let handle = Handle::new();
+        let readonly_routes = readonly_routes().layer(from_fn(readonly_auth));
+        let admin_routes = admin_routes().layer(from_fn(admin_auth));
+
+        let routes = readonly_routes
+            .merge(admin_routes)
+            .fallback(handler_404)
+            .layer(cors)
+            .layer(Extension(bitcoind_api))
+            .layer(Extension(lightning_api))
+            .layer(Extension(wallet_api))
+            .layer(Extension(macaroon_auth));
+
+        self.run(routes, quit_signal).await
+    }
+
+    /// Serve the LND compatible routes for tools that only speak LND REST. Macaroons are checked
+    /// in the same way as on the other routes.
+    pub async fn serve_lnd(
+        self,
+        bitcoind_api: Arc<dyn BitcoindInterface + Send + Sync>,
+        lightning_api: Arc<dyn LightningInterface + Send + Sync>,
+        wallet_api: Arc<dyn WalletInterface + Send + Sync>,
+        macaroon_auth: Arc<MacaroonAuth>,
+        quit_signal: Shared<impl Future<Output = ()>>,
+    ) -> Result<()> {
+        let cors = CorsLayer::permissive();
         let readonly_routes = Router::new()
-            .route(routes::ROOT, get(root))
-            .route(routes::GET_INFO, get(get_info))
-            .route(
-                routes::ESTIMATE_CHANNEL_LIQUIDITY,
-                get(estimate_channel_liquidity_range),
-            )
-            .route(routes::GET_BALANCE, get(get_balance))
-            .route(routes::LIST_FUNDS, get(list_funds))
-            .route(routes::LIST_PEER_CHANNELS, get(list_peer_channels))
-            .route(routes::LIST_PEERS, get(list_peers))
-            .route(routes::LIST_NETWORK_NODE, get(get_network_node))
-            .route(routes::LIST_NETWORK_NODES, get(list_network_nodes))
-            .route(routes::LIST_NETWORK_CHANNEL, get(get_network_channel))
-            .route(routes::LIST_NETWORK_CHANNELS, get(list_network_channels))
-            .route(routes::FEE_RATES, get(fee_rates))
-            .route(routes::LIST_INVOICES, get(list_invoices))
-            .route(routes::LIST_PAYMENTS, get(list_payments))
-            .route(routes::LOCAL_REMOTE_BALANCE, get(local_remote_balance))
-            .route(routes::GET_FEES, get(get_fees))
-            .route(routes::LIST_FORWARDS, get(list_forwards))
-            .route(routes::LIST_CHANNEL_HISTORY, get(channel_history))
-            .route(routes::DECODE_INVOICE, get(decode_invoice))
+            .route(routes::lnd::GET_INFO, get(lnd::get_info))
+            .route(routes::lnd::DECODE_PAY_REQ, get(lnd::decode_pay_req))
+            .route(routes::lnd::WALLET_BALANCE, get(lnd::wallet_balance))
+            .route(routes::lnd::CHANNEL_BALANCE, get(lnd::channel_balance))
+            .route(routes::lnd::LIST_CHANNELS, get(lnd::list_channels))
+            .route(routes::lnd::LIST_PEERS, get(lnd::list_peers))
+            .route(routes::lnd::INVOICES, get(lnd::list_invoices))
+            .route(routes::lnd::LIST_PAYMENTS, get(lnd::list_payments))
             .layer(from_fn(readonly_auth));
 
         let admin_routes = Router::new()
-            .route(routes::SIGN, post(sign))
-            .route(routes::OPEN_CHANNEL, post(open_channel))
-            .route(routes::SET_CHANNEL_FEE, post(set_channel_fee))
-            .route(routes::CLOSE_CHANNEL, delete(close_channel))
-            .route(
-                routes::FORCE_CLOSE_CHANNEL_WITH_BROADCAST,
-                delete(force_close_channel_with_broadcast),
-            )
-            .route(
-                routes::FORCE_CLOSE_CHANNEL_WITHOUT_BROADCAST,
-                delete(force_close_channel_without_broadcast),
-            )
-            .route(routes::NEW_ADDR, get(new_address))
-            .route(routes::WITHDRAW, post(transfer))
-            .route(routes::CONNECT_PEER, post(connect_peer))
-            .route(routes::DISCONNECT_PEER, delete(disconnect_peer))
-            .route(routes::KEYSEND, post(keysend))
-            .route(routes::GENERATE_INVOICE, post(generate_invoice))
-            .route(routes::PAY_INVOICE, post(pay_invoice))
-            .route(routes::WEBSOCKET, get(ws_handler))
+            .route(routes::lnd::NEW_ADDRESS, get(lnd::new_address))
+            .route(routes::lnd::SEND_COINS, post(lnd::send_coins))
+            .route(routes::lnd::INVOICES, post(lnd::add_invoice))
+            .route(routes::lnd::SEND_PAYMENT, post(lnd::send_payment))
             .layer(from_fn(admin_auth));
 
         let routes = readonly_routes
@@ -141,6 +158,15 @@ impl RestApi {
             .layer(Extension(wallet_api))
             .layer(Extension(macaroon_auth));
 
+        self.run(routes, quit_signal).await
+    }
+
+    async fn run(
+        self,
+        routes: Router,
+        quit_signal: Shared<impl Future<Output = ()>>,
+    ) -> Result<()> {
+        let handle = Handle::new();
         tokio::select!(
             result = self.server.serve(routes.into_make_service_with_connect_info::<SocketAddr>()) => {
                     if let Err(e) = result {
@@ -157,6 +183,94 @@ impl RestApi {
     }
 }
 
+/// The routes that a readonly macaroon can access.
+fn readonly_routes() -> Router {
+    Router::new()
+        .route(routes::ROOT, get(root))
+        .route(routes::GET_INFO, get(get_info))
+        .route(routes::OPENAPI, get(openapi))
+        .route(
+            routes::ESTIMATE_CHANNEL_LIQUIDITY,
+            get(estimate_channel_liquidity_range),
+        )
+        .route(routes::GET_BALANCE, get(get_balance))
+        .route(routes::LIST_FUNDS, get(list_funds))
+        .route(routes::LIST_PEER_CHANNELS, get(list_peer_channels))
+        .route(routes::LIST_PEERS, get(list_peers))
+        .route(routes::LIST_NETWORK_NODE, get(get_network_node))
+        .route(routes::LIST_NETWORK_NODES, get(list_network_nodes))
+        .route(routes::LIST_NETWORK_CHANNEL, get(get_network_channel))
+        .route(routes::LIST_NETWORK_CHANNELS, get(list_network_channels))
+        .route(routes::FEE_RATES, get(fee_rates))
+        .route(routes::LIST_INVOICES, get(list_invoices))
+        .route(routes::LIST_PAYMENTS, get(list_payments))
+        .route(routes::GET_PAYMENT, get(get_payment))
+        .route(routes::PAYMENT_DETAIL, get(payment_detail))
+        .route(routes::LOCAL_REMOTE_BALANCE, get(local_remote_balance))
+        .route(routes::GET_FEES, get(get_fees))
+        .route(routes::LIST_FORWARDS, get(list_forwards))
+        .route(routes::LIST_CHANNEL_HISTORY, get(channel_history))
+        .route(routes::FORWARD_STATS, get(forward_stats))
+        .route(routes::DECODE_INVOICE, get(decode_invoice))
+        .route(routes::LIST_OFFERS, get(list_offers))
+        .route(routes::LIST_INVOICE_REQUESTS, get(list_invoice_requests))
+        .route(routes::JIT_CHANNEL_OFFER, get(get_jit_channel_offer))
+        .route(routes::LIST_JIT_CHANNELS, get(list_jit_channels))
+}
+
+/// The routes that need an admin macaroon.
+fn admin_routes() -> Router {
+    Router::new()
+        .route(routes::SIGN, post(sign))
+        .route(routes::OPEN_CHANNEL, post(open_channel))
+        .route(routes::SET_CHANNEL_FEE, post(set_channel_fee))
+        .route(routes::CLOSE_CHANNEL, delete(close_channel))
+        .route(
+            routes::FORCE_CLOSE_CHANNEL_WITH_BROADCAST,
+            delete(force_close_channel_with_broadcast),
+        )
+        .route(
+            routes::FORCE_CLOSE_CHANNEL_WITHOUT_BROADCAST,
+            delete(force_close_channel_without_broadcast),
+        )
+        .route(routes::NEW_ADDR, get(new_address))
+        .route(routes::WITHDRAW, post(transfer))
+        .route(routes::CONNECT_PEER, post(connect_peer))
+        .route(routes::DISCONNECT_PEER, delete(disconnect_peer))
+        .route(routes::KEYSEND, post(keysend))
+        .route(routes::GENERATE_INVOICE, post(generate_invoice))
+        .route(routes::PAY_INVOICE, post(pay_invoice))
+        .route(routes::GENERATE_HOLD_INVOICE, post(generate_hold_invoice))
+        .route(routes::SETTLE_HOLD_INVOICE, post(settle_hold_invoice))
+        .route(routes::CANCEL_HOLD_INVOICE, post(cancel_hold_invoice))
+        .route(routes::PROBE, post(probe))
+        .route(routes::CREATE_OFFER, post(create_offer))
+        .route(routes::PAY_OFFER, post(pay_offer))
+        .route(routes::CREATE_REFUND, post(create_refund))
+        .route(routes::REQUEST_REFUND_PAYMENT, post(request_refund_payment))
+        .route(routes::WEBSOCKET, get(ws_handler))
+        .route(
+            routes::CHANNEL_ACCEPTANCE_POLICY,
+            get(get_channel_acceptance_policy).post(set_channel_acceptance_policy),
+        )
+        .route(routes::CHANNEL_BACKUP, get(channel_backup))
+        .route(routes::WEBHOOKS, get(list_webhooks).post(create_webhook))
+        .route(routes::DELETE_WEBHOOK, delete(delete_webhook))
+        .route(routes::BAKE_MACAROON, post(bake_macaroon))
+        .route(
+            routes::LIST_MACAROON_ROOT_KEYS,
+            get(list_macaroon_root_keys),
+        )
+        .route(
+            routes::DELETE_MACAROON_ROOT_KEY,
+            delete(delete_macaroon_root_key),
+        )
+        .route(
+            routes::ROTATE_MACAROON_ROOT_KEYS,
+            post(rotate_macaroon_root_keys),
+        )
+}
+
 async fn root() -> Result<impl IntoResponse, ApiError> {
     Ok(())
 }
@@ -236,12 +350,44 @@ pub fn bad_request(e: impl Into<anyhow::Error>) -> ApiError {
     ApiError::BadRequest(anyhow_err.into())
 }
 
+/// A page that starts after an unknown cursor is a bad request.
+pub fn list_error(e: anyhow::Error) -> ApiError {
+    if e.is::<UnknownCursor>() {
+        bad_request(e)
+    } else {
+        internal_server(e)
+    }
+}
+
 #[allow(clippy::all)]
 pub mod codegen {
     #![allow(dead_code)]
     include!(concat!(env!("OUT_DIR"), "/mod.rs"));
 }
 
+/// The time range and size of the page, the cursor depends on the route.
+pub(crate) fn to_page(pagination: &Pagination) -> Result<Page, ApiError> {
+    let to_time = |timestamp: Option<i64>| {
+        timestamp
+            .map(OffsetDateTime::from_unix_timestamp)
+            .transpose()
+            .map_err(bad_request)
+    };
+    Ok(Page {
+        since: to_time(pagination.since)?,
+        until: to_time(pagination.until)?,
+        limit: pagination.limit,
+    })
+}
+
+/// Decode a hex encoded ID, like a channel ID or a payment hash.
+pub(crate) fn from_hex_id<const N: usize>(hex: &str) -> Result<[u8; N], ApiError> {
+    Vec::<u8>::from_hex(hex)
+        .map_err(bad_request)?
+        .try_into()
+        .map_err(|_| bad_request(anyhow!("Expected {N} bytes of hex: {hex}")))
+}
+
 pub(crate) fn empty_string_as_none<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
 where
     D: Deserializer<'de>,
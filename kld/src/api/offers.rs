@@ -0,0 +1,194 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use api::{
+    CreateOffer, CreateRefund, OfferKind, OfferResponse, PayOffer, PaymentResponse,
+    RequestRefundPayment,
+};
+use axum::{extract::Query, response::IntoResponse, Extension, Json};
+use bitcoin::hashes::{hex::ToHex, sha256};
+use lightning::offers::{
+    offer::{Amount, Offer as Bolt12Offer},
+    refund::Refund,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::offer::{self, InvoiceRequestStatus, Offer},
+    ldk::{bolt12_parse_error, payment_limits::PaymentLimits, LightningInterface},
+};
+
+use super::{
+    bad_request, empty_string_as_none, internal_server, macaroon_auth::MacaroonRestrictions,
+    ApiError,
+};
+
+pub(crate) async fn create_offer(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(create_offer): Json<CreateOffer>,
+) -> Result<impl IntoResponse, ApiError> {
+    let offer = lightning_interface
+        .create_offer(
+            create_offer.description,
+            create_offer.amount_msat,
+            create_offer.expiry.map(Duration::from_secs),
+            create_offer.issuer,
+            create_offer.label,
+        )
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(OfferResponse {
+        id: offer.id.to_hex(),
+        bolt12: offer.bolt12,
+    }))
+}
+
+pub(crate) async fn create_refund(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(restrictions): Extension<MacaroonRestrictions>,
+    Json(create_refund): Json<CreateRefund>,
+) -> Result<impl IntoResponse, ApiError> {
+    restrictions.check_routed_payment_msat(Some(create_refund.amount_msat), |amount| {
+        lightning_interface.max_fee_msat(&PaymentLimits::default(), amount)
+    })?;
+    let refund = lightning_interface
+        .create_refund(
+            create_refund.description,
+            create_refund.amount_msat,
+            create_refund.expiry.map(Duration::from_secs),
+            create_refund.label,
+        )
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(OfferResponse {
+        id: refund.id.to_hex(),
+        bolt12: refund.bolt12,
+    }))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListOffersParams {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub label: Option<String>,
+}
+
+pub(crate) async fn list_offers(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListOffersParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let offers: Vec<api::Offer> = lightning_interface
+        .list_offers(params.label)
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(to_api_offer)
+        .collect();
+    Ok(Json(offers))
+}
+
+pub(crate) async fn pay_offer(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(restrictions): Extension<MacaroonRestrictions>,
+    Json(pay_offer): Json<PayOffer>,
+) -> Result<impl IntoResponse, ApiError> {
+    let offer =
+        Bolt12Offer::from_str(&pay_offer.offer).map_err(|e| bad_request(bolt12_parse_error(e)))?;
+    let amount_msat = pay_offer.amount_msat.or(match offer.amount() {
+        Some(Amount::Bitcoin { amount_msats }) => {
+            amount_msats.checked_mul(pay_offer.quantity.unwrap_or(1))
+        }
+        _ => None,
+    });
+    restrictions.check_routed_payment_msat(amount_msat, |amount| {
+        lightning_interface.max_fee_msat(&PaymentLimits::default(), amount)
+    })?;
+    let destination = offer.signing_pubkey().to_string();
+    let payment = lightning_interface
+        .pay_offer(
+            offer,
+            pay_offer.amount_msat,
+            pay_offer.quantity,
+            pay_offer.payer_note,
+            pay_offer.label,
+        )
+        .await
+        .map_err(internal_server)?;
+    let response = PaymentResponse {
+        id: payment.id.0.to_hex(),
+        destination,
+        payment_hash: payment.hash.map(|h| h.0.to_hex()).unwrap_or_default(),
+        created_at: payment.timestamp.unix_timestamp() as u64,
+        amount_msat: Some(payment.amount),
+        amount_sent_msat: payment.amount + payment.fee.unwrap_or_default(),
+        payment_preimage: payment.preimage.map(|i| i.0.to_hex()).unwrap_or_default(),
+        status: payment.status.to_string(),
+    };
+    Ok(Json(response))
+}
+
+pub(crate) async fn request_refund_payment(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(request): Json<RequestRefundPayment>,
+) -> Result<impl IntoResponse, ApiError> {
+    let refund =
+        Refund::from_str(&request.refund).map_err(|e| bad_request(bolt12_parse_error(e)))?;
+    lightning_interface
+        .request_refund_payment(refund)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(()))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListInvoiceRequestsParams {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub offer_id: Option<String>,
+}
+
+pub(crate) async fn list_invoice_requests(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListInvoiceRequestsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let offer_id = params
+        .offer_id
+        .map(|id| sha256::Hash::from_str(&id))
+        .transpose()
+        .map_err(bad_request)?;
+    let invoice_requests: Vec<api::InvoiceRequest> = lightning_interface
+        .list_invoice_requests(offer_id)
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(|r| api::InvoiceRequest {
+            payment_id: r.payment_id.0.to_hex(),
+            offer_id: r.offer_id.to_hex(),
+            amount_msat: r.amount,
+            quantity: r.quantity,
+            payer_note: r.payer_note,
+            status: match r.status {
+                InvoiceRequestStatus::Pending => api::InvoiceRequestStatus::Pending,
+                InvoiceRequestStatus::Paid => api::InvoiceRequestStatus::Paid,
+                InvoiceRequestStatus::Failed => api::InvoiceRequestStatus::Failed,
+            },
+            created_at: r.timestamp.unix_timestamp() as u64,
+        })
+        .collect();
+    Ok(Json(invoice_requests))
+}
+
+fn to_api_offer(offer: Offer) -> api::Offer {
+    api::Offer {
+        id: offer.id.to_hex(),
+        kind: match offer.kind {
+            offer::OfferKind::Offer => OfferKind::Offer,
+            offer::OfferKind::Refund => OfferKind::Refund,
+        },
+        bolt12: offer.bolt12,
+        description: offer.description,
+        label: offer.label,
+        amount_msat: offer.amount,
+        direction: offer.direction.to_string(),
+        created_at: offer.timestamp.unix_timestamp() as u64,
+    }
+}
@@ -1,17 +1,27 @@
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
-use anyhow::Context;
-use api::{KeysendRequest, PayInvoice, PaymentResponse};
-use axum::{extract::Query, response::IntoResponse, Extension, Json};
-use bitcoin::hashes::hex::ToHex;
-use lightning::routing::gossip::NodeId;
+use anyhow::{anyhow, Context};
+use api::{
+    KeysendRequest, Pagination, PayInvoice, PaymentAttempt, PaymentDetailResponse, PaymentHop,
+    PaymentResponse, PaymentStatusResponse, ProbeRequest, ProbeResponse, ProbeStatus,
+};
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
+use bitcoin::{
+    hashes::hex::{FromHex, ToHex},
+    secp256k1::PublicKey,
+};
+use lightning::{ln::channelmanager::PaymentId, routing::gossip::NodeId};
 
 use crate::{
     database::{
         invoice::Invoice,
-        payment::{PaymentDirection, PaymentStatus},
+        payment::{Payment, PaymentDirection, PaymentFilter, PaymentStatus},
     },
-    ldk::LightningInterface,
+    ldk::{payment_limits::PaymentLimits, LightningInterface, ProbeResult},
 };
 
 use super::{
@@ -20,19 +30,37 @@ use super::{
         GetV1PayListPaymentsResponse, GetV1PayListPaymentsResponsePaymentsItem,
         GetV1PayListPaymentsResponsePaymentsItemStatus,
     },
-    empty_string_as_none, internal_server, ApiError,
+    empty_string_as_none, from_hex_id, internal_server, list_error,
+    macaroon_auth::MacaroonRestrictions,
+    to_page, ApiError,
 };
 
 pub(crate) async fn keysend(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(restrictions): Extension<MacaroonRestrictions>,
     Json(keysend_request): Json<KeysendRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
     let node_id = NodeId::from_str(&keysend_request.pubkey).map_err(bad_request)?;
+    let limits = payment_limits(
+        keysend_request.maxfeepercent,
+        keysend_request.exemptfee,
+        keysend_request.retry_for,
+        keysend_request.maxdelay,
+    )?;
+    restrictions.check_routed_payment_msat(Some(keysend_request.amount), |amount| {
+        lightning_interface.max_fee_msat(&limits, amount)
+    })?;
     let payment = lightning_interface
-        .keysend_payment(node_id, keysend_request.amount)
+        .keysend_payment(
+            node_id,
+            keysend_request.amount,
+            limits,
+            !keysend_request.nowait.unwrap_or_default(),
+        )
         .await
         .map_err(internal_server)?;
     let response = PaymentResponse {
+        id: payment.id.0.to_hex(),
         destination: keysend_request.pubkey,
         payment_hash: payment
             .hash
@@ -41,7 +69,6 @@ pub(crate) async fn keysend(
             .0
             .to_hex(),
         created_at: payment.timestamp.unix_timestamp() as u64,
-        parts: 1,
         amount_msat: Some(keysend_request.amount),
         amount_sent_msat: keysend_request.amount * 1000,
         payment_preimage: payment.preimage.map(|i| i.0.to_hex()).unwrap_or_default(),
@@ -52,19 +79,49 @@ pub(crate) async fn keysend(
 
 pub(crate) async fn pay_invoice(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(restrictions): Extension<MacaroonRestrictions>,
     Json(pay_invoice_request): Json<PayInvoice>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let invoice: Invoice = pay_invoice_request
+    let mut invoice: Invoice = pay_invoice_request
         .invoice
         .try_into()
         .map_err(bad_request)?;
+    match (invoice.amount, pay_invoice_request.amount_msat) {
+        (Some(_), Some(_)) => {
+            return Err(bad_request(anyhow!(
+                "amount_msat is only for an invoice without an amount"
+            )))
+        }
+        (None, None) => {
+            return Err(bad_request(anyhow!(
+                "amount_msat is required for an invoice without an amount"
+            )))
+        }
+        (None, amount_msat) => invoice.amount = amount_msat,
+        (Some(_), None) => (),
+    }
+    let limits = payment_limits(
+        pay_invoice_request.maxfeepercent,
+        pay_invoice_request.exemptfee,
+        pay_invoice_request.retry_for,
+        pay_invoice_request.maxdelay,
+    )?;
+    restrictions.check_routed_payment_msat(invoice.amount, |amount| {
+        lightning_interface.max_fee_msat(&limits, amount)
+    })?;
     let destination = invoice.payee_pub_key.to_string();
     let amount = invoice.amount;
     let payment = lightning_interface
-        .pay_invoice(invoice, pay_invoice_request.label)
+        .pay_invoice(
+            invoice,
+            pay_invoice_request.label,
+            limits,
+            !pay_invoice_request.nowait.unwrap_or_default(),
+        )
         .await
         .map_err(internal_server)?;
     let response = PaymentResponse {
+        id: payment.id.0.to_hex(),
         destination,
         payment_hash: payment
             .hash
@@ -73,7 +130,6 @@ pub(crate) async fn pay_invoice(
             .0
             .to_hex(),
         created_at: payment.timestamp.unix_timestamp() as u64,
-        parts: 1,
         amount_msat: amount,
         amount_sent_msat: payment.amount,
         payment_preimage: payment.preimage.map(|i| i.0.to_hex()).unwrap_or_default(),
@@ -82,6 +138,74 @@ pub(crate) async fn pay_invoice(
     Ok(Json(response))
 }
 
+fn payment_limits(
+    maxfeepercent: Option<f64>,
+    exemptfee: Option<u64>,
+    retry_for: Option<u64>,
+    maxdelay: Option<u64>,
+) -> Result<PaymentLimits, ApiError> {
+    let limits = PaymentLimits {
+        max_fee_percent: maxfeepercent,
+        exempt_fee_msat: exemptfee,
+        retry_for: retry_for.map(Duration::from_secs),
+        max_cltv_expiry_delta: maxdelay
+            .map(u32::try_from)
+            .transpose()
+            .map_err(bad_request)?,
+    };
+    limits.validate().map_err(bad_request)?;
+    Ok(limits)
+}
+
+pub(crate) async fn probe(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(probe_request): Json<ProbeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (payee, amount, route_hints) = match (probe_request.invoice, probe_request.pubkey) {
+        (Some(invoice), _) => {
+            let invoice: Invoice = invoice.try_into().map_err(bad_request)?;
+            let amount = probe_request
+                .amount_msat
+                .or(invoice.amount)
+                .context("amount missing from invoice")
+                .map_err(bad_request)?;
+            (invoice.payee_pub_key, amount, invoice.bolt11.route_hints())
+        }
+        (None, Some(pubkey)) => {
+            let payee = PublicKey::from_str(&pubkey).map_err(bad_request)?;
+            let amount = probe_request
+                .amount_msat
+                .context("amount is required to probe a node")
+                .map_err(bad_request)?;
+            (payee, amount, vec![])
+        }
+        (None, None) => return Err(bad_request(anyhow!("either pubkey or invoice is required"))),
+    };
+    let response = match lightning_interface
+        .probe(payee, amount, route_hints)
+        .await
+        .map_err(internal_server)?
+    {
+        ProbeResult::Succeeded { paths } => ProbeResponse {
+            status: ProbeStatus::Succeeded,
+            paths: paths as u64,
+            failed_short_channel_id: None,
+            failed_node_id: None,
+        },
+        ProbeResult::Failed {
+            paths,
+            short_channel_id,
+            node_id,
+        } => ProbeResponse {
+            status: ProbeStatus::Failed,
+            paths: paths as u64,
+            failed_short_channel_id: short_channel_id,
+            failed_node_id: node_id.map(|n| n.to_string()),
+        },
+    };
+    Ok(Json(response))
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListPaysParams {
@@ -89,13 +213,15 @@ pub struct ListPaysParams {
     pub invoice: Option<String>,
     #[serde(default, deserialize_with = "empty_string_as_none")]
     pub direction: Option<String>,
+    pub status: Option<GetV1PayListPaymentsResponsePaymentsItemStatus>,
 }
 
 pub(crate) async fn list_payments(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
     Query(params): Query<ListPaysParams>,
+    Query(pagination): Query<Pagination>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let invoice = params
+    let invoice: Option<Invoice> = params
         .invoice
         .map(|i| i.try_into())
         .transpose()
@@ -105,12 +231,43 @@ pub(crate) async fn list_payments(
         .map(|d| PaymentDirection::from_str(&d))
         .transpose()
         .map_err(bad_request)?;
+    let statuses = match params.status {
+        None => vec![],
+        Some(GetV1PayListPaymentsResponsePaymentsItemStatus::Pending) => {
+            vec![PaymentStatus::Pending]
+        }
+        Some(GetV1PayListPaymentsResponsePaymentsItemStatus::Complete) => {
+            vec![PaymentStatus::Succeeded]
+        }
+        Some(GetV1PayListPaymentsResponsePaymentsItemStatus::Failed) => vec![
+            PaymentStatus::RecipientRejected,
+            PaymentStatus::UserAbandoned,
+            PaymentStatus::RetriesExhausted,
+            PaymentStatus::Expired,
+            PaymentStatus::RouteNotFound,
+            PaymentStatus::Error,
+        ],
+    };
+    let filter = PaymentFilter {
+        payment_hash: invoice.map(|i| i.payment_hash),
+        direction,
+        statuses,
+        page: to_page(&pagination)?,
+        cursor: pagination
+            .cursor
+            .as_deref()
+            .map(from_hex_id)
+            .transpose()?
+            .map(PaymentId),
+    };
     let payments: Vec<GetV1PayListPaymentsResponsePaymentsItem> = lightning_interface
-        .list_payments(invoice, direction)
+        .list_payments(filter)
         .await
-        .map_err(internal_server)?
+        .map_err(list_error)?
         .into_iter()
         .map(|p| GetV1PayListPaymentsResponsePaymentsItem {
+            parts: p.parts() as i64,
+            attempts: p.attempts as i64,
             bolt11: p.bolt11.as_ref().map(|b| b.to_string()),
             status: match p.status {
                 PaymentStatus::Pending => GetV1PayListPaymentsResponsePaymentsItemStatus::Pending,
@@ -136,3 +293,69 @@ pub(crate) async fn list_payments(
         .collect();
     Ok(Json(GetV1PayListPaymentsResponse { payments }))
 }
+
+pub(crate) async fn get_payment(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let payment_id = PaymentId(<[u8; 32]>::from_hex(&id).map_err(bad_request)?);
+    let payment = lightning_interface
+        .get_payment(payment_id)
+        .await
+        .map_err(internal_server)?
+        .ok_or(ApiError::NotFound(id))?;
+    Ok(Json(payment_status(&payment)))
+}
+
+pub(crate) async fn payment_detail(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let payment_id = PaymentId(<[u8; 32]>::from_hex(&id).map_err(bad_request)?);
+    let payment = lightning_interface
+        .get_payment(payment_id)
+        .await
+        .map_err(internal_server)?
+        .ok_or(ApiError::NotFound(id))?;
+    let attempts = lightning_interface
+        .get_payment_attempts(payment_id)
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(|attempt| PaymentAttempt {
+            status: attempt.status.to_string(),
+            hops: attempt
+                .hops
+                .into_iter()
+                .map(|hop| PaymentHop {
+                    node_id: hop.node_id.to_string(),
+                    short_channel_id: hop.short_channel_id,
+                })
+                .collect(),
+            amount_msat: attempt.amount,
+            fee_msat: attempt.fee,
+            failed_short_channel_id: attempt.failed_short_channel_id,
+            failed_permanently: attempt.failed_permanently,
+            created_at: attempt.timestamp.unix_timestamp() as u64,
+        })
+        .collect();
+    Ok(Json(PaymentDetailResponse {
+        payment: payment_status(&payment),
+        attempts,
+    }))
+}
+
+fn payment_status(payment: &Payment) -> PaymentStatusResponse {
+    PaymentStatusResponse {
+        id: payment.id.0.to_hex(),
+        payment_hash: payment.hash.map(|h| h.0.to_hex()),
+        payment_preimage: payment.preimage.map(|p| p.0.to_hex()),
+        status: payment.status.to_string(),
+        direction: payment.direction.to_string(),
+        amount_msat: payment.amount,
+        fee_msat: payment.fee,
+        attempts: payment.attempts,
+        parts: payment.parts(),
+        created_at: payment.timestamp.unix_timestamp() as u64,
+    }
+}
@@ -0,0 +1,546 @@
+use std::{
+    fs,
+    net::SocketAddr,
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use api::routes;
+use axum::{extract::Extension, Router};
+use futures::{future::Shared, Future};
+use hyper::{header::CONTENT_TYPE, service::Service, Body, Method, Request};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+};
+
+use crate::{
+    bitcoind::bitcoind_interface::BitcoindInterface,
+    ldk::{
+        channel_utils::{block_from_scid, tx_index_from_scid, vout_from_scid},
+        LightningInterface,
+    },
+    wallet::WalletInterface,
+};
+
+use super::{admin_routes, macaroon_auth::MacaroonRestrictions, readonly_routes};
+
+// Error codes of the JSON-RPC 2.0 specification.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+// Error code of Core Lightning for a payment that failed after trying every route.
+const PAY_STOPPED_RETRYING: i64 = 210;
+
+/// A Core Lightning method and the REST route that serves it.
+struct RpcMethod {
+    name: &'static str,
+    method: Method,
+    route: &'static str,
+    // Parameters in their positional order, with the name of the field in the REST request.
+    // Parameters that kld does not support have no field.
+    params: &'static [(&'static str, Option<&'static str>)],
+    // Translates the REST response to the result of Core Lightning.
+    result: fn(Value) -> Result<Value, RpcError>,
+}
+
+fn rpc_methods() -> [RpcMethod; 5] {
+    [
+        RpcMethod {
+            name: "getinfo",
+            method: Method::GET,
+            route: routes::GET_INFO,
+            params: &[],
+            result: getinfo_result,
+        },
+        RpcMethod {
+            name: "listfunds",
+            method: Method::GET,
+            route: routes::LIST_FUNDS,
+            params: &[],
+            result: listfunds_result,
+        },
+        RpcMethod {
+            name: "listpeerchannels",
+            method: Method::GET,
+            route: routes::LIST_PEER_CHANNELS,
+            params: &[],
+            result: listpeerchannels_result,
+        },
+        RpcMethod {
+            name: "invoice",
+            method: Method::POST,
+            route: routes::GENERATE_INVOICE,
+            params: &[
+                ("amount_msat", Some("amount")),
+                ("label", Some("label")),
+                ("description", Some("description")),
+                ("expiry", Some("expiry")),
+                ("fallbacks", Some("fallbacks")),
+                ("preimage", Some("preimage")),
+                ("exposeprivatechannels", Some("private")),
+            ],
+            result: invoice_result,
+        },
+        RpcMethod {
+            name: "pay",
+            method: Method::POST,
+            route: routes::PAY_INVOICE,
+            params: &[
+                ("bolt11", Some("invoice")),
+                ("amount_msat", Some("amount_msat")),
+                ("label", Some("label")),
+                ("riskfactor", None),
+                ("maxfeepercent", Some("maxfeepercent")),
+                ("retry_for", Some("retry_for")),
+                ("maxdelay", Some("maxdelay")),
+                ("exemptfee", Some("exemptfee")),
+            ],
+            result: pay_result,
+        },
+    ]
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// JSON-RPC 2.0 server with Core Lightning method names for local automation. There are no
+/// macaroons, access is controlled by the permissions of the socket file.
+pub struct RpcServer {
+    listener: UnixListener,
+    path: String,
+}
+
+pub fn bind_rpc_server(path: String) -> Result<RpcServer> {
+    // A socket that is left over from an earlier run stops us from binding.
+    if fs::metadata(&path).is_ok() {
+        fs::remove_file(&path).with_context(|| format!("failed to remove old socket {path}"))?;
+    }
+    // The socket is created with the permissions of the umask. Bind it in a directory that only
+    // we can enter and move it into place once only we can connect to it.
+    let dir = format!("{path}.tmp");
+    if fs::metadata(&dir).is_ok() {
+        fs::remove_dir_all(&dir)
+            .with_context(|| format!("failed to remove old directory {dir}"))?;
+    }
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .with_context(|| format!("failed to create directory {dir}"))?;
+    let listener = bind_private(&dir, &path);
+    let _ = fs::remove_dir_all(&dir);
+    let listener = listener?;
+    info!("Starting JSON-RPC server on {path}");
+    Ok(RpcServer { listener, path })
+}
+
+fn bind_private(dir: &str, path: &str) -> Result<UnixListener> {
+    let tmp_path = format!("{dir}/socket");
+    let listener = UnixListener::bind(&tmp_path)
+        .with_context(|| format!("failed to bind socket {tmp_path}"))?;
+    fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o600))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("failed to move socket to {path}"))?;
+    Ok(listener)
+}
+
+impl RpcServer {
+    pub async fn serve(
+        self,
+        bitcoind_api: Arc<dyn BitcoindInterface + Send + Sync>,
+        lightning_api: Arc<dyn LightningInterface + Send + Sync>,
+        wallet_api: Arc<dyn WalletInterface + Send + Sync>,
+        quit_signal: Shared<impl Future<Output = ()>>,
+    ) -> Result<()> {
+        let routes = readonly_routes()
+            .merge(admin_routes())
+            .layer(Extension(bitcoind_api))
+            .layer(Extension(lightning_api))
+            .layer(Extension(wallet_api))
+            .layer(Extension(MacaroonRestrictions::default()));
+
+        loop {
+            tokio::select!(
+                accepted = self.listener.accept() => {
+                    let (stream, _) = accepted?;
+                    let routes = routes.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, routes).await {
+                            debug!("JSON-RPC connection closed: {e}");
+                        }
+                    });
+                }
+                _ = quit_signal.clone() => break,
+            );
+        }
+        let _ = fs::remove_file(&self.path);
+        info!("JSON-RPC server shutdown successfully.");
+        Ok(())
+    }
+}
+
+/// Requests are JSON objects without a separator, responses are followed by an empty line like
+/// the ones of Core Lightning.
+async fn handle_connection(mut stream: UnixStream, routes: Router) -> Result<()> {
+    let mut buf = vec![];
+    loop {
+        let mut read_buf = [0u8; 4096];
+        let n = stream.read(&mut read_buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&read_buf[..n]);
+
+        let mut requests = serde_json::Deserializer::from_slice(&buf).into_iter::<Value>();
+        let mut responses = vec![];
+        let mut consumed = 0;
+        loop {
+            match requests.next() {
+                Some(Ok(request)) => {
+                    consumed = requests.byte_offset();
+                    responses.push(handle_request(request, routes.clone()).await);
+                }
+                Some(Err(e)) if e.is_eof() => break,
+                Some(Err(e)) => {
+                    let response =
+                        error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()));
+                    write_response(&mut stream, &response).await?;
+                    bail!("Invalid JSON: {e}");
+                }
+                None => break,
+            }
+        }
+        buf.drain(..consumed);
+        for response in responses {
+            write_response(&mut stream, &response).await?;
+        }
+    }
+}
+
+async fn write_response(stream: &mut UnixStream, response: &Value) -> Result<()> {
+    let mut bytes = serde_json::to_vec(response)?;
+    bytes.extend_from_slice(b"\n\n");
+    stream.write_all(&bytes).await?;
+    Ok(())
+}
+
+async fn handle_request(request: Value, routes: Router) -> Value {
+    let request: RpcRequest = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(e) => {
+            return error_response(Value::Null, RpcError::new(INVALID_REQUEST, e.to_string()))
+        }
+    };
+    if request.jsonrpc != "2.0" {
+        return error_response(
+            request.id,
+            RpcError::new(INVALID_REQUEST, "jsonrpc must be 2.0"),
+        );
+    }
+    match call(&request, routes).await {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": request.id, "result": result}),
+        Err(error) => error_response(request.id, error),
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": error})
+}
+
+/// Serve the request with the handler of the REST route.
+async fn call(request: &RpcRequest, mut routes: Router) -> Result<Value, RpcError> {
+    let methods = rpc_methods();
+    let method = methods
+        .iter()
+        .find(|m| m.name == request.method)
+        .ok_or_else(|| {
+            RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {}", request.method),
+            )
+        })?;
+    let body = rest_body(method, &request.params)?;
+    let http_request = Request::builder()
+        .method(method.method.clone())
+        .uri(method.route)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+    let response = routes
+        .call(http_request)
+        .await
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+    if !status.is_success() {
+        let message = serde_json::from_slice::<api::Error>(&bytes)
+            .map(|e| e.detail)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&bytes).to_string());
+        let code = if status.is_client_error() {
+            INVALID_PARAMS
+        } else {
+            warn!("JSON-RPC {} failed: {message}", method.name);
+            INTERNAL_ERROR
+        };
+        return Err(RpcError::new(code, message));
+    }
+    let result: Value = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?
+    };
+    (method.result)(without_nulls(result))
+}
+
+// Core Lightning leaves out the fields that have no value.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+fn getinfo_result(mut result: Value) -> Result<Value, RpcError> {
+    let info = object(&mut result)?;
+    let synced = info
+        .remove("synced_to_chain")
+        .and_then(|synced| synced.as_bool())
+        .unwrap_or(true);
+    for field in ["testnet", "chains", "api_version"] {
+        info.remove(field);
+    }
+    if let Some(Value::Array(addresses)) = info.get_mut("address") {
+        for address in addresses {
+            if let Some(cln) = address.as_str().map(cln_address) {
+                *address = cln;
+            }
+        }
+    }
+    if !synced {
+        info.insert(
+            "warning_lightningd_sync".to_string(),
+            "Still loading latest blocks from bitcoind.".into(),
+        );
+    }
+    Ok(result)
+}
+
+fn cln_address(address: &str) -> Value {
+    let (address_type, host, port) = match address.parse::<SocketAddr>() {
+        Ok(SocketAddr::V4(a)) => ("ipv4", a.ip().to_string(), a.port()),
+        Ok(SocketAddr::V6(a)) => ("ipv6", a.ip().to_string(), a.port()),
+        Err(_) => {
+            let (host, port) = address.rsplit_once(':').unwrap_or((address, "9735"));
+            let address_type = if host.ends_with(".onion") {
+                "torv3"
+            } else {
+                "dns"
+            };
+            (address_type, host.to_string(), port.parse().unwrap_or(9735))
+        }
+    };
+    json!({"type": address_type, "address": host, "port": port})
+}
+
+fn listfunds_result(mut result: Value) -> Result<Value, RpcError> {
+    let funds = object(&mut result)?;
+    if let Some(Value::Array(outputs)) = funds.get_mut("outputs") {
+        for output in outputs {
+            let output = object(output)?;
+            if let Some(status) = output.get("status").and_then(Value::as_str) {
+                let status = status.to_lowercase();
+                output.insert("status".to_string(), status.into());
+            }
+            output.insert("reserved".to_string(), false.into());
+        }
+    }
+    if let Some(Value::Array(channels)) = funds.get_mut("channels") {
+        for channel in channels {
+            let channel = object(channel)?;
+            channel.remove("channel_sat");
+            let state = match channel.get("state").and_then(Value::as_str) {
+                Some("Pending") => "CHANNELD_AWAITING_LOCKIN",
+                _ => "CHANNELD_NORMAL",
+            };
+            channel.insert("state".to_string(), state.into());
+            cln_short_channel_id(channel);
+        }
+    }
+    Ok(result)
+}
+
+fn listpeerchannels_result(mut result: Value) -> Result<Value, RpcError> {
+    if let Value::Array(channels) = &mut result {
+        for channel in channels {
+            let channel = object(channel)?;
+            // The alias of Core Lightning are the short channel ID aliases, not the peer alias.
+            channel.remove("alias");
+            channel
+                .entry("htlcs")
+                .or_insert_with(|| Value::Array(vec![]));
+            cln_short_channel_id(channel);
+        }
+    }
+    Ok(json!({ "channels": result }))
+}
+
+fn invoice_result(mut result: Value) -> Result<Value, RpcError> {
+    let invoice = object(&mut result)?;
+    rename(invoice, "paymentHash", "payment_hash");
+    rename(invoice, "expiresAt", "expires_at");
+    Ok(result)
+}
+
+fn pay_result(mut result: Value) -> Result<Value, RpcError> {
+    let payment = object(&mut result)?;
+    payment.remove("id");
+    rename(payment, "paymentHash", "payment_hash");
+    rename(payment, "createdAt", "created_at");
+    rename(payment, "amountMsat", "amount_msat");
+    rename(payment, "amountSentMsat", "amount_sent_msat");
+    rename(payment, "paymentPreimage", "payment_preimage");
+    let status = match payment.get("status").and_then(Value::as_str) {
+        Some("succeeded") => "complete",
+        Some("pending") => "pending",
+        status => {
+            return Err(RpcError::new(
+                PAY_STOPPED_RETRYING,
+                format!("Payment failed: {}", status.unwrap_or("unknown")),
+            ))
+        }
+    };
+    payment.insert("status".to_string(), status.into());
+    Ok(result)
+}
+
+fn object(value: &mut Value) -> Result<&mut Map<String, Value>, RpcError> {
+    value
+        .as_object_mut()
+        .ok_or_else(|| RpcError::new(INTERNAL_ERROR, "Expected an object in the response"))
+}
+
+fn rename(map: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = map.remove(from) {
+        map.insert(to.to_string(), value);
+    }
+}
+
+// Core Lightning formats short channel IDs as block x transaction x output.
+fn cln_short_channel_id(channel: &mut Map<String, Value>) {
+    let short_channel_id = channel
+        .get("short_channel_id")
+        .and_then(Value::as_str)
+        .map(|id| id.parse::<u64>());
+    match short_channel_id {
+        Some(Ok(id)) => {
+            let id = format!(
+                "{}x{}x{}",
+                block_from_scid(&id),
+                tx_index_from_scid(&id),
+                vout_from_scid(&id)
+            );
+            channel.insert("short_channel_id".to_string(), id.into());
+        }
+        // kld returns an empty string for a channel that is not confirmed yet.
+        Some(Err(_)) => {
+            channel.remove("short_channel_id");
+        }
+        None => {}
+    }
+}
+
+/// Translate the positional or named parameters to the JSON body of the REST route.
+fn rest_body(method: &RpcMethod, params: &Value) -> Result<Value, RpcError> {
+    let named: Vec<(&str, &Value)> = match params {
+        Value::Null => vec![],
+        Value::Array(values) => {
+            if values.len() > method.params.len() {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!(
+                        "{} takes at most {} parameters",
+                        method.name,
+                        method.params.len()
+                    ),
+                ));
+            }
+            method
+                .params
+                .iter()
+                .zip(values)
+                .map(|((name, _), value)| (*name, value))
+                .collect()
+        }
+        Value::Object(values) => values.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+        _ => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "params must be an array or an object",
+            ))
+        }
+    };
+    let mut body = Map::new();
+    for (name, value) in named {
+        if value.is_null() {
+            continue;
+        }
+        let field = method
+            .params
+            .iter()
+            .find(|(param, _)| *param == name)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown parameter {name}")))?
+            .1
+            .ok_or_else(|| {
+                RpcError::new(INVALID_PARAMS, format!("Unsupported parameter {name}"))
+            })?;
+        body.insert(field.to_string(), msat_value(name, value)?);
+    }
+    Ok(Value::Object(body))
+}
+
+/// Core Lightning also accepts amounts like "1000msat".
+fn msat_value(name: &str, value: &Value) -> Result<Value, RpcError> {
+    match value.as_str() {
+        Some(amount) if name.ends_with("_msat") => amount
+            .trim_end_matches("msat")
+            .parse::<u64>()
+            .map(Value::from)
+            .map_err(|_| RpcError::new(INVALID_PARAMS, format!("Invalid {name} {amount}"))),
+        _ => Ok(value.clone()),
+    }
+}
@@ -97,6 +97,14 @@ pub(crate) async fn estimate_channel_liquidity_range(
     }
 }
 
+/// The document is maintained as YAML next to this module. kld and kld-client generate their
+/// types from it.
+pub(crate) async fn openapi() -> Result<impl IntoResponse, ApiError> {
+    let spec: serde_json::Value =
+        serde_yaml::from_str(include_str!("spec.yaml")).map_err(internal_server)?;
+    Ok(Json(spec))
+}
+
 pub(crate) async fn get_fees(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
 ) -> Result<impl IntoResponse, ApiError> {
@@ -20,7 +20,7 @@ use crate::to_string_empty;
 use crate::wallet::WalletInterface;
 
 use super::codegen::get_v1_newaddr_response::GetV1NewaddrResponse;
-use super::{bad_request, internal_server, ApiError};
+use super::{bad_request, internal_server, macaroon_auth::MacaroonRestrictions, ApiError};
 
 pub(crate) async fn get_balance(
     Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
@@ -58,13 +58,17 @@ pub(crate) async fn new_address(
 
 pub(crate) async fn transfer(
     Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Extension(restrictions): Extension<MacaroonRestrictions>,
     Json(wallet_transfer): Json<WalletTransfer>,
 ) -> Result<impl IntoResponse, ApiError> {
     let address = Address::from_str(&wallet_transfer.address).map_err(bad_request)?;
     let amount = if wallet_transfer.satoshis == "all" {
+        restrictions.check_payment_msat(None)?;
         u64::MAX
     } else {
-        u64::from_str(&wallet_transfer.satoshis).map_err(bad_request)?
+        let amount = u64::from_str(&wallet_transfer.satoshis).map_err(bad_request)?;
+        restrictions.check_payment_msat(Some(amount.saturating_mul(1000)))?;
+        amount
     };
     let (tx, tx_details) = wallet
         .transfer(address, amount, wallet_transfer.fee_rate, None, vec![])
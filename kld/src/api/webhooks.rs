@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use api::CreateWebhook;
+use axum::{extract::Path, response::IntoResponse, Extension, Json};
+use reqwest::Url;
+use uuid::Uuid;
+
+use crate::{database::webhook::Webhook, ldk::LightningInterface};
+
+use super::{bad_request, internal_server, ApiError};
+
+pub(crate) async fn list_webhooks(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let webhooks: Vec<api::Webhook> = lightning_interface
+        .list_webhooks()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(to_api_webhook)
+        .collect();
+    Ok(Json(webhooks))
+}
+
+pub(crate) async fn create_webhook(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(create_webhook): Json<CreateWebhook>,
+) -> Result<impl IntoResponse, ApiError> {
+    let url = Url::parse(&create_webhook.url).map_err(bad_request)?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(bad_request(anyhow!("Webhook URL must be http or https")));
+    }
+    if create_webhook.secret.is_empty() {
+        return Err(bad_request(anyhow!("Webhook secret must not be empty")));
+    }
+    if create_webhook.events.is_empty() {
+        return Err(bad_request(anyhow!("Webhook must have at least one event")));
+    }
+    let webhook = Webhook::new(
+        url.to_string(),
+        create_webhook.secret,
+        create_webhook.events,
+    );
+    lightning_interface
+        .create_webhook(webhook.clone())
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(to_api_webhook(webhook)))
+}
+
+pub(crate) async fn delete_webhook(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let webhook_id = Uuid::parse_str(&id).map_err(bad_request)?;
+    if lightning_interface
+        .delete_webhook(webhook_id)
+        .await
+        .map_err(internal_server)?
+    {
+        Ok(Json(()))
+    } else {
+        Err(ApiError::NotFound(id))
+    }
+}
+
+fn to_api_webhook(webhook: Webhook) -> api::Webhook {
+    api::Webhook {
+        id: webhook.id.to_string(),
+        url: webhook.url,
+        events: webhook.events,
+        created_at: webhook.timestamp.unix_timestamp() as u64,
+    }
+}
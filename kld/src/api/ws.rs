@@ -1,20 +1,37 @@
-use std::{net::SocketAddr, ops::ControlFlow};
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    ops::ControlFlow,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
 
+use api::{EventSubscription, EventTopic, NodeEvent};
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        ConnectInfo, WebSocketUpgrade,
+        ConnectInfo, Query, WebSocketUpgrade,
     },
     headers::UserAgent,
     response::IntoResponse,
-    TypedHeader,
+    Extension, TypedHeader,
 };
-use futures::StreamExt;
-use log::{debug, error, info};
+use futures::{SinkExt, StreamExt};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use crate::ldk::LightningInterface;
+
+use super::{bad_request, ApiError};
 
-use super::ApiError;
+#[derive(Deserialize)]
+pub struct WsQueryParams {
+    // Comma separated topics to subscribe to, all topics when missing.
+    pub topics: Option<String>,
+}
 
-/// This is WIP. Just connects and checks macaroon at the moment.
+type Topics = Arc<RwLock<HashSet<EventTopic>>>;
 
 /// The handler for the HTTP request (this gets called when the HTTP GET lands at the start
 /// of websocket negotiation. After this completes, the actual switching from HTTP to
@@ -25,21 +42,36 @@ pub async fn ws_handler(
     ws: WebSocketUpgrade,
     user_agent: Option<TypedHeader<UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<WsQueryParams>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let topics: HashSet<EventTopic> = match params.topics.as_deref() {
+        None | Some("") => EventTopic::ALL.into_iter().collect(),
+        Some(topics) => topics
+            .split(',')
+            .map(|topic| EventTopic::from_str(topic.trim()))
+            .collect::<anyhow::Result<_>>()
+            .map_err(bad_request)?,
+    };
     let user_agent = user_agent
         .map(|a| a.to_string())
         .unwrap_or_else(|| "Unknown client".to_string());
 
     info!("`{}` at {} connected.", user_agent, addr.to_string());
-    // finalize the upgrade process by returning upgrade callback.
-    // we can customize the callback by sending additional info such as address.
-    Ok(ws
-        .protocols(["hex"])
-        .on_upgrade(move |socket| handle_socket(socket, addr)))
+    // Subscribe before the upgrade so that no events are missed while it completes.
+    let events = lightning_interface.subscribe_events();
+    Ok(ws.protocols(["hex"]).on_upgrade(move |socket| {
+        handle_socket(socket, addr, events, Arc::new(RwLock::new(topics)))
+    }))
 }
 
 /// Actual websocket statemachine (one will be spawned per connection)
-async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
+async fn handle_socket(
+    mut socket: WebSocket,
+    who: SocketAddr,
+    mut events: Receiver<NodeEvent>,
+    topics: Topics,
+) {
     //send a ping (unsupported by some browsers) just to kick things off and get a response
     if socket.send(Message::Ping(vec![])).await.is_ok() {
         debug!("Pinged {}...", who);
@@ -50,52 +82,75 @@ async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
         return;
     }
 
-    // Since each client gets individual statemachine, we can pause handling
-    // when necessary to wait for some external event (in this case illustrated by sleeping).
-    // Waiting for this client to finish getting his greetings does not prevent other clients form
-    // connecting to server and receiving their greetings.
-
-    // By splitting socket we can send and receive at the same time. In this example we will send
-    // unsolicited messages to client based on some sort of server's internal event (i.e .timer).
-    let (mut _sender, mut receiver) = socket.split();
-
-    /* To close connection.
-        debug!("Sending close to {}...", who);
-        if let Err(e) = sender
-            .send(Message::Close(Some(CloseFrame {
-                code: axum::extract::ws::close_code::NORMAL,
-                reason: Cow::from("Goodbye"),
-            })))
-            .await
-        {
-            debug!("Could not send Close due to {}, probably it is ok?", e);
+    // By splitting socket we can send and receive at the same time.
+    let (mut sender, mut receiver) = socket.split();
+
+    // This task pushes the events of the subscribed topics to the client.
+    let send_topics = topics.clone();
+    let mut send_task = tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Websocket client {who} is too slow, skipped {skipped} events");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if !send_topics
+                .read()
+                .map(|topics| topics.contains(&event.topic()))
+                .unwrap_or_default()
+            {
+                continue;
+            }
+            match serde_json::to_string(&event) {
+                Ok(json) => {
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => warn!("Failed to serialize event {event:?}: {e}"),
+            }
         }
-    });*/
+    });
 
-    // This second task will receive messages from client and print them on server console
-    let recv_task = tokio::spawn(async move {
+    // This task receives the subscription changes of the client.
+    let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
-            // print message and break if instructed to do so
-            if process_message(msg, who).is_break() {
+            if process_message(msg, who, &topics).is_break() {
                 break;
             }
         }
     });
 
-    if let Err(e) = recv_task.await {
-        error!("{e}")
-    };
+    // If any one of the tasks exit, abort the other.
+    tokio::select! {
+        _ = &mut send_task => recv_task.abort(),
+        _ = &mut recv_task => send_task.abort(),
+    }
 
     // returning from the handler closes the websocket connection
     info!("Websocket context {} destroyed", who);
 }
 
-/// helper to print contents of messages to stdout. Has special treatment for Close.
-fn process_message(msg: Message, who: SocketAddr) -> ControlFlow<(), ()> {
+/// Apply subscription changes from the client. Has special treatment for Close.
+fn process_message(msg: Message, who: SocketAddr, topics: &Topics) -> ControlFlow<(), ()> {
     match msg {
-        Message::Text(t) => {
-            info!(">>> {} sent str: {:?}", who, t);
-        }
+        Message::Text(t) => match serde_json::from_str::<EventSubscription>(&t) {
+            Ok(subscription) => {
+                info!(">>> {} changed subscription: {:?}", who, subscription);
+                if let Ok(mut topics) = topics.write() {
+                    match subscription {
+                        EventSubscription::Subscribe(new) => topics.extend(new),
+                        EventSubscription::Unsubscribe(old) => {
+                            topics.retain(|topic| !old.contains(topic))
+                        }
+                    }
+                }
+            }
+            Err(e) => info!(">>> {} sent invalid subscription {:?}: {}", who, t, e),
+        },
         Message::Binary(d) => {
             info!(">>> {} sent {} bytes: {:?}", who, d.len(), d);
         }
@@ -123,3 +178,36 @@ fn process_message(msg: Message, who: SocketAddr) -> ControlFlow<(), ()> {
     }
     ControlFlow::Continue(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashSet,
+        net::SocketAddr,
+        sync::{Arc, RwLock},
+    };
+
+    use api::EventTopic;
+    use axum::extract::ws::Message;
+
+    use super::process_message;
+
+    #[test]
+    fn test_process_message() {
+        let who: SocketAddr = "127.0.0.1:2244".parse().unwrap();
+        let topics = Arc::new(RwLock::new(HashSet::from([EventTopic::Payments])));
+
+        let subscribe = Message::Text(r#"{"subscribe":["channels","forwards"]}"#.to_string());
+        assert!(process_message(subscribe, who, &topics).is_continue());
+        let unsubscribe = Message::Text(r#"{"unsubscribe":["payments"]}"#.to_string());
+        assert!(process_message(unsubscribe, who, &topics).is_continue());
+        let invalid = Message::Text(r#"{"subscribe":["blocks"]}"#.to_string());
+        assert!(process_message(invalid, who, &topics).is_continue());
+        assert_eq!(
+            HashSet::from([EventTopic::Channels, EventTopic::Forwards]),
+            *topics.read().unwrap()
+        );
+
+        assert!(process_message(Message::Close(None), who, &topics).is_break());
+    }
+}
@@ -108,6 +108,16 @@ impl BitcoindClient {
             .deserialize()
     }
 
+    /// True if the output has been spent by a confirmed transaction (or never existed).
+    pub async fn is_spent(&self, txid: &Txid, vout: u32) -> Result<bool> {
+        let output: Option<Value> = self
+            .client
+            .call_method::<JsonString>("gettxout", &[json!(txid), json!(vout), json!(false)])
+            .await?
+            .deserialize()?;
+        Ok(output.is_none())
+    }
+
     pub fn poll_for_fee_estimates(&self) {
         let client = self.client.clone();
         let priorities = self.priorities.clone();
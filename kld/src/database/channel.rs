@@ -11,7 +11,7 @@ use tokio_postgres::Row;
 
 use crate::MillisatAmount;
 
-use super::{microsecond_timestamp, RowExt};
+use super::{microsecond_timestamp, Page, RowExt};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Channel {
@@ -29,6 +29,15 @@ pub struct Channel {
     pub closure_reason: Option<ClosureReason>,
 }
 
+/// Filter of the closed channels, which are paged by their close time.
+#[derive(Debug, Default, Clone)]
+pub struct ChannelFilter {
+    pub channel_id: Option<ChannelId>,
+    pub peer: Option<NodeId>,
+    pub page: Page,
+    pub cursor: Option<ChannelId>,
+}
+
 impl TryFrom<ChannelDetails> for Channel {
     type Error = anyhow::Error;
 
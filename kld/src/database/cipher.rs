@@ -0,0 +1,91 @@
+use anyhow::{anyhow, bail, Result};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher as SymmetricCipher};
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Version of the key that new blobs are encrypted with. It is stored next to every blob so that
+/// the key can be rotated, blobs without a key version were written before encryption was added.
+pub const KEY_VERSION: i64 = 0;
+
+/// Authenticated encryption (AES-256-GCM) of the channel state that we store in the database.
+pub struct Cipher {
+    key: [u8; 32],
+}
+
+impl Cipher {
+    pub fn new(database_seed: &[u8; 32]) -> Cipher {
+        Cipher {
+            key: *database_seed,
+        }
+    }
+
+    /// Encrypt with the current key, the output is nonce | ciphertext | tag.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(
+            SymmetricCipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce),
+            &[],
+            plaintext,
+            &mut tag,
+        )?;
+        let mut blob = Vec::with_capacity(NONCE_LEN + ciphertext.len() + TAG_LEN);
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+        blob.extend_from_slice(&tag);
+        Ok(blob)
+    }
+
+    /// Decrypt a blob that was stored with the given key version.
+    pub fn decrypt(&self, key_version: Option<i64>, blob: &[u8]) -> Result<Vec<u8>> {
+        match key_version {
+            None => Ok(blob.to_vec()),
+            Some(KEY_VERSION) => {
+                if blob.len() < NONCE_LEN + TAG_LEN {
+                    bail!("Encrypted blob is too short");
+                }
+                let (nonce, rest) = blob.split_at(NONCE_LEN);
+                let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+                decrypt_aead(
+                    SymmetricCipher::aes_256_gcm(),
+                    &self.key,
+                    Some(nonce),
+                    &[],
+                    ciphertext,
+                    tag,
+                )
+                .map_err(|_| anyhow!("Failed to decrypt blob with key version {KEY_VERSION}"))
+            }
+            Some(version) => bail!("Unknown key version {version}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cipher, KEY_VERSION};
+
+    #[test]
+    fn test_cipher() {
+        let cipher = Cipher::new(&[1; 32]);
+        let plaintext = b"channel monitor".to_vec();
+
+        let blob = cipher.encrypt(&plaintext).unwrap();
+        assert_ne!(plaintext, blob);
+        assert_ne!(blob, cipher.encrypt(&plaintext).unwrap());
+        assert_eq!(plaintext, cipher.decrypt(Some(KEY_VERSION), &blob).unwrap());
+        assert_eq!(plaintext, cipher.decrypt(None, &plaintext).unwrap());
+
+        let mut tampered = blob.clone();
+        tampered[20] ^= 1;
+        assert!(cipher.decrypt(Some(KEY_VERSION), &tampered).is_err());
+        assert!(Cipher::new(&[2; 32])
+            .decrypt(Some(KEY_VERSION), &blob)
+            .is_err());
+        assert!(cipher.decrypt(Some(KEY_VERSION + 1), &blob).is_err());
+        assert!(cipher.decrypt(Some(KEY_VERSION), &[0; 10]).is_err());
+    }
+}
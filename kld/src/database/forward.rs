@@ -2,12 +2,13 @@ use crate::MillisatAmount;
 
 use lightning::events::HTLCDestination;
 use lightning::ln::ChannelId;
+use lightning::routing::gossip::NodeId;
 use postgres_types::{FromSql, ToSql};
 use time::OffsetDateTime;
 use tokio_postgres::Row;
 use uuid::Uuid;
 
-use super::{microsecond_timestamp, RowExt};
+use super::{microsecond_timestamp, Page, RowExt};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Forward {
@@ -90,6 +91,68 @@ pub enum ForwardStatus {
     Failed,
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct ForwardFilter {
+    pub status: Option<ForwardStatus>,
+    /// Forwards into or out of this channel.
+    pub channel_id: Option<ChannelId>,
+    /// Forwards through any of our channels with this peer.
+    pub peer: Option<NodeId>,
+    pub page: Page,
+    pub cursor: Option<Uuid>,
+}
+
+/// Succeeded forwards into and out of a channel, summed up by the database.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChannelForwardTotals {
+    pub channel_id: ChannelId,
+    pub succeeded_in: u64,
+    pub succeeded_out: u64,
+    /// Amount received for the forwards into the channel.
+    pub volume_in: MillisatAmount,
+    /// Amount sent for the forwards out of the channel.
+    pub volume_out: MillisatAmount,
+    /// Fees of the forwards out of the channel.
+    pub fees: MillisatAmount,
+}
+
+impl TryFrom<Row> for ChannelForwardTotals {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(ChannelForwardTotals {
+            channel_id: ChannelId::from_bytes(row.get::<&str, &[u8]>("channel_id").try_into()?),
+            succeeded_in: row.get::<&str, i64>("succeeded_in") as u64,
+            succeeded_out: row.get::<&str, i64>("succeeded_out") as u64,
+            volume_in: row.get::<&str, i64>("volume_in") as MillisatAmount,
+            volume_out: row.get::<&str, i64>("volume_out") as MillisatAmount,
+            fees: row.get::<&str, i64>("fees") as MillisatAmount,
+        })
+    }
+}
+
+/// Number of failed forwards that came in through a channel and failed in the same way.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FailedForwards {
+    pub inbound_channel_id: ChannelId,
+    pub htlc_destination: Option<HTLCDestination>,
+    pub count: u64,
+}
+
+impl TryFrom<Row> for FailedForwards {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(FailedForwards {
+            inbound_channel_id: ChannelId::from_bytes(
+                row.get::<&str, &[u8]>("inbound_channel_id").try_into()?,
+            ),
+            htlc_destination: row.read_optional("htlc_destination")?,
+            count: row.get::<&str, i64>("count") as u64,
+        })
+    }
+}
+
 impl From<Row> for TotalForwards {
     fn from(row: Row) -> Self {
         TotalForwards {
@@ -3,10 +3,33 @@ use std::{str::FromStr, time::SystemTime};
 use anyhow::{anyhow, Result};
 use bitcoin::{hashes::Hash, secp256k1::PublicKey};
 use lightning::ln::PaymentHash;
+use postgres_types::{FromSql, ToSql};
 
 use crate::MillisatAmount;
 
-use super::payment::Payment;
+use super::{payment::Payment, Page};
+
+/// The state of an invoice that we only know the payment hash of. The HTLCs are held until the
+/// preimage is given to settle the invoice, or the invoice gets cancelled.
+#[derive(Debug, ToSql, FromSql, PartialEq, Eq, Clone, Copy)]
+#[postgres(name = "hold_invoice_status")]
+pub enum HoldInvoiceStatus {
+    #[postgres(name = "open")]
+    Open,
+    #[postgres(name = "accepted")]
+    Accepted,
+    #[postgres(name = "settled")]
+    Settled,
+    #[postgres(name = "cancelled")]
+    Cancelled,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct InvoiceFilter {
+    pub label: Option<String>,
+    pub page: Page,
+    pub cursor: Option<PaymentHash>,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Invoice {
@@ -22,6 +45,10 @@ pub struct Invoice {
     pub timestamp: SystemTime,
     // Payments with the payment_hash of the bolt11 invoice.
     pub payments: Vec<Payment>,
+    // None if this is not a hold invoice.
+    pub hold_status: Option<HoldInvoiceStatus>,
+    // Block height by which the held HTLCs must be settled.
+    pub claim_deadline: Option<u32>,
 }
 
 impl TryFrom<String> for Invoice {
@@ -35,6 +62,13 @@ impl TryFrom<String> for Invoice {
 }
 
 impl Invoice {
+    /// LDK fails the held HTLCs back itself once the best block reaches the claim deadline, so the
+    /// payment can only be claimed while the height is below it. At the deadline it is too late.
+    pub fn claim_deadline_reached(&self, height: u32) -> bool {
+        self.claim_deadline
+            .is_some_and(|deadline| height >= deadline)
+    }
+
     pub fn new(label: Option<String>, bolt11: lightning_invoice::Bolt11Invoice) -> Result<Self> {
         let raw = bolt11.clone().into_signed_raw();
         let expiry = raw.expiry_time().map(|t| t.as_seconds());
@@ -55,9 +89,17 @@ impl Invoice {
             amount,
             timestamp,
             payments: vec![],
+            hold_status: None,
+            claim_deadline: None,
         })
     }
 
+    pub fn hold(label: Option<String>, bolt11: lightning_invoice::Bolt11Invoice) -> Result<Self> {
+        let mut invoice = Invoice::new(label, bolt11)?;
+        invoice.hold_status = Some(HoldInvoiceStatus::Open);
+        Ok(invoice)
+    }
+
     pub fn deserialize(
         payment_hash: PaymentHash,
         label: Option<String>,
@@ -76,6 +118,8 @@ impl Invoice {
             amount: amount.map(|a| a as u64),
             timestamp,
             payments: vec![],
+            hold_status: None,
+            claim_deadline: None,
         })
     }
 }
@@ -0,0 +1,123 @@
+use api::JitChannelState;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::ChannelId;
+use postgres_types::{FromSql, ToSql};
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+
+use crate::ldk::jit_channels::opening_fee;
+
+use super::{microsecond_timestamp, RowExt};
+
+/// A just in time channel (LSPS2) that we have sold to a client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JitChannel {
+    pub user_channel_id: u64,
+    pub counterparty_node_id: PublicKey,
+    pub intercept_scid: u64,
+    pub payment_size_msat: Option<u64>,
+    pub opening_fee_msat: Option<u64>,
+    pub channel_id: Option<ChannelId>,
+    pub state: JitChannelState,
+    pub created_at: OffsetDateTime,
+}
+
+impl JitChannel {
+    /// A channel that a client has bought and that waits for the payment to open it.
+    pub fn bought(
+        user_channel_id: u64,
+        counterparty_node_id: PublicKey,
+        intercept_scid: u64,
+        payment_size_msat: Option<u64>,
+        min_fee_msat: u64,
+        proportional: u32,
+    ) -> JitChannel {
+        JitChannel {
+            user_channel_id,
+            counterparty_node_id,
+            intercept_scid,
+            payment_size_msat,
+            opening_fee_msat: payment_size_msat
+                .map(|size| opening_fee(size, min_fee_msat, proportional)),
+            channel_id: None,
+            state: JitChannelState::AwaitingPayment,
+            created_at: microsecond_timestamp(),
+        }
+    }
+}
+
+impl From<JitChannel> for api::JitChannelSession {
+    fn from(channel: JitChannel) -> Self {
+        api::JitChannelSession {
+            user_channel_id: channel.user_channel_id,
+            counterparty_node_id: channel.counterparty_node_id.to_string(),
+            intercept_scid: channel.intercept_scid,
+            payment_size_msat: channel.payment_size_msat,
+            opening_fee_msat: channel.opening_fee_msat,
+            channel_id: channel.channel_id.map(|id| id.to_hex()),
+            state: channel.state,
+            created_at: channel.created_at.unix_timestamp() as u64,
+        }
+    }
+}
+
+impl TryFrom<Row> for JitChannel {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        let channel_id: Option<[u8; 32]> = row
+            .get::<&str, Option<&[u8]>>("channel_id")
+            .map(|x| x.try_into())
+            .transpose()?;
+        Ok(JitChannel {
+            user_channel_id: row.get::<&str, i64>("user_channel_id") as u64,
+            counterparty_node_id: PublicKey::from_slice(row.get("counterparty_node_id"))?,
+            intercept_scid: row.get::<&str, i64>("intercept_scid") as u64,
+            payment_size_msat: row
+                .get::<&str, Option<i64>>("payment_size_msat")
+                .map(|x| x as u64),
+            opening_fee_msat: row
+                .get::<&str, Option<i64>>("opening_fee_msat")
+                .map(|x| x as u64),
+            channel_id: channel_id.map(ChannelId::from_bytes),
+            state: row.get::<&str, JitChannelStatus>("state").into(),
+            created_at: row.get_timestamp("timestamp"),
+        })
+    }
+}
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "jit_channel_state")]
+pub(crate) enum JitChannelStatus {
+    #[postgres(name = "awaiting_payment")]
+    AwaitingPayment,
+    #[postgres(name = "pending_channel_open")]
+    PendingChannelOpen,
+    #[postgres(name = "channel_ready")]
+    ChannelReady,
+    #[postgres(name = "failed")]
+    Failed,
+}
+
+impl From<JitChannelState> for JitChannelStatus {
+    fn from(state: JitChannelState) -> Self {
+        match state {
+            JitChannelState::AwaitingPayment => JitChannelStatus::AwaitingPayment,
+            JitChannelState::PendingChannelOpen => JitChannelStatus::PendingChannelOpen,
+            JitChannelState::ChannelReady => JitChannelStatus::ChannelReady,
+            JitChannelState::Failed => JitChannelStatus::Failed,
+        }
+    }
+}
+
+impl From<JitChannelStatus> for JitChannelState {
+    fn from(status: JitChannelStatus) -> Self {
+        match status {
+            JitChannelStatus::AwaitingPayment => JitChannelState::AwaitingPayment,
+            JitChannelStatus::PendingChannelOpen => JitChannelState::PendingChannelOpen,
+            JitChannelStatus::ChannelReady => JitChannelState::ChannelReady,
+            JitChannelStatus::Failed => JitChannelState::Failed,
+        }
+    }
+}
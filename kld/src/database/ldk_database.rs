@@ -4,28 +4,39 @@ use crate::logger::KldLogger;
 use crate::settings::Settings;
 use crate::to_i64;
 
-use super::channel::Channel;
-use super::forward::{Forward, ForwardStatus, TotalForwards};
-use super::invoice::Invoice;
-use super::payment::{Payment, PaymentDirection};
+use super::channel::{Channel, ChannelFilter};
+use super::cipher::{Cipher, KEY_VERSION};
+use super::forward::{ChannelForwardTotals, FailedForwards, Forward, ForwardFilter, TotalForwards};
+use super::invoice::{HoldInvoiceStatus, Invoice, InvoiceFilter};
+use super::jit_channel::{JitChannel, JitChannelStatus};
+use super::macaroon_root_key::MacaroonRootKey;
+use super::offer::{InvoiceRequest, InvoiceRequestStatus, Offer};
+use super::payment::{Payment, PaymentDirection, PaymentFilter, PaymentStatus};
+use super::payment_attempt::PaymentAttempt;
 use super::spendable_output::SpendableOutput;
-use super::{DurableConnection, Params};
+use super::webhook::{Webhook, WebhookDelivery, WebhookDeliveryStatus};
+use super::{DurableConnection, Params, RowExt};
 use anyhow::{anyhow, bail, Result};
+use api::{ChannelAcceptancePolicy, WebhookEvent};
 use bitcoin::hashes::hex::ToHex;
-use bitcoin::hashes::Hash;
+use bitcoin::hashes::{sha256, Hash};
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::{BlockHash, Txid};
 use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
 use lightning::chain::chainmonitor::MonitorUpdateId;
-use lightning::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate};
+use lightning::chain::channelmonitor::{
+    ChannelMonitor, ChannelMonitorUpdate, CLOSED_CHANNEL_UPDATE_ID,
+};
 use lightning::chain::transaction::OutPoint;
 use lightning::chain::{self, ChannelMonitorUpdateStatus, Watch};
 use lightning::events::ClosureReason;
-use lightning::ln::channelmanager::{ChannelManager, ChannelManagerReadArgs};
+use lightning::ln::channelmanager::{
+    ChannelManager, ChannelManagerReadArgs, InterceptId, PaymentId,
+};
 use lightning::ln::msgs::SocketAddress;
 use lightning::ln::ChannelId;
 use lightning::ln::PaymentHash;
-use lightning::routing::gossip::NetworkGraph;
+use lightning::routing::gossip::{NetworkGraph, NodeId};
 use lightning::routing::router::Router;
 use lightning::routing::scoring::{
     ProbabilisticScorer, ProbabilisticScoringDecayParameters, WriteableScore,
@@ -33,35 +44,50 @@ use lightning::routing::scoring::{
 use lightning::sign::{EntropySource, NodeSigner, SignerProvider, WriteableEcdsaChannelSigner};
 use lightning::util::logger::Logger;
 use lightning::util::persist::Persister;
-use lightning::util::ser::ReadableArgs;
 use lightning::util::ser::Writeable;
-use log::{debug, error};
+use lightning::util::ser::{Readable, ReadableArgs};
+use log::{debug, error, info};
 
 use super::peer::Peer;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io::Cursor;
 use std::ops::Deref;
-use std::sync::{Arc, OnceLock};
-use std::time::SystemTime;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
 use std::{fs, io};
+use time::OffsetDateTime;
 use tokio::runtime::Handle;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use uuid::Uuid;
+
+// Rows encrypted per round trip by encrypt_blobs.
+const ENCRYPT_BATCH_SIZE: usize = 100;
 
 pub struct LdkDatabase {
     settings: Arc<Settings>,
     durable_connection: Arc<DurableConnection>,
+    cipher: Arc<Cipher>,
     // Persist graph/scorer gets called from a background thread in LDK so need a handle to the runtime.
     runtime: Handle,
     chain_monitor: OnceLock<Arc<ChainMonitor>>,
+    // One queue per channel so that its monitor writes are stored in order.
+    monitor_writers: Mutex<HashMap<OutPoint, UnboundedSender<MonitorWrite>>>,
 }
 
 impl LdkDatabase {
-    pub fn new(settings: Arc<Settings>, durable_connection: Arc<DurableConnection>) -> LdkDatabase {
+    pub fn new(
+        settings: Arc<Settings>,
+        durable_connection: Arc<DurableConnection>,
+        cipher: Arc<Cipher>,
+    ) -> LdkDatabase {
         LdkDatabase {
             settings,
             durable_connection,
+            cipher,
             runtime: Handle::current(),
             chain_monitor: OnceLock::new(),
+            monitor_writers: Mutex::new(HashMap::new()),
         }
     }
 
@@ -72,6 +98,106 @@ impl LdkDatabase {
             .expect("Incorrect initialisation");
     }
 
+    /// Encrypt the blobs that were stored in plaintext or with an older key using the current key.
+    ///
+    /// Rows are encrypted in batches and a row is only replaced if it was not written in the meantime,
+    /// so this can be interrupted and run again.
+    pub async fn encrypt_blobs(&self) -> Result<()> {
+        // The updates of a channel are told apart by their update id, the other tables have one key.
+        for (table, blob_column, id_column, has_update_id) in [
+            ("channel_manager", "manager", "id", false),
+            ("channel_monitors", "monitor", "out_point", false),
+            ("scorer", "scorer", "id", false),
+            ("channel_monitor_updates", "update", "out_point", true),
+        ] {
+            let (update_id_column, update_id_filter) = if has_update_id {
+                ("update_id", "update_id = $4")
+            } else {
+                ("NULL::INT8 AS update_id", "$4::INT8 IS NULL")
+            };
+            let mut encrypted = 0;
+            loop {
+                let client = self.durable_connection.wait().await;
+                let rows = client
+                    .query(
+                        &format!(
+                            "SELECT {id_column}, {update_id_column}, {blob_column}, key_version \
+                            FROM {table} \
+                            WHERE key_version IS NULL OR key_version != $1 \
+                            LIMIT {ENCRYPT_BATCH_SIZE}"
+                        ),
+                        &[&KEY_VERSION],
+                    )
+                    .await?;
+                if rows.is_empty() {
+                    break;
+                }
+                for row in &rows {
+                    let id: &[u8] = row.get(id_column);
+                    let update_id: Option<i64> = row.get("update_id");
+                    let key_version: Option<i64> = row.get("key_version");
+                    let blob = self.reencrypt(key_version, row.get(blob_column))?;
+                    client
+                        .execute(
+                            &format!(
+                                "UPDATE {table} SET {blob_column} = $1, key_version = $2 \
+                                WHERE {id_column} = $3 AND {update_id_filter} \
+                                AND key_version IS NOT DISTINCT FROM $5"
+                            ),
+                            &[&blob, &KEY_VERSION, &id, &update_id, &key_version],
+                        )
+                        .await?;
+                }
+                encrypted += rows.len();
+            }
+            if encrypted > 0 {
+                info!("Encrypted {encrypted} rows of {table}");
+            }
+        }
+        Ok(())
+    }
+
+    fn reencrypt(&self, key_version: Option<i64>, blob: &[u8]) -> Result<Vec<u8>> {
+        self.cipher
+            .encrypt(&self.cipher.decrypt(key_version, blob)?)
+    }
+
+    // The Persister trait wants io errors.
+    fn encrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, io::Error> {
+        self.cipher
+            .encrypt(bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
+    fn graph_path(&self) -> String {
+        format!("{}/network_graph.enc", self.settings.data_dir)
+    }
+
+    // Network graph could get very large so just write it to disk for now.
+    pub fn write_graph<L: Deref>(
+        &self,
+        network_graph: &lightning::routing::gossip::NetworkGraph<L>,
+    ) -> Result<(), io::Error>
+    where
+        L::Target: Logger,
+    {
+        let mut buf = vec![];
+        network_graph.write(&mut buf)?;
+        let blob = self.encrypt(&buf)?;
+        if let Err(e) = fs::write(self.graph_path(), blob) {
+            error!("Failed to persist graph: {e}");
+            return Ok(());
+        }
+        // The plaintext graph of older versions is replaced by the encrypted one.
+        match fs::remove_file(format!("{}/network_graph", self.settings.data_dir)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                error!("Failed to remove plaintext graph: {e}")
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
     pub async fn is_first_start(&self) -> Result<bool> {
         Ok(self
             .durable_connection
@@ -202,32 +328,65 @@ impl LdkDatabase {
         Ok(())
     }
 
-    pub async fn fetch_channel_history(&self) -> Result<Vec<Channel>> {
+    /// The peer of every channel that we have had, to group the channels by peer.
+    pub async fn fetch_channel_counterparties(&self) -> Result<HashMap<ChannelId, NodeId>> {
         let rows = self
             .durable_connection
             .get()
             .await
-            .query(
-                "SELECT
-                id,
-                scid,
-                user_channel_id,
-                counterparty,
-                funding_txo,
-                is_public,
-                is_outbound,
-                value,
-                type_features,
-                open_timestamp,
-                close_timestamp,
-                closure_reason
+            .query("SELECT id, counterparty FROM channels", &[])
+            .await?;
+        let mut counterparties = HashMap::new();
+        for row in rows {
+            let id = ChannelId::from_bytes(row.get::<&str, &[u8]>("id").try_into()?);
+            counterparties.insert(id, row.read("counterparty")?);
+        }
+        Ok(counterparties)
+    }
+
+    pub async fn fetch_channel_history(&self, filter: &ChannelFilter) -> Result<Vec<Channel>> {
+        let connection = self.durable_connection.get().await;
+        let mut query = "SELECT
+                c.id,
+                c.scid,
+                c.user_channel_id,
+                c.counterparty,
+                c.funding_txo,
+                c.is_public,
+                c.is_outbound,
+                c.value,
+                c.type_features,
+                c.open_timestamp,
+                c.close_timestamp,
+                c.closure_reason
             FROM
-                channels
-            WHERE close_timestamp IS NOT NULL
-            ",
-                &[],
+                channels c"
+            .to_string();
+        let mut params = Params::default();
+        let mut filters = vec!["c.close_timestamp IS NOT NULL".to_string()];
+        if let Some(channel_id) = &filter.channel_id {
+            params.push(channel_id.0.to_vec());
+            filters.push(format!("c.id = ${}", params.count()));
+        }
+        if let Some(peer) = &filter.peer {
+            params.push(peer.encode());
+            filters.push(format!("c.counterparty = ${}", params.count()));
+        }
+        filter
+            .page
+            .push_filters(
+                &connection,
+                "channels c",
+                "c.close_timestamp",
+                "c.id",
+                filter.cursor.map(|c| c.0.to_vec()),
+                &mut filters,
+                &mut params,
             )
             .await?;
+        query.push_str(&format!("\nWHERE {}", filters.join(" AND ")));
+        query.push_str(&filter.page.order_and_limit("c.close_timestamp", "c.id"));
+        let rows = connection.query(&query, &params.to_params()).await?;
 
         let mut channels = vec![];
         for row in rows {
@@ -238,17 +397,56 @@ impl LdkDatabase {
 
     pub async fn persist_spendable_output(&self, output: SpendableOutput) -> Result<()> {
         debug!("Persist spendable output {}:{}", output.txid, output.vout);
+        let sweep_txids: Vec<&[u8]> = output.sweep_txids.iter().map(|t| t.as_ref()).collect();
         self.durable_connection
             .get()
             .await
             .execute(
                 "UPSERT INTO spendable_outputs (
+                    txid,
+                    vout,
+                    value,
+                    descriptor,
+                    status,
+                    first_seen_height,
+                    sweep_tx,
+                    sweep_txids,
+                    sweep_feerate,
+                    sweep_height
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                &[
+                    &output.txid.as_ref(),
+                    &(output.vout as i64),
+                    &(output.value as i64),
+                    &output.serialize_descriptor()?,
+                    &output.status,
+                    &output.first_seen_height.map(|h| h as i64),
+                    &output.serialize_sweep_tx(),
+                    &sweep_txids,
+                    &output.sweep_feerate.map(|f| f as i64),
+                    &output.sweep_height.map(|h| h as i64),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Store an output that LDK handed to us, outputs that we already know are left untouched so
+    /// that a replayed event does not reset their sweep.
+    pub async fn insert_spendable_output(&self, output: SpendableOutput) -> Result<()> {
+        debug!("Insert spendable output {}:{}", output.txid, output.vout);
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "INSERT INTO spendable_outputs (
                     txid,
                     vout,
                     value,
                     descriptor,
                     status
-                ) VALUES ($1, $2, $3, $4, $5)",
+                ) VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (txid, vout) DO NOTHING",
                 &[
                     &output.txid.as_ref(),
                     &(output.vout as i64),
@@ -272,7 +470,12 @@ impl LdkDatabase {
                 vout,
                 value,
                 descriptor,
-                status
+                status,
+                first_seen_height,
+                sweep_tx,
+                sweep_txids,
+                sweep_feerate,
+                sweep_height
             FROM
                 spendable_outputs",
                 &[],
@@ -302,8 +505,10 @@ impl LdkDatabase {
                     payee_pub_key,
                     expiry,
                     amount,
-                    timestamp
-                ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                    timestamp,
+                    hold_status,
+                    claim_deadline
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
                 &[
                     &invoice.payment_hash.0.as_ref(),
                     &invoice.label,
@@ -312,13 +517,37 @@ impl LdkDatabase {
                     &(invoice.bolt11.expiry_time().as_secs() as i64),
                     &invoice.amount.map(|a| a as i64),
                     &invoice.timestamp,
+                    &invoice.hold_status,
+                    &invoice.claim_deadline.map(|d| d as i64),
                 ],
             )
             .await?;
         Ok(())
     }
 
-    pub async fn fetch_invoices(&self, label: Option<String>) -> Result<Vec<Invoice>> {
+    pub async fn fetch_invoices(&self, filter: &InvoiceFilter) -> Result<Vec<Invoice>> {
+        self.query_invoices(filter, None, None).await
+    }
+
+    pub async fn fetch_invoice(&self, payment_hash: &PaymentHash) -> Result<Option<Invoice>> {
+        Ok(self
+            .query_invoices(&InvoiceFilter::default(), Some(payment_hash), None)
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    pub async fn fetch_hold_invoices(&self, status: HoldInvoiceStatus) -> Result<Vec<Invoice>> {
+        self.query_invoices(&InvoiceFilter::default(), None, Some(status))
+            .await
+    }
+
+    async fn query_invoices(
+        &self,
+        filter: &InvoiceFilter,
+        payment_hash: Option<&PaymentHash>,
+        hold_status: Option<HoldInvoiceStatus>,
+    ) -> Result<Vec<Invoice>> {
         debug!("Fetching invoices from database");
         let connection = self.durable_connection.get().await;
         let mut params = Params::default();
@@ -331,6 +560,8 @@ impl LdkDatabase {
                 i.amount as invoice_amount,
                 i.payee_pub_key,
                 i.timestamp as invoice_timestamp,
+                i.hold_status,
+                i.claim_deadline,
                 p.id,
                 p.hash,
                 p.preimage,
@@ -340,15 +571,49 @@ impl LdkDatabase {
                 p.fee,
                 p.direction,
                 p.timestamp,
-                p.label
-            FROM invoices i
-            LEFT OUTER JOIN payments p ON i.payment_hash = p.hash"
+                p.label,
+                p.attempts,
+                (SELECT count(*) FROM payment_attempts a
+                    WHERE a.payment_id = p.id AND a.status = 'succeeded') AS successful_paths
+            FROM (SELECT * FROM invoices i"
             .to_string();
-        if let Some(label) = &label {
+        let payment_hash = payment_hash.map(|hash| hash.0.to_vec());
+        let mut filters = vec![];
+        if let Some(label) = &filter.label {
             params.push(label);
-            query.push_str(&format!("\nWHERE i.label = ${}", params.count()));
+            filters.push(format!("i.label = ${}", params.count()));
+        }
+        if let Some(payment_hash) = &payment_hash {
+            params.push(payment_hash);
+            filters.push(format!("i.payment_hash = ${}", params.count()));
+        }
+        if let Some(hold_status) = &hold_status {
+            params.push(hold_status);
+            filters.push(format!("i.hold_status = ${}", params.count()));
         }
-        let mut invoices: HashMap<PaymentHash, Invoice> = HashMap::new();
+        filter
+            .page
+            .push_filters(
+                &connection,
+                "invoices i",
+                "i.timestamp",
+                "i.payment_hash",
+                filter.cursor.map(|c| c.0.to_vec()),
+                &mut filters,
+                &mut params,
+            )
+            .await?;
+        if !filters.is_empty() {
+            query.push_str(&format!("\nWHERE {}", filters.join(" AND ")));
+        }
+        // The page is of invoices, not of their payments.
+        query.push_str(&filter.page.order_and_limit("i.timestamp", "i.payment_hash"));
+        query.push_str(
+            ") AS i
+            LEFT OUTER JOIN payments p ON i.payment_hash = p.hash
+            ORDER BY i.timestamp, i.payment_hash",
+        );
+        let mut invoices: Vec<Invoice> = vec![];
         for row in connection.query(&query, &params.to_params()).await? {
             let payment_hash: Vec<u8> = row.get("payment_hash");
             let payment_hash = PaymentHash(payment_hash.as_slice().try_into()?);
@@ -357,7 +622,11 @@ impl LdkDatabase {
             } else {
                 None
             };
-            if let Some(invoice) = invoices.get_mut(&payment_hash) {
+            // The rows of an invoice are next to each other.
+            if let Some(invoice) = invoices
+                .last_mut()
+                .filter(|i| i.payment_hash == payment_hash)
+            {
                 if let Some(payment) = payment {
                     invoice.payments.push(payment);
                 }
@@ -377,13 +646,17 @@ impl LdkDatabase {
                     amount,
                     timestamp,
                 )?;
+                invoice.hold_status = row.get("hold_status");
+                invoice.claim_deadline = row
+                    .get::<&str, Option<i64>>("claim_deadline")
+                    .map(|d| d as u32);
                 if let Some(payment) = payment {
                     invoice.payments.push(payment);
                 }
-                invoices.insert(invoice.payment_hash, invoice);
+                invoices.push(invoice);
             }
         }
-        Ok(Vec::from_iter(invoices.into_values()))
+        Ok(invoices)
     }
 
     pub async fn persist_payment(&self, payment: &Payment) -> Result<()> {
@@ -421,11 +694,7 @@ impl LdkDatabase {
         Ok(())
     }
 
-    pub async fn fetch_payments(
-        &self,
-        payment_hash: Option<PaymentHash>,
-        direction: Option<PaymentDirection>,
-    ) -> Result<Vec<Payment>> {
+    pub async fn fetch_payments(&self, filter: &PaymentFilter) -> Result<Vec<Payment>> {
         let connection = self.durable_connection.get().await;
         let mut payments = vec![];
         let mut params = Params::default();
@@ -441,19 +710,46 @@ impl LdkDatabase {
                 p.fee,
                 p.direction,
                 p.timestamp,
+                p.attempts,
+                (SELECT count(*) FROM payment_attempts a
+                    WHERE a.payment_id = p.id AND a.status = 'succeeded') AS successful_paths,
                 i.bolt11
             FROM payments as p
-            LEFT OUTER JOIN invoices i ON p.hash = i.payment_hash
-            WHERE 1 = 1"
+            LEFT OUTER JOIN invoices i ON p.hash = i.payment_hash"
             .to_string();
-        if let Some(hash) = &payment_hash {
+        let mut filters = vec![];
+        if let Some(hash) = &filter.payment_hash {
             params.push(hash.0.as_ref());
-            query.push_str(&format!("AND p.hash = ${}", params.count()));
+            filters.push(format!("p.hash = ${}", params.count()));
         }
-        if let Some(direction) = direction {
+        if let Some(direction) = filter.direction {
             params.push(direction);
-            query.push_str(&format!("AND p.direction = ${}", params.count()));
+            filters.push(format!("p.direction = ${}", params.count()));
+        }
+        if !filter.statuses.is_empty() {
+            let mut statuses = vec![];
+            for status in &filter.statuses {
+                params.push(*status);
+                statuses.push(format!("${}", params.count()));
+            }
+            filters.push(format!("p.status IN ({})", statuses.join(", ")));
         }
+        filter
+            .page
+            .push_filters(
+                &connection,
+                "payments p",
+                "p.timestamp",
+                "p.id",
+                filter.cursor.map(|c| c.0.to_vec()),
+                &mut filters,
+                &mut params,
+            )
+            .await?;
+        if !filters.is_empty() {
+            query.push_str(&format!("\nWHERE {}", filters.join(" AND ")));
+        }
+        query.push_str(&filter.page.order_and_limit("p.timestamp", "p.id"));
         for row in connection
             .query(&query.to_string(), &params.to_params())
             .await?
@@ -463,6 +759,289 @@ impl LdkDatabase {
         Ok(payments)
     }
 
+    pub async fn fetch_payment(&self, id: &PaymentId) -> Result<Option<Payment>> {
+        self.durable_connection
+            .get()
+            .await
+            .query_opt(
+                "SELECT
+                    p.id,
+                    p.hash,
+                    p.preimage,
+                    p.secret,
+                    p.label,
+                    p.status,
+                    p.amount,
+                    p.fee,
+                    p.direction,
+                    p.timestamp,
+                    p.attempts,
+                    (SELECT count(*) FROM payment_attempts a
+                        WHERE a.payment_id = p.id AND a.status = 'succeeded') AS successful_paths,
+                    i.bolt11
+                FROM payments as p
+                LEFT OUTER JOIN invoices i ON p.hash = i.payment_hash
+                WHERE p.id = $1",
+                &[&id.0.as_ref()],
+            )
+            .await?
+            .map(|row| Payment::try_from(&row))
+            .transpose()
+    }
+
+    pub async fn persist_payment_attempt(&self, attempt: &PaymentAttempt) -> Result<()> {
+        debug!(
+            "Persist attempt {} of payment {}",
+            attempt.id,
+            attempt.payment_id.0.to_hex()
+        );
+        let node_ids: Vec<Vec<u8>> = attempt
+            .hops
+            .iter()
+            .map(|hop| hop.node_id.serialize().to_vec())
+            .collect();
+        let short_channel_ids: Vec<i64> = attempt
+            .hops
+            .iter()
+            .map(|hop| hop.short_channel_id as i64)
+            .collect();
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "WITH attempt AS (
+                    INSERT INTO payment_attempts (
+                        id,
+                        payment_id,
+                        status,
+                        hop_node_ids,
+                        hop_short_channel_ids,
+                        amount,
+                        fee,
+                        failed_short_channel_id,
+                        failed_permanently,
+                        timestamp
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                    ON CONFLICT (id) DO NOTHING
+                    RETURNING payment_id
+                )
+                UPDATE payments SET attempts = attempts + 1
+                WHERE id IN (SELECT payment_id FROM attempt)",
+                &[
+                    &attempt.id,
+                    &attempt.payment_id.0.as_ref(),
+                    &attempt.status,
+                    &node_ids,
+                    &short_channel_ids,
+                    &(attempt.amount as i64),
+                    &(attempt.fee as i64),
+                    &attempt.failed_short_channel_id.map(|x| x as i64),
+                    &attempt.failed_permanently,
+                    &to_primitive(&attempt.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_payment_attempts(
+        &self,
+        payment_id: &PaymentId,
+    ) -> Result<Vec<PaymentAttempt>> {
+        let mut attempts = vec![];
+        for row in self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT
+                    id,
+                    payment_id,
+                    status,
+                    hop_node_ids,
+                    hop_short_channel_ids,
+                    amount,
+                    fee,
+                    failed_short_channel_id,
+                    failed_permanently,
+                    timestamp
+                FROM payment_attempts
+                WHERE payment_id = $1
+                ORDER BY timestamp",
+                &[&payment_id.0.as_ref()],
+            )
+            .await?
+        {
+            attempts.push(PaymentAttempt::try_from(row)?);
+        }
+        Ok(attempts)
+    }
+
+    pub async fn persist_offer(&self, offer: &Offer) -> Result<()> {
+        debug!("Persist offer with ID: {}", offer.id.to_hex());
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO offers (
+                    id,
+                    kind,
+                    bolt12,
+                    description,
+                    label,
+                    amount,
+                    direction,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &offer.id.as_ref(),
+                    &offer.kind,
+                    &offer.bolt12,
+                    &offer.description,
+                    &offer.label,
+                    &offer.amount.map(|a| a as i64),
+                    &offer.direction,
+                    &to_primitive(&offer.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_offers(&self, label: Option<String>) -> Result<Vec<Offer>> {
+        let connection = self.durable_connection.get().await;
+        let mut params = Params::default();
+        let mut query = "
+            SELECT
+                id,
+                kind,
+                bolt12,
+                description,
+                label,
+                amount,
+                direction,
+                timestamp
+            FROM offers"
+            .to_string();
+        if let Some(label) = &label {
+            params.push(label);
+            query.push_str(&format!("\nWHERE label = ${}", params.count()));
+        }
+        query.push_str("\nORDER BY timestamp");
+        let mut offers = vec![];
+        for row in connection.query(&query, &params.to_params()).await? {
+            offers.push(Offer::try_from(&row)?);
+        }
+        Ok(offers)
+    }
+
+    pub async fn persist_invoice_request(&self, invoice_request: &InvoiceRequest) -> Result<()> {
+        debug!(
+            "Persist invoice request with payment ID: {}",
+            invoice_request.payment_id.0.to_hex()
+        );
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO invoice_requests (
+                    payment_id,
+                    offer_id,
+                    amount,
+                    quantity,
+                    payer_note,
+                    status,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &invoice_request.payment_id.0.as_ref(),
+                    &invoice_request.offer_id.as_ref(),
+                    &invoice_request.amount.map(|a| a as i64),
+                    &invoice_request.quantity.map(|q| q as i64),
+                    &invoice_request.payer_note,
+                    &invoice_request.status,
+                    &to_primitive(&invoice_request.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn update_invoice_request_status(
+        &self,
+        payment_id: &PaymentId,
+        status: InvoiceRequestStatus,
+    ) -> Result<()> {
+        debug!(
+            "Update invoice request with payment ID {} to {status}",
+            payment_id.0.to_hex()
+        );
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPDATE invoice_requests SET status = $1 WHERE payment_id = $2",
+                &[&status, &payment_id.0.as_ref()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Mark the invoice request and its payment as failed in one statement. A payment that has
+    /// already been resolved keeps its status.
+    pub async fn fail_invoice_request(&self, payment_id: &PaymentId) -> Result<()> {
+        debug!(
+            "Fail invoice request with payment ID {}",
+            payment_id.0.to_hex()
+        );
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "WITH request AS (
+                    UPDATE invoice_requests SET status = $1 WHERE payment_id = $2 RETURNING payment_id
+                )
+                UPDATE payments SET status = $3 WHERE id = $2 AND status = $4",
+                &[
+                    &InvoiceRequestStatus::Failed,
+                    &payment_id.0.as_ref(),
+                    &PaymentStatus::Error,
+                    &PaymentStatus::Pending,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_invoice_requests(
+        &self,
+        offer_id: Option<sha256::Hash>,
+    ) -> Result<Vec<InvoiceRequest>> {
+        let connection = self.durable_connection.get().await;
+        let mut params = Params::default();
+        let mut query = "
+            SELECT
+                payment_id,
+                offer_id,
+                amount,
+                quantity,
+                payer_note,
+                status,
+                timestamp
+            FROM invoice_requests"
+            .to_string();
+        if let Some(offer_id) = &offer_id {
+            params.push(offer_id.as_ref());
+            query.push_str(&format!("\nWHERE offer_id = ${}", params.count()));
+        }
+        query.push_str("\nORDER BY timestamp");
+        let mut invoice_requests = vec![];
+        for row in connection.query(&query, &params.to_params()).await? {
+            invoice_requests.push(InvoiceRequest::try_from(&row)?);
+        }
+        Ok(invoice_requests)
+    }
+
     pub async fn persist_forward(&self, forward: Forward) -> Result<()> {
         debug!("Persist forward with ID {}", forward.id);
 
@@ -502,34 +1081,60 @@ impl LdkDatabase {
         Ok(())
     }
 
-    pub async fn fetch_forwards(&self, status: Option<ForwardStatus>) -> Result<Vec<Forward>> {
+    pub async fn fetch_forwards(&self, filter: &ForwardFilter) -> Result<Vec<Forward>> {
+        let connection = self.durable_connection.get().await;
         let mut statement = "
             SELECT
-                id,
-                inbound_channel_id,
-                outbound_channel_id,
-                amount,
-                fee,
-                status,
-                htlc_destination,
-                timestamp
+                f.id,
+                f.inbound_channel_id,
+                f.outbound_channel_id,
+                f.amount,
+                f.fee,
+                f.status,
+                f.htlc_destination,
+                f.timestamp
             FROM
-                forwards
-            "
-        .to_string();
+                forwards f"
+            .to_string();
         let mut params = Params::default();
-        if let Some(status) = status {
-            statement.push_str("WHERE status = $1");
+        let mut filters = vec![];
+        if let Some(status) = filter.status {
             params.push(status);
+            filters.push(format!("f.status = ${}", params.count()));
         }
-        statement.push_str("ORDER BY timestamp ASC");
-        let mut forwards = vec![];
-        let rows = self
-            .durable_connection
-            .get()
-            .await
-            .query(&statement, &params.to_params())
+        if let Some(channel_id) = &filter.channel_id {
+            params.push(channel_id.0.to_vec());
+            filters.push(format!(
+                "(f.inbound_channel_id = ${0} OR f.outbound_channel_id = ${0})",
+                params.count()
+            ));
+        }
+        if let Some(peer) = &filter.peer {
+            params.push(peer.encode());
+            filters.push(format!(
+                "(f.inbound_channel_id IN (SELECT id FROM channels WHERE counterparty = ${0})
+                OR f.outbound_channel_id IN (SELECT id FROM channels WHERE counterparty = ${0}))",
+                params.count()
+            ));
+        }
+        filter
+            .page
+            .push_filters(
+                &connection,
+                "forwards f",
+                "f.timestamp",
+                "f.id",
+                filter.cursor,
+                &mut filters,
+                &mut params,
+            )
             .await?;
+        if !filters.is_empty() {
+            statement.push_str(&format!("\nWHERE {}", filters.join(" AND ")));
+        }
+        statement.push_str(&filter.page.order_and_limit("f.timestamp", "f.id"));
+        let mut forwards = vec![];
+        let rows = connection.query(&statement, &params.to_params()).await?;
 
         for row in rows {
             forwards.push(row.try_into()?);
@@ -555,23 +1160,410 @@ impl LdkDatabase {
             .into())
     }
 
-    pub async fn fetch_channel_monitors<ES: EntropySource, SP: SignerProvider>(
+    /// The succeeded forwards in the time window, summed up for each channel that they came in or
+    /// went out through.
+    pub async fn fetch_channel_forward_totals(
         &self,
-        entropy_source: &ES,
-        signer_provider: &SP, //		broadcaster: &B,
-                              //		fee_estimator: &F,
-    ) -> Result<Vec<(BlockHash, ChannelMonitor<SP::Signer>)>>
-where
-        //      B::Target: BroadcasterInterface,
-        //		F::Target: FeeEstimator,
-    {
-        let rows = self
-            .durable_connection
-            .wait()
-            .await
-            .query(
-                "SELECT out_point, monitor \
-            FROM channel_monitors",
+        window: &Page,
+    ) -> Result<Vec<ChannelForwardTotals>> {
+        let connection = self.durable_connection.get().await;
+        let mut params = Params::default();
+        let mut filters = vec!["f.status = 'succeeded'".to_string()];
+        window
+            .push_filters(
+                &connection,
+                "forwards f",
+                "f.timestamp",
+                "f.id",
+                None::<Uuid>,
+                &mut filters,
+                &mut params,
+            )
+            .await?;
+        let filters = filters.join(" AND ");
+        // The amount is what we forwarded out, the fee is on top of it.
+        let query = format!(
+            "SELECT
+                t.channel_id,
+                CAST(sum(t.succeeded_in) AS INT) AS succeeded_in,
+                CAST(sum(t.succeeded_out) AS INT) AS succeeded_out,
+                CAST(sum(t.volume_in) AS INT) AS volume_in,
+                CAST(sum(t.volume_out) AS INT) AS volume_out,
+                CAST(sum(t.fees) AS INT) AS fees
+            FROM (
+                SELECT
+                    f.inbound_channel_id AS channel_id,
+                    count(*) AS succeeded_in,
+                    0 AS succeeded_out,
+                    CAST(sum(COALESCE(f.amount, 0) + COALESCE(f.fee, 0)) AS INT) AS volume_in,
+                    0 AS volume_out,
+                    0 AS fees
+                FROM forwards f
+                WHERE {filters}
+                GROUP BY f.inbound_channel_id
+                UNION ALL
+                SELECT
+                    f.outbound_channel_id,
+                    0,
+                    count(*),
+                    0,
+                    COALESCE(CAST(sum(f.amount) AS INT), 0),
+                    COALESCE(CAST(sum(f.fee) AS INT), 0)
+                FROM forwards f
+                WHERE {filters} AND f.outbound_channel_id IS NOT NULL
+                GROUP BY f.outbound_channel_id
+            ) AS t
+            GROUP BY t.channel_id"
+        );
+        let mut totals = vec![];
+        for row in connection.query(&query, &params.to_params()).await? {
+            totals.push(row.try_into()?);
+        }
+        Ok(totals)
+    }
+
+    /// The failed forwards in the time window, counted for each channel that they came in through
+    /// and each way that they failed.
+    pub async fn fetch_failed_forwards(&self, window: &Page) -> Result<Vec<FailedForwards>> {
+        let connection = self.durable_connection.get().await;
+        let mut params = Params::default();
+        let mut filters = vec!["f.status = 'failed'".to_string()];
+        window
+            .push_filters(
+                &connection,
+                "forwards f",
+                "f.timestamp",
+                "f.id",
+                None::<Uuid>,
+                &mut filters,
+                &mut params,
+            )
+            .await?;
+        let query = format!(
+            "SELECT
+                f.inbound_channel_id,
+                f.htlc_destination,
+                count(*) AS count
+            FROM forwards f
+            WHERE {}
+            GROUP BY f.inbound_channel_id, f.htlc_destination",
+            filters.join(" AND ")
+        );
+        let mut failed = vec![];
+        for row in connection.query(&query, &params.to_params()).await? {
+            failed.push(row.try_into()?);
+        }
+        Ok(failed)
+    }
+
+    pub async fn persist_webhook(&self, webhook: &Webhook) -> Result<()> {
+        debug!("Persist webhook {} for {}", webhook.id, webhook.url);
+        let events: Vec<String> = webhook.events.iter().map(|e| e.to_string()).collect();
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO webhooks (
+                    id,
+                    url,
+                    secret,
+                    key_version,
+                    events,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6)",
+                &[
+                    &webhook.id,
+                    &webhook.url,
+                    &self.cipher.encrypt(webhook.secret.as_bytes())?,
+                    &KEY_VERSION,
+                    &events,
+                    &to_primitive(&webhook.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_webhooks(&self) -> Result<Vec<Webhook>> {
+        let rows = self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT id, url, secret, key_version, events, timestamp \
+                FROM webhooks ORDER BY timestamp ASC",
+                &[],
+            )
+            .await?;
+        let mut webhooks = vec![];
+        for row in rows {
+            let secret = self
+                .cipher
+                .decrypt(Some(row.get("key_version")), row.get("secret"))?;
+            webhooks.push(Webhook::from_row(&row, String::from_utf8(secret)?)?);
+        }
+        Ok(webhooks)
+    }
+
+    /// Returns false when there is no webhook with the ID.
+    pub async fn delete_webhook(&self, id: &Uuid) -> Result<bool> {
+        debug!("Delete webhook {id}");
+        let deleted = self
+            .durable_connection
+            .get()
+            .await
+            .execute("DELETE FROM webhooks WHERE id = $1", &[id])
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    /// Queue a delivery of the event for every webhook that is registered for it. An event id that
+    /// was queued before is not queued again. Returns the number of new deliveries.
+    pub async fn insert_webhook_deliveries(
+        &self,
+        event: WebhookEvent,
+        event_id: &str,
+        payload: &str,
+    ) -> Result<u64> {
+        Ok(self
+            .durable_connection
+            .get()
+            .await
+            .execute(
+                "INSERT INTO webhook_deliveries (webhook_id, event, event_id, payload, status) \
+                SELECT id, $1, $2, $3, $4 FROM webhooks WHERE $1 = ANY(events) \
+                ON CONFLICT (event_id, webhook_id) DO NOTHING",
+                &[
+                    &event.to_string(),
+                    &event_id,
+                    &payload,
+                    &WebhookDeliveryStatus::Pending,
+                ],
+            )
+            .await?)
+    }
+
+    /// Pending deliveries that are due at the given time, the oldest first.
+    pub async fn fetch_due_webhook_deliveries(
+        &self,
+        now: &OffsetDateTime,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let rows = self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT id, webhook_id, event, payload, status, attempts, next_attempt, last_error, timestamp \
+                FROM webhook_deliveries \
+                WHERE status = $1 AND next_attempt <= $2 \
+                ORDER BY next_attempt ASC LIMIT $3",
+                &[&WebhookDeliveryStatus::Pending, &to_primitive(now), &limit],
+            )
+            .await?;
+        rows.iter().map(WebhookDelivery::try_from).collect()
+    }
+
+    pub async fn update_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPDATE webhook_deliveries \
+                SET status = $2, attempts = $3, next_attempt = $4, last_error = $5 \
+                WHERE id = $1",
+                &[
+                    &delivery.id,
+                    &delivery.status,
+                    &(delivery.attempts as i64),
+                    &to_primitive(&delivery.next_attempt),
+                    &delivery.last_error,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn persist_macaroon_root_key(&self, root_key: &MacaroonRootKey) -> Result<()> {
+        debug!(
+            "Persist macaroon root key {} ({})",
+            root_key.id, root_key.name
+        );
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO macaroon_root_keys (id, name, timestamp) VALUES ($1, $2, $3)",
+                &[
+                    &root_key.id,
+                    &root_key.name,
+                    &to_primitive(&root_key.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>> {
+        let rows = self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT id, name, timestamp FROM macaroon_root_keys ORDER BY timestamp ASC",
+                &[],
+            )
+            .await?;
+        Ok(rows.iter().map(MacaroonRootKey::from).collect())
+    }
+
+    pub async fn delete_macaroon_root_key(&self, id: &Uuid) -> Result<bool> {
+        debug!("Delete macaroon root key {id}");
+        let deleted = self
+            .durable_connection
+            .get()
+            .await
+            .execute("DELETE FROM macaroon_root_keys WHERE id = $1", &[id])
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    /// Store an encrypted full monitor and delete the updates that it contains. Returns false when a
+    /// newer monitor is already stored.
+    pub async fn persist_channel_monitor(
+        &self,
+        funding_txo: &OutPoint,
+        monitor: &[u8],
+        update_id: u64,
+    ) -> Result<bool> {
+        let blob = MonitorBlob::Monitor(self.cipher.encrypt(monitor)?);
+        write_monitor(&self.durable_connection, funding_txo, &blob, update_id).await
+    }
+
+    /// Store an encrypted monitor update, which is applied to the monitor when it is fetched.
+    pub async fn persist_channel_monitor_update(
+        &self,
+        funding_txo: &OutPoint,
+        update: &[u8],
+        update_id: u64,
+    ) -> Result<()> {
+        let blob = MonitorBlob::Update(self.cipher.encrypt(update)?);
+        write_monitor(&self.durable_connection, funding_txo, &blob, update_id).await?;
+        Ok(())
+    }
+
+    /// Fetch the stored updates of a channel that come after the given update id, in order.
+    pub async fn fetch_channel_monitor_updates(
+        &self,
+        funding_txo: &OutPoint,
+        after_update_id: u64,
+    ) -> Result<Vec<ChannelMonitorUpdate>> {
+        let rows = self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT update, key_version \
+                FROM channel_monitor_updates \
+                WHERE out_point = $1 AND update_id > $2 \
+                ORDER BY update_id ASC",
+                &[&out_point_key(funding_txo), &sql_update_id(after_update_id)],
+            )
+            .await?;
+        let mut updates = vec![];
+        for row in &rows {
+            let update = self
+                .cipher
+                .decrypt(row.get("key_version"), row.get("update"))?;
+            let update: ChannelMonitorUpdate = Readable::read(&mut Cursor::new(&update))
+                .map_err(|e| anyhow!("Failed to deserialize update: {e}"))?;
+            updates.push(update);
+        }
+        Ok(updates)
+    }
+
+    // Writes of a channel go through its own queue, so a monitor is never overwritten by an older one
+    // and LDK is only told about a write after it and the writes before it are stored.
+    fn queue_monitor_write(
+        &self,
+        funding_txo: OutPoint,
+        write: MonitorWrite,
+    ) -> ChannelMonitorUpdateStatus {
+        let mut writers = match self.monitor_writers.lock() {
+            Ok(writers) => writers,
+            Err(e) => {
+                error!("Failed to queue channel monitor write: {e}");
+                return ChannelMonitorUpdateStatus::UnrecoverableError;
+            }
+        };
+        let sender = writers
+            .entry(funding_txo)
+            .or_insert_with(|| self.spawn_monitor_writer(funding_txo));
+        match sender.send(write) {
+            Ok(()) => ChannelMonitorUpdateStatus::InProgress,
+            Err(e) => {
+                error!("Channel monitor writer for {funding_txo:?} stopped: {e}");
+                ChannelMonitorUpdateStatus::UnrecoverableError
+            }
+        }
+    }
+
+    fn spawn_monitor_writer(&self, funding_txo: OutPoint) -> UnboundedSender<MonitorWrite> {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<MonitorWrite>();
+        let durable_connection = self.durable_connection.clone();
+        let chain_monitor = self
+            .chain_monitor
+            .get()
+            .expect("bad initialisation")
+            .clone();
+        self.runtime.spawn(async move {
+            while let Some(write) = receiver.recv().await {
+                // LDK can not make progress on the channel until the write is stored so keep trying.
+                loop {
+                    match write_monitor(
+                        &durable_connection,
+                        &funding_txo,
+                        &write.blob,
+                        write.channel_update_id,
+                    )
+                    .await
+                    {
+                        Ok(_) => break,
+                        Err(e) => {
+                            error!("Failed to persist channel monitor for {funding_txo:?}: {e}");
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        }
+                    }
+                }
+                if let Err(e) = chain_monitor.channel_monitor_updated(funding_txo, write.update_id)
+                {
+                    error!("Failed to update chain monitor: {}", ldk_error(e));
+                }
+            }
+        });
+        sender
+    }
+
+    /// Fetch the channel monitors with the updates that were stored after them applied.
+    pub async fn fetch_channel_monitors<
+        ES: EntropySource,
+        SP: SignerProvider,
+        B: BroadcasterInterface,
+        F: FeeEstimator,
+    >(
+        &self,
+        entropy_source: &ES,
+        signer_provider: &SP,
+        broadcaster: &B,
+        fee_estimator: &F,
+    ) -> Result<Vec<(BlockHash, ChannelMonitor<SP::Signer>)>> {
+        let rows = self
+            .durable_connection
+            .wait()
+            .await
+            .query(
+                "SELECT out_point, monitor, key_version \
+            FROM channel_monitors",
                 &[],
             )
             .await?;
@@ -583,7 +1575,9 @@ where
             let txid = Txid::from_slice(txid_bytes).unwrap();
             let index = u16::from_be_bytes(index_bytes.try_into().unwrap());
 
-            let monitor: Vec<u8> = row.get("monitor");
+            let monitor = self
+                .cipher
+                .decrypt(row.get("key_version"), row.get("monitor"))?;
             let mut buffer = Cursor::new(&monitor);
             match <(BlockHash, ChannelMonitor<SP::Signer>)>::read(
                 &mut buffer,
@@ -595,34 +1589,39 @@ where
                     {
                         bail!("Unable to find ChannelMonitor for: {}:{}", txid, index);
                     }
-                    /*
-                                        let update_rows = self
-                                            .client
-                                            .read()
-                                            .await
-                                            .query(
-                                                "SELECT update \
-                                            FROM channel_monitor_updates \
-                                            WHERE out_point = $1 \
-                                            ORDER BY update_id ASC",
-                                                &[&out_point],
-                                            )
-                                            .await
-                                            .unwrap();
-
-                                        let updates: Vec<ChannelMonitorUpdate> = update_rows
-                                            .iter()
-                                            .map(|row| {
-                                                let ciphertext: Vec<u8> = row.get("update");
-                                                let update = self.cipher.decrypt(&ciphertext);
-                                                ChannelMonitorUpdate::read(&mut Cursor::new(&update)).unwrap()
-                                            })
-                                            .collect();
-                                        for update in updates {
-                                            channel_monitor
-                                                .update_monitor(&update, broadcaster, fee_estimator.clone(), &KndLogger::global()).unwrap();
-                                        }
-                    */
+                    let latest_update_id = channel_monitor.get_latest_update_id();
+                    // Nothing is stored on its own after the channel closed.
+                    if latest_update_id != CLOSED_CHANNEL_UPDATE_ID {
+                        let updates = self
+                            .fetch_channel_monitor_updates(
+                                &channel_monitor.get_funding_txo().0,
+                                latest_update_id,
+                            )
+                            .await?;
+                        for update in &updates {
+                            channel_monitor
+                                .update_monitor(
+                                    update,
+                                    &broadcaster,
+                                    fee_estimator,
+                                    &KldLogger::global(),
+                                )
+                                .map_err(|()| {
+                                    anyhow!(
+                                        "Failed to apply update {} to ChannelMonitor for: {}:{}",
+                                        update.update_id,
+                                        txid,
+                                        index
+                                    )
+                                })?;
+                        }
+                        debug!(
+                            "Applied {} updates to ChannelMonitor for: {}:{}",
+                            updates.len(),
+                            txid,
+                            index
+                        );
+                    }
                     monitors.push((blockhash, channel_monitor));
                 }
                 Err(e) => bail!("Failed to deserialize ChannelMonitor: {}", e),
@@ -659,12 +1658,14 @@ where
             .get()
             .await
             .query_one(
-                "SELECT manager \
+                "SELECT manager, key_version \
             FROM channel_manager",
                 &[],
             )
             .await?;
-        let manager: Vec<u8> = row.get("manager");
+        let manager = self
+            .cipher
+            .decrypt(row.get("key_version"), row.get("manager"))?;
         <(BlockHash, ChannelManager<M, T, ES, NS, SP, F, R, L>)>::read(
             &mut Cursor::new(manager),
             read_args,
@@ -673,7 +1674,15 @@ where
     }
 
     pub async fn fetch_graph(&self) -> Result<Option<NetworkGraph<Arc<KldLogger>>>> {
-        match fs::read(format!("{}/network_graph", self.settings.data_dir)) {
+        // Graphs that were written before encryption was added are still read from the plaintext file.
+        let bytes = match fs::read(self.graph_path()) {
+            Ok(blob) => Ok(self.cipher.decrypt(Some(KEY_VERSION), &blob)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                fs::read(format!("{}/network_graph", self.settings.data_dir))
+            }
+            Err(e) => Err(e),
+        };
+        match bytes {
             Ok(bytes) => {
                 let graph = NetworkGraph::read(&mut Cursor::new(bytes), KldLogger::global())
                     .map_err(|e| anyhow!(e))?;
@@ -684,6 +1693,130 @@ where
         }
     }
 
+    /// The inbound channel policy that was last set through the API.
+    pub async fn fetch_channel_acceptance_policy(&self) -> Result<Option<ChannelAcceptancePolicy>> {
+        self.durable_connection
+            .get()
+            .await
+            .query_opt(
+                "SELECT policy FROM channel_acceptance_policy WHERE id = 'channel_acceptance_policy'",
+                &[],
+            )
+            .await?
+            .map(|row| Ok(serde_json::from_str(row.get("policy"))?))
+            .transpose()
+    }
+
+    pub async fn persist_channel_acceptance_policy(
+        &self,
+        policy: &ChannelAcceptancePolicy,
+    ) -> Result<()> {
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO channel_acceptance_policy (id, policy, timestamp) \
+                    VALUES ('channel_acceptance_policy', $1, CURRENT_TIMESTAMP)",
+                &[&serde_json::to_string(policy)?],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn persist_jit_channel(&self, channel: &JitChannel) -> Result<()> {
+        debug!("Persist jit channel {}", channel.user_channel_id);
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO jit_channels (
+                    user_channel_id,
+                    counterparty_node_id,
+                    intercept_scid,
+                    payment_size_msat,
+                    opening_fee_msat,
+                    channel_id,
+                    state,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &(channel.user_channel_id as i64),
+                    &channel.counterparty_node_id.encode(),
+                    &(channel.intercept_scid as i64),
+                    &channel.payment_size_msat.map(|x| x as i64),
+                    &channel.opening_fee_msat.map(|x| x as i64),
+                    &channel.channel_id.map(|id| id.0.to_vec()),
+                    &JitChannelStatus::from(channel.state),
+                    &to_primitive(&channel.created_at),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_jit_channels(&self) -> Result<Vec<JitChannel>> {
+        let mut channels = vec![];
+        for row in self
+            .durable_connection
+            .get()
+            .await
+            .query("SELECT * FROM jit_channels ORDER BY timestamp ASC", &[])
+            .await?
+        {
+            channels.push(row.try_into()?);
+        }
+        Ok(channels)
+    }
+
+    /// Remember an HTLC that waits for the just in time channel, so that it can be failed back
+    /// if we restart before the channel is ready.
+    pub async fn persist_jit_channel_htlc(
+        &self,
+        user_channel_id: u64,
+        intercept_id: &InterceptId,
+    ) -> Result<()> {
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO jit_channel_htlcs (intercept_id, user_channel_id) VALUES ($1, $2)",
+                &[&intercept_id.0.as_slice(), &(user_channel_id as i64)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_jit_channel_htlcs(&self, user_channel_id: u64) -> Result<Vec<InterceptId>> {
+        let mut htlcs = vec![];
+        for row in self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT intercept_id FROM jit_channel_htlcs WHERE user_channel_id = $1",
+                &[&(user_channel_id as i64)],
+            )
+            .await?
+        {
+            htlcs.push(InterceptId(
+                row.get::<&str, &[u8]>("intercept_id").try_into()?,
+            ));
+        }
+        Ok(htlcs)
+    }
+
+    pub async fn delete_jit_channel_htlcs(&self, user_channel_id: u64) -> Result<()> {
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "DELETE FROM jit_channel_htlcs WHERE user_channel_id = $1",
+                &[&(user_channel_id as i64)],
+            )
+            .await?;
+        Ok(())
+    }
+
     pub async fn fetch_scorer(
         &self,
         params: ProbabilisticScoringDecayParameters,
@@ -698,18 +1831,21 @@ where
             .durable_connection
             .wait()
             .await
-            .query_opt("SELECT scorer, timestamp FROM scorer", &[])
+            .query_opt("SELECT scorer, timestamp, key_version FROM scorer", &[])
             .await?
-            .map(|row| {
-                let bytes: Vec<u8> = row.get(0);
-                let timestamp: SystemTime = row.get(1);
+            .map(|row| -> Result<_> {
+                let bytes = self
+                    .cipher
+                    .decrypt(row.get("key_version"), row.get("scorer"))?;
+                let timestamp: SystemTime = row.get("timestamp");
                 let scorer = ProbabilisticScorer::read(
                     &mut Cursor::new(bytes),
                     (params, graph.clone(), KldLogger::global()),
                 )
                 .expect("Unable to deserialize scorer");
-                (scorer, timestamp)
-            });
+                Ok((scorer, timestamp))
+            })
+            .transpose()?;
         Ok(scorer)
     }
 }
@@ -733,15 +1869,16 @@ where
     ) -> Result<(), io::Error> {
         let mut buf = vec![];
         channel_manager.write(&mut buf)?;
+        let blob = self.encrypt(&buf)?;
         let durable_connection = self.durable_connection.clone();
         self.runtime.spawn(async move {
             if let Err(e) = durable_connection
                 .get()
                 .await
                 .execute(
-                    "UPSERT INTO channel_manager (id, manager, timestamp) \
-                        VALUES ('manager', $1, CURRENT_TIMESTAMP)",
-                    &[&buf],
+                    "UPSERT INTO channel_manager (id, manager, key_version, timestamp) \
+                        VALUES ('manager', $1, $2, CURRENT_TIMESTAMP)",
+                    &[&blob, &KEY_VERSION],
                 )
                 .await
             {
@@ -751,31 +1888,26 @@ where
         Ok(())
     }
 
-    // Network graph could get very large so just write it to disk for now.
     fn persist_graph(
         &self,
         network_graph: &lightning::routing::gossip::NetworkGraph<L>,
     ) -> Result<(), io::Error> {
-        let mut buf = vec![];
-        network_graph.write(&mut buf)?;
-        if let Err(e) = fs::write(format!("{}/network_graph", self.settings.data_dir), &buf) {
-            error!("Failed to persist graph: {e}");
-        }
-        Ok(())
+        self.write_graph(network_graph)
     }
 
     fn persist_scorer(&self, scorer: &S) -> Result<(), io::Error> {
         let mut buf = vec![];
         scorer.write(&mut buf)?;
+        let blob = self.encrypt(&buf)?;
         let durable_connection = self.durable_connection.clone();
         self.runtime.spawn(async move {
             if let Err(e) = durable_connection
                 .get()
                 .await
                 .execute(
-                    "UPSERT INTO scorer (id, scorer, timestamp)
-                        VALUES ('scorer', $1, CURRENT_TIMESTAMP)",
-                    &[&buf],
+                    "UPSERT INTO scorer (id, scorer, key_version, timestamp)
+                        VALUES ('scorer', $1, $2, CURRENT_TIMESTAMP)",
+                    &[&blob, &KEY_VERSION],
                 )
                 .await
             {
@@ -789,7 +1921,7 @@ where
 impl<ChannelSigner: WriteableEcdsaChannelSigner> chain::chainmonitor::Persist<ChannelSigner>
     for LdkDatabase
 {
-    // The CHANNEL_MONITORS table stores the latest monitor and its update_id.
+    // The CHANNEL_MONITORS table stores the latest full monitor and its update_id.
     fn persist_new_channel(
         &self,
         funding_txo: OutPoint,
@@ -801,112 +1933,160 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner> chain::chainmonitor::Persist<Ch
             funding_txo,
             monitor.get_latest_update_id()
         );
-        let mut out_point_buf = vec![];
-        funding_txo.write(&mut out_point_buf).unwrap();
-
         let mut monitor_buf = vec![];
         monitor.write(&mut monitor_buf).unwrap();
-        let latest_update_id = monitor.get_latest_update_id();
-        let latest_update_id = if latest_update_id == u64::MAX {
-            i64::MAX
-        } else {
-            to_i64!(latest_update_id)
-        };
-
-        let durable_connection = self.durable_connection.clone();
-        let chain_monitor = self
-            .chain_monitor
-            .get()
-            .expect("bad initialisation")
-            .clone();
-        tokio::spawn(async move {
-            let result = durable_connection
-                .get()
-                .await
-                .execute(
-                    "UPSERT INTO channel_monitors (out_point, monitor, update_id) \
-                VALUES ($1, $2, $3)",
-                    &[&out_point_buf, &monitor_buf, &latest_update_id],
-                )
-                .await;
-            match result {
-                Ok(_) => {
-                    debug!(
-                        "Stored channel: {}:{} with update id: {}",
-                        funding_txo.txid, funding_txo.index, latest_update_id
-                    );
-                    if let Err(e) = chain_monitor.channel_monitor_updated(funding_txo, update_id) {
-                        error!("Failed to update chain monitor: {}", ldk_error(e));
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to persist channel update: {e}");
-                }
+        let monitor_buf = match self.cipher.encrypt(&monitor_buf) {
+            Ok(blob) => blob,
+            Err(e) => {
+                error!("Failed to encrypt channel monitor: {e}");
+                return ChannelMonitorUpdateStatus::UnrecoverableError;
             }
-        });
-        ChannelMonitorUpdateStatus::InProgress
+        };
+        self.queue_monitor_write(
+            funding_txo,
+            MonitorWrite {
+                blob: MonitorBlob::Monitor(monitor_buf),
+                channel_update_id: monitor.get_latest_update_id(),
+                update_id,
+            },
+        )
     }
 
-    // Updates are applied to the monitor when fetched from database.
+    // Only the update is stored, the updates are applied to the monitor when fetched from database.
+    // The full monitor is written when there is no update (chain sync), when the channel is closed and
+    // every channel_monitor_max_pending_updates updates so that the updates do not pile up.
     fn update_persisted_channel(
         &self,
         funding_txo: OutPoint,
-        _update: Option<&ChannelMonitorUpdate>,
+        update: Option<&ChannelMonitorUpdate>,
         monitor: &ChannelMonitor<ChannelSigner>,
         update_id: MonitorUpdateId,
     ) -> ChannelMonitorUpdateStatus {
-        self.persist_new_channel(funding_txo, monitor, update_id)
-
-        // Hope we can enable this soon. Probably after https://github.com/lightningdevkit/rust-lightning/issues/1426
-        /*
-                let mut out_point_buf = vec![];
-                funding_txo.write(&mut out_point_buf).unwrap();
-
-                // If its the last update then store the last monitor and delete the updates.
-                if update.as_ref().map_or(true, |x| x.update_id == CLOSED_CHANNEL_UPDATE_ID) {
-                    let mut monitor_buf = vec![];
-                    monitor.write(&mut monitor_buf).unwrap();
-                    let ciphertext = self.cipher.encrypt(&monitor_buf);
-
-                    tokio::task::block_in_place(move || {
-                        Handle::current().block_on(async move {
-                            let mut lock = self.client.write().await;
-                            let tx = lock.transaction().await.unwrap();
-                            tx.execute(
-                                "UPSERT INTO channel_monitors (out_point, monitor, update_id) VALUES ($1, $2, $3)",
-                                &[
-                                    &out_point_buf,
-                                    &ciphertext,
-                                    &to_i64!(monitor.get_latest_update_id()),
-                                ],
-                            )
-                            .await
-                            .unwrap();
-                            let deleted = tx
-                                .execute(
-                                    "DELETE FROM channel_monitor_updates WHERE out_point = $1",
-                                    &[&out_point_buf],
-                                )
-                                .await
-                                .unwrap();
-                            tx.commit().await.unwrap();
-                            debug!("Stored latest monitor and deleted {} updates.", deleted);
-                        })
-                    })
-                } else {
-                    let update = update.as_ref().unwrap();
-                    let mut update_buf = vec![];
-                    update.write(&mut update_buf).unwrap();
-                    let ciphertext = self.cipher.encrypt(&update_buf);
-
-                    block_in_place!(
-                        "UPSERT INTO channel_monitor_updates (out_point, update, update_id) \
-                        VALUES ($1, $2, $3)",
-                        &[&out_point_buf, &ciphertext, &to_i64!(update.update_id)],
-                        self
-                    );
-                }
-                ChannelMonitorUpdateStatus::Completed
-        */
+        let update = match update {
+            Some(update)
+                if update.update_id != CLOSED_CHANNEL_UPDATE_ID
+                    && update.update_id % self.settings.channel_monitor_max_pending_updates
+                        != 0 =>
+            {
+                update
+            }
+            _ => return self.persist_new_channel(funding_txo, monitor, update_id),
+        };
+        debug!(
+            "Persisting channel update: {:?} {}",
+            funding_txo, update.update_id
+        );
+        let mut update_buf = vec![];
+        update.write(&mut update_buf).unwrap();
+        let update_buf = match self.cipher.encrypt(&update_buf) {
+            Ok(blob) => blob,
+            Err(e) => {
+                error!("Failed to encrypt channel monitor update: {e}");
+                return ChannelMonitorUpdateStatus::UnrecoverableError;
+            }
+        };
+        self.queue_monitor_write(
+            funding_txo,
+            MonitorWrite {
+                blob: MonitorBlob::Update(update_buf),
+                channel_update_id: update.update_id,
+                update_id,
+            },
+        )
     }
 }
+
+/// A write to the channel monitor tables. LDK is told that the write completed once it is stored.
+struct MonitorWrite {
+    blob: MonitorBlob,
+    channel_update_id: u64,
+    update_id: MonitorUpdateId,
+}
+
+// Encrypted monitor or monitor update.
+enum MonitorBlob {
+    Monitor(Vec<u8>),
+    Update(Vec<u8>),
+}
+
+// Closed channels get updates with CLOSED_CHANNEL_UPDATE_ID (u64::MAX) which does not fit in an INT.
+// It is stored as the largest INT so that the monitor of a closed channel is always the latest.
+fn sql_update_id(update_id: u64) -> i64 {
+    if update_id == CLOSED_CHANNEL_UPDATE_ID {
+        i64::MAX
+    } else {
+        to_i64!(update_id)
+    }
+}
+
+// Stores the monitor and deletes the updates that it contains in one statement. A chain sync writes the
+// monitor again with the same update id, so only a monitor with a lower update id is replaced.
+async fn write_monitor(
+    durable_connection: &DurableConnection,
+    funding_txo: &OutPoint,
+    blob: &MonitorBlob,
+    channel_update_id: u64,
+) -> Result<bool> {
+    let out_point = out_point_key(funding_txo);
+    let update_id = sql_update_id(channel_update_id);
+    let client = durable_connection.get().await;
+    match blob {
+        MonitorBlob::Monitor(monitor) => {
+            let row = client
+                .query_one(
+                    "WITH monitor AS ( \
+                        INSERT INTO channel_monitors (out_point, monitor, update_id, key_version) \
+                        VALUES ($1, $2, $3, $4) \
+                        ON CONFLICT (out_point) DO UPDATE SET \
+                            monitor = excluded.monitor, \
+                            update_id = excluded.update_id, \
+                            key_version = excluded.key_version \
+                        WHERE channel_monitors.update_id <= excluded.update_id \
+                        RETURNING update_id \
+                    ), pruned AS ( \
+                        DELETE FROM channel_monitor_updates \
+                        WHERE out_point = $1 AND update_id <= (SELECT update_id FROM monitor) \
+                        RETURNING update_id \
+                    ) \
+                    SELECT (SELECT count(*) FROM monitor) AS stored, \
+                        (SELECT count(*) FROM pruned) AS pruned",
+                    &[&out_point, monitor, &update_id, &KEY_VERSION],
+                )
+                .await?;
+            let stored: i64 = row.get("stored");
+            let pruned: i64 = row.get("pruned");
+            if stored > 0 {
+                debug!(
+                    "Stored channel: {}:{} with update id: {channel_update_id}, compacted {pruned} updates",
+                    funding_txo.txid, funding_txo.index
+                );
+            } else {
+                debug!(
+                    "Skipped channel: {}:{} with update id: {channel_update_id}, a newer monitor is stored",
+                    funding_txo.txid, funding_txo.index
+                );
+            }
+            Ok(stored > 0)
+        }
+        MonitorBlob::Update(update) => {
+            client
+                .execute(
+                    "UPSERT INTO channel_monitor_updates (out_point, update, update_id, key_version) \
+                    VALUES ($1, $2, $3, $4)",
+                    &[&out_point, update, &update_id, &KEY_VERSION],
+                )
+                .await?;
+            debug!(
+                "Stored channel update: {}:{} with update id: {channel_update_id}",
+                funding_txo.txid, funding_txo.index
+            );
+            Ok(true)
+        }
+    }
+}
+
+fn out_point_key(funding_txo: &OutPoint) -> Vec<u8> {
+    let mut out_point_buf = vec![];
+    funding_txo.write(&mut out_point_buf).unwrap();
+    out_point_buf
+}
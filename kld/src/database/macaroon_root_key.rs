@@ -0,0 +1,44 @@
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use super::{microsecond_timestamp, RowExt};
+
+/// Identifies the key that a group of macaroons is signed with. The key itself is derived from
+/// the macaroon seed and the ID, so deleting the row revokes every macaroon of the group.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MacaroonRootKey {
+    pub id: Uuid,
+    pub name: String,
+    pub timestamp: OffsetDateTime,
+}
+
+impl MacaroonRootKey {
+    pub fn new(name: String) -> MacaroonRootKey {
+        MacaroonRootKey {
+            id: Uuid::new_v4(),
+            name,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+}
+
+impl From<MacaroonRootKey> for api::MacaroonRootKey {
+    fn from(root_key: MacaroonRootKey) -> Self {
+        api::MacaroonRootKey {
+            id: root_key.id.to_string(),
+            name: root_key.name,
+            created_at: root_key.timestamp.unix_timestamp() as u64,
+        }
+    }
+}
+
+impl From<&Row> for MacaroonRootKey {
+    fn from(row: &Row) -> Self {
+        MacaroonRootKey {
+            id: row.get("id"),
+            name: row.get("name"),
+            timestamp: row.get_timestamp("timestamp"),
+        }
+    }
+}
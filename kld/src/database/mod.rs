@@ -1,22 +1,28 @@
 pub mod channel;
+pub mod cipher;
 pub mod forward;
 pub mod invoice;
+pub mod jit_channel;
 mod ldk_database;
+pub mod macaroon_root_key;
+pub mod offer;
 pub mod payment;
+pub mod payment_attempt;
 pub mod peer;
 pub mod spendable_output;
 mod wallet_database;
-use tokio_postgres::NoTls;
-
+pub mod webhook;
 use std::{
+    fs,
     sync::{Arc, RwLock},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use async_trait::async_trait;
 pub use ldk_database::LdkDatabase;
 use lightning::util::ser::MaybeReadable;
 use postgres_types::ToSql;
+use thiserror::Error;
 use time::{OffsetDateTime, PrimitiveDateTime};
 use tokio::{sync::OwnedRwLockReadGuard, task::JoinHandle};
 pub use wallet_database::WalletDatabase;
@@ -26,7 +32,7 @@ use log::{error, info};
 use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
 use postgres_openssl::MakeTlsConnector;
 use tokio::sync::RwLock as AsyncRwLock;
-use tokio_postgres::{Client, Row};
+use tokio_postgres::{Client, Config, Row};
 
 use crate::{ldk::decode_error, settings::Settings};
 
@@ -139,23 +145,38 @@ impl DurableConnection {
 
     async fn create_connection(settings: Arc<Settings>) -> Result<(Client, JoinHandle<()>)> {
         let log_safe_params = format!(
-            "host={} port={} user={} dbname={} password=mysecretpassword",
+            "host={} port={} user={} dbname={} sslmode={}",
             settings.database_host,
             settings.database_port,
             settings.database_user,
-            settings.database_name
+            settings.database_name,
+            settings.database_ssl_mode
         );
-        // let mut builder = SslConnector::builder(SslMethod::tls()).expect("TLS initialisation");
-        // builder.set_ca_file(&settings.database_ca_cert_path)?;
-        // builder
-        //     .set_certificate_file(&settings.database_client_cert_path, SslFiletype::PEM)
-        //     .expect("Database certificate");
-        // builder
-        //     .set_private_key_file(&settings.database_client_key_path, SslFiletype::PEM)
-        //     .expect("Database private key");
-        // let connector = MakeTlsConnector::new(builder.build());
-        let (client, connection) = tokio_postgres::connect(&log_safe_params, NoTls)
-        .await
+        let mut config: Config = log_safe_params
+            .parse()
+            .with_context(|| format!("Invalid database parameters ({log_safe_params})"))?;
+        if let Some(password) = &settings.database_password {
+            config.password(password);
+        }
+        // The certificates are read again for every connection so that renewed certificates are used.
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+        if !settings.database_ca_cert_path.is_empty() {
+            builder
+                .set_ca_file(&settings.database_ca_cert_path)
+                .context("Database CA certificate")?;
+        }
+        if !settings.database_client_cert_path.is_empty() {
+            builder
+                .set_certificate_file(&settings.database_client_cert_path, SslFiletype::PEM)
+                .context("Database client certificate")?;
+            builder
+                .set_private_key_file(&settings.database_client_key_path, SslFiletype::PEM)
+                .context("Database client private key")?;
+        }
+        let connector = MakeTlsConnector::new(builder.build());
+        let (client, connection) = config
+            .connect(connector)
+            .await
             .with_context(|| format!("Cannot connect to database ({log_safe_params})"))?;
         let connection_task = tokio::spawn(async move {
             if let Err(e) = connection.await {
@@ -184,20 +205,31 @@ impl DurableConnection {
         }
     }
 
+    /// Reconnect when the connection has been dropped or when the certificates have changed.
     fn keep_connected(
         settings: Arc<Settings>,
         client: Arc<AsyncRwLock<Client>>,
         connection_task: Arc<RwLock<JoinHandle<()>>>,
     ) -> JoinHandle<()> {
         tokio::spawn(async move {
+            let mut certs_modified = DurableConnection::certs_modified(&settings);
             loop {
-                if client.read().await.is_closed() {
+                let modified = DurableConnection::certs_modified(&settings);
+                let reload = modified != certs_modified;
+                if reload {
+                    info!("Database certificates have changed, reconnecting");
+                }
+                if reload || client.read().await.is_closed() {
                     let mut client_guard = client.write().await;
                     match DurableConnection::create_connection(settings.clone()).await {
                         Ok((client, connect_task)) => {
                             *client_guard = client;
+                            certs_modified = modified;
                             match connection_task.write() {
-                                Ok(mut task_guard) => *task_guard = connect_task,
+                                Ok(mut task_guard) => {
+                                    // Nothing uses the old connection any more as we hold the write lock.
+                                    std::mem::replace(&mut *task_guard, connect_task).abort()
+                                }
                                 Err(e) => error!("{e}"),
                             }
                         }
@@ -210,6 +242,17 @@ impl DurableConnection {
             }
         })
     }
+
+    fn certs_modified(settings: &Settings) -> Vec<Option<SystemTime>> {
+        [
+            &settings.database_ca_cert_path,
+            &settings.database_client_cert_path,
+            &settings.database_client_key_path,
+        ]
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+    }
 }
 
 impl Drop for DurableConnection {
@@ -245,6 +288,77 @@ impl<'a> Params<'a> {
     }
 }
 
+/// The cursor of a page is not the ID of a row, it may have been deleted.
+#[derive(Debug, Error)]
+#[error("Unknown cursor")]
+pub struct UnknownCursor;
+
+/// Time range and size of a page of a list query. Lists are ordered from old to new and the next
+/// page starts after the cursor, which is the ID of the last row of the previous page.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Page {
+    /// Rows from this time on.
+    pub since: Option<OffsetDateTime>,
+    /// Rows before this time.
+    pub until: Option<OffsetDateTime>,
+    pub limit: Option<u32>,
+}
+
+impl Page {
+    /// Filter on the time and start after the cursor. The columns must be qualified with the
+    /// alias of the table, which is also used to look up the cursor row. Fails with
+    /// [UnknownCursor] when the cursor is not the ID of a row that can be listed.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn push_filters<'a>(
+        &self,
+        client: &Client,
+        table: &str,
+        time_column: &str,
+        id_column: &str,
+        cursor: Option<impl ToSql + Sync + Send + 'a>,
+        filters: &mut Vec<String>,
+        params: &mut Params<'a>,
+    ) -> Result<()> {
+        if let Some(since) = self.since {
+            params.push(to_primitive(&since));
+            filters.push(format!("{time_column} >= ${}", params.count()));
+        }
+        if let Some(until) = self.until {
+            params.push(to_primitive(&until));
+            filters.push(format!("{time_column} < ${}", params.count()));
+        }
+        if let Some(cursor) = cursor {
+            let cursor_time: Option<PrimitiveDateTime> = client
+                .query_opt(
+                    &format!("SELECT {time_column} FROM {table} WHERE {id_column} = $1"),
+                    &[&cursor],
+                )
+                .await?
+                .and_then(|row| row.get(0));
+            let Some(cursor_time) = cursor_time else {
+                return Err(UnknownCursor.into());
+            };
+            params.push(cursor_time);
+            params.push(cursor);
+            // Comparing the ID as well does not skip rows with the same time as the cursor.
+            filters.push(format!(
+                "({time_column}, {id_column}) > (${}, ${})",
+                params.count() - 1,
+                params.count()
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn order_and_limit(&self, time_column: &str, id_column: &str) -> String {
+        let mut clause = format!("\nORDER BY {time_column}, {id_column}");
+        if let Some(limit) = self.limit {
+            clause.push_str(&format!("\nLIMIT {limit}"));
+        }
+        clause
+    }
+}
+
 pub fn microsecond_timestamp() -> OffsetDateTime {
     let timestamp = OffsetDateTime::now_utc();
     timestamp
@@ -289,3 +403,41 @@ impl RowExt for Row {
             .map(|time| time.assume_utc())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{fs, thread, time::Duration};
+
+    use test_utils::TempDir;
+
+    use crate::settings::Settings;
+
+    use super::DurableConnection;
+
+    #[test]
+    fn test_certs_modified() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = |name: &str| format!("{}/{name}", temp_dir.path().display());
+        let mut settings = Settings::default();
+        settings.database_ca_cert_path = path("ca.crt");
+        settings.database_client_cert_path = path("client.crt");
+        settings.database_client_key_path = path("client.key");
+        for name in ["ca.crt", "client.crt", "client.key"] {
+            fs::write(path(name), name).unwrap();
+        }
+
+        let modified = DurableConnection::certs_modified(&settings);
+        assert!(modified.iter().all(Option::is_some));
+        assert_eq!(modified, DurableConnection::certs_modified(&settings));
+
+        // Some file systems only keep the modification time in seconds.
+        thread::sleep(Duration::from_secs(1));
+        fs::write(path("client.crt"), "renewed").unwrap();
+        let renewed = DurableConnection::certs_modified(&settings);
+        assert_ne!(modified, renewed);
+        assert_eq!(modified[0], renewed[0]);
+
+        fs::remove_file(path("client.key")).unwrap();
+        assert_eq!(None, DurableConnection::certs_modified(&settings)[2]);
+    }
+}
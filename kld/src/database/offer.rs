@@ -0,0 +1,167 @@
+use std::fmt::{self, Display};
+
+use anyhow::{Context, Result};
+use bitcoin::hashes::{sha256, Hash};
+use lightning::{
+    ln::channelmanager::PaymentId,
+    offers::{offer::Amount, refund::Refund},
+};
+use postgres_types::{FromSql, ToSql};
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+
+use crate::MillisatAmount;
+
+use super::{microsecond_timestamp, payment::PaymentDirection, RowExt};
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "offer_kind")]
+pub enum OfferKind {
+    #[postgres(name = "offer")]
+    Offer,
+    #[postgres(name = "refund")]
+    Refund,
+}
+
+/// A bolt12 offer or refund that we created or paid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Offer {
+    // Hash of the encoded offer.
+    pub id: sha256::Hash,
+    pub kind: OfferKind,
+    // The bech32 encoded offer (lno) or refund (lnr).
+    pub bolt12: String,
+    pub description: String,
+    // User generated label.
+    pub label: Option<String>,
+    pub amount: Option<MillisatAmount>,
+    // Inbound if we get paid, outbound if we pay.
+    pub direction: PaymentDirection,
+    pub timestamp: OffsetDateTime,
+}
+
+impl Offer {
+    pub fn of_offer(
+        offer: &lightning::offers::offer::Offer,
+        label: Option<String>,
+        direction: PaymentDirection,
+    ) -> Self {
+        Offer {
+            id: sha256::Hash::hash(offer.as_ref()),
+            kind: OfferKind::Offer,
+            bolt12: offer.to_string(),
+            description: offer.description().to_string(),
+            label,
+            amount: match offer.amount() {
+                Some(Amount::Bitcoin { amount_msats }) => Some(*amount_msats),
+                _ => None,
+            },
+            direction,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+
+    pub fn of_refund(refund: &Refund, label: Option<String>, direction: PaymentDirection) -> Self {
+        Offer {
+            id: sha256::Hash::hash(refund.as_ref()),
+            kind: OfferKind::Refund,
+            bolt12: refund.to_string(),
+            description: refund.description().to_string(),
+            label,
+            amount: Some(refund.amount_msats()),
+            direction,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+}
+
+impl TryFrom<&Row> for Offer {
+    type Error = anyhow::Error;
+
+    fn try_from(row: &Row) -> std::result::Result<Self, Self::Error> {
+        Ok(Offer {
+            id: sha256::Hash::from_slice(row.get("id")).context("bad offer ID")?,
+            kind: row.get("kind"),
+            bolt12: row.get("bolt12"),
+            description: row.get("description"),
+            label: row.get("label"),
+            amount: row
+                .get::<&str, Option<i64>>("amount")
+                .map(|a| a as MillisatAmount),
+            direction: row.get("direction"),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "invoice_request_status")]
+pub enum InvoiceRequestStatus {
+    #[postgres(name = "pending")]
+    Pending,
+    #[postgres(name = "paid")]
+    Paid,
+    #[postgres(name = "failed")]
+    Failed,
+}
+
+impl Display for InvoiceRequestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvoiceRequestStatus::Pending => f.write_str("pending"),
+            InvoiceRequestStatus::Paid => f.write_str("paid"),
+            InvoiceRequestStatus::Failed => f.write_str("failed"),
+        }
+    }
+}
+
+/// An invoice request that we sent to pay an offer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvoiceRequest {
+    pub payment_id: PaymentId,
+    pub offer_id: sha256::Hash,
+    pub amount: Option<MillisatAmount>,
+    pub quantity: Option<u64>,
+    pub payer_note: Option<String>,
+    pub status: InvoiceRequestStatus,
+    pub timestamp: OffsetDateTime,
+}
+
+impl InvoiceRequest {
+    pub fn new(
+        payment_id: PaymentId,
+        offer_id: sha256::Hash,
+        amount: Option<MillisatAmount>,
+        quantity: Option<u64>,
+        payer_note: Option<String>,
+    ) -> Self {
+        InvoiceRequest {
+            payment_id,
+            offer_id,
+            amount,
+            quantity,
+            payer_note,
+            status: InvoiceRequestStatus::Pending,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+}
+
+impl TryFrom<&Row> for InvoiceRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(row: &Row) -> Result<Self> {
+        let payment_id: &[u8] = row.get("payment_id");
+        Ok(InvoiceRequest {
+            payment_id: PaymentId(payment_id.try_into().context("bad payment ID")?),
+            offer_id: sha256::Hash::from_slice(row.get("offer_id")).context("bad offer ID")?,
+            amount: row
+                .get::<&str, Option<i64>>("amount")
+                .map(|a| a as MillisatAmount),
+            quantity: row.get::<&str, Option<i64>>("quantity").map(|q| q as u64),
+            payer_note: row.get("payer_note"),
+            status: row.get("status"),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
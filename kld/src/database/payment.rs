@@ -17,7 +17,7 @@ use tokio_postgres::Row;
 
 use crate::MillisatAmount;
 
-use super::{invoice::Invoice, microsecond_timestamp, RowExt};
+use super::{invoice::Invoice, microsecond_timestamp, Page, RowExt};
 
 #[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
 #[postgres(name = "payment_status")]
@@ -91,6 +91,16 @@ impl FromStr for PaymentDirection {
     }
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct PaymentFilter {
+    pub payment_hash: Option<PaymentHash>,
+    pub direction: Option<PaymentDirection>,
+    /// Payments with any of these statuses, all payments when it is empty.
+    pub statuses: Vec<PaymentStatus>,
+    pub page: Page,
+    pub cursor: Option<PaymentId>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Payment {
     pub id: PaymentId,
@@ -108,6 +118,10 @@ pub struct Payment {
     pub timestamp: OffsetDateTime,
     // The bolt11 invoice with corresponding payment hash. Useful when querying payments.
     pub bolt11: Option<Bolt11Invoice>,
+    // Number of paths that were tried for an outbound payment.
+    pub attempts: u32,
+    // Number of paths that delivered a part of an outbound payment.
+    pub successful_paths: u32,
 }
 
 impl Payment {
@@ -132,6 +146,8 @@ impl Payment {
             direction: PaymentDirection::Inbound,
             timestamp: microsecond_timestamp(),
             bolt11: None,
+            attempts: 0,
+            successful_paths: 0,
         }
     }
 
@@ -148,6 +164,8 @@ impl Payment {
             direction: PaymentDirection::Outbound,
             timestamp: microsecond_timestamp(),
             bolt11: None,
+            attempts: 0,
+            successful_paths: 0,
         }
     }
 
@@ -169,6 +187,8 @@ impl Payment {
             direction: PaymentDirection::Inbound,
             timestamp: microsecond_timestamp(),
             bolt11: None,
+            attempts: 0,
+            successful_paths: 0,
         }
     }
 
@@ -180,11 +200,32 @@ impl Payment {
             secret: Some(*invoice.bolt11.payment_secret()),
             label,
             status: PaymentStatus::Pending,
-            amount: invoice.bolt11.amount_milli_satoshis().unwrap_or_default(),
+            amount: invoice.amount.unwrap_or_default(),
             fee: None,
             direction: PaymentDirection::Outbound,
             timestamp: microsecond_timestamp(),
             bolt11: Some(invoice.bolt11.clone()),
+            attempts: 0,
+            successful_paths: 0,
+        }
+    }
+
+    // The payment hash is only known once we receive the bolt12 invoice.
+    pub fn of_offer_outbound(id: PaymentId, amount: MillisatAmount, label: Option<String>) -> Self {
+        Payment {
+            id,
+            hash: None,
+            preimage: None,
+            secret: None,
+            label,
+            status: PaymentStatus::Pending,
+            amount,
+            fee: None,
+            direction: PaymentDirection::Outbound,
+            timestamp: microsecond_timestamp(),
+            bolt11: None,
+            attempts: 0,
+            successful_paths: 0,
         }
     }
 
@@ -200,6 +241,16 @@ impl Payment {
         self.status = PaymentStatus::Succeeded;
     }
 
+    /// Number of parts that the payment was split into. Successful paths are only reported after
+    /// the payment succeeded, until then a successful payment counts as a single part.
+    pub fn parts(&self) -> u32 {
+        if self.status == PaymentStatus::Succeeded {
+            self.successful_paths.max(1)
+        } else {
+            self.successful_paths
+        }
+    }
+
     pub fn failed(&mut self, reason: Option<PaymentFailureReason>) {
         self.status = match reason {
             Some(PaymentFailureReason::RecipientRejected) => PaymentStatus::RecipientRejected,
@@ -249,6 +300,8 @@ impl TryFrom<&Row> for Payment {
             direction: row.get("direction"),
             timestamp: row.get_timestamp("timestamp"),
             bolt11: Bolt11Invoice::from_str(row.get("bolt11")).ok(),
+            attempts: row.get::<&str, i64>("attempts") as u32,
+            successful_paths: row.get::<&str, i64>("successful_paths") as u32,
         })
     }
 }
@@ -0,0 +1,131 @@
+use crate::MillisatAmount;
+
+use anyhow::{Context, Result};
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::channelmanager::PaymentId;
+use lightning::routing::router::Path;
+use postgres_types::{FromSql, ToSql};
+use std::fmt::{self, Display};
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use super::{microsecond_timestamp, RowExt};
+
+/// A single path that was tried for an outbound payment. Multi-path payments have an attempt for each part.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PaymentAttempt {
+    pub id: Uuid,
+    pub payment_id: PaymentId,
+    pub status: PaymentAttemptStatus,
+    pub hops: Vec<PaymentHop>,
+    // Amount delivered to the payee over this path.
+    pub amount: MillisatAmount,
+    // Routing fee of the path.
+    pub fee: MillisatAmount,
+    // The channel that failed the path, when known.
+    pub failed_short_channel_id: Option<u64>,
+    // The payee or a hop rejected the payment, it is not retried.
+    pub failed_permanently: bool,
+    pub timestamp: OffsetDateTime,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PaymentHop {
+    pub node_id: PublicKey,
+    pub short_channel_id: u64,
+}
+
+impl PaymentAttempt {
+    pub fn success(payment_id: PaymentId, path: &Path) -> PaymentAttempt {
+        PaymentAttempt::new(payment_id, PaymentAttemptStatus::Succeeded, path)
+    }
+
+    pub fn failure(
+        payment_id: PaymentId,
+        path: &Path,
+        failed_short_channel_id: Option<u64>,
+        failed_permanently: bool,
+    ) -> PaymentAttempt {
+        PaymentAttempt {
+            failed_short_channel_id,
+            failed_permanently,
+            ..PaymentAttempt::new(payment_id, PaymentAttemptStatus::Failed, path)
+        }
+    }
+
+    fn new(payment_id: PaymentId, status: PaymentAttemptStatus, path: &Path) -> PaymentAttempt {
+        PaymentAttempt {
+            id: Uuid::new_v4(),
+            payment_id,
+            status,
+            hops: path
+                .hops
+                .iter()
+                .map(|hop| PaymentHop {
+                    node_id: hop.pubkey,
+                    short_channel_id: hop.short_channel_id,
+                })
+                .collect(),
+            amount: path.final_value_msat(),
+            fee: path.fee_msat(),
+            failed_short_channel_id: None,
+            failed_permanently: false,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+}
+
+impl TryFrom<Row> for PaymentAttempt {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        let node_ids: Vec<Vec<u8>> = row.get("hop_node_ids");
+        let short_channel_ids: Vec<i64> = row.get("hop_short_channel_ids");
+        let hops = node_ids
+            .iter()
+            .zip(short_channel_ids)
+            .map(|(node_id, short_channel_id)| {
+                Ok(PaymentHop {
+                    node_id: PublicKey::from_slice(node_id).context("bad hop node ID")?,
+                    short_channel_id: short_channel_id as u64,
+                })
+            })
+            .collect::<Result<Vec<PaymentHop>>>()?;
+        Ok(PaymentAttempt {
+            id: row.get("id"),
+            payment_id: PaymentId(
+                row.get::<&str, &[u8]>("payment_id")
+                    .try_into()
+                    .context("bad payment ID")?,
+            ),
+            status: row.get("status"),
+            hops,
+            amount: row.get::<&str, i64>("amount") as MillisatAmount,
+            fee: row.get::<&str, i64>("fee") as MillisatAmount,
+            failed_short_channel_id: row
+                .get::<&str, Option<i64>>("failed_short_channel_id")
+                .map(|x| x as u64),
+            failed_permanently: row.get("failed_permanently"),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "payment_attempt_status")]
+pub enum PaymentAttemptStatus {
+    #[postgres(name = "succeeded")]
+    Succeeded,
+    #[postgres(name = "failed")]
+    Failed,
+}
+
+impl Display for PaymentAttemptStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaymentAttemptStatus::Succeeded => f.write_str("succeeded"),
+            PaymentAttemptStatus::Failed => f.write_str("failed"),
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use bitcoin::{hashes::Hash, Txid};
+use bitcoin::{consensus::encode, hashes::Hash, Transaction, Txid};
 use lightning::{sign::SpendableOutputDescriptor, util::ser::Writeable};
 use postgres_types::{FromSql, ToSql};
 use tokio_postgres::Row;
@@ -15,6 +15,16 @@ pub struct SpendableOutput {
     pub value: u64,
     pub descriptor: SpendableOutputDescriptor,
     pub status: SpendableOutputStatus,
+    // Block height at which the sweeper first saw the output.
+    pub first_seen_height: Option<u32>,
+    // The latest transaction that sweeps the output to our wallet.
+    pub sweep_tx: Option<Transaction>,
+    // IDs of every sweep transaction that was broadcast for the output, the latest one last.
+    pub sweep_txids: Vec<Txid>,
+    // Fee rate (sat per 1000 weight) of the latest sweep transaction.
+    pub sweep_feerate: Option<u32>,
+    // Block height at which the latest sweep transaction was broadcast.
+    pub sweep_height: Option<u32>,
 }
 
 impl SpendableOutput {
@@ -40,6 +50,11 @@ impl SpendableOutput {
             value,
             descriptor,
             status: SpendableOutputStatus::Unspent,
+            first_seen_height: None,
+            sweep_tx: None,
+            sweep_txids: vec![],
+            sweep_feerate: None,
+            sweep_height: None,
         }
     }
 
@@ -48,6 +63,10 @@ impl SpendableOutput {
         self.descriptor.write(&mut bytes)?;
         Ok(bytes)
     }
+
+    pub fn serialize_sweep_tx(&self) -> Option<Vec<u8>> {
+        self.sweep_tx.as_ref().map(encode::serialize)
+    }
 }
 
 impl TryFrom<Row> for SpendableOutput {
@@ -55,12 +74,30 @@ impl TryFrom<Row> for SpendableOutput {
 
     fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
         let bytes: &[u8] = row.get("txid");
+        let sweep_txids: Vec<&[u8]> = row.get("sweep_txids");
         Ok(SpendableOutput {
             txid: Txid::from_slice(bytes)?,
             vout: row.get::<&str, i64>("vout") as u16,
             value: row.get::<&str, i64>("value") as MillisatAmount,
             descriptor: row.read("descriptor")?,
             status: row.get("status"),
+            first_seen_height: row
+                .get::<&str, Option<i64>>("first_seen_height")
+                .map(|h| h as u32),
+            sweep_tx: row
+                .get::<&str, Option<&[u8]>>("sweep_tx")
+                .map(encode::deserialize)
+                .transpose()?,
+            sweep_txids: sweep_txids
+                .into_iter()
+                .map(Txid::from_slice)
+                .collect::<Result<Vec<Txid>, _>>()?,
+            sweep_feerate: row
+                .get::<&str, Option<i64>>("sweep_feerate")
+                .map(|f| f as u32),
+            sweep_height: row
+                .get::<&str, Option<i64>>("sweep_height")
+                .map(|h| h as u32),
         })
     }
 }
@@ -70,6 +107,9 @@ impl TryFrom<Row> for SpendableOutput {
 pub enum SpendableOutputStatus {
     #[postgres(name = "unspent")]
     Unspent,
+    // A sweep transaction was broadcast but is not deeply confirmed yet.
+    #[postgres(name = "sweeping")]
+    Sweeping,
     #[postgres(name = "spent")]
     Spent,
 }
@@ -0,0 +1,30 @@
+CREATE TYPE offer_kind AS ENUM ('offer', 'refund');
+
+CREATE TABLE offers (
+    id              BYTEA NOT NULL,
+    kind            offer_kind NOT NULL,
+    bolt12          VARCHAR NOT NULL,
+    description     VARCHAR NOT NULL,
+    label           VARCHAR,
+    amount          INT,
+    direction       payment_direction NOT NULL,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY ( id )
+);
+
+CREATE INDEX idx_offers_label ON offers (label);
+
+CREATE TYPE invoice_request_status AS ENUM ('pending', 'paid', 'failed');
+
+CREATE TABLE invoice_requests (
+    payment_id      BYTEA NOT NULL,
+    offer_id        BYTEA NOT NULL REFERENCES offers (id),
+    amount          INT,
+    quantity        INT,
+    payer_note      VARCHAR,
+    status          invoice_request_status NOT NULL,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY ( payment_id )
+);
+
+CREATE INDEX idx_invoice_requests_offer_id ON invoice_requests (offer_id);
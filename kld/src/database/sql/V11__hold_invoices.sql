@@ -0,0 +1,6 @@
+CREATE TYPE hold_invoice_status AS ENUM ('open', 'accepted', 'settled', 'cancelled');
+
+ALTER TABLE invoices ADD COLUMN hold_status hold_invoice_status;
+ALTER TABLE invoices ADD COLUMN claim_deadline INT;
+
+CREATE INDEX idx_invoices_hold_status ON invoices (hold_status);
@@ -0,0 +1,2 @@
+-- Number of paths that were tried for an outbound payment.
+ALTER TABLE payments ADD COLUMN attempts INT NOT NULL DEFAULT 0;
@@ -0,0 +1,17 @@
+CREATE TYPE payment_attempt_status AS ENUM ('succeeded', 'failed');
+
+CREATE TABLE payment_attempts (
+    id                       UUID NOT NULL,
+    payment_id               BYTEA NOT NULL,
+    status                   payment_attempt_status NOT NULL,
+    hop_node_ids             BYTEA[] NOT NULL,
+    hop_short_channel_ids    INT[] NOT NULL,
+    amount                   INT NOT NULL,
+    fee                      INT NOT NULL,
+    failed_short_channel_id  INT,
+    failed_permanently       BOOLEAN NOT NULL DEFAULT false,
+    timestamp                TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY ( id )
+);
+
+CREATE INDEX idx_payment_attempts_payment_id ON payment_attempts (payment_id);
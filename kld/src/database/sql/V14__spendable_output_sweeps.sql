@@ -0,0 +1,7 @@
+ALTER TYPE spendable_output_status ADD VALUE 'sweeping';
+
+ALTER TABLE spendable_outputs ADD COLUMN first_seen_height INT;
+ALTER TABLE spendable_outputs ADD COLUMN sweep_tx BYTEA;
+ALTER TABLE spendable_outputs ADD COLUMN sweep_txids BYTEA[] NOT NULL DEFAULT ARRAY[];
+ALTER TABLE spendable_outputs ADD COLUMN sweep_feerate INT;
+ALTER TABLE spendable_outputs ADD COLUMN sweep_height INT;
@@ -0,0 +1,5 @@
+-- Blobs without a key version are plaintext, kld encrypts them when it starts.
+ALTER TABLE channel_manager ADD COLUMN key_version INT;
+ALTER TABLE channel_monitors ADD COLUMN key_version INT;
+ALTER TABLE channel_monitor_updates ADD COLUMN key_version INT;
+ALTER TABLE scorer ADD COLUMN key_version INT;
@@ -0,0 +1,23 @@
+CREATE TYPE webhook_delivery_status AS ENUM ('pending', 'delivered', 'failed');
+
+CREATE TABLE webhooks (
+    id              UUID PRIMARY KEY,
+    url             STRING NOT NULL,
+    secret          BYTEA NOT NULL,
+    key_version     INT NOT NULL,
+    events          STRING[] NOT NULL,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE TABLE webhook_deliveries (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    webhook_id      UUID NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+    event           STRING NOT NULL,
+    payload         STRING NOT NULL,
+    status          webhook_delivery_status NOT NULL,
+    attempts        INT NOT NULL DEFAULT 0,
+    next_attempt    TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    last_error      STRING,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    INDEX (status, next_attempt)
+);
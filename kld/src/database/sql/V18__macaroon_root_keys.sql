@@ -0,0 +1,5 @@
+CREATE TABLE macaroon_root_keys (
+    id              UUID PRIMARY KEY,
+    name            STRING NOT NULL,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
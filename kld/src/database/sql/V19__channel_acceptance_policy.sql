@@ -0,0 +1,5 @@
+CREATE TABLE channel_acceptance_policy (
+    id                      BYTEA PRIMARY KEY,
+    policy                  STRING NOT NULL,
+    timestamp               TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
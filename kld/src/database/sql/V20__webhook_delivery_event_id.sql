@@ -0,0 +1,3 @@
+-- Replayed events are only delivered once to each webhook.
+ALTER TABLE webhook_deliveries ADD COLUMN event_id STRING;
+CREATE UNIQUE INDEX ON webhook_deliveries (event_id, webhook_id);
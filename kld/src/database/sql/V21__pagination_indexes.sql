@@ -0,0 +1,5 @@
+-- Pages are ordered by time and ID and start after the time and ID of the cursor.
+CREATE INDEX idx_channels_close_timestamp_id ON channels (close_timestamp, id);
+CREATE INDEX idx_invoices_timestamp_payment_hash ON invoices (timestamp, payment_hash);
+CREATE INDEX idx_payments_timestamp_id ON payments (timestamp, id);
+CREATE INDEX idx_forwards_timestamp_id ON forwards (timestamp, id);
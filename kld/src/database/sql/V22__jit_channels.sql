@@ -0,0 +1,20 @@
+CREATE TYPE jit_channel_state AS ENUM ('awaiting_payment', 'pending_channel_open', 'channel_ready', 'failed');
+
+CREATE TABLE jit_channels (
+    user_channel_id         INT PRIMARY KEY,
+    counterparty_node_id    BYTEA NOT NULL,
+    intercept_scid          INT NOT NULL,
+    payment_size_msat       INT,
+    opening_fee_msat        INT,
+    channel_id              BYTEA,
+    state                   jit_channel_state NOT NULL,
+    timestamp               TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+-- HTLCs that were intercepted for a just in time channel and wait for it to be ready.
+CREATE TABLE jit_channel_htlcs (
+    intercept_id            BYTEA PRIMARY KEY,
+    user_channel_id         INT NOT NULL REFERENCES jit_channels (user_channel_id) ON DELETE CASCADE,
+    timestamp               TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    INDEX (user_channel_id)
+);
@@ -0,0 +1,90 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use api::WebhookEvent;
+use postgres_types::{FromSql, ToSql};
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use super::{microsecond_timestamp, RowExt};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    // Key for the HMAC signature of the deliveries, it is stored encrypted.
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub timestamp: OffsetDateTime,
+}
+
+impl Webhook {
+    pub fn new(url: String, secret: String, events: Vec<WebhookEvent>) -> Webhook {
+        Webhook {
+            id: Uuid::new_v4(),
+            url,
+            secret,
+            events,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+
+    pub(crate) fn from_row(row: &Row, secret: String) -> Result<Webhook> {
+        Ok(Webhook {
+            id: row.get("id"),
+            url: row.get("url"),
+            secret,
+            events: parse_events(row.get("events"))?,
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
+
+pub(crate) fn parse_events(events: Vec<String>) -> Result<Vec<WebhookEvent>> {
+    events.iter().map(|e| WebhookEvent::from_str(e)).collect()
+}
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "webhook_delivery_status")]
+pub enum WebhookDeliveryStatus {
+    #[postgres(name = "pending")]
+    Pending,
+    #[postgres(name = "delivered")]
+    Delivered,
+    #[postgres(name = "failed")]
+    Failed,
+}
+
+/// One event to be posted to one webhook.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    // JSON body of the request.
+    pub payload: String,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt: OffsetDateTime,
+    pub last_error: Option<String>,
+    pub timestamp: OffsetDateTime,
+}
+
+impl TryFrom<&Row> for WebhookDelivery {
+    type Error = anyhow::Error;
+
+    fn try_from(row: &Row) -> std::result::Result<Self, Self::Error> {
+        Ok(WebhookDelivery {
+            id: row.get("id"),
+            webhook_id: row.get("webhook_id"),
+            event: WebhookEvent::from_str(row.get("event"))?,
+            payload: row.get("payload"),
+            status: row.get("status"),
+            attempts: row.get::<&str, i64>("attempts") as u32,
+            next_attempt: row.get_timestamp("next_attempt"),
+            last_error: row.get("last_error"),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
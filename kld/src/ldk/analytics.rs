@@ -0,0 +1,227 @@
+use std::collections::{BTreeMap, HashMap};
+
+use lightning::events::HTLCDestination;
+use lightning::ln::ChannelId;
+use lightning::routing::gossip::NodeId;
+
+use crate::database::forward::{ChannelForwardTotals, FailedForwards};
+use crate::MillisatAmount;
+
+/// Routing totals of a channel, a peer or the whole node over a time window.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ForwardCounts {
+    /// Succeeded forwards that came in through the channel.
+    pub succeeded_in: u64,
+    /// Succeeded forwards that went out through the channel.
+    pub succeeded_out: u64,
+    /// Amount (msat) that the channel received for the succeeded forwards.
+    pub volume_in: MillisatAmount,
+    /// Amount (msat) that the channel sent for the succeeded forwards.
+    pub volume_out: MillisatAmount,
+    /// Fees (msat) of the forwards out of the channel, as we charge for its outbound liquidity.
+    pub fees: MillisatAmount,
+    /// Failed forwards that came in through the channel by the reason that they failed.
+    pub failed_in: BTreeMap<String, u64>,
+    /// Failed forwards that the channel was the next hop of.
+    pub failed_out: u64,
+}
+
+impl ForwardCounts {
+    /// Fees earned per million msat that was forwarded out.
+    pub fn effective_fee_rate_ppm(&self) -> Option<u64> {
+        (self.volume_out > 0).then(|| self.fees * 1_000_000 / self.volume_out)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelForwardStats {
+    pub peer: Option<NodeId>,
+    pub counts: ForwardCounts,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ForwardStats {
+    pub total: ForwardCounts,
+    pub channels: HashMap<ChannelId, ChannelForwardStats>,
+    pub peers: HashMap<NodeId, ForwardCounts>,
+}
+
+impl ForwardStats {
+    /// Combine the totals that the database summed up for each channel. The channels are grouped
+    /// by their counterparty for the peer stats. Channels that are not in the counterparties are
+    /// only counted in the total and the channel stats.
+    pub fn new(
+        totals: &[ChannelForwardTotals],
+        failed: &[FailedForwards],
+        counterparties: &HashMap<ChannelId, NodeId>,
+    ) -> ForwardStats {
+        let mut stats = ForwardStats::default();
+        for channel in totals {
+            stats.count(channel.channel_id, counterparties, |counts| {
+                counts.succeeded_in += channel.succeeded_in;
+                counts.succeeded_out += channel.succeeded_out;
+                counts.volume_in += channel.volume_in;
+                counts.volume_out += channel.volume_out;
+                counts.fees += channel.fees;
+            });
+        }
+        // The destination is serialized by LDK, so the reason is only known after reading it.
+        for forwards in failed {
+            let reason = forwards
+                .htlc_destination
+                .as_ref()
+                .map(failure_reason)
+                .unwrap_or("unknown");
+            stats.count(forwards.inbound_channel_id, counterparties, |counts| {
+                *counts.failed_in.entry(reason.to_string()).or_default() += forwards.count;
+            });
+            if let Some(HTLCDestination::NextHopChannel { channel_id, .. }) =
+                forwards.htlc_destination
+            {
+                stats.count(channel_id, counterparties, |counts| {
+                    counts.failed_out += forwards.count;
+                });
+            }
+        }
+        stats
+    }
+
+    fn count(
+        &mut self,
+        channel_id: ChannelId,
+        counterparties: &HashMap<ChannelId, NodeId>,
+        add: impl Fn(&mut ForwardCounts),
+    ) {
+        let peer = counterparties.get(&channel_id).copied();
+        add(&mut self.total);
+        let channel = self
+            .channels
+            .entry(channel_id)
+            .or_insert_with(|| ChannelForwardStats {
+                peer,
+                counts: ForwardCounts::default(),
+            });
+        add(&mut channel.counts);
+        if let Some(peer) = peer {
+            add(self.peers.entry(peer).or_default());
+        }
+    }
+}
+
+fn failure_reason(destination: &HTLCDestination) -> &'static str {
+    match destination {
+        HTLCDestination::NextHopChannel { .. } => "next_hop_channel",
+        HTLCDestination::UnknownNextHop { .. } => "unknown_next_hop",
+        HTLCDestination::InvalidForward { .. } => "invalid_forward",
+        HTLCDestination::FailedPayment { .. } => "failed_payment",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use lightning::events::HTLCDestination;
+    use lightning::ln::{ChannelId, PaymentHash};
+    use lightning::routing::gossip::NodeId;
+
+    use crate::database::forward::{ChannelForwardTotals, FailedForwards};
+
+    use super::ForwardStats;
+
+    #[test]
+    fn test_forward_stats() {
+        let channel_a = ChannelId::from_bytes([1u8; 32]);
+        let channel_b = ChannelId::from_bytes([2u8; 32]);
+        let channel_c = ChannelId::from_bytes([3u8; 32]);
+        let node_id = |secret: u8| {
+            let secret_key = SecretKey::from_slice(&[secret; 32]).unwrap();
+            NodeId::from_pubkey(&PublicKey::from_secret_key(&Secp256k1::new(), &secret_key))
+        };
+        let peer_a = node_id(1);
+        let peer_b = node_id(2);
+        let counterparties = HashMap::from([
+            (channel_a, peer_a),
+            (channel_b, peer_b),
+            (channel_c, peer_b),
+        ]);
+
+        // Forwards a -> b of 1_000_000 (fee 100), a -> c of 3_000_000 (fee 300) and b -> a of
+        // 500_000 (fee 1).
+        let totals = vec![
+            ChannelForwardTotals {
+                channel_id: channel_a,
+                succeeded_in: 2,
+                succeeded_out: 1,
+                volume_in: 4_000_400,
+                volume_out: 500_000,
+                fees: 1,
+            },
+            ChannelForwardTotals {
+                channel_id: channel_b,
+                succeeded_in: 1,
+                succeeded_out: 1,
+                volume_in: 500_001,
+                volume_out: 1_000_000,
+                fees: 100,
+            },
+            ChannelForwardTotals {
+                channel_id: channel_c,
+                succeeded_in: 0,
+                succeeded_out: 1,
+                volume_in: 0,
+                volume_out: 3_000_000,
+                fees: 300,
+            },
+        ];
+        let failed = vec![
+            FailedForwards {
+                inbound_channel_id: channel_a,
+                htlc_destination: Some(HTLCDestination::NextHopChannel {
+                    node_id: None,
+                    channel_id: channel_b,
+                }),
+                count: 1,
+            },
+            FailedForwards {
+                inbound_channel_id: channel_a,
+                htlc_destination: Some(HTLCDestination::FailedPayment {
+                    payment_hash: PaymentHash([1u8; 32]),
+                }),
+                count: 1,
+            },
+        ];
+        let stats = ForwardStats::new(&totals, &failed, &counterparties);
+
+        assert_eq!(3, stats.total.succeeded_in);
+        assert_eq!(3, stats.total.succeeded_out);
+        assert_eq!(4_500_401, stats.total.volume_in);
+        assert_eq!(4_500_000, stats.total.volume_out);
+        assert_eq!(401, stats.total.fees);
+        assert_eq!(1, stats.total.failed_out);
+        assert_eq!(Some(&1), stats.total.failed_in.get("next_hop_channel"));
+        assert_eq!(Some(&1), stats.total.failed_in.get("failed_payment"));
+
+        let a = &stats.channels[&channel_a];
+        assert_eq!(Some(peer_a), a.peer);
+        assert_eq!(2, a.counts.succeeded_in);
+        assert_eq!(4_000_400, a.counts.volume_in);
+        assert_eq!(1, a.counts.succeeded_out);
+        assert_eq!(500_000, a.counts.volume_out);
+        assert_eq!(1, a.counts.fees);
+        assert_eq!(2, a.counts.failed_in.values().sum::<u64>());
+        assert_eq!(Some(2), a.counts.effective_fee_rate_ppm());
+
+        let b = &stats.channels[&channel_b];
+        assert_eq!(100, b.counts.fees);
+        assert_eq!(1, b.counts.failed_out);
+        assert_eq!(Some(100), b.counts.effective_fee_rate_ppm());
+
+        let peer_b_counts = &stats.peers[&peer_b];
+        assert_eq!(400, peer_b_counts.fees);
+        assert_eq!(4_000_000, peer_b_counts.volume_out);
+        assert_eq!(1, peer_b_counts.succeeded_in);
+        assert_eq!(Some(100), peer_b_counts.effective_fee_rate_ppm());
+    }
+}
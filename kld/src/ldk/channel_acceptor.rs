@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use api::{ChannelAcceptancePolicy, ChannelTypeFeature};
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::features::ChannelTypeFeatures;
+
+use crate::settings::Settings;
+
+/// Rules that an inbound channel request has to pass before we accept it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InboundChannelPolicy {
+    pub min_channel_sats: Option<u64>,
+    pub max_channel_sats: Option<u64>,
+    pub allow_list: HashSet<PublicKey>,
+    pub deny_list: HashSet<PublicKey>,
+    pub max_channels_per_peer: Option<usize>,
+    pub required_features: Vec<ChannelTypeFeature>,
+    pub reject_reason: String,
+}
+
+impl InboundChannelPolicy {
+    /// Check an inbound channel request against the policy.
+    /// Returns the rule that was broken if the channel should be rejected.
+    pub fn check(
+        &self,
+        counterparty_node_id: &PublicKey,
+        funding_satoshis: u64,
+        channel_type: &ChannelTypeFeatures,
+        existing_channels: usize,
+        has_anchor_reserve: bool,
+    ) -> Result<(), String> {
+        if self.deny_list.contains(counterparty_node_id) {
+            return Err("node is on the deny list".to_string());
+        }
+        if !self.allow_list.is_empty() && !self.allow_list.contains(counterparty_node_id) {
+            return Err("node is not on the allow list".to_string());
+        }
+        if let Some(min) = self.min_channel_sats {
+            if funding_satoshis < min {
+                return Err(format!(
+                    "channel size {funding_satoshis} is below the minimum of {min} sats"
+                ));
+            }
+        }
+        if let Some(max) = self.max_channel_sats {
+            if funding_satoshis > max {
+                return Err(format!(
+                    "channel size {funding_satoshis} is above the maximum of {max} sats"
+                ));
+            }
+        }
+        if let Some(max) = self.max_channels_per_peer {
+            if existing_channels >= max {
+                return Err(format!(
+                    "node already has {existing_channels} channels (maximum {max})"
+                ));
+            }
+        }
+        for feature in &self.required_features {
+            let supported = match feature {
+                ChannelTypeFeature::StaticRemoteKey => channel_type.supports_static_remote_key(),
+                ChannelTypeFeature::AnchorsZeroFeeHtlcTx => {
+                    channel_type.supports_anchors_zero_fee_htlc_tx()
+                }
+                ChannelTypeFeature::ScidPrivacy => channel_type.supports_scid_privacy(),
+                ChannelTypeFeature::ZeroConf => channel_type.supports_zero_conf(),
+            };
+            if !supported {
+                return Err(format!("channel type does not support {feature}"));
+            }
+        }
+        if channel_type.supports_anchors_zero_fee_htlc_tx() && !has_anchor_reserve {
+            return Err(
+                "not enough on chain funds to reserve for fee bumping an anchor channel"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl From<&Settings> for InboundChannelPolicy {
+    fn from(settings: &Settings) -> Self {
+        InboundChannelPolicy {
+            min_channel_sats: settings.inbound_channel_min_sats,
+            max_channel_sats: settings.inbound_channel_max_sats,
+            allow_list: settings
+                .inbound_channel_allow_list
+                .iter()
+                .cloned()
+                .collect(),
+            deny_list: settings.inbound_channel_deny_list.iter().cloned().collect(),
+            max_channels_per_peer: settings.inbound_channel_max_per_peer,
+            required_features: settings.inbound_channel_required_features.clone(),
+            reject_reason: settings.inbound_channel_reject_reason.clone(),
+        }
+    }
+}
+
+impl TryFrom<ChannelAcceptancePolicy> for InboundChannelPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(policy: ChannelAcceptancePolicy) -> Result<Self> {
+        if let (Some(min), Some(max)) = (policy.min_channel_sats, policy.max_channel_sats) {
+            if min > max {
+                bail!("minimum channel size {min} is larger than the maximum {max}");
+            }
+        }
+        let parse_node_ids = |ids: Vec<String>| {
+            ids.iter()
+                .map(|id| PublicKey::from_str(id))
+                .collect::<Result<HashSet<PublicKey>, _>>()
+        };
+        Ok(InboundChannelPolicy {
+            min_channel_sats: policy.min_channel_sats,
+            max_channel_sats: policy.max_channel_sats,
+            allow_list: parse_node_ids(policy.allow_list)?,
+            deny_list: parse_node_ids(policy.deny_list)?,
+            max_channels_per_peer: policy.max_channels_per_peer,
+            required_features: policy.required_features,
+            reject_reason: policy.reject_reason,
+        })
+    }
+}
+
+impl From<InboundChannelPolicy> for ChannelAcceptancePolicy {
+    fn from(policy: InboundChannelPolicy) -> Self {
+        ChannelAcceptancePolicy {
+            min_channel_sats: policy.min_channel_sats,
+            max_channel_sats: policy.max_channel_sats,
+            allow_list: policy.allow_list.iter().map(|p| p.to_string()).collect(),
+            deny_list: policy.deny_list.iter().map(|p| p.to_string()).collect(),
+            max_channels_per_peer: policy.max_channels_per_peer,
+            required_features: policy.required_features,
+            reject_reason: policy.reject_reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use api::{ChannelAcceptancePolicy, ChannelTypeFeature};
+    use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use lightning::ln::features::ChannelTypeFeatures;
+
+    use super::InboundChannelPolicy;
+
+    #[test]
+    fn test_inbound_channel_policy() {
+        let secp = Secp256k1::new();
+        let node = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[1u8; 32]).unwrap());
+        let other_node =
+            PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[2u8; 32]).unwrap());
+        let mut features = ChannelTypeFeatures::empty();
+        features.set_static_remote_key_required();
+        let mut policy = InboundChannelPolicy {
+            min_channel_sats: Some(20000),
+            max_channel_sats: Some(1000000),
+            max_channels_per_peer: Some(2),
+            ..Default::default()
+        };
+        assert!(policy.check(&node, 50000, &features, 0, true).is_ok());
+        assert!(policy.check(&node, 10000, &features, 0, true).is_err());
+        assert!(policy.check(&node, 2000000, &features, 0, true).is_err());
+        assert!(policy.check(&node, 50000, &features, 2, true).is_err());
+
+        policy.required_features = vec![ChannelTypeFeature::AnchorsZeroFeeHtlcTx];
+        assert!(policy.check(&node, 50000, &features, 0, true).is_err());
+        policy.required_features = vec![ChannelTypeFeature::StaticRemoteKey];
+        assert!(policy.check(&node, 50000, &features, 0, true).is_ok());
+
+        policy.deny_list.insert(node);
+        assert!(policy.check(&node, 50000, &features, 0, true).is_err());
+        policy.deny_list.clear();
+        policy.allow_list.insert(other_node);
+        assert!(policy.check(&node, 50000, &features, 0, true).is_err());
+        policy.allow_list.insert(node);
+        assert!(policy.check(&node, 50000, &features, 0, true).is_ok());
+
+        // Anchor channels need on chain funds to bump the commitment fees.
+        assert!(policy.check(&node, 50000, &features, 0, false).is_ok());
+        features.set_anchors_zero_fee_htlc_tx_required();
+        assert!(policy.check(&node, 50000, &features, 0, true).is_ok());
+        assert!(policy.check(&node, 50000, &features, 0, false).is_err());
+    }
+
+    #[test]
+    fn test_channel_acceptance_policy_conversion() {
+        let secp = Secp256k1::new();
+        let node = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[1u8; 32]).unwrap());
+        let policy = InboundChannelPolicy {
+            min_channel_sats: Some(20000),
+            deny_list: [node].into_iter().collect(),
+            required_features: vec![ChannelTypeFeature::StaticRemoteKey],
+            reject_reason: "no thanks".to_string(),
+            ..Default::default()
+        };
+        let api_policy = ChannelAcceptancePolicy::from(policy.clone());
+        assert_eq!(policy, InboundChannelPolicy::try_from(api_policy).unwrap());
+
+        let invalid = ChannelAcceptancePolicy {
+            min_channel_sats: Some(2),
+            max_channel_sats: Some(1),
+            ..Default::default()
+        };
+        assert!(InboundChannelPolicy::try_from(invalid).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{Script, WPubkeyHash};
+use hex::FromHex;
+use lightning::chain::channelmonitor::ChannelMonitor;
+use lightning::ln::chan_utils::get_to_countersignatory_with_anchors_redeemscript;
+use lightning::ln::ChannelId;
+use lightning::sign::{ChannelSigner, InMemorySigner, KeysManager};
+use lightning::util::ser::Writeable;
+use serde::{Deserialize, Serialize};
+
+use crate::api::SocketAddress;
+use crate::database::cipher::{Cipher, KEY_VERSION};
+
+/// The channel peers and funding outpoints of the node. It contains no channel state. After the
+/// database was lost the node starts in recovery mode with it, connects to the peers and sends them
+/// an error for each channel so that they force close it. The channel keys ID is kept to derive the
+/// keys of the outputs that pay us on their commitment transactions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaticChannelBackup {
+    // Our node ID, the backup can only be restored by the same node.
+    pub node_id: String,
+    // Seconds since epoch when the backup was created.
+    pub timestamp: u64,
+    pub channels: Vec<BackupChannel>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupChannel {
+    pub channel_id: String,
+    pub counterparty: String,
+    // Last known address of the counterparty.
+    pub address: Option<String>,
+    // Funding outpoint as txid:index.
+    pub funding_txo: Option<String>,
+    pub value_sats: u64,
+    // Hex encoded derivation parameters of the channel keys in the KeysManager, as used in
+    // the SpendableOutputDescriptors of the channel. Unknown until the channel is funded.
+    pub channel_keys_id: Option<String>,
+}
+
+impl BackupChannel {
+    /// The keys of the channel that sign for the outputs that pay us when the peer force closes.
+    pub fn channel_keys(&self, keys_manager: &KeysManager) -> Result<Option<InMemorySigner>> {
+        let Some(channel_keys_id) = &self.channel_keys_id else {
+            return Ok(None);
+        };
+        let channel_keys_id =
+            <[u8; 32]>::from_hex(channel_keys_id).context("Invalid channel keys ID in backup")?;
+        Ok(Some(
+            keys_manager.derive_channel_keys(self.value_sats, &channel_keys_id),
+        ))
+    }
+}
+
+impl StaticChannelBackup {
+    /// Serialize and encrypt the backup with the node's database key.
+    pub fn encrypt(&self, cipher: &Cipher) -> Result<Vec<u8>> {
+        cipher.encrypt(&serde_json::to_vec(self)?)
+    }
+
+    pub fn decrypt(cipher: &Cipher, blob: &[u8]) -> Result<StaticChannelBackup> {
+        let plaintext = cipher
+            .decrypt(Some(KEY_VERSION), blob)
+            .context("Channel backup was not created by this node")?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Read a hex encoded backup as written by kld-cli and check that it belongs to this node.
+    pub fn read(cipher: &Cipher, path: &str, node_id: &PublicKey) -> Result<StaticChannelBackup> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read channel backup {path}"))?;
+        let blob = hex::decode(contents.trim()).context("Channel backup is not hex encoded")?;
+        let backup = StaticChannelBackup::decrypt(cipher, &blob)?;
+        if backup.node_id != node_id.to_string() {
+            bail!("Channel backup belongs to node {}", backup.node_id);
+        }
+        Ok(backup)
+    }
+
+    /// The peers that we need to connect to for them to close our channels.
+    pub fn peers(&self) -> Result<Vec<(PublicKey, Option<SocketAddress>)>> {
+        let mut peers: Vec<(PublicKey, Option<SocketAddress>)> = vec![];
+        for channel in &self.channels {
+            let public_key = PublicKey::from_str(&channel.counterparty)?;
+            if peers.iter().any(|(pk, _)| *pk == public_key) {
+                continue;
+            }
+            let address = channel
+                .address
+                .as_deref()
+                .map(SocketAddress::from_str)
+                .transpose()?;
+            peers.push((public_key, address));
+        }
+        Ok(peers)
+    }
+
+    /// The channels that we have with the peer, for the peer to force close them.
+    pub fn channel_ids(&self, counterparty: &PublicKey) -> Result<Vec<ChannelId>> {
+        let counterparty = counterparty.to_string();
+        let mut channel_ids = vec![];
+        for channel in &self.channels {
+            if channel.counterparty != counterparty {
+                continue;
+            }
+            let channel_id = <[u8; 32]>::from_hex(&channel.channel_id)
+                .with_context(|| format!("Invalid channel ID {}", channel.channel_id))?;
+            channel_ids.push(ChannelId::from_bytes(channel_id));
+        }
+        Ok(channel_ids)
+    }
+}
+
+/// LDK has no getter for the channel keys ID of a channel. It is serialized near the start of the
+/// channel monitor, after a few scripts, so read it from there and check that the keys derived from
+/// it pay to the script that the monitor expects on the commitment transactions of the peer.
+pub fn channel_keys_id(
+    monitor: &ChannelMonitor<InMemorySigner>,
+    keys_manager: &KeysManager,
+    value_sats: u64,
+) -> Result<[u8; 32]> {
+    let bytes = monitor.encode();
+    let mut reader = MonitorReader {
+        bytes: &bytes,
+        pos: 0,
+    };
+    // Version prefix, latest update ID and commitment number obscure factor.
+    reader.skip(2 + 8 + 6)?;
+    // Destination script.
+    reader.script()?;
+    // Revokable script of a broadcasted holder commitment.
+    if reader.take(1)? == [0] {
+        reader.skip(33 + 2)?;
+        reader.script()?;
+    }
+    let counterparty_payment_script = reader.script()?;
+    // Shutdown script.
+    reader.script()?;
+    let channel_keys_id: [u8; 32] = reader.take(32)?.try_into()?;
+
+    let payment_point = keys_manager
+        .derive_channel_keys(value_sats, &channel_keys_id)
+        .pubkeys()
+        .payment_point;
+    let static_remote_key_script =
+        Script::new_v0_p2wpkh(&WPubkeyHash::hash(&payment_point.serialize()));
+    let anchors_script =
+        get_to_countersignatory_with_anchors_redeemscript(&payment_point).to_v0_p2wsh();
+    if counterparty_payment_script != static_remote_key_script
+        && counterparty_payment_script != anchors_script
+    {
+        bail!("Channel keys ID does not match the channel monitor");
+    }
+    Ok(channel_keys_id)
+}
+
+struct MonitorReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> MonitorReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .context("Unexpected end of channel monitor")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.take(len).map(|_| ())
+    }
+
+    // Scripts are serialized with a u16 length prefix.
+    fn script(&mut self) -> Result<Script> {
+        let len = u16::from_be_bytes(self.take(2)?.try_into()?) as usize;
+        Ok(Script::from(self.take(len)?.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::secp256k1::PublicKey;
+    use lightning::ln::ChannelId;
+    use lightning::sign::{ChannelSigner, KeysManager};
+    use test_utils::TempDir;
+
+    use crate::database::cipher::Cipher;
+
+    use super::{BackupChannel, StaticChannelBackup};
+
+    const NODE_ID: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    const PEER_ID: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    fn backup() -> StaticChannelBackup {
+        let channel = BackupChannel {
+            channel_id: "ab".repeat(32),
+            counterparty: PEER_ID.to_string(),
+            address: Some("127.0.0.1:9735".to_string()),
+            funding_txo: Some(format!("{}:1", "cd".repeat(32))),
+            value_sats: 1_000_000,
+            channel_keys_id: Some("01".repeat(32)),
+        };
+        StaticChannelBackup {
+            node_id: NODE_ID.to_string(),
+            timestamp: 1_700_000_000,
+            channels: vec![
+                channel.clone(),
+                BackupChannel {
+                    channel_id: "ef".repeat(32),
+                    ..channel
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_static_channel_backup() {
+        let cipher = Cipher::new(&[1; 32]);
+        let backup = backup();
+
+        let blob = backup.encrypt(&cipher).unwrap();
+        assert_eq!(
+            backup,
+            StaticChannelBackup::decrypt(&cipher, &blob).unwrap()
+        );
+        assert!(StaticChannelBackup::decrypt(&Cipher::new(&[2; 32]), &blob).is_err());
+
+        let peers = backup.peers().unwrap();
+        assert_eq!(1, peers.len());
+        assert_eq!(PublicKey::from_str(PEER_ID).unwrap(), peers[0].0);
+        assert_eq!(
+            Some("127.0.0.1:9735".to_string()),
+            peers[0].1.as_ref().map(|a| a.to_string())
+        );
+        assert_eq!(
+            vec![
+                ChannelId::from_bytes([0xab; 32]),
+                ChannelId::from_bytes([0xef; 32])
+            ],
+            backup.channel_ids(&peers[0].0).unwrap()
+        );
+        assert!(backup
+            .channel_ids(&PublicKey::from_str(NODE_ID).unwrap())
+            .unwrap()
+            .is_empty());
+
+        let keys_manager = KeysManager::new(&[3; 32], 0, 0);
+        let channel_keys = backup.channels[0]
+            .channel_keys(&keys_manager)
+            .unwrap()
+            .expect("expected channel keys");
+        assert_eq!([1; 32], channel_keys.channel_keys_id());
+        assert_eq!(
+            keys_manager
+                .derive_channel_keys(1_000_000, &[1; 32])
+                .pubkeys(),
+            channel_keys.pubkeys()
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("backup");
+        std::fs::write(&path, hex::encode(&blob)).unwrap();
+        let path = path.to_str().unwrap();
+        let node_id = PublicKey::from_str(NODE_ID).unwrap();
+        assert_eq!(
+            backup,
+            StaticChannelBackup::read(&cipher, path, &node_id).unwrap()
+        );
+        let other_node = PublicKey::from_str(PEER_ID).unwrap();
+        assert!(StaticChannelBackup::read(&cipher, path, &other_node).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use lightning::ln::channelmanager::ChannelDetails;
+
 /// Maximum transaction index that can be used in a `short_channel_id`.
 /// This value is based on the 3-bytes available for tx index.
 pub const MAX_SCID_TX_INDEX: u64 = 0x00ffffff;
@@ -6,6 +8,18 @@ pub const MAX_SCID_TX_INDEX: u64 = 0x00ffffff;
 /// value is based on the 2-bytes available for the vout index.
 pub const MAX_SCID_VOUT_INDEX: u64 = 0xffff;
 
+/// Counts the channels that use anchor outputs and may need fee bumping from the on chain wallet.
+pub fn anchor_channel_count(channels: &[ChannelDetails]) -> usize {
+    channels
+        .iter()
+        .filter(|c| {
+            c.channel_type
+                .as_ref()
+                .is_some_and(|t| t.supports_anchors_zero_fee_htlc_tx())
+        })
+        .count()
+}
+
 /// Extracts the block height (most significant 3-bytes) from the `short_channel_id`
 pub fn block_from_scid(short_channel_id: &u64) -> u32 {
     (short_channel_id >> 40) as u32
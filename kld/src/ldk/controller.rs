@@ -1,38 +1,50 @@
 use crate::bitcoind::bitcoind_interface::BitcoindInterface;
 use crate::bitcoind::{BitcoindClient, BitcoindUtxoLookup};
-use crate::database::channel::Channel;
-use crate::database::forward::{Forward, ForwardStatus, TotalForwards};
-use crate::database::invoice::Invoice;
-use crate::database::payment::{Payment, PaymentDirection};
+use crate::database::channel::{Channel, ChannelFilter};
+use crate::database::cipher::Cipher;
+use crate::database::forward::{Forward, ForwardFilter, TotalForwards};
+use crate::database::invoice::{HoldInvoiceStatus, Invoice, InvoiceFilter};
+use crate::database::jit_channel::JitChannel;
+use crate::database::macaroon_root_key::MacaroonRootKey;
+use crate::database::offer::{InvoiceRequest, InvoiceRequestStatus, Offer};
+use crate::database::payment::{Payment, PaymentDirection, PaymentFilter, PaymentStatus};
+use crate::database::payment_attempt::PaymentAttempt;
+use crate::database::peer::Peer as DatabasePeer;
+use crate::database::webhook::Webhook;
 use crate::wallet::{Wallet, WalletInterface};
 use crate::{log_error, MillisatAmount, Service};
 
 use crate::api::SocketAddress;
-use crate::database::{DurableConnection, LdkDatabase, WalletDatabase};
+use crate::database::{DurableConnection, LdkDatabase, Page, WalletDatabase};
 use anyhow::{anyhow, bail, Context, Result};
-use api::FeeRate;
+use api::{FeeRate, JitChannelOffer, NodeEvent};
 use async_trait::async_trait;
 use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::{BlockHash, Network, Transaction};
 use lightning::chain;
 use lightning::chain::channelmonitor::ChannelMonitor;
 use lightning::chain::BestBlock;
 use lightning::chain::Watch;
+use lightning::events::{bump_transaction, PaymentFailureReason};
 use lightning::ln::channelmanager::{
-    self, ChannelDetails, PaymentId, PaymentSendFailure, RecipientOnionFields,
+    self, ChannelDetails, PaymentId, RecentPaymentDetails, RecipientOnionFields,
 };
 use lightning::ln::channelmanager::{ChainParameters, ChannelManagerReadArgs};
 use lightning::ln::peer_handler::{IgnoringMessageHandler, MessageHandler};
-use lightning::ln::ChannelId;
+use lightning::ln::{ChannelId, PaymentHash, PaymentPreimage};
+use lightning::offers::offer::Offer as Bolt12Offer;
+use lightning::offers::refund::Refund;
 use lightning::routing::gossip::{ChannelInfo, NodeId, NodeInfo, P2PGossipSync};
-use lightning::routing::router::{DefaultRouter, PaymentParameters, RouteParameters, Router};
+use lightning::routing::router::{
+    DefaultRouter, PaymentParameters, RouteHint, RouteParameters, Router,
+};
 use lightning::routing::scoring::{
     ProbabilisticScorer, ProbabilisticScoringDecayParameters, ProbabilisticScoringFeeParameters,
 };
 use lightning::sign::{InMemorySigner, KeysManager};
 use lightning::util::config::UserConfig;
-use lightning::util::errors::APIError;
 
 use crate::ldk::peer_manager::KuutamoPeerManger;
 use crate::logger::KldLogger;
@@ -44,23 +56,38 @@ use lightning_block_sync::poll;
 use lightning_block_sync::SpvClient;
 use lightning_block_sync::UnboundedCache;
 use lightning_block_sync::{init, BlockSourceResult};
+use lightning_invoice::utils::create_invoice_from_channelmanager_and_duration_since_epoch_with_payment_hash;
 use lightning_invoice::DEFAULT_EXPIRY_TIME;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use rand::random;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 use futures::{future::Shared, Future};
+use tokio::sync::broadcast;
 use tokio::sync::oneshot::{self, Receiver, Sender};
 use tokio::sync::RwLock;
+use uuid::Uuid;
 
+use super::analytics::ForwardStats;
+use super::channel_acceptor::InboundChannelPolicy;
+use super::channel_backup::{channel_keys_id, BackupChannel, StaticChannelBackup};
+use super::channel_utils::anchor_channel_count;
 use super::event_handler::EventHandler;
+use super::jit_channels::JitChannels;
+use super::message_handler::KldMessageHandler;
+use super::payment_limits::PaymentLimits;
 use super::peer_manager::PeerManager;
+use super::prober::Prober;
+use super::rapid_gossip_sync::RapidGossipSyncer;
+use super::sweeper::Sweeper;
+use super::webhooks::WebhookDispatcher;
 use super::{
-    ldk_error, lightning_error, payment_send_failure, retryable_send_failure,
-    sign_or_creation_error, ChainMonitor, ChannelManager, KldRouter, LightningInterface,
-    LiquidityManager, NetworkGraph, OnionMessenger, OpenChannelResult, Peer, PeerStatus, Scorer,
+    bolt12_semantic_error, ldk_error, lightning_error, payment_send_failure,
+    retryable_send_failure, sign_or_creation_error, BumpTransactionEventHandler, ChainMonitor,
+    ChannelManager, KldRouter, LightningInterface, LiquidityManager, NetworkGraph, OnionMessenger,
+    OpenChannelResult, Peer, PeerStatus, ProbeResult, Scorer,
 };
 
 #[async_trait]
@@ -349,6 +376,10 @@ impl LightningInterface for Controller {
         *self.channel_manager.get_current_default_configuration()
     }
 
+    fn max_fee_msat(&self, limits: &PaymentLimits, amount: MillisatAmount) -> MillisatAmount {
+        limits.max_fee_msat(&self.settings, amount)
+    }
+
     async fn generate_invoice(
         &self,
         label: String,
@@ -376,26 +407,101 @@ impl LightningInterface for Controller {
         Ok(invoice)
     }
 
-    async fn list_invoices(&self, label: Option<String>) -> Result<Vec<Invoice>> {
-        self.database.fetch_invoices(label).await
+    async fn generate_hold_invoice(
+        &self,
+        label: String,
+        amount: u64,
+        description: String,
+        expiry: Option<u32>,
+        payment_hash: PaymentHash,
+    ) -> Result<Invoice> {
+        let bolt11 = create_invoice_from_channelmanager_and_duration_since_epoch_with_payment_hash(
+            &self.channel_manager,
+            self.keys_manager.clone(),
+            KldLogger::global(),
+            self.network().into(),
+            Some(amount),
+            description,
+            SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?,
+            expiry.unwrap_or(DEFAULT_EXPIRY_TIME as u32),
+            payment_hash,
+            None,
+        )
+        .map_err(sign_or_creation_error)?;
+        let invoice = Invoice::hold(Some(label), bolt11)?;
+        info!(
+            "Generated hold invoice with payment hash {}",
+            invoice.payment_hash.0.to_hex()
+        );
+        self.database.persist_invoice(&invoice).await?;
+        Ok(invoice)
+    }
+
+    async fn settle_hold_invoice(&self, preimage: PaymentPreimage) -> Result<()> {
+        let payment_hash = PaymentHash(sha256::Hash::hash(&preimage.0).into_inner());
+        let mut invoice = self.hold_invoice(&payment_hash).await?;
+        if invoice.hold_status != Some(HoldInvoiceStatus::Accepted) {
+            bail!("Hold invoice has no payment to settle");
+        }
+        let height = self.channel_manager.current_best_block().height();
+        if invoice.claim_deadline_reached(height) {
+            // LDK has failed the HTLCs back already, claiming them would do nothing.
+            self.channel_manager.fail_htlc_backwards(&payment_hash);
+            invoice.hold_status = Some(HoldInvoiceStatus::Cancelled);
+            self.database.persist_invoice(&invoice).await?;
+            bail!("Hold invoice has reached its claim deadline");
+        }
+        self.channel_manager.claim_funds(preimage);
+        info!(
+            "Settled hold invoice with payment hash {}",
+            payment_hash.0.to_hex()
+        );
+        invoice.hold_status = Some(HoldInvoiceStatus::Settled);
+        self.database.persist_invoice(&invoice).await
+    }
+
+    async fn cancel_hold_invoice(&self, payment_hash: PaymentHash) -> Result<()> {
+        let mut invoice = self.hold_invoice(&payment_hash).await?;
+        if invoice.hold_status == Some(HoldInvoiceStatus::Settled) {
+            bail!("Hold invoice is already settled");
+        }
+        self.channel_manager.fail_htlc_backwards(&payment_hash);
+        info!(
+            "Cancelled hold invoice with payment hash {}",
+            payment_hash.0.to_hex()
+        );
+        invoice.hold_status = Some(HoldInvoiceStatus::Cancelled);
+        self.database.persist_invoice(&invoice).await
     }
 
-    async fn pay_invoice(&self, invoice: Invoice, label: Option<String>) -> Result<Payment> {
+    async fn list_invoices(&self, filter: InvoiceFilter) -> Result<Vec<Invoice>> {
+        self.database.fetch_invoices(&filter).await
+    }
+
+    async fn pay_invoice(
+        &self,
+        invoice: Invoice,
+        label: Option<String>,
+        limits: PaymentLimits,
+        wait: bool,
+    ) -> Result<Payment> {
         let payment = Payment::of_invoice_outbound(&invoice, label);
 
-        let route_params = RouteParameters {
-            payment_params: PaymentParameters::from_node_id(invoice.payee_pub_key, 40),
-            final_value_msat: invoice.amount.context("amount missing from invoice")?,
-            // TODO: configurable, when opening a channel or starting kld
-            max_total_routing_fee_msat: None,
-        };
+        let route_params = limits.route_parameters(
+            &self.settings,
+            PaymentParameters::from_node_id(
+                invoice.payee_pub_key,
+                invoice.bolt11.min_final_cltv_expiry_delta() as u32,
+            ),
+            invoice.amount.context("amount missing from invoice")?,
+        );
         self.channel_manager
             .send_payment(
                 payment.hash.context("expected payment hash")?,
                 RecipientOnionFields::secret_only(*invoice.bolt11.payment_secret()),
                 payment.id,
                 route_params,
-                channelmanager::Retry::Timeout(Duration::from_secs(60)),
+                limits.retry(&self.settings),
             )
             .map_err(retryable_send_failure)?;
         info!(
@@ -404,81 +510,273 @@ impl LightningInterface for Controller {
         );
         self.database.persist_invoice(&invoice).await?;
         self.database.persist_payment(&payment).await?;
-        let receiver = self
-            .async_api_requests
-            .payments
-            .insert(payment.id, payment)
-            .await;
-        let payment = receiver.await??;
-        self.database.persist_payment(&payment).await?;
-        Ok(payment)
+        self.payment_result(payment, wait).await
     }
 
-    async fn keysend_payment(&self, payee: NodeId, amount: MillisatAmount) -> Result<Payment> {
+    async fn keysend_payment(
+        &self,
+        payee: NodeId,
+        amount: MillisatAmount,
+        limits: PaymentLimits,
+        wait: bool,
+    ) -> Result<Payment> {
         let payment_id = Payment::new_id();
+        let route_params = limits.route_parameters(
+            &self.settings,
+            PaymentParameters::for_keysend(payee.as_pubkey()?, 40, false),
+            amount,
+        );
+        let payment_hash = self
+            .channel_manager
+            .send_spontaneous_payment_with_retry(
+                None,
+                RecipientOnionFields::spontaneous_empty(),
+                payment_id,
+                route_params,
+                limits.retry(&self.settings),
+            )
+            .map_err(retryable_send_failure)?;
+        let mut payment = Payment::spontaneous_outbound(payment_id, amount);
+        payment.hash = Some(payment_hash);
+        info!(
+            "Initiated keysend payment with id {}",
+            payment_id.0.to_hex()
+        );
+        self.database.persist_payment(&payment).await?;
+        self.payment_result(payment, wait).await
+    }
+
+    async fn probe(
+        &self,
+        payee: PublicKey,
+        amount: MillisatAmount,
+        route_hints: Vec<RouteHint>,
+    ) -> Result<ProbeResult> {
         let inflight_htlcs = self.channel_manager.compute_inflight_htlcs();
         let route_params = RouteParameters {
-            payment_params: PaymentParameters::for_keysend(payee.as_pubkey()?, 40, false),
+            payment_params: PaymentParameters::from_node_id(payee, 40)
+                .with_route_hints(route_hints)
+                .map_err(|_| anyhow!("Invalid route hints"))?,
             final_value_msat: amount,
-            // TODO: configurable, when opening a channel or starting kld
             max_total_routing_fee_msat: None,
         };
         let route = self
             .router
             .find_route(&self.identity_pubkey(), &route_params, None, inflight_htlcs)
             .map_err(lightning_error)?;
-        match self.channel_manager.send_spontaneous_payment(
-            &route,
-            None,
-            RecipientOnionFields::spontaneous_empty(),
-            payment_id,
-        ) {
-            Ok(_hash) => (),
-            Err(e) => {
-                match &e {
-                    PaymentSendFailure::PartialFailure {
-                        results,
-                        failed_paths_retry: _,
-                        payment_id: _,
-                    } => {
-                        // Monitor updates are persisted async so continue if MonitorUpdateInProgress is the only "error" we get.
-                        if !results.iter().all(|result| {
-                            result.is_ok()
-                                || result
-                                    .as_ref()
-                                    .is_err_and(|f| matches!(f, APIError::MonitorUpdateInProgress))
-                        }) {
-                            return Err(payment_send_failure(e));
-                        }
+        let mut probe_ids = vec![];
+        let mut receivers = vec![];
+        for path in route.paths {
+            let (_hash, probe_id) = self
+                .channel_manager
+                .send_probe(path)
+                .map_err(payment_send_failure)?;
+            info!("Sent probe with id {} to {payee}", probe_id.0.to_hex());
+            receivers.push(self.async_api_requests.probes.insert(probe_id, ()).await);
+            probe_ids.push(probe_id);
+        }
+        let paths = receivers.len();
+        let result: Result<ProbeResult> = async {
+            for receiver in receivers {
+                let result = tokio::time::timeout(Duration::from_secs(60), receiver)
+                    .await
+                    .context("Timed out waiting for probe result")??;
+                if let ProbeResult::Failed {
+                    short_channel_id,
+                    node_id,
+                    ..
+                } = result
+                {
+                    return Ok(ProbeResult::Failed {
+                        paths,
+                        short_channel_id,
+                        node_id,
+                    });
+                }
+            }
+            Ok(ProbeResult::Succeeded { paths })
+        }
+        .await;
+        // Nobody waits for the probes that timed out or that are left after a failed one.
+        for probe_id in probe_ids {
+            self.async_api_requests.probes.remove(&probe_id).await;
+        }
+        result
+    }
+
+    async fn list_payments(&self, filter: PaymentFilter) -> Result<Vec<Payment>> {
+        self.database.fetch_payments(&filter).await
+    }
+
+    async fn get_payment(&self, id: PaymentId) -> Result<Option<Payment>> {
+        self.database.fetch_payment(&id).await
+    }
+
+    async fn get_payment_attempts(&self, id: PaymentId) -> Result<Vec<PaymentAttempt>> {
+        self.database.fetch_payment_attempts(&id).await
+    }
+
+    async fn create_offer(
+        &self,
+        description: String,
+        amount: Option<MillisatAmount>,
+        expiry: Option<Duration>,
+        issuer: Option<String>,
+        label: Option<String>,
+    ) -> Result<Offer> {
+        let mut builder = self.channel_manager.create_offer_builder(description);
+        if let Some(amount) = amount {
+            builder = builder.amount_msats(amount);
+        }
+        if let Some(expiry) = expiry {
+            builder = builder.absolute_expiry(
+                SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)? + expiry,
+            );
+        }
+        if let Some(issuer) = issuer {
+            builder = builder.issuer(issuer);
+        }
+        let bolt12 = builder.build().map_err(bolt12_semantic_error)?;
+        let offer = Offer::of_offer(&bolt12, label, PaymentDirection::Inbound);
+        info!("Created offer {}", offer.id.to_hex());
+        self.database.persist_offer(&offer).await?;
+        Ok(offer)
+    }
+
+    async fn create_refund(
+        &self,
+        description: String,
+        amount: MillisatAmount,
+        expiry: Option<Duration>,
+        label: Option<String>,
+    ) -> Result<Offer> {
+        let payment = Payment::of_offer_outbound(Payment::new_id(), amount, label.clone());
+        let limits = PaymentLimits::default();
+        let absolute_expiry = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?
+            + expiry.unwrap_or(Duration::from_secs(DEFAULT_EXPIRY_TIME));
+        let refund = self
+            .channel_manager
+            .create_refund_builder(
+                description,
+                amount,
+                absolute_expiry,
+                payment.id,
+                limits.retry(&self.settings),
+                Some(limits.max_fee_msat(&self.settings, amount)),
+            )
+            .map_err(bolt12_semantic_error)?
+            .build()
+            .map_err(bolt12_semantic_error)?;
+        let offer = Offer::of_refund(&refund, label, PaymentDirection::Outbound);
+        info!(
+            "Created refund {} with payment ID {}",
+            offer.id.to_hex(),
+            payment.id.0.to_hex()
+        );
+        self.database.persist_offer(&offer).await?;
+        self.database.persist_payment(&payment).await?;
+        // The refund is paid when the recipient sends us an invoice, which may take a while.
+        let receiver = self
+            .async_api_requests
+            .payments
+            .insert(payment.id, payment)
+            .await;
+        let database = self.database.clone();
+        tokio::spawn(async move {
+            match receiver.await {
+                Ok(Ok(payment)) => {
+                    if let Err(e) = database.persist_payment(&payment).await {
+                        log_error(&e);
                     }
-                    _ => return Err(payment_send_failure(e)),
-                };
+                }
+                Ok(Err(e)) => log_error(&e),
+                Err(e) => warn!("Refund payment dropped: {e}"),
             }
-        };
-        let payment = Payment::spontaneous_outbound(payment_id, amount);
+        });
+        Ok(offer)
+    }
+
+    async fn list_offers(&self, label: Option<String>) -> Result<Vec<Offer>> {
+        self.database.fetch_offers(label).await
+    }
+
+    async fn pay_offer(
+        &self,
+        offer: Bolt12Offer,
+        amount: Option<MillisatAmount>,
+        quantity: Option<u64>,
+        payer_note: Option<String>,
+        label: Option<String>,
+    ) -> Result<Payment> {
+        let stored_offer = Offer::of_offer(&offer, label.clone(), PaymentDirection::Outbound);
+        let amount_msat = amount
+            .or(stored_offer.amount.map(|a| a * quantity.unwrap_or(1)))
+            .context("amount is required for an offer without an amount")?;
+        let payment = Payment::of_offer_outbound(Payment::new_id(), amount_msat, label);
+        let limits = PaymentLimits::default();
+        self.channel_manager
+            .pay_for_offer(
+                &offer,
+                quantity,
+                amount,
+                payer_note.clone(),
+                payment.id,
+                limits.retry(&self.settings),
+                Some(limits.max_fee_msat(&self.settings, amount_msat)),
+            )
+            .map_err(bolt12_semantic_error)?;
         info!(
-            "Initiated keysend payment with id {}",
-            payment_id.0.to_hex()
+            "Requested invoice for offer {} with payment ID {}",
+            stored_offer.id.to_hex(),
+            payment.id.0.to_hex()
         );
+        let invoice_request =
+            InvoiceRequest::new(payment.id, stored_offer.id, amount, quantity, payer_note);
+        self.database.persist_offer(&stored_offer).await?;
+        self.database
+            .persist_invoice_request(&invoice_request)
+            .await?;
         self.database.persist_payment(&payment).await?;
+        let payment_id = payment.id;
         let receiver = self
             .async_api_requests
             .payments
-            .insert(payment_id, payment)
+            .insert(payment.id, payment)
             .await;
-        let payment = receiver.await??;
+        let payment = match receiver.await.map_err(anyhow::Error::from).and_then(|r| r) {
+            Ok(payment) => payment,
+            Err(e) => {
+                self.database.fail_invoice_request(&payment_id).await?;
+                return Err(e);
+            }
+        };
         self.database.persist_payment(&payment).await?;
+        let status = if payment.status == PaymentStatus::Succeeded {
+            InvoiceRequestStatus::Paid
+        } else {
+            InvoiceRequestStatus::Failed
+        };
+        self.database
+            .update_invoice_request_status(&payment.id, status)
+            .await?;
         Ok(payment)
     }
 
-    async fn list_payments(
+    async fn request_refund_payment(&self, refund: Refund) -> Result<()> {
+        self.channel_manager
+            .request_refund_payment(&refund)
+            .map_err(bolt12_semantic_error)?;
+        let offer = Offer::of_refund(&refund, None, PaymentDirection::Inbound);
+        info!("Sent invoice for refund {}", offer.id.to_hex());
+        self.database.persist_offer(&offer).await?;
+        Ok(())
+    }
+
+    async fn list_invoice_requests(
         &self,
-        invoice: Option<Invoice>,
-        direction: Option<PaymentDirection>,
-    ) -> Result<Vec<Payment>> {
-        self.database
-            .fetch_payments(invoice.map(|i| i.payment_hash), direction)
-            .await
+        offer_id: Option<sha256::Hash>,
+    ) -> Result<Vec<InvoiceRequest>> {
+        self.database.fetch_invoice_requests(offer_id).await
     }
 
     async fn estimated_channel_liquidity_range(
@@ -497,18 +795,127 @@ impl LightningInterface for Controller {
         self.database.fetch_total_forwards().await
     }
 
-    async fn fetch_forwards(&self, status: Option<ForwardStatus>) -> Result<Vec<Forward>> {
-        self.database.fetch_forwards(status).await
+    async fn fetch_forwards(&self, filter: ForwardFilter) -> Result<Vec<Forward>> {
+        self.database.fetch_forwards(&filter).await
+    }
+
+    async fn forward_stats(&self, window: Page) -> Result<ForwardStats> {
+        let totals = self.database.fetch_channel_forward_totals(&window).await?;
+        let failed = self.database.fetch_failed_forwards(&window).await?;
+        let counterparties = self.database.fetch_channel_counterparties().await?;
+        Ok(ForwardStats::new(&totals, &failed, &counterparties))
+    }
+
+    async fn channel_history(&self, filter: ChannelFilter) -> Result<Vec<Channel>> {
+        self.database.fetch_channel_history(&filter).await
+    }
+
+    async fn channel_backup(&self) -> Result<StaticChannelBackup> {
+        let peers = self.database.fetch_peers().await?;
+        let mut channels = vec![];
+        for channel in self.channel_manager.list_channels() {
+            // The monitor only exists once the channel is funded.
+            let channel_keys_id = channel
+                .funding_txo
+                .and_then(|txo| self.chain_monitor.get_monitor(txo).ok())
+                .map(|monitor| {
+                    channel_keys_id(&monitor, &self.keys_manager, channel.channel_value_satoshis)
+                })
+                .transpose()?;
+            channels.push(BackupChannel {
+                channel_id: channel.channel_id.to_hex(),
+                counterparty: channel.counterparty.node_id.to_string(),
+                address: peers
+                    .get(&channel.counterparty.node_id)
+                    .map(|address| SocketAddress::from(address.clone()).to_string()),
+                funding_txo: channel
+                    .funding_txo
+                    .map(|txo| format!("{}:{}", txo.txid, txo.index)),
+                value_sats: channel.channel_value_satoshis,
+                channel_keys_id: channel_keys_id.map(hex::encode),
+            });
+        }
+        Ok(StaticChannelBackup {
+            node_id: self.identity_pubkey().to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_secs(),
+            channels,
+        })
+    }
+
+    fn encrypt_channel_backup(&self, backup: &StaticChannelBackup) -> Result<Vec<u8>> {
+        backup.encrypt(&self.cipher)
+    }
+
+    fn inbound_channel_policy(&self) -> Result<InboundChannelPolicy> {
+        Ok(self
+            .inbound_channel_policy
+            .read()
+            .map_err(|e| anyhow!("failed to acquire lock on inbound channel policy {}", e))?
+            .clone())
+    }
+
+    async fn set_inbound_channel_policy(&self, policy: InboundChannelPolicy) -> Result<()> {
+        info!("Updating inbound channel policy: {policy:?}");
+        self.database
+            .persist_channel_acceptance_policy(&policy.clone().into())
+            .await?;
+        let mut current_policy = self
+            .inbound_channel_policy
+            .write()
+            .map_err(|e| anyhow!("failed to acquire lock on inbound channel policy {}", e))?;
+        *current_policy = policy;
+        Ok(())
+    }
+
+    fn jit_channel_offer(&self) -> Option<JitChannelOffer> {
+        self.jit_channels.offer()
+    }
+
+    fn list_jit_channels(&self) -> Result<Vec<JitChannel>> {
+        self.jit_channels.list()
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<NodeEvent> {
+        self.event_sender.subscribe()
+    }
+
+    async fn create_webhook(&self, webhook: Webhook) -> Result<()> {
+        info!("Registering webhook {} for {}", webhook.id, webhook.url);
+        self.database.persist_webhook(&webhook).await
+    }
+
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        self.database.fetch_webhooks().await
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> Result<bool> {
+        self.database.delete_webhook(&id).await
     }
 
-    async fn channel_history(&self) -> Result<Vec<Channel>> {
-        self.database.fetch_channel_history().await
+    async fn create_macaroon_root_key(&self, root_key: MacaroonRootKey) -> Result<()> {
+        info!(
+            "Creating macaroon root key {} ({})",
+            root_key.id, root_key.name
+        );
+        self.database.persist_macaroon_root_key(&root_key).await
+    }
+
+    async fn list_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>> {
+        self.database.fetch_macaroon_root_keys().await
+    }
+
+    async fn delete_macaroon_root_key(&self, id: Uuid) -> Result<bool> {
+        info!("Deleting macaroon root key {id}");
+        self.database.delete_macaroon_root_key(&id).await
     }
 }
 
 pub(crate) struct AsyncAPIRequests {
     pub funding_transactions: AsyncSenders<u64, FeeRate, Result<Transaction>>,
     pub payments: AsyncSenders<PaymentId, Payment, Result<Payment>>,
+    pub probes: AsyncSenders<PaymentId, (), ProbeResult>,
 }
 
 impl AsyncAPIRequests {
@@ -516,6 +923,7 @@ impl AsyncAPIRequests {
         AsyncAPIRequests {
             funding_transactions: AsyncSenders::new(),
             payments: AsyncSenders::new(),
+            probes: AsyncSenders::new(),
         }
     }
 }
@@ -549,6 +957,10 @@ impl<K: Eq + std::hash::Hash, V: Clone, RV> AsyncSenders<K, V, RV> {
         None
     }
 
+    async fn remove(&self, k: &K) {
+        self.senders.write().await.remove(k);
+    }
+
     pub async fn respond(&self, k: &K, rv: RV) {
         if let Some((_, tx)) = self.senders.write().await.remove(k) {
             if tx.send(rv).is_err() {
@@ -563,6 +975,7 @@ pub struct Controller {
     database: Arc<LdkDatabase>,
     bitcoind_client: Arc<BitcoindClient>,
     channel_manager: Arc<ChannelManager>,
+    chain_monitor: Arc<ChainMonitor>,
     peer_manager: Arc<PeerManager>,
     keys_manager: Arc<KeysManager>,
     network_graph: Arc<NetworkGraph>,
@@ -570,7 +983,10 @@ pub struct Controller {
     scorer: Arc<std::sync::RwLock<Scorer>>,
     wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
     async_api_requests: Arc<AsyncAPIRequests>,
-    _liquidity_manager: Arc<LiquidityManager>,
+    inbound_channel_policy: Arc<std::sync::RwLock<InboundChannelPolicy>>,
+    jit_channels: Arc<JitChannels>,
+    cipher: Arc<Cipher>,
+    event_sender: broadcast::Sender<NodeEvent>,
 }
 
 impl Controller {
@@ -580,18 +996,124 @@ impl Controller {
         self.peer_manager.disconnect_all_peers();
     }
 
+    /// Wait for the payment to complete, or return the pending payment straight away and persist
+    /// the result when it arrives.
+    async fn payment_result(&self, payment: Payment, wait: bool) -> Result<Payment> {
+        let receiver = self
+            .async_api_requests
+            .payments
+            .insert(payment.id, payment.clone())
+            .await;
+        if wait {
+            let payment = receiver.await??;
+            self.database.persist_payment(&payment).await?;
+            return Ok(payment);
+        }
+        let database = self.database.clone();
+        tokio::spawn(async move {
+            let result = match receiver.await {
+                Ok(Ok(payment)) => database.persist_payment(&payment).await,
+                Ok(Err(e)) => Err(e),
+                Err(e) => Err(anyhow!(e)),
+            };
+            if let Err(e) = result {
+                log_error(&e);
+            }
+        });
+        Ok(payment)
+    }
+
+    async fn hold_invoice(&self, payment_hash: &PaymentHash) -> Result<Invoice> {
+        self.database
+            .fetch_invoice(payment_hash)
+            .await?
+            .filter(|invoice| invoice.hold_status.is_some())
+            .context("Hold invoice not found")
+    }
+
+    /// Fail back held payments that have reached their claim deadline, LDK will not be able to claim them after that.
+    async fn cancel_expired_hold_invoices(
+        database: &LdkDatabase,
+        channel_manager: &ChannelManager,
+    ) -> Result<()> {
+        let height = channel_manager.current_best_block().height();
+        for mut invoice in database
+            .fetch_hold_invoices(HoldInvoiceStatus::Accepted)
+            .await?
+        {
+            if invoice.claim_deadline_reached(height) {
+                channel_manager.fail_htlc_backwards(&invoice.payment_hash);
+                warn!(
+                    "Cancelled hold invoice with payment hash {} at its claim deadline",
+                    invoice.payment_hash.0.to_hex()
+                );
+                invoice.hold_status = Some(HoldInvoiceStatus::Cancelled);
+                database.persist_invoice(&invoice).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Payments that were in flight when kld stopped have lost their API waiter. Bring them up to date
+    /// with the payments that the channel manager knows about, later payment events update them further.
+    async fn reconcile_pending_payments(
+        database: &LdkDatabase,
+        channel_manager: &ChannelManager,
+    ) -> Result<()> {
+        let recent_payments = channel_manager.list_recent_payments();
+        for mut payment in database
+            .fetch_payments(&PaymentFilter {
+                direction: Some(PaymentDirection::Outbound),
+                ..Default::default()
+            })
+            .await?
+            .into_iter()
+            .filter(|p| p.status == PaymentStatus::Pending)
+        {
+            let recent_payment = recent_payments.iter().find(|recent| match recent {
+                RecentPaymentDetails::AwaitingInvoice { payment_id }
+                | RecentPaymentDetails::Pending { payment_id, .. }
+                | RecentPaymentDetails::Fulfilled { payment_id, .. }
+                | RecentPaymentDetails::Abandoned { payment_id, .. } => *payment_id == payment.id,
+            });
+            if recent_payment.is_none() && payment.preimage.is_none() {
+                warn!(
+                    "Payment with ID {} is unknown to the channel manager, marking it as failed",
+                    payment.id.0.to_hex()
+                );
+            }
+            if !reconcile_payment(&mut payment, recent_payment) {
+                info!("Payment with ID {} is still pending", payment.id.0.to_hex());
+                continue;
+            }
+            info!(
+                "Reconciled payment with ID {}, status: {}",
+                payment.id.0.to_hex(),
+                payment.status
+            );
+            database.persist_payment(&payment).await?;
+        }
+        Ok(())
+    }
+
     pub async fn start_ldk(
         settings: Arc<Settings>,
         durable_connection: Arc<DurableConnection>,
         bitcoind_client: Arc<BitcoindClient>,
         wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
         seed: &[u8; 32],
+        cipher: Arc<Cipher>,
         quit_signal: Shared<impl Future<Output = ()> + Send + 'static>,
     ) -> Result<Controller> {
         let database = Arc::new(LdkDatabase::new(
             settings.clone(),
             durable_connection.clone(),
+            cipher.clone(),
         ));
+        database
+            .encrypt_blobs()
+            .await
+            .context("failed to encrypt database blobs")?;
 
         // BitcoindClient implements the FeeEstimator trait, so it'll act as our fee estimator.
         let fee_estimator = bitcoind_client.clone();
@@ -633,6 +1155,14 @@ impl Controller {
                 .context("Could not query network graph from database")?
                 .unwrap_or_else(|| NetworkGraph::new(network, KldLogger::global())),
         );
+        let rapid_gossip_syncer = Arc::new(RapidGossipSyncer::new(
+            settings.clone(),
+            database.clone(),
+            network_graph.clone(),
+        )?);
+        rapid_gossip_syncer
+            .bootstrap()
+            .context("failed to bootstrap network graph")?;
         let scorer = Arc::new(std::sync::RwLock::new(
             database
                 .fetch_scorer(
@@ -659,7 +1189,12 @@ impl Controller {
         ));
 
         let mut channel_monitors = database
-            .fetch_channel_monitors(keys_manager.as_ref(), keys_manager.as_ref())
+            .fetch_channel_monitors(
+                keys_manager.as_ref(),
+                keys_manager.as_ref(),
+                broadcaster.as_ref(),
+                fee_estimator.as_ref(),
+            )
             .await?;
         let mut user_config = UserConfig::default();
         user_config
@@ -674,6 +1209,17 @@ impl Controller {
         user_config
             .channel_handshake_limits
             .force_announced_channel_preference = false;
+        // Inbound channels are checked against the InboundChannelPolicy in the event handler.
+        user_config.manually_accept_inbound_channels = true;
+        user_config
+            .channel_handshake_config
+            .negotiate_anchors_zero_fee_htlc_tx = settings.anchor_channels;
+        // HTLCs for the intercept SCIDs of just in time channels are handed to the LiquidityManager.
+        let lsps2_enabled = super::jit_channels::offer(&settings).is_some();
+        if settings.lsps2_enabled && !lsps2_enabled {
+            warn!("LSPS2 is disabled because the opening fees are not configured");
+        }
+        user_config.accept_intercept_htlcs = lsps2_enabled;
 
         let getinfo_resp = bitcoind_client.get_blockchain_info().await?;
         let chain_params = ChainParameters {
@@ -718,10 +1264,14 @@ impl Controller {
             }
         };
         let channel_manager: Arc<ChannelManager> = Arc::new(channel_manager);
+        wallet.set_anchor_channels(anchor_channel_count(&channel_manager.list_channels()));
+        Controller::reconcile_pending_payments(&database, &channel_manager)
+            .await
+            .context("failed to reconcile pending payments")?;
 
         let liquidity_manager = Arc::new(LiquidityManager::new(
             keys_manager.clone(),
-            Some(LiquidityProviderConfig {}),
+            lsps2_enabled.then_some(LiquidityProviderConfig {}),
             channel_manager.clone(),
             None,
             chain_params,
@@ -749,12 +1299,16 @@ impl Controller {
             channel_manager.clone(),
             IgnoringMessageHandler {},
         ));
+        let message_handler = Arc::new(KldMessageHandler::new(
+            liquidity_manager.clone(),
+            channel_manager.clone(),
+        ));
         let ephemeral_bytes: [u8; 32] = random();
         let lightning_msg_handler = MessageHandler {
             chan_handler: channel_manager.clone(),
             route_handler: gossip_sync.clone(),
             onion_message_handler: onion_messenger,
-            custom_message_handler: liquidity_manager.clone(),
+            custom_message_handler: message_handler.clone(),
         };
         let peer_manager = Arc::new(PeerManager::new(
             lightning_msg_handler,
@@ -765,19 +1319,78 @@ impl Controller {
         ));
 
         let async_api_requests = Arc::new(AsyncAPIRequests::new());
+        // A policy that was set through the API replaces the one in the settings.
+        let inbound_channel_policy = match database.fetch_channel_acceptance_policy().await? {
+            Some(policy) => InboundChannelPolicy::try_from(policy)
+                .context("failed to load the inbound channel policy")?,
+            None => InboundChannelPolicy::from(settings.as_ref()),
+        };
+        let inbound_channel_policy = Arc::new(std::sync::RwLock::new(inbound_channel_policy));
+
+        let jit_channels = Arc::new(
+            JitChannels::new(
+                settings.clone(),
+                database.clone(),
+                channel_manager.clone(),
+                liquidity_manager.clone(),
+                bitcoind_client.clone(),
+                async_api_requests.clone(),
+                &user_config,
+            )
+            .await
+            .context("failed to load the jit channels")?,
+        );
+
+        let bump_tx_event_handler = Arc::new(BumpTransactionEventHandler::new(
+            broadcaster.clone(),
+            Arc::new(bump_transaction::Wallet::new(
+                wallet.clone(),
+                KldLogger::global(),
+            )),
+            keys_manager.clone(),
+            KldLogger::global(),
+        ));
 
+        // Websocket clients subscribe to the events, they are dropped when nobody listens.
+        let (event_sender, _) = broadcast::channel(1024);
+        let webhooks = Arc::new(WebhookDispatcher::new(
+            settings.clone(),
+            database.clone(),
+            wallet.clone(),
+        )?);
         let event_handler = EventHandler::new(
             channel_manager.clone(),
-            bitcoind_client.clone(),
-            keys_manager.clone(),
             network_graph.clone(),
             wallet.clone(),
             database.clone(),
             peer_manager.clone(),
+            message_handler.clone(),
             async_api_requests.clone(),
+            inbound_channel_policy.clone(),
+            jit_channels.clone(),
+            bump_tx_event_handler,
             settings.clone(),
+            event_sender.clone(),
+            webhooks.clone(),
         );
 
+        // With rapid gossip sync the background processor waits for the first sync before pruning the graph.
+        let background_gossip_sync = if rapid_gossip_syncer.is_enabled() {
+            GossipSync::Rapid(rapid_gossip_syncer.rapid_gossip_sync())
+        } else {
+            GossipSync::P2P(gossip_sync)
+        };
+        let recovery_channels = match &settings.recovery_channel_backup_path {
+            Some(path) => Controller::recovery_channels(
+                &database,
+                &cipher,
+                path,
+                &channel_manager.get_our_node_id(),
+            )
+            .await
+            .context("could not restore channel backup")?,
+            None => vec![],
+        };
         let bitcoind_client_clone = bitcoind_client.clone();
         let peer_manager_clone = peer_manager.clone();
         let wallet_clone = wallet.clone();
@@ -787,6 +1400,116 @@ impl Controller {
         let chain_monitor_clone = chain_monitor.clone();
         let scorer_clone = scorer.clone();
         let settings_clone = settings.clone();
+        let jit_channels_clone = jit_channels.clone();
+        let liquidity_quit_signal = quit_signal.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = jit_channels_clone.process_pending_events().await {
+                    log_error(&e);
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                    _ = liquidity_quit_signal.clone() => break,
+                }
+            }
+        });
+        let hold_invoice_database = database.clone();
+        let hold_invoice_channel_manager = channel_manager.clone();
+        let hold_invoice_quit_signal = quit_signal.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+                    _ = hold_invoice_quit_signal.clone() => break,
+                }
+                if let Err(e) = Controller::cancel_expired_hold_invoices(
+                    &hold_invoice_database,
+                    &hold_invoice_channel_manager,
+                )
+                .await
+                {
+                    log_error(&e);
+                }
+            }
+        });
+        let sweeper = Sweeper::new(
+            settings.clone(),
+            database.clone(),
+            bitcoind_client.clone(),
+            keys_manager.clone(),
+            wallet.clone(),
+        );
+        let sweeper_quit_signal = quit_signal.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+                    _ = sweeper_quit_signal.clone() => break,
+                }
+                if let Err(e) = sweeper.sweep().await {
+                    log_error(&e);
+                }
+            }
+        });
+        let webhook_event_sender = event_sender.clone();
+        let webhook_quit_signal = quit_signal.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                    _ = webhook_quit_signal.clone() => break,
+                }
+                match webhooks.check_wallet_balance() {
+                    Ok(Some(event)) => {
+                        if let Err(e) = webhooks.notify(&event).await {
+                            log_error(&e);
+                        }
+                        let _ = webhook_event_sender.send(event);
+                    }
+                    Ok(None) => {}
+                    Err(e) => log_error(&e),
+                }
+                if let Err(e) = webhooks.deliver().await {
+                    log_error(&e);
+                }
+            }
+        });
+        if settings.rgs_url.is_some() {
+            let rgs_interval = Duration::from_secs(settings.rgs_sync_interval_secs);
+            let rgs_syncer = rapid_gossip_syncer.clone();
+            let rgs_quit_signal = quit_signal.clone();
+            tokio::spawn(async move {
+                loop {
+                    if let Err(e) = rgs_syncer.sync().await {
+                        log_error(&e);
+                    }
+                    tokio::select! {
+                        _ = tokio::time::sleep(rgs_interval) => {}
+                        _ = rgs_quit_signal.clone() => break,
+                    }
+                }
+            });
+        }
+        if settings.probing_enabled {
+            let prober = Prober::new(
+                settings.clone(),
+                channel_manager.clone(),
+                network_graph.clone(),
+            );
+            let probing_interval = Duration::from_secs(settings.probing_interval_secs);
+            let probing_quit_signal = quit_signal.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::select! {
+                        _ = tokio::time::sleep(probing_interval) => {}
+                        _ = probing_quit_signal.clone() => break,
+                    }
+                    if let Err(e) = prober.probe_popular_destination() {
+                        debug!("Background probe failed: {e}");
+                    }
+                }
+            });
+        }
         tokio::spawn(async move {
             bitcoind_client_clone
                 .wait_for_blockchain_synchronisation()
@@ -794,7 +1517,7 @@ impl Controller {
             if let Err(e) = Controller::sync_to_chain_tip(
                 network,
                 bitcoind_client_clone,
-                chain_monitor,
+                chain_monitor_clone.clone(),
                 channel_manager_blockhash,
                 channel_manager_clone.clone(),
                 channel_monitors,
@@ -813,6 +1536,12 @@ impl Controller {
             peer_manager_clone.keep_channel_peers_connected(
                 database_clone.clone(),
                 channel_manager_clone.clone(),
+                recovery_channels.iter().map(|(peer, _)| *peer).collect(),
+            );
+            Controller::request_force_close(
+                peer_manager_clone.clone(),
+                message_handler,
+                recovery_channels,
             );
             peer_manager_clone.broadcast_node_announcement_from_settings(settings_clone);
 
@@ -826,7 +1555,7 @@ impl Controller {
                     },
                     chain_monitor_clone,
                     channel_manager_clone,
-                    GossipSync::p2p(gossip_sync),
+                    background_gossip_sync,
                     peer_manager_clone,
                     KldLogger::global(),
                     Some(scorer_clone),
@@ -854,6 +1583,7 @@ impl Controller {
             database,
             bitcoind_client,
             channel_manager,
+            chain_monitor,
             peer_manager,
             keys_manager,
             network_graph,
@@ -861,10 +1591,77 @@ impl Controller {
             scorer,
             wallet,
             async_api_requests,
-            _liquidity_manager: liquidity_manager,
+            inbound_channel_policy,
+            jit_channels,
+            cipher,
+            event_sender,
         })
     }
 
+    /// Store the peer addresses from the channel backup and return the peers to keep connected to,
+    /// with the channels that they need to force close.
+    async fn recovery_channels(
+        database: &LdkDatabase,
+        cipher: &Cipher,
+        path: &str,
+        node_id: &PublicKey,
+    ) -> Result<Vec<(PublicKey, Vec<ChannelId>)>> {
+        let backup = StaticChannelBackup::read(cipher, path, node_id)?;
+        info!(
+            "Recovery mode: connecting to the peers of {} channels from backup {path}",
+            backup.channels.len()
+        );
+        let mut recovery_channels = vec![];
+        for (public_key, address) in backup.peers()? {
+            match address {
+                Some(address) => {
+                    database
+                        .persist_peer(&DatabasePeer {
+                            public_key,
+                            address: address.inner(),
+                        })
+                        .await?
+                }
+                None => warn!("Channel backup has no address for peer {public_key}"),
+            }
+            recovery_channels.push((public_key, backup.channel_ids(&public_key)?));
+        }
+        Ok(recovery_channels)
+    }
+
+    /// Ask the peers from the channel backup to force close our channels each time that they
+    /// connect, the error may have been lost if the peer disconnected before it handled it.
+    fn request_force_close(
+        peer_manager: Arc<PeerManager>,
+        message_handler: Arc<KldMessageHandler>,
+        recovery_channels: Vec<(PublicKey, Vec<ChannelId>)>,
+    ) {
+        if recovery_channels.is_empty() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut requested: HashSet<PublicKey> = HashSet::new();
+            loop {
+                requested.retain(|peer| peer_manager.is_connected(peer));
+                for (peer, channel_ids) in &recovery_channels {
+                    if requested.contains(peer) || !peer_manager.is_connected(peer) {
+                        continue;
+                    }
+                    info!(
+                        "Recovery mode: asking peer {peer} to force close {} channels",
+                        channel_ids.len()
+                    );
+                    for channel_id in channel_ids {
+                        message_handler.request_force_close(*peer, *channel_id);
+                    }
+                    requested.insert(*peer);
+                }
+                peer_manager.process_events();
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
     async fn sync_to_chain_tip(
         network: Network,
         bitcoind_client: Arc<BitcoindClient>,
@@ -943,3 +1740,88 @@ impl Drop for Controller {
         self.stop()
     }
 }
+
+/// Update a pending payment with what the channel manager knows about it. Returns false if the
+/// payment is still pending.
+fn reconcile_payment(payment: &mut Payment, recent_payment: Option<&RecentPaymentDetails>) -> bool {
+    match recent_payment {
+        Some(RecentPaymentDetails::AwaitingInvoice { .. })
+        | Some(RecentPaymentDetails::Pending { .. }) => false,
+        Some(RecentPaymentDetails::Fulfilled { payment_hash, .. }) => {
+            payment.hash = payment_hash.or(payment.hash);
+            payment.status = PaymentStatus::Succeeded;
+            true
+        }
+        Some(RecentPaymentDetails::Abandoned { .. }) => {
+            payment.failed(None);
+            true
+        }
+        // The channel manager forgets payments a few timer ticks after they resolve. The preimage
+        // is stored when the payment succeeds.
+        None if payment.preimage.is_some() => {
+            payment.status = PaymentStatus::Succeeded;
+            true
+        }
+        // Otherwise it failed, or it was never sent because we stopped before the channel manager
+        // was persisted. A PaymentSent event that we had not handled yet is replayed after this
+        // and still marks the payment as succeeded.
+        None => {
+            payment.failed(Some(PaymentFailureReason::UnexpectedError));
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lightning::ln::channelmanager::{PaymentId, RecentPaymentDetails};
+    use lightning::ln::{PaymentHash, PaymentPreimage};
+
+    use crate::database::payment::{Payment, PaymentStatus};
+
+    use super::reconcile_payment;
+
+    #[test]
+    fn test_reconcile_payment() {
+        let payment_id = PaymentId([1u8; 32]);
+        let payment_hash = PaymentHash([2u8; 32]);
+        let pending = Payment::spontaneous_outbound(payment_id, 1000);
+
+        let mut payment = pending.clone();
+        let recent = RecentPaymentDetails::Pending {
+            payment_id,
+            payment_hash,
+            total_msat: 1000,
+        };
+        assert!(!reconcile_payment(&mut payment, Some(&recent)));
+        assert_eq!(PaymentStatus::Pending, payment.status);
+
+        let mut payment = pending.clone();
+        let recent = RecentPaymentDetails::Fulfilled {
+            payment_id,
+            payment_hash: Some(payment_hash),
+        };
+        assert!(reconcile_payment(&mut payment, Some(&recent)));
+        assert_eq!(PaymentStatus::Succeeded, payment.status);
+        assert_eq!(Some(payment_hash), payment.hash);
+
+        let mut payment = pending.clone();
+        let recent = RecentPaymentDetails::Abandoned {
+            payment_id,
+            payment_hash,
+        };
+        assert!(reconcile_payment(&mut payment, Some(&recent)));
+        assert_eq!(PaymentStatus::Error, payment.status);
+
+        // A payment that the channel manager no longer knows has failed, unless we already have
+        // the preimage.
+        let mut payment = pending.clone();
+        assert!(reconcile_payment(&mut payment, None));
+        assert_eq!(PaymentStatus::Error, payment.status);
+
+        let mut payment = pending;
+        payment.preimage = Some(PaymentPreimage([3u8; 32]));
+        assert!(reconcile_payment(&mut payment, None));
+        assert_eq!(PaymentStatus::Succeeded, payment.status);
+    }
+}
@@ -1,47 +1,60 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, bail, Context, Result};
+use api::NodeEvent;
 
-use bitcoin::blockdata::locktime::PackedLockTime;
 use bitcoin::hashes::hex::ToHex;
-use bitcoin::secp256k1::Secp256k1;
 
-use crate::bitcoind::bitcoind_interface::BitcoindInterface;
 use crate::database::forward::Forward;
-use crate::database::payment::Payment;
-use crate::database::spendable_output::{SpendableOutput, SpendableOutputStatus};
+use crate::database::invoice::HoldInvoiceStatus;
+use crate::database::payment::{Payment, PaymentStatus};
+use crate::database::payment_attempt::PaymentAttempt;
+use crate::database::spendable_output::SpendableOutput;
 use crate::database::{LdkDatabase, WalletDatabase};
 use crate::ldk::peer_manager::KuutamoPeerManger;
 use crate::log_error;
 use crate::settings::Settings;
-use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
-use lightning::events::{Event, PathFailure, PaymentPurpose};
-use lightning::ln::ChannelId;
+use lightning::events::bump_transaction::BumpTransactionEvent;
+use lightning::events::{ClosureReason, Event, PathFailure, PaymentPurpose};
+use lightning::ln::channelmanager::PaymentId;
+use lightning::ln::{ChannelId, PaymentHash};
 use lightning::routing::gossip::NodeId;
-use lightning::sign::KeysManager;
 use log::{error, info, warn};
-use rand::{thread_rng, Rng};
+use rand::{random, thread_rng, Rng};
 use tokio::runtime::Handle;
+use tokio::sync::broadcast;
 
 use crate::bitcoind::BitcoindClient;
 use crate::ldk::{htlc_destination_to_string, ldk_error};
-use crate::wallet::{Wallet, WalletInterface};
+use crate::wallet::Wallet;
 
+use super::channel_acceptor::InboundChannelPolicy;
+use super::channel_utils::anchor_channel_count;
 use super::controller::AsyncAPIRequests;
+use super::jit_channels::JitChannels;
+use super::message_handler::KldMessageHandler;
 use super::peer_manager::PeerManager;
-use super::{ChannelManager, NetworkGraph};
+use super::webhooks::WebhookDispatcher;
+use super::{BumpTransactionEventHandler, ChannelManager, NetworkGraph, ProbeResult};
+
+// Times that the webhook deliveries of an event are queued before handling it fails.
+const NOTIFY_ATTEMPTS: u32 = 3;
 
 pub(crate) struct EventHandler {
     channel_manager: Arc<ChannelManager>,
-    bitcoind_client: Arc<BitcoindClient>,
-    keys_manager: Arc<KeysManager>,
     network_graph: Arc<NetworkGraph>,
     wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
     ldk_database: Arc<LdkDatabase>,
     peer_manager: Arc<PeerManager>,
+    message_handler: Arc<KldMessageHandler>,
     async_api_requests: Arc<AsyncAPIRequests>,
+    inbound_channel_policy: Arc<RwLock<InboundChannelPolicy>>,
+    jit_channels: Arc<JitChannels>,
+    bump_tx_event_handler: Arc<BumpTransactionEventHandler>,
     settings: Arc<Settings>,
+    event_sender: broadcast::Sender<NodeEvent>,
+    webhooks: Arc<WebhookDispatcher>,
     runtime_handle: Handle,
 }
 
@@ -49,25 +62,33 @@ impl EventHandler {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         channel_manager: Arc<ChannelManager>,
-        bitcoind_client: Arc<BitcoindClient>,
-        keys_manager: Arc<KeysManager>,
         network_graph: Arc<NetworkGraph>,
         wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
         database: Arc<LdkDatabase>,
         peer_manager: Arc<PeerManager>,
+        message_handler: Arc<KldMessageHandler>,
         async_api_requests: Arc<AsyncAPIRequests>,
+        inbound_channel_policy: Arc<RwLock<InboundChannelPolicy>>,
+        jit_channels: Arc<JitChannels>,
+        bump_tx_event_handler: Arc<BumpTransactionEventHandler>,
         settings: Arc<Settings>,
+        event_sender: broadcast::Sender<NodeEvent>,
+        webhooks: Arc<WebhookDispatcher>,
     ) -> EventHandler {
         EventHandler {
             channel_manager,
-            bitcoind_client,
-            keys_manager,
             network_graph,
             wallet,
             ldk_database: database,
             peer_manager,
+            message_handler,
             async_api_requests,
+            inbound_channel_policy,
+            jit_channels,
+            bump_tx_event_handler,
             settings,
+            event_sender,
+            webhooks,
             runtime_handle: Handle::current(),
         }
     }
@@ -131,6 +152,13 @@ impl EventHandler {
                     "EVENT: Channel {} - {user_channel_id} with counterparty {counterparty_node_id} is pending. OutPoint: {funding_txo}",
                     channel_id.to_hex(),
                 );
+                self.update_anchor_reserve();
+                self.notify(NodeEvent::ChannelPending {
+                    channel_id: channel_id.to_hex(),
+                    counterparty: counterparty_node_id.to_string(),
+                    funding_txo: funding_txo.to_string(),
+                })
+                .await?;
             }
             Event::ChannelReady {
                 channel_id,
@@ -151,9 +179,18 @@ impl EventHandler {
                     let channel = channel_details.clone().try_into()?;
                     self.ldk_database.persist_channel(channel).await?;
                 }
+                self.update_anchor_reserve();
+                self.jit_channels
+                    .channel_ready(user_channel_id as u64, &channel_id, &counterparty_node_id)
+                    .await?;
                 info!("Broadcasting node announcement message");
                 self.peer_manager
                     .broadcast_node_announcement_from_settings(self.settings.clone());
+                self.notify(NodeEvent::ChannelReady {
+                    channel_id: channel_id.to_hex(),
+                    counterparty: counterparty_node_id.to_string(),
+                })
+                .await?;
             }
             Event::ChannelClosed {
                 channel_id,
@@ -172,6 +209,22 @@ impl EventHandler {
                 self.ldk_database
                     .close_channel(&channel_id, &reason)
                     .await?;
+                self.update_anchor_reserve();
+                self.jit_channels
+                    .channel_closed(user_channel_id as u64)
+                    .await?;
+                self.notify(NodeEvent::ChannelClosed {
+                    channel_id: channel_id.to_hex(),
+                    reason: reason.to_string(),
+                    force_closed: matches!(
+                        reason,
+                        ClosureReason::HolderForceClosed
+                            | ClosureReason::CounterpartyForceClosed { .. }
+                            | ClosureReason::CommitmentTxConfirmed
+                            | ClosureReason::ProcessingError { .. }
+                    ),
+                })
+                .await?;
             }
             Event::DiscardFunding {
                 channel_id,
@@ -183,10 +236,67 @@ impl EventHandler {
                     transaction.txid()
                 );
             }
-            Event::OpenChannelRequest { .. } => {
-                unreachable!(
-                    "This event will not fire as we do not manually accept inbound channels."
-                )
+            Event::OpenChannelRequest {
+                temporary_channel_id,
+                counterparty_node_id,
+                funding_satoshis,
+                push_msat,
+                channel_type,
+            } => {
+                info!(
+                    "EVENT: Open channel request from {counterparty_node_id} for {funding_satoshis} sats, pushing {push_msat} msat. Channel {}",
+                    temporary_channel_id.to_hex()
+                );
+                let policy = self
+                    .inbound_channel_policy
+                    .read()
+                    .map_err(|e| anyhow!("failed to acquire lock on inbound channel policy {}", e))?
+                    .clone();
+                let existing_channels = self
+                    .channel_manager
+                    .list_channels_with_counterparty(&counterparty_node_id)
+                    .len();
+                let has_anchor_reserve = channel_type.supports_anchors_zero_fee_htlc_tx()
+                    && self
+                        .wallet
+                        .has_reserve_for_anchor_channel()
+                        .unwrap_or(false);
+                match policy.check(
+                    &counterparty_node_id,
+                    funding_satoshis,
+                    &channel_type,
+                    existing_channels,
+                    has_anchor_reserve,
+                ) {
+                    Ok(()) => {
+                        let user_channel_id: u64 = random::<u64>() / 2; // To fit into the database INT
+                        self.channel_manager
+                            .accept_inbound_channel(
+                                &temporary_channel_id,
+                                &counterparty_node_id,
+                                user_channel_id as u128,
+                            )
+                            .map_err(ldk_error)?;
+                        info!(
+                            "Accepted inbound channel {} - {user_channel_id} from {counterparty_node_id}",
+                            temporary_channel_id.to_hex()
+                        );
+                    }
+                    Err(rule) => {
+                        warn!(
+                            "Rejecting inbound channel {} from {counterparty_node_id}: {rule}. {}",
+                            temporary_channel_id.to_hex(),
+                            policy.reject_reason
+                        );
+                        // The peer gets the reason in an error message for the temporary channel id
+                        // and forgets the channel.
+                        self.message_handler.reject_channel(
+                            counterparty_node_id,
+                            temporary_channel_id,
+                            policy.reject_reason.clone(),
+                        );
+                    }
+                }
             }
             Event::PaymentClaimable {
                 payment_hash,
@@ -223,6 +333,8 @@ impl EventHandler {
                     } => {
                         if let Some(payment_preimage) = payment_preimage {
                             self.channel_manager.claim_funds(payment_preimage);
+                        } else {
+                            self.hold_payment(payment_hash, claim_deadline).await?;
                         }
                     }
                     PaymentPurpose::SpontaneousPayment(preimage) => {
@@ -260,6 +372,11 @@ impl EventHandler {
                     .persist_payment(&payment)
                     .await
                     .context("Failed to persist payment")?;
+                self.notify(NodeEvent::InvoicePaid {
+                    payment_hash: payment_hash.0.to_hex(),
+                    amount_msat,
+                })
+                .await?;
             }
             Event::PaymentSent {
                 payment_id,
@@ -285,14 +402,16 @@ impl EventHandler {
                     "Failed to update payment with hash {}",
                     payment_hash.0.to_hex()
                 ))?;
-                let (mut payment, respond) = self
-                    .async_api_requests
-                    .payments
-                    .get(&payment_id)
-                    .await
-                    .context(format!("Can't find payment for {}", payment_id.0.to_hex()))?;
-                payment.succeeded(payment_hash, payment_preimage, fee_paid_msat);
-                respond(Ok(payment));
+                self.update_payment(payment_id, |payment| {
+                    payment.succeeded(payment_hash, payment_preimage, fee_paid_msat)
+                })
+                .await?;
+                self.notify(NodeEvent::PaymentSent {
+                    payment_id: payment_id.0.to_hex(),
+                    payment_hash: payment_hash.0.to_hex(),
+                    fee_msat: fee_paid_msat,
+                })
+                .await?;
             }
             Event::PaymentPathSuccessful {
                 payment_id,
@@ -307,6 +426,9 @@ impl EventHandler {
                         .map(|h| format!(" and hash {}", h.0.to_hex()))
                         .unwrap_or_default()
                 );
+                self.ldk_database
+                    .persist_payment_attempt(&PaymentAttempt::success(payment_id, &path))
+                    .await?;
             }
             Event::PaymentPathFailed {
                 payment_id,
@@ -340,6 +462,16 @@ impl EventHandler {
                     },
                     path
                 );
+                if let Some(payment_id) = payment_id {
+                    self.ldk_database
+                        .persist_payment_attempt(&PaymentAttempt::failure(
+                            payment_id,
+                            &path,
+                            short_channel_id,
+                            payment_failed_permanently,
+                        ))
+                        .await?;
+                }
             }
             Event::PaymentFailed {
                 payment_id,
@@ -354,14 +486,14 @@ impl EventHandler {
                         .map(|r| format!(" for reason {r:?}"))
                         .unwrap_or_default()
                 );
-                let (mut payment, respond) = self
-                    .async_api_requests
-                    .payments
-                    .get(&payment_id)
-                    .await
-                    .context(format!("Can't find payment for {}", payment_id.0.to_hex()))?;
-                payment.failed(reason);
-                respond(Ok(payment));
+                self.update_payment(payment_id, |payment| payment.failed(reason))
+                    .await?;
+                self.notify(NodeEvent::PaymentFailed {
+                    payment_id: payment_id.0.to_hex(),
+                    payment_hash: payment_hash.0.to_hex(),
+                    reason: reason.map(|r| format!("{r:?}")),
+                })
+                .await?;
             }
             Event::PaymentForwarded {
                 prev_channel_id,
@@ -438,6 +570,14 @@ impl EventHandler {
                         Forward::success(inbound_channel_id, outbound_channel_id, amount, fee);
                     let id = forward.id.to_string();
                     self.persist_forward(forward);
+                    self.notify(NodeEvent::ForwardSucceeded {
+                        id: id.clone(),
+                        inbound_channel_id: inbound_channel_id.to_hex(),
+                        outbound_channel_id: outbound_channel_id.to_hex(),
+                        amount_msat: amount,
+                        fee_msat: fee,
+                    })
+                    .await?;
                     format!(" with ID {id}")
                 } else {
                     "".to_string()
@@ -446,8 +586,50 @@ impl EventHandler {
                     "EVENT: Forwarded payment{id}{from_prev_str}{to_next_str} {amount_str},{fee_str} {from_onchain_str}",
                 );
             }
-            Event::ProbeSuccessful { .. } => {}
-            Event::ProbeFailed { .. } => {}
+            Event::ProbeSuccessful {
+                payment_id, path, ..
+            } => {
+                info!(
+                    "EVENT: Probe with ID {} succeeded over {} hops",
+                    payment_id.0.to_hex(),
+                    path.hops.len()
+                );
+                self.async_api_requests
+                    .probes
+                    .respond(&payment_id, ProbeResult::Succeeded { paths: 1 })
+                    .await;
+            }
+            Event::ProbeFailed {
+                payment_id,
+                path,
+                short_channel_id,
+                ..
+            } => {
+                info!(
+                    "EVENT: Probe with ID {} failed at channel {}",
+                    payment_id.0.to_hex(),
+                    short_channel_id
+                        .map(|scid| scid.to_string())
+                        .unwrap_or_default()
+                );
+                let node_id = short_channel_id.and_then(|scid| {
+                    path.hops
+                        .iter()
+                        .find(|hop| hop.short_channel_id == scid)
+                        .map(|hop| hop.pubkey)
+                });
+                self.async_api_requests
+                    .probes
+                    .respond(
+                        &payment_id,
+                        ProbeResult::Failed {
+                            paths: 1,
+                            short_channel_id,
+                            node_id,
+                        },
+                    )
+                    .await;
+            }
             Event::HTLCHandlingFailed {
                 prev_channel_id,
                 failed_next_destination,
@@ -455,6 +637,12 @@ impl EventHandler {
                 let forward = Forward::failure(prev_channel_id, failed_next_destination.clone());
                 let id = forward.id.to_string();
                 self.persist_forward(forward);
+                self.notify(NodeEvent::ForwardFailed {
+                    id: id.clone(),
+                    inbound_channel_id: prev_channel_id.to_hex(),
+                    reason: htlc_destination_to_string(&failed_next_destination),
+                })
+                .await?;
                 error!(
                     "EVENT: Failed handling HTLC with ID {id} from channel {}. {}",
                     prev_channel_id.to_hex(),
@@ -471,66 +659,158 @@ impl EventHandler {
                 });
             }
             Event::SpendableOutputs { outputs, .. } => {
-                let mut spendable_outputs: Vec<SpendableOutput> =
-                    outputs.into_iter().map(SpendableOutput::new).collect();
-                for spendable_output in &spendable_outputs {
+                // The sweeper picks these up, the event can be replayed so existing outputs are left as they are.
+                for spendable_output in outputs.into_iter().map(SpendableOutput::new) {
                     info!("EVENT: New {:?}", spendable_output);
-                    self.persist_spendable_output(spendable_output.clone());
+                    let event = NodeEvent::SpendableOutput {
+                        txid: spendable_output.txid.to_string(),
+                        vout: spendable_output.vout,
+                        value_sats: spendable_output.value,
+                    };
+                    self.ldk_database
+                        .insert_spendable_output(spendable_output)
+                        .await?;
+                    self.notify(event).await?;
                 }
-                let destination_address = self.wallet.new_internal_address()?;
-                let output_descriptors = &spendable_outputs
-                    .iter()
-                    .map(|o| &o.descriptor)
-                    .collect::<Vec<_>>();
-                let tx_feerate = self
-                    .bitcoind_client
-                    .get_est_sat_per_1000_weight(ConfirmationTarget::OnChainSweep);
-
-                let best_block_height = self.bitcoind_client.block_height().await?;
-                let spending_tx = self
-                    .keys_manager
-                    .spend_spendable_outputs(
-                        output_descriptors,
-                        Vec::new(),
-                        destination_address.script_pubkey(),
-                        tx_feerate,
-                        Some(PackedLockTime(best_block_height as u32)),
-                        &Secp256k1::new(),
+            }
+            Event::HTLCIntercepted {
+                intercept_id,
+                requested_next_hop_scid,
+                payment_hash,
+                inbound_amount_msat,
+                expected_outbound_amount_msat,
+            } => {
+                info!(
+                    "EVENT: Intercepted HTLC {} for SCID {requested_next_hop_scid}. Inbound {inbound_amount_msat} msat, outbound {expected_outbound_amount_msat} msat",
+                    payment_hash.0.to_hex()
+                );
+                self.jit_channels
+                    .htlc_intercepted(
+                        intercept_id,
+                        requested_next_hop_scid,
+                        expected_outbound_amount_msat,
                     )
-                    .map_err(|()| anyhow!("Failed to build spending transaction"))?;
+                    .await?;
+            }
+            Event::InvoiceRequestFailed { payment_id } => {
                 info!(
-                    "Sending spendable output to {}",
-                    destination_address.address
+                    "EVENT: Invoice request failed for payment with ID {}",
+                    payment_id.0.to_hex()
                 );
-                self.bitcoind_client.broadcast_transactions(&[&spending_tx]);
-                for spendable_output in spendable_outputs.iter_mut() {
-                    spendable_output.status = SpendableOutputStatus::Spent;
-                    self.persist_spendable_output(spendable_output.clone());
+                match self.async_api_requests.payments.get(&payment_id).await {
+                    Some((mut payment, respond)) => {
+                        payment.status = PaymentStatus::Error;
+                        respond(Ok(payment));
+                    }
+                    None => self.ldk_database.fail_invoice_request(&payment_id).await?,
                 }
             }
-            Event::HTLCIntercepted {
-                intercept_id: _,
-                requested_next_hop_scid: _,
-                payment_hash: _,
-                inbound_amount_msat: _,
-                expected_outbound_amount_msat: _,
-            } => unreachable!(),
-            Event::InvoiceRequestFailed { payment_id } => {
-                // XXX Handle it
-                info!("Invoice request failed, payment id: {payment_id:}");
+            Event::BumpTransaction(event) => {
+                match &event {
+                    BumpTransactionEvent::ChannelClose {
+                        channel_id,
+                        commitment_tx,
+                        ..
+                    } => info!(
+                        "EVENT: Bumping commitment transaction {} for channel {}",
+                        commitment_tx.txid(),
+                        channel_id.to_hex()
+                    ),
+                    BumpTransactionEvent::HTLCResolution {
+                        channel_id,
+                        htlc_descriptors,
+                        ..
+                    } => info!(
+                        "EVENT: Bumping {} HTLC transaction(s) for channel {}",
+                        htlc_descriptors.len(),
+                        channel_id.to_hex()
+                    ),
+                }
+                self.bump_tx_event_handler.handle_event(&event);
             }
-            Event::BumpTransaction(_) => unreachable!(),
         };
         Ok(())
     }
 
-    fn persist_spendable_output(&self, spendable_output: SpendableOutput) {
-        let database = self.ldk_database.clone();
-        self.runtime_handle.spawn(async move {
-            if let Err(e) = database.persist_spendable_output(spendable_output).await {
-                log_error(&e)
+    // Hold the HTLCs of a hold invoice until it is settled or cancelled through the API.
+    // Payments for any other unknown preimage are failed back straight away.
+    async fn hold_payment(
+        &self,
+        payment_hash: PaymentHash,
+        claim_deadline: Option<u32>,
+    ) -> Result<()> {
+        match self.ldk_database.fetch_invoice(&payment_hash).await? {
+            Some(mut invoice)
+                if matches!(
+                    invoice.hold_status,
+                    Some(HoldInvoiceStatus::Open) | Some(HoldInvoiceStatus::Accepted)
+                ) =>
+            {
+                info!(
+                    "Holding payment with hash {} until the invoice is settled or cancelled",
+                    payment_hash.0.to_hex()
+                );
+                invoice.hold_status = Some(HoldInvoiceStatus::Accepted);
+                invoice.claim_deadline = claim_deadline;
+                self.ldk_database.persist_invoice(&invoice).await?;
             }
-        });
+            _ => {
+                warn!(
+                    "Failing back payment with hash {}, no preimage or open hold invoice",
+                    payment_hash.0.to_hex()
+                );
+                self.channel_manager.fail_htlc_backwards(&payment_hash);
+            }
+        }
+        Ok(())
+    }
+
+    // Hand the updated payment to the API caller that waits for it. When nobody waits, e.g. after a
+    // restart or for a payment sent without waiting, the payment is updated in the database.
+    async fn update_payment(
+        &self,
+        payment_id: PaymentId,
+        update: impl FnOnce(&mut Payment),
+    ) -> Result<()> {
+        match self.async_api_requests.payments.get(&payment_id).await {
+            Some((mut payment, respond)) => {
+                update(&mut payment);
+                respond(Ok(payment));
+            }
+            None => {
+                let mut payment = self
+                    .ldk_database
+                    .fetch_payment(&payment_id)
+                    .await?
+                    .context(format!("Can't find payment for {}", payment_id.0.to_hex()))?;
+                update(&mut payment);
+                self.ldk_database.persist_payment(&payment).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Queue the webhook deliveries and push the event to the websocket clients, sending only
+    // fails when no client is subscribed. LDK does not hand over an event again when handling it
+    // fails, so the deliveries are retried before the error is returned.
+    async fn notify(&self, event: NodeEvent) -> Result<()> {
+        let mut attempt = 1;
+        while let Err(e) = self.webhooks.notify(&event).await {
+            if attempt == NOTIFY_ATTEMPTS {
+                return Err(e.context("Failed to queue webhook deliveries"));
+            }
+            log_error(&e);
+            attempt += 1;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        let _ = self.event_sender.send(event);
+        Ok(())
+    }
+
+    // Keep enough funds in the wallet to fee bump all our anchor channels.
+    fn update_anchor_reserve(&self) {
+        self.wallet
+            .set_anchor_channels(anchor_channel_count(&self.channel_manager.list_channels()));
     }
 
     fn persist_forward(&self, forward: Forward) {
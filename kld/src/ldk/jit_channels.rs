@@ -0,0 +1,530 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use api::{JitChannelOffer, JitChannelState};
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::secp256k1::PublicKey;
+use ldk_lsp_client::events::Event as LiquidityEvent;
+use ldk_lsp_client::lsps2::{event::LSPS2Event, msgs::RawOpeningFeeParams};
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+use lightning::ln::channelmanager::InterceptId;
+use lightning::ln::ChannelId;
+use lightning::util::config::UserConfig;
+use log::{info, warn};
+use rand::random;
+use time::OffsetDateTime;
+
+use crate::bitcoind::BitcoindClient;
+use crate::database::jit_channel::JitChannel;
+use crate::database::LdkDatabase;
+use crate::settings::Settings;
+
+use super::controller::AsyncAPIRequests;
+use super::{ldk_error, ChannelManager, LiquidityManager};
+
+// Weight of a commitment transaction with anchors, and of each HTLC output on it (BOLT 3).
+const COMMITMENT_TX_BASE_WEIGHT: u64 = 1124;
+const COMMITMENT_TX_WEIGHT_PER_HTLC: u64 = 172;
+// The two anchor outputs of the commitment transaction are paid by the funder.
+const ANCHOR_OUTPUTS_SATS: u64 = 2 * 330;
+// Clients make us keep 1% of the channel in reserve, but at least this much (LDK defaults).
+const MIN_CHANNEL_RESERVE_SATS: u64 = 1000;
+
+/// Sells LSPS2 just in time channels to clients of our LSP. The client gets an intercept SCID
+/// for its invoice and we open the channel when the first HTLC for that SCID arrives.
+pub(crate) struct JitChannels {
+    settings: Arc<Settings>,
+    database: Arc<LdkDatabase>,
+    channel_manager: Arc<ChannelManager>,
+    liquidity_manager: Arc<LiquidityManager>,
+    fee_estimator: Arc<BitcoindClient>,
+    async_api_requests: Arc<AsyncAPIRequests>,
+    cltv_expiry_delta: u32,
+    channels: RwLock<HashMap<u64, JitChannel>>,
+}
+
+impl JitChannels {
+    /// The LiquidityManager only keeps its sessions in memory. The sessions that were not finished
+    /// before we stopped fail, and so do the HTLCs that were waiting for their channels.
+    pub async fn new(
+        settings: Arc<Settings>,
+        database: Arc<LdkDatabase>,
+        channel_manager: Arc<ChannelManager>,
+        liquidity_manager: Arc<LiquidityManager>,
+        fee_estimator: Arc<BitcoindClient>,
+        async_api_requests: Arc<AsyncAPIRequests>,
+        user_config: &UserConfig,
+    ) -> Result<JitChannels> {
+        let mut channels = HashMap::new();
+        for mut channel in database.fetch_jit_channels().await? {
+            if channel.state != JitChannelState::ChannelReady
+                && channel.state != JitChannelState::Failed
+            {
+                warn!(
+                    "LSPS2: Channel {} was not ready before the restart",
+                    channel.user_channel_id
+                );
+                channel.state = JitChannelState::Failed;
+                database.persist_jit_channel(&channel).await?;
+                fail_htlcs(&database, &channel_manager, channel.user_channel_id).await?;
+            }
+            channels.insert(channel.user_channel_id, channel);
+        }
+        Ok(JitChannels {
+            settings,
+            database,
+            channel_manager,
+            liquidity_manager,
+            fee_estimator,
+            async_api_requests,
+            cltv_expiry_delta: user_config.channel_config.cltv_expiry_delta as u32,
+            channels: RwLock::new(channels),
+        })
+    }
+
+    /// The opening fee parameters that we currently advertise to clients.
+    pub fn offer(&self) -> Option<JitChannelOffer> {
+        offer(&self.settings)
+    }
+
+    pub fn list(&self) -> Result<Vec<JitChannel>> {
+        let channels = self
+            .channels
+            .read()
+            .map_err(|e| anyhow!("failed to acquire lock on jit channels {}", e))?;
+        let mut channels: Vec<JitChannel> = channels.values().cloned().collect();
+        channels.sort_by_key(|c| c.created_at);
+        Ok(channels)
+    }
+
+    pub fn is_jit_channel(&self, user_channel_id: u64) -> bool {
+        self.channels
+            .read()
+            .is_ok_and(|channels| channels.contains_key(&user_channel_id))
+    }
+
+    /// Process the LSPS events that the liquidity manager has queued up from our clients.
+    pub async fn process_pending_events(&self) -> Result<()> {
+        for event in self.liquidity_manager.get_and_clear_pending_events() {
+            let Some(offer) = self.offer() else {
+                warn!("LSPS: Ignoring event because LSPS2 is disabled");
+                continue;
+            };
+            match event {
+                LiquidityEvent::LSPS2(LSPS2Event::GetInfo {
+                    request_id,
+                    counterparty_node_id,
+                    ..
+                }) => {
+                    info!("LSPS2: Get info request from {counterparty_node_id}");
+                    self.liquidity_manager
+                        .opening_fee_params_generated(
+                            counterparty_node_id,
+                            request_id,
+                            vec![opening_fee_params(&offer)],
+                            offer.min_payment_size_msat,
+                            offer.max_payment_size_msat,
+                        )
+                        .map_err(ldk_error)?;
+                }
+                LiquidityEvent::LSPS2(LSPS2Event::BuyRequest {
+                    request_id,
+                    counterparty_node_id,
+                    opening_fee_params,
+                    payment_size_msat,
+                    ..
+                }) => {
+                    let user_channel_id: u64 = random::<u64>() / 2; // To fit into the database INT
+                    let intercept_scid = self.channel_manager.get_intercept_scid();
+                    info!("LSPS2: Buy request from {counterparty_node_id}. Issuing intercept SCID {intercept_scid} for channel {user_channel_id}");
+                    self.insert(JitChannel::bought(
+                        user_channel_id,
+                        counterparty_node_id,
+                        intercept_scid,
+                        payment_size_msat,
+                        opening_fee_params.min_fee_msat,
+                        opening_fee_params.proportional,
+                    ))
+                    .await?;
+                    self.liquidity_manager
+                        .invoice_parameters_generated(
+                            counterparty_node_id,
+                            request_id,
+                            intercept_scid,
+                            self.cltv_expiry_delta,
+                            false,
+                            user_channel_id as u128,
+                        )
+                        .map_err(ldk_error)?;
+                }
+                LiquidityEvent::LSPS2(LSPS2Event::OpenChannel {
+                    their_network_key,
+                    amt_to_forward_msat,
+                    opening_fee_msat,
+                    user_channel_id,
+                }) => {
+                    self.open_channel(
+                        their_network_key,
+                        amt_to_forward_msat,
+                        opening_fee_msat,
+                        user_channel_id as u64,
+                    )
+                    .await?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// An HTLC arrived for one of our intercept SCIDs.
+    pub async fn htlc_intercepted(
+        &self,
+        intercept_id: InterceptId,
+        requested_next_hop_scid: u64,
+        expected_outbound_amount_msat: u64,
+    ) -> Result<()> {
+        let user_channel_id = self
+            .channels
+            .read()
+            .map_err(|e| anyhow!("failed to acquire lock on jit channels {}", e))?
+            .values()
+            .find(|c| c.intercept_scid == requested_next_hop_scid)
+            .map(|c| c.user_channel_id);
+        let Some(user_channel_id) = user_channel_id else {
+            warn!("LSPS2: Failing intercepted HTLC for unknown SCID {requested_next_hop_scid}");
+            self.channel_manager
+                .fail_intercepted_htlc(intercept_id)
+                .map_err(ldk_error)?;
+            return Ok(());
+        };
+        self.database
+            .persist_jit_channel_htlc(user_channel_id, &intercept_id)
+            .await?;
+        if let Err(e) = self.liquidity_manager.htlc_intercepted(
+            requested_next_hop_scid,
+            intercept_id,
+            expected_outbound_amount_msat,
+        ) {
+            self.channel_manager
+                .fail_intercepted_htlc(intercept_id)
+                .map_err(ldk_error)?;
+            bail!(ldk_error(e));
+        }
+        Ok(())
+    }
+
+    /// Forward the intercepted HTLCs (minus the opening fee) when the channel is ready.
+    pub async fn channel_ready(
+        &self,
+        user_channel_id: u64,
+        channel_id: &ChannelId,
+        counterparty_node_id: &PublicKey,
+    ) -> Result<()> {
+        if !self.is_jit_channel(user_channel_id) {
+            return Ok(());
+        }
+        self.update(user_channel_id, |channel| {
+            channel.channel_id = Some(*channel_id);
+            channel.state = JitChannelState::ChannelReady;
+        })
+        .await?;
+        info!("LSPS2: Channel {user_channel_id} is ready. Forwarding intercepted HTLCs");
+        self.liquidity_manager
+            .channel_ready(user_channel_id as u128, channel_id, counterparty_node_id)
+            .map_err(ldk_error)?;
+        self.database
+            .delete_jit_channel_htlcs(user_channel_id)
+            .await
+    }
+
+    pub async fn channel_closed(&self, user_channel_id: u64) -> Result<()> {
+        if !self.is_jit_channel(user_channel_id) {
+            return Ok(());
+        }
+        let mut failed = false;
+        self.update(user_channel_id, |channel| {
+            if channel.state != JitChannelState::ChannelReady {
+                channel.state = JitChannelState::Failed;
+                failed = true;
+            }
+        })
+        .await?;
+        if failed {
+            fail_htlcs(&self.database, &self.channel_manager, user_channel_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn open_channel(
+        &self,
+        their_network_key: PublicKey,
+        amt_to_forward_msat: u64,
+        opening_fee_msat: u64,
+        user_channel_id: u64,
+    ) -> Result<()> {
+        // LDK pays the commitment transaction at the anchor or the non anchor feerate.
+        let feerate = self
+            .fee_estimator
+            .get_est_sat_per_1000_weight(ConfirmationTarget::AnchorChannelFee)
+            .max(
+                self.fee_estimator
+                    .get_est_sat_per_1000_weight(ConfirmationTarget::NonAnchorChannelFee),
+            );
+        let channel_value_satoshis = channel_size(
+            amt_to_forward_msat,
+            feerate,
+            self.settings.lsps2_min_channel_sats,
+        );
+        info!("LSPS2: Opening channel {user_channel_id} of {channel_value_satoshis} sats to {their_network_key}, forwarding {amt_to_forward_msat} msat with an opening fee of {opening_fee_msat} msat");
+        self.update(user_channel_id, |channel| {
+            channel.opening_fee_msat = Some(opening_fee_msat);
+            channel.state = JitChannelState::PendingChannelOpen;
+        })
+        .await?;
+
+        let mut user_config = self
+            .channel_manager
+            .get_current_default_configuration()
+            .clone();
+        // The opening fee is skimmed from the first HTLCs we forward over the channel.
+        user_config.channel_config.accept_underpaying_htlcs = true;
+        if let Err(e) = self.channel_manager.create_channel(
+            their_network_key,
+            channel_value_satoshis,
+            0,
+            user_channel_id as u128,
+            Some(user_config),
+        ) {
+            self.update(user_channel_id, |channel| {
+                channel.state = JitChannelState::Failed;
+            })
+            .await?;
+            fail_htlcs(&self.database, &self.channel_manager, user_channel_id).await?;
+            bail!(ldk_error(e));
+        }
+        let receiver = self
+            .async_api_requests
+            .funding_transactions
+            .insert(user_channel_id, api::FeeRate::default())
+            .await;
+        tokio::spawn(async move {
+            match receiver.await {
+                Ok(Ok(tx)) => info!(
+                    "LSPS2: Funded channel {user_channel_id} with transaction {}",
+                    tx.txid()
+                ),
+                Ok(Err(e)) => warn!("LSPS2: Failed to fund channel {user_channel_id}: {e}"),
+                Err(e) => warn!("LSPS2: Failed to fund channel {user_channel_id}: {e}"),
+            }
+        });
+        Ok(())
+    }
+
+    async fn insert(&self, channel: JitChannel) -> Result<()> {
+        self.database.persist_jit_channel(&channel).await?;
+        self.channels
+            .write()
+            .map_err(|e| anyhow!("failed to acquire lock on jit channels {}", e))?
+            .insert(channel.user_channel_id, channel);
+        Ok(())
+    }
+
+    async fn update(&self, user_channel_id: u64, f: impl FnOnce(&mut JitChannel)) -> Result<()> {
+        let channel = {
+            let mut channels = self
+                .channels
+                .write()
+                .map_err(|e| anyhow!("failed to acquire lock on jit channels {}", e))?;
+            let Some(channel) = channels.get_mut(&user_channel_id) else {
+                return Ok(());
+            };
+            f(channel);
+            channel.clone()
+        };
+        self.database.persist_jit_channel(&channel).await
+    }
+}
+
+/// Fail the HTLCs that were waiting for a channel that will never be ready. LDK has forgotten the
+/// ones that it already failed itself when they were about to expire.
+async fn fail_htlcs(
+    database: &LdkDatabase,
+    channel_manager: &ChannelManager,
+    user_channel_id: u64,
+) -> Result<()> {
+    for intercept_id in database.fetch_jit_channel_htlcs(user_channel_id).await? {
+        if let Err(e) = channel_manager.fail_intercepted_htlc(intercept_id) {
+            warn!(
+                "LSPS2: Failed to fail intercepted HTLC {} of channel {user_channel_id}: {e:?}",
+                intercept_id.0.to_hex()
+            );
+        }
+    }
+    database.delete_jit_channel_htlcs(user_channel_id).await
+}
+
+/// Size (sats) of a just in time channel that can forward the payment. We fund the channel, so the
+/// commitment transaction fee, the anchors and the reserve that the client asks for come out of our
+/// balance. LDK holds back twice the commitment fee with our HTLC and one more as a fee spike buffer.
+pub fn channel_size(amt_to_forward_msat: u64, feerate_per_kw: u32, min_channel_sats: u64) -> u64 {
+    let commitment_fee_sats =
+        2 * feerate_per_kw as u64 * (COMMITMENT_TX_BASE_WEIGHT + 2 * COMMITMENT_TX_WEIGHT_PER_HTLC)
+            / 1000;
+    let spendable_sats =
+        (amt_to_forward_msat + 999) / 1000 + commitment_fee_sats + ANCHOR_OUTPUTS_SATS;
+    // The 1% reserve is of the whole channel.
+    let with_reserve = (spendable_sats * 100 + 98) / 99;
+    with_reserve
+        .max(spendable_sats + MIN_CHANNEL_RESERVE_SATS)
+        .max(min_channel_sats)
+}
+
+/// LSPS2 is only enabled when the opening fees are configured, so that we never sell channels for free.
+pub fn offer(settings: &Settings) -> Option<JitChannelOffer> {
+    let (Some(min_fee_msat), Some(proportional)) =
+        (settings.lsps2_min_fee_msat, settings.lsps2_fee_proportional)
+    else {
+        return None;
+    };
+    if !settings.lsps2_enabled {
+        return None;
+    }
+    let valid_until =
+        OffsetDateTime::now_utc() + Duration::from_secs(settings.lsps2_offer_valid_secs);
+    Some(JitChannelOffer {
+        min_fee_msat,
+        proportional,
+        valid_until: to_iso8601(&valid_until),
+        min_lifetime: settings.lsps2_min_lifetime,
+        max_client_to_self_delay: settings.lsps2_max_client_to_self_delay,
+        min_payment_size_msat: settings.lsps2_min_payment_size_msat,
+        max_payment_size_msat: settings.lsps2_max_payment_size_msat,
+    })
+}
+
+fn opening_fee_params(offer: &JitChannelOffer) -> RawOpeningFeeParams {
+    RawOpeningFeeParams {
+        min_fee_msat: offer.min_fee_msat,
+        proportional: offer.proportional,
+        valid_until: offer.valid_until.clone(),
+        min_lifetime: offer.min_lifetime,
+        max_client_to_self_delay: offer.max_client_to_self_delay,
+    }
+}
+
+/// The LSPS2 opening fee for a payment: max(min_fee_msat, payment_size_msat * proportional / 1000000).
+pub fn opening_fee(payment_size_msat: u64, min_fee_msat: u64, proportional: u32) -> u64 {
+    let proportional_fee = (payment_size_msat as u128 * proportional as u128 + 999_999) / 1_000_000;
+    min_fee_msat.max(u64::try_from(proportional_fee).unwrap_or(u64::MAX))
+}
+
+// LSPS uses ISO8601 (UTC) strings for timestamps.
+fn to_iso8601(time: &OffsetDateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.000Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use api::JitChannelState;
+    use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use time::OffsetDateTime;
+
+    use crate::database::jit_channel::JitChannel;
+    use crate::settings::Settings;
+
+    use super::{channel_size, offer, opening_fee, opening_fee_params, to_iso8601};
+
+    #[test]
+    fn test_lsps2_disabled_without_fees() {
+        let mut settings = Settings {
+            lsps2_enabled: true,
+            ..Default::default()
+        };
+        assert!(offer(&settings).is_none());
+        settings.lsps2_min_fee_msat = Some(2000);
+        assert!(offer(&settings).is_none());
+        settings.lsps2_fee_proportional = Some(1000);
+        assert!(offer(&settings).is_some());
+        settings.lsps2_enabled = false;
+        assert!(offer(&settings).is_none());
+    }
+
+    #[test]
+    fn test_get_info_and_buy() {
+        let settings = Settings {
+            lsps2_enabled: true,
+            lsps2_min_fee_msat: Some(2000),
+            lsps2_fee_proportional: Some(1000),
+            lsps2_min_lifetime: 1000,
+            lsps2_max_client_to_self_delay: 144,
+            ..Default::default()
+        };
+        let offer = offer(&settings).unwrap();
+
+        // The client gets the configured fees in the get_info response.
+        let params = opening_fee_params(&offer);
+        assert_eq!(2000, params.min_fee_msat);
+        assert_eq!(1000, params.proportional);
+        assert_eq!(1000, params.min_lifetime);
+        assert_eq!(144, params.max_client_to_self_delay);
+        assert_eq!(offer.valid_until, params.valid_until);
+
+        // And buys a channel with them.
+        let client = PublicKey::from_secret_key(
+            &Secp256k1::new(),
+            &SecretKey::from_slice(&[1u8; 32]).unwrap(),
+        );
+        let channel = JitChannel::bought(
+            5,
+            client,
+            42,
+            Some(10_000_001),
+            params.min_fee_msat,
+            params.proportional,
+        );
+        assert_eq!(Some(10001), channel.opening_fee_msat);
+        assert_eq!(JitChannelState::AwaitingPayment, channel.state);
+        assert_eq!(None, channel.channel_id);
+
+        // Without a payment size the fee is only known when the payment arrives.
+        let channel = JitChannel::bought(6, client, 43, None, 2000, 1000);
+        assert_eq!(None, channel.opening_fee_msat);
+    }
+
+    #[test]
+    fn test_opening_fee() {
+        assert_eq!(opening_fee(1_000_000, 2000, 1000), 2000);
+        assert_eq!(opening_fee(10_000_000, 2000, 1000), 10000);
+        assert_eq!(opening_fee(10_000_001, 2000, 1000), 10001);
+    }
+
+    #[test]
+    fn test_channel_size() {
+        // The payment, twice the commitment fee (742 sats), the anchors and the 1% reserve.
+        assert_eq!(channel_size(1_000_000_000, 253, 100_000), 1_011_518);
+        // A payment of the minimum channel size does not fit into a channel of that size.
+        assert_eq!(channel_size(100_000_000, 253, 100_000), 102_427);
+        assert_eq!(channel_size(10_000_000, 253, 100_000), 100_000);
+        // Small channels keep at least 1000 sats in reserve.
+        assert_eq!(channel_size(50_000_000, 5000, 0), 66_340);
+    }
+
+    #[test]
+    fn test_to_iso8601() {
+        assert_eq!(
+            to_iso8601(&OffsetDateTime::from_unix_timestamp(1699174987).unwrap()),
+            "2023-11-05T09:03:07.000Z"
+        );
+    }
+}
@@ -1,24 +1,46 @@
+use std::time::Duration;
+
 use anyhow::Result;
+use bitcoin::hashes::sha256;
 use lightning::{
-    ln::{channelmanager::ChannelDetails, ChannelId},
-    routing::gossip::{ChannelInfo, NodeId, NodeInfo},
+    ln::{
+        channelmanager::{ChannelDetails, PaymentId},
+        ChannelId, PaymentHash, PaymentPreimage,
+    },
+    offers::{offer::Offer as Bolt12Offer, refund::Refund},
+    routing::{
+        gossip::{ChannelInfo, NodeId, NodeInfo},
+        router::RouteHint,
+    },
     util::{config::UserConfig, indexed_map::IndexedMap},
 };
 
 use crate::{
     database::{
-        channel::Channel,
-        forward::{Forward, ForwardStatus, TotalForwards},
-        invoice::Invoice,
-        payment::{Payment, PaymentDirection},
+        channel::{Channel, ChannelFilter},
+        forward::{Forward, ForwardFilter, TotalForwards},
+        invoice::{Invoice, InvoiceFilter},
+        jit_channel::JitChannel,
+        macaroon_root_key::MacaroonRootKey,
+        offer::{InvoiceRequest, Offer},
+        payment::{Payment, PaymentFilter},
+        payment_attempt::PaymentAttempt,
+        webhook::Webhook,
+        Page,
+    },
+    ldk::{
+        analytics::ForwardStats, channel_acceptor::InboundChannelPolicy,
+        channel_backup::StaticChannelBackup, payment_limits::PaymentLimits,
     },
     MillisatAmount,
 };
 
 use crate::api::SocketAddress;
-use api::FeeRate;
+use api::{FeeRate, JitChannelOffer, NodeEvent};
 use async_trait::async_trait;
 use bitcoin::{secp256k1::PublicKey, Network, Transaction, Txid};
+use tokio::sync::broadcast;
+use uuid::Uuid;
 
 #[async_trait]
 pub trait LightningInterface: Send + Sync {
@@ -104,9 +126,33 @@ pub trait LightningInterface: Send + Sync {
 
     fn user_config(&self) -> UserConfig;
 
-    async fn pay_invoice(&self, invoice: Invoice, label: Option<String>) -> Result<Payment>;
+    /// The highest routing fee that a payment of the amount pays with these limits.
+    fn max_fee_msat(&self, limits: &PaymentLimits, amount: MillisatAmount) -> MillisatAmount;
+
+    /// Pay the invoice. Unless wait is set the pending payment is returned as soon as it has been
+    /// sent and the result can be fetched later with get_payment.
+    async fn pay_invoice(
+        &self,
+        invoice: Invoice,
+        label: Option<String>,
+        limits: PaymentLimits,
+        wait: bool,
+    ) -> Result<Payment>;
+
+    async fn keysend_payment(
+        &self,
+        payee: NodeId,
+        amount: MillisatAmount,
+        limits: PaymentLimits,
+        wait: bool,
+    ) -> Result<Payment>;
 
-    async fn keysend_payment(&self, payee: NodeId, amount: MillisatAmount) -> Result<Payment>;
+    async fn probe(
+        &self,
+        payee: PublicKey,
+        amount: MillisatAmount,
+        route_hints: Vec<RouteHint>,
+    ) -> Result<ProbeResult>;
 
     async fn generate_invoice(
         &self,
@@ -116,13 +162,61 @@ pub trait LightningInterface: Send + Sync {
         expiry: Option<u32>,
     ) -> Result<Invoice>;
 
-    async fn list_invoices(&self, label: Option<String>) -> Result<Vec<Invoice>>;
+    async fn generate_hold_invoice(
+        &self,
+        label: String,
+        amount: u64,
+        description: String,
+        expiry: Option<u32>,
+        payment_hash: PaymentHash,
+    ) -> Result<Invoice>;
+
+    async fn settle_hold_invoice(&self, preimage: PaymentPreimage) -> Result<()>;
+
+    async fn cancel_hold_invoice(&self, payment_hash: PaymentHash) -> Result<()>;
+
+    async fn list_invoices(&self, filter: InvoiceFilter) -> Result<Vec<Invoice>>;
+
+    async fn list_payments(&self, filter: PaymentFilter) -> Result<Vec<Payment>>;
+
+    async fn get_payment(&self, id: PaymentId) -> Result<Option<Payment>>;
+
+    async fn get_payment_attempts(&self, id: PaymentId) -> Result<Vec<PaymentAttempt>>;
+
+    async fn create_offer(
+        &self,
+        description: String,
+        amount: Option<MillisatAmount>,
+        expiry: Option<Duration>,
+        issuer: Option<String>,
+        label: Option<String>,
+    ) -> Result<Offer>;
+
+    async fn create_refund(
+        &self,
+        description: String,
+        amount: MillisatAmount,
+        expiry: Option<Duration>,
+        label: Option<String>,
+    ) -> Result<Offer>;
+
+    async fn list_offers(&self, label: Option<String>) -> Result<Vec<Offer>>;
+
+    async fn pay_offer(
+        &self,
+        offer: Bolt12Offer,
+        amount: Option<MillisatAmount>,
+        quantity: Option<u64>,
+        payer_note: Option<String>,
+        label: Option<String>,
+    ) -> Result<Payment>;
+
+    async fn request_refund_payment(&self, refund: Refund) -> Result<()>;
 
-    async fn list_payments(
+    async fn list_invoice_requests(
         &self,
-        bolt11: Option<Invoice>,
-        direction: Option<PaymentDirection>,
-    ) -> Result<Vec<Payment>>;
+        offer_id: Option<sha256::Hash>,
+    ) -> Result<Vec<InvoiceRequest>>;
 
     async fn estimated_channel_liquidity_range(
         &self,
@@ -130,11 +224,45 @@ pub trait LightningInterface: Send + Sync {
         target: &NodeId,
     ) -> Result<Option<(u64, u64)>>;
 
-    async fn fetch_forwards(&self, status: Option<ForwardStatus>) -> Result<Vec<Forward>>;
+    async fn fetch_forwards(&self, filter: ForwardFilter) -> Result<Vec<Forward>>;
 
     async fn fetch_total_forwards(&self) -> Result<TotalForwards>;
 
-    async fn channel_history(&self) -> Result<Vec<Channel>>;
+    /// Routing stats per channel and per peer of the forwards in the time window of the page.
+    async fn forward_stats(&self, window: Page) -> Result<ForwardStats>;
+
+    async fn channel_history(&self, filter: ChannelFilter) -> Result<Vec<Channel>>;
+
+    async fn channel_backup(&self) -> Result<StaticChannelBackup>;
+
+    fn encrypt_channel_backup(&self, backup: &StaticChannelBackup) -> Result<Vec<u8>>;
+
+    fn inbound_channel_policy(&self) -> Result<InboundChannelPolicy>;
+
+    /// Replace the inbound channel policy and persist it so that it survives a restart.
+    async fn set_inbound_channel_policy(&self, policy: InboundChannelPolicy) -> Result<()>;
+
+    /// None when LSPS2 is disabled.
+    fn jit_channel_offer(&self) -> Option<JitChannelOffer>;
+
+    fn list_jit_channels(&self) -> Result<Vec<JitChannel>>;
+
+    /// Receive the events that the node handles from now on.
+    fn subscribe_events(&self) -> broadcast::Receiver<NodeEvent>;
+
+    async fn create_webhook(&self, webhook: Webhook) -> Result<()>;
+
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>>;
+
+    /// Returns false when there is no webhook with the ID.
+    async fn delete_webhook(&self, id: Uuid) -> Result<bool>;
+
+    async fn create_macaroon_root_key(&self, root_key: MacaroonRootKey) -> Result<()>;
+
+    async fn list_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>>;
+
+    /// Revokes every macaroon of the root key. Returns false when there is no root key with the ID.
+    async fn delete_macaroon_root_key(&self, id: Uuid) -> Result<bool>;
 }
 
 pub struct Peer {
@@ -166,3 +294,17 @@ pub struct OpenChannelResult {
     pub txid: Txid,
     pub channel_id: ChannelId,
 }
+
+/// The outcome of probing one or more paths to a destination.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProbeResult {
+    Succeeded {
+        paths: usize,
+    },
+    Failed {
+        paths: usize,
+        // The channel that could not forward the probe and the node it leads to.
+        short_channel_id: Option<u64>,
+        node_id: Option<PublicKey>,
+    },
+}
@@ -0,0 +1,184 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::features::{InitFeatures, NodeFeatures};
+use lightning::ln::msgs::{DecodeError, ErrorMessage, LightningError};
+use lightning::ln::peer_handler::CustomMessageHandler;
+use lightning::ln::wire::{CustomMessageReader, Type};
+use lightning::ln::ChannelId;
+use lightning::util::ser::{Writeable, Writer};
+use log::{error, warn};
+
+use super::{ChannelManager, LiquidityManager};
+
+/// The type of the error message in BOLT 1.
+const ERROR_MESSAGE_TYPE: u16 = 17;
+
+type LiquidityMessage = <LiquidityManager as CustomMessageReader>::CustomMessage;
+
+#[derive(Debug)]
+pub(crate) enum KldMessage {
+    Liquidity(LiquidityMessage),
+    Error(ErrorMessage),
+}
+
+impl Type for KldMessage {
+    fn type_id(&self) -> u16 {
+        match self {
+            KldMessage::Liquidity(msg) => msg.type_id(),
+            KldMessage::Error(_) => ERROR_MESSAGE_TYPE,
+        }
+    }
+}
+
+impl Writeable for KldMessage {
+    fn write<W: Writer>(&self, writer: &mut W) -> Result<(), io::Error> {
+        match self {
+            KldMessage::Liquidity(msg) => msg.write(writer),
+            KldMessage::Error(msg) => msg.write(writer),
+        }
+    }
+}
+
+/// Custom message handler of the peer manager. It passes the LSPS messages to the LiquidityManager
+/// and sends the error messages for the inbound channels that we reject, as the ChannelManager can
+/// only send its own generic error. In recovery mode it also sends the errors that make our peers
+/// force close the channels that the ChannelManager does not know any more.
+pub(crate) struct KldMessageHandler {
+    liquidity_manager: Arc<LiquidityManager>,
+    channel_manager: Arc<ChannelManager>,
+    rejected_channels: Mutex<Vec<(PublicKey, ErrorMessage)>>,
+    force_close_requests: Mutex<Vec<(PublicKey, ErrorMessage)>>,
+}
+
+impl KldMessageHandler {
+    pub fn new(
+        liquidity_manager: Arc<LiquidityManager>,
+        channel_manager: Arc<ChannelManager>,
+    ) -> KldMessageHandler {
+        KldMessageHandler {
+            liquidity_manager,
+            channel_manager,
+            rejected_channels: Mutex::new(vec![]),
+            force_close_requests: Mutex::new(vec![]),
+        }
+    }
+
+    /// Reject an inbound channel request with our own reason. The ChannelManager only forgets the
+    /// channel once the error is queued for the peer, so the peer gets our error before the generic
+    /// one of the ChannelManager.
+    pub fn reject_channel(
+        &self,
+        counterparty_node_id: PublicKey,
+        temporary_channel_id: ChannelId,
+        reason: String,
+    ) {
+        match self.rejected_channels.lock() {
+            Ok(mut rejected_channels) => rejected_channels.push((
+                counterparty_node_id,
+                ErrorMessage {
+                    channel_id: temporary_channel_id,
+                    data: reason,
+                },
+            )),
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    /// Ask a connected peer to force close a channel that we lost after restoring a channel backup.
+    /// A peer closes the channel when it gets an error for it, we have no state to close it ourselves.
+    pub fn request_force_close(&self, counterparty_node_id: PublicKey, channel_id: ChannelId) {
+        match self.force_close_requests.lock() {
+            Ok(mut force_close_requests) => force_close_requests.push((
+                counterparty_node_id,
+                ErrorMessage {
+                    channel_id,
+                    data: "Channel state was lost, please force close the channel".to_string(),
+                },
+            )),
+            Err(e) => error!("{e}"),
+        }
+    }
+}
+
+impl CustomMessageReader for KldMessageHandler {
+    type CustomMessage = KldMessage;
+
+    fn read<R: io::Read>(
+        &self,
+        message_type: u16,
+        buffer: &mut R,
+    ) -> Result<Option<KldMessage>, DecodeError> {
+        // Error messages are read by the peer manager, they never get here.
+        Ok(self
+            .liquidity_manager
+            .read(message_type, buffer)?
+            .map(KldMessage::Liquidity))
+    }
+}
+
+impl CustomMessageHandler for KldMessageHandler {
+    fn handle_custom_message(
+        &self,
+        msg: KldMessage,
+        sender_node_id: &PublicKey,
+    ) -> Result<(), LightningError> {
+        match msg {
+            KldMessage::Liquidity(msg) => self
+                .liquidity_manager
+                .handle_custom_message(msg, sender_node_id),
+            KldMessage::Error(_) => Ok(()),
+        }
+    }
+
+    fn get_and_clear_pending_msg(&self) -> Vec<(PublicKey, KldMessage)> {
+        let rejected_channels = match self.rejected_channels.lock() {
+            Ok(mut rejected_channels) => std::mem::take(&mut *rejected_channels),
+            Err(e) => {
+                error!("{e}");
+                vec![]
+            }
+        };
+        let mut messages = vec![];
+        for (counterparty_node_id, error) in rejected_channels {
+            // This is how the ChannelManager rejects a channel that it has not accepted yet. Nothing
+            // was funded so nothing is broadcast. Its error message is only sent the next time that
+            // the peer manager processes events, after ours.
+            if let Err(e) = self
+                .channel_manager
+                .force_close_without_broadcasting_txn(&error.channel_id, &counterparty_node_id)
+            {
+                warn!(
+                    "Failed to close rejected channel {}: {e:?}",
+                    error.channel_id.to_hex()
+                );
+            }
+            messages.push((counterparty_node_id, KldMessage::Error(error)));
+        }
+        match self.force_close_requests.lock() {
+            Ok(mut force_close_requests) => messages.extend(
+                force_close_requests
+                    .drain(..)
+                    .map(|(node_id, error)| (node_id, KldMessage::Error(error))),
+            ),
+            Err(e) => error!("{e}"),
+        }
+        messages.extend(
+            self.liquidity_manager
+                .get_and_clear_pending_msg()
+                .into_iter()
+                .map(|(node_id, msg)| (node_id, KldMessage::Liquidity(msg))),
+        );
+        messages
+    }
+
+    fn provided_node_features(&self) -> NodeFeatures {
+        self.liquidity_manager.provided_node_features()
+    }
+
+    fn provided_init_features(&self, their_node_id: &PublicKey) -> InitFeatures {
+        self.liquidity_manager.provided_init_features(their_node_id)
+    }
+}
@@ -1,22 +1,35 @@
+pub mod analytics;
+pub mod channel_acceptor;
+pub mod channel_backup;
 pub mod channel_utils;
 pub mod controller;
 mod event_handler;
+pub mod jit_channels;
 pub mod lightning_interface;
+mod message_handler;
+pub mod payment_limits;
 mod peer_manager;
+mod prober;
+mod rapid_gossip_sync;
+mod sweeper;
+mod webhooks;
 
 use std::sync::{Arc, RwLock};
 
-use crate::database::LdkDatabase;
+use crate::database::{LdkDatabase, WalletDatabase};
 use crate::logger::KldLogger;
 use anyhow::anyhow;
 use bitcoin::hashes::hex::ToHex;
 use lightning::{
     chain::{chainmonitor, Filter},
-    events::HTLCDestination,
+    events::{bump_transaction, HTLCDestination},
     ln::{
-        channelmanager::{PaymentSendFailure, RetryableSendFailure, SimpleArcChannelManager},
+        channelmanager::{
+            PaymentSendFailure, ProbeSendFailure, RetryableSendFailure, SimpleArcChannelManager,
+        },
         msgs::{DecodeError, LightningError},
     },
+    offers::parse::{Bolt12ParseError, Bolt12SemanticError},
     onion_message::SimpleArcOnionMessenger,
     routing::{
         gossip,
@@ -29,10 +42,13 @@ use lightning::{
 use lightning_invoice::SignOrCreationError;
 
 pub use controller::Controller;
-pub use lightning_interface::{LightningInterface, OpenChannelResult, Peer, PeerStatus};
+pub use lightning_interface::{
+    LightningInterface, OpenChannelResult, Peer, PeerStatus, ProbeResult,
+};
 use log::warn;
 
 use crate::bitcoind::BitcoindClient;
+use crate::wallet::Wallet;
 
 /// The minimum feerate we are allowed to send, as specify by LDK (sats/kwu).
 pub static MIN_FEERATE: u32 = 2000;
@@ -66,6 +82,13 @@ pub(crate) type ChannelManager =
 pub(crate) type OnionMessenger =
     SimpleArcOnionMessenger<ChainMonitor, BitcoindClient, BitcoindClient, KldLogger>;
 
+pub(crate) type BumpTransactionEventHandler = bump_transaction::BumpTransactionEventHandler<
+    Arc<BitcoindClient>,
+    Arc<bump_transaction::Wallet<Arc<Wallet<WalletDatabase, BitcoindClient>>, Arc<KldLogger>>>,
+    Arc<KeysManager>,
+    Arc<KldLogger>,
+>;
+
 pub type Scorer = ProbabilisticScorer<Arc<NetworkGraph>, Arc<KldLogger>>;
 
 pub(crate) type KldRouter = DefaultRouter<
@@ -113,6 +136,14 @@ pub fn retryable_send_failure(error: RetryableSendFailure) -> anyhow::Error {
     }
 }
 
+pub fn bolt12_semantic_error(error: Bolt12SemanticError) -> anyhow::Error {
+    anyhow!("Bolt12 error: {error:?}")
+}
+
+pub fn bolt12_parse_error(error: Bolt12ParseError) -> anyhow::Error {
+    anyhow!("Failed to parse bolt12: {error:?}")
+}
+
 pub fn sign_or_creation_error(error: SignOrCreationError) -> anyhow::Error {
     match error {
         SignOrCreationError::SignError(()) => anyhow!("Error signing invoice"),
@@ -153,6 +184,13 @@ pub fn payment_send_failure(error: PaymentSendFailure) -> anyhow::Error {
     }
 }
 
+pub fn probe_send_failure(error: ProbeSendFailure) -> anyhow::Error {
+    match error {
+        ProbeSendFailure::RouteNotFound => anyhow!("Probe failure: route not found"),
+        ProbeSendFailure::SendingFailed(e) => payment_send_failure(e),
+    }
+}
+
 pub fn decode_error(error: DecodeError) -> anyhow::Error {
     match error {
         DecodeError::UnknownVersion => anyhow!("Unknown version"),
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use lightning::ln::channelmanager::Retry;
+use lightning::routing::router::{PaymentParameters, RouteParameters};
+
+use crate::settings::Settings;
+use crate::MillisatAmount;
+
+/// Limits on the routes that a single payment may take. Limits that are not set fall back to
+/// the node wide defaults in the settings.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PaymentLimits {
+    /// Maximum routing fee as a percentage of the amount.
+    pub max_fee_percent: Option<f64>,
+    /// Routing fee (msat) that is always allowed, even when it is above max_fee_percent.
+    pub exempt_fee_msat: Option<MillisatAmount>,
+    /// How long to keep retrying the payment over other routes.
+    pub retry_for: Option<Duration>,
+    /// Maximum total CLTV expiry delta (blocks) of a route.
+    pub max_cltv_expiry_delta: Option<u32>,
+}
+
+impl PaymentLimits {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(percent) = self.max_fee_percent {
+            if !(0.0..=100.0).contains(&percent) {
+                bail!("maxfeepercent must be between 0 and 100");
+            }
+        }
+        if self.max_cltv_expiry_delta == Some(0) {
+            bail!("maxdelay must be greater than 0");
+        }
+        Ok(())
+    }
+
+    /// The highest routing fee we pay to send the amount.
+    pub fn max_fee_msat(&self, settings: &Settings, amount: MillisatAmount) -> MillisatAmount {
+        let percent = self
+            .max_fee_percent
+            .unwrap_or(settings.payment_max_fee_percent);
+        let exempt_fee = self
+            .exempt_fee_msat
+            .unwrap_or(settings.payment_exempt_fee_msat);
+        ((amount as f64 * percent / 100.0) as MillisatAmount).max(exempt_fee)
+    }
+
+    pub fn retry(&self, settings: &Settings) -> Retry {
+        Retry::Timeout(
+            self.retry_for
+                .unwrap_or(Duration::from_secs(settings.payment_retry_for_secs)),
+        )
+    }
+
+    pub fn route_parameters(
+        &self,
+        settings: &Settings,
+        mut payment_params: PaymentParameters,
+        amount: MillisatAmount,
+    ) -> RouteParameters {
+        payment_params.max_total_cltv_expiry_delta = self
+            .max_cltv_expiry_delta
+            .unwrap_or(settings.payment_max_cltv_expiry_delta);
+        RouteParameters {
+            payment_params,
+            final_value_msat: amount,
+            max_total_routing_fee_msat: Some(self.max_fee_msat(settings, amount)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use lightning::ln::channelmanager::Retry;
+
+    use crate::settings::Settings;
+
+    use super::PaymentLimits;
+
+    #[test]
+    fn test_payment_limits() {
+        let settings = Settings::default();
+        let defaults = PaymentLimits::default();
+        assert_eq!(5000, defaults.max_fee_msat(&settings, 100_000));
+        assert_eq!(50_000, defaults.max_fee_msat(&settings, 10_000_000));
+        assert!(
+            matches!(defaults.retry(&settings), Retry::Timeout(t) if t == Duration::from_secs(60))
+        );
+
+        let limits = PaymentLimits {
+            max_fee_percent: Some(1.0),
+            exempt_fee_msat: Some(0),
+            retry_for: Some(Duration::from_secs(10)),
+            max_cltv_expiry_delta: Some(144),
+        };
+        assert!(limits.validate().is_ok());
+        assert_eq!(1000, limits.max_fee_msat(&settings, 100_000));
+        assert!(
+            matches!(limits.retry(&settings), Retry::Timeout(t) if t == Duration::from_secs(10))
+        );
+
+        let invalid = PaymentLimits {
+            max_fee_percent: Some(101.0),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
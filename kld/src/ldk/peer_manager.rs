@@ -9,10 +9,8 @@ use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use bitcoin::secp256k1::PublicKey;
 use hex::FromHex;
-use ldk_lsp_client::LiquidityManager;
 use lightning::sign::KeysManager;
 use lightning::{
-    chain::Filter,
     ln::{channelmanager::SimpleArcChannelManager, peer_handler},
     onion_message::SimpleArcOnionMessenger,
     routing::gossip,
@@ -21,7 +19,8 @@ use lightning_net_tokio::SocketDescriptor;
 use log::{error, info, warn};
 use tokio::task::JoinHandle;
 
-use super::{ChainMonitor, ChannelManager, KldRouter};
+use super::message_handler::KldMessageHandler;
+use super::{ChainMonitor, ChannelManager};
 
 pub(crate) type PeerManager = peer_handler::PeerManager<
     SocketDescriptor,
@@ -35,19 +34,7 @@ pub(crate) type PeerManager = peer_handler::PeerManager<
     >,
     Arc<SimpleArcOnionMessenger<ChainMonitor, BitcoindClient, BitcoindClient, KldLogger>>,
     Arc<KldLogger>,
-    Arc<
-        LiquidityManager<
-            Arc<KeysManager>,
-            Arc<ChainMonitor>,
-            Arc<BitcoindClient>,
-            Arc<BitcoindClient>,
-            Arc<KldRouter>,
-            Arc<KeysManager>,
-            Arc<KldLogger>,
-            Arc<KeysManager>,
-            Arc<dyn Filter + Send + Sync>,
-        >,
-    >,
+    Arc<KldMessageHandler>,
     Arc<KeysManager>,
 >;
 
@@ -61,10 +48,12 @@ pub trait KuutamoPeerManger {
         peer_addr: SocketAddress,
     ) -> Result<()>;
 
+    /// Reconnect to the peers of our channels, and to the peers from a channel backup in recovery mode.
     fn keep_channel_peers_connected(
         &self,
         database: Arc<LdkDatabase>,
         channel_manager: Arc<ChannelManager>,
+        recovery_peers: Vec<PublicKey>,
     );
 
     fn get_connected_peers(&self) -> Vec<(PublicKey, Option<SocketAddress>)>;
@@ -137,6 +126,7 @@ impl KuutamoPeerManger for Arc<PeerManager> {
         &self,
         database: Arc<LdkDatabase>,
         channel_manager: Arc<ChannelManager>,
+        recovery_peers: Vec<PublicKey>,
     ) {
         let peer_manager = self.clone();
         tokio::spawn(async move {
@@ -146,6 +136,7 @@ impl KuutamoPeerManger for Arc<PeerManager> {
                     .list_channels()
                     .iter()
                     .map(|chan| chan.counterparty.node_id)
+                    .chain(recovery_peers.iter().copied())
                     .filter(|id| !connected_node_ids.iter().any(|(pk, _)| pk == id))
                 {
                     match database.fetch_peer(&unconnected_node_id).await {
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use bitcoin::secp256k1::PublicKey;
+use lightning::routing::gossip::NodeId;
+use log::debug;
+use rand::{thread_rng, Rng};
+
+use crate::settings::Settings;
+
+use super::{probe_send_failure, ChannelManager, NetworkGraph};
+
+/// Sends probes to the best connected nodes in the network graph. The results are not used
+/// directly, they update the scorer through the probe events so that later payments find better routes.
+pub(crate) struct Prober {
+    settings: Arc<Settings>,
+    channel_manager: Arc<ChannelManager>,
+    network_graph: Arc<NetworkGraph>,
+}
+
+impl Prober {
+    pub fn new(
+        settings: Arc<Settings>,
+        channel_manager: Arc<ChannelManager>,
+        network_graph: Arc<NetworkGraph>,
+    ) -> Prober {
+        Prober {
+            settings,
+            channel_manager,
+            network_graph,
+        }
+    }
+
+    /// Probe a random node out of the best connected nodes that we know of.
+    pub fn probe_popular_destination(&self) -> Result<()> {
+        let destinations = popular_destinations(
+            &self.network_graph,
+            &NodeId::from_pubkey(&self.channel_manager.get_our_node_id()),
+            self.settings.probing_destinations,
+        );
+        if destinations.is_empty() {
+            return Ok(());
+        }
+        let destination = destinations[thread_rng().gen_range(0..destinations.len())];
+        let probes = self
+            .channel_manager
+            .send_spontaneous_preflight_probes(
+                destination,
+                self.settings.probing_amount_msat,
+                40,
+                None,
+            )
+            .map_err(probe_send_failure)?;
+        debug!("Sent {} probes to {destination}", probes.len());
+        Ok(())
+    }
+}
+
+/// The nodes with the most channels in the network graph, excluding ourself.
+pub(crate) fn popular_destinations(
+    network_graph: &NetworkGraph,
+    our_node_id: &NodeId,
+    count: usize,
+) -> Vec<PublicKey> {
+    let graph = network_graph.read_only();
+    let mut nodes: Vec<(&NodeId, usize)> = graph
+        .nodes()
+        .unordered_iter()
+        .filter(|(node_id, _)| *node_id != our_node_id)
+        .map(|(node_id, info)| (node_id, info.channels.len()))
+        .collect();
+    nodes.sort_by(|a, b| b.1.cmp(&a.1));
+    nodes
+        .into_iter()
+        .take(count)
+        .filter_map(|(node_id, _)| {
+            node_id
+                .as_pubkey()
+                .context("invalid node ID in network graph")
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use bitcoin::Network;
+    use lightning::ln::features::ChannelFeatures;
+    use lightning::routing::gossip::NodeId;
+
+    use crate::ldk::NetworkGraph;
+    use crate::logger::KldLogger;
+
+    use super::popular_destinations;
+
+    fn public_key(byte: u8) -> PublicKey {
+        PublicKey::from_secret_key(
+            &Secp256k1::new(),
+            &SecretKey::from_slice(&[byte; 32]).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_popular_destinations() {
+        KldLogger::init("test", log::LevelFilter::Info);
+        let network_graph = NetworkGraph::new(Network::Regtest, KldLogger::global());
+        let (us, hub, a, b) = (public_key(1), public_key(2), public_key(3), public_key(4));
+        for (scid, (node_1, node_2)) in [(us, hub), (hub, a), (hub, b), (a, b)]
+            .into_iter()
+            .enumerate()
+        {
+            network_graph
+                .add_channel_from_partial_announcement(
+                    scid as u64,
+                    0,
+                    ChannelFeatures::empty(),
+                    node_1,
+                    node_2,
+                )
+                .unwrap();
+        }
+        let destinations = popular_destinations(&network_graph, &NodeId::from_pubkey(&us), 2);
+        assert_eq!(2, destinations.len());
+        assert_eq!(hub, destinations[0]);
+        assert!(!destinations.contains(&us));
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use lightning_rapid_gossip_sync::RapidGossipSync;
+use log::{debug, info};
+
+use crate::database::LdkDatabase;
+use crate::logger::KldLogger;
+use crate::settings::Settings;
+
+use super::NetworkGraph;
+
+pub(crate) type KldRapidGossipSync = RapidGossipSync<Arc<NetworkGraph>, Arc<KldLogger>>;
+
+/// Fills the network graph from a Rapid Gossip Sync server or a snapshot file, so that a new node
+/// can route payments without waiting for the whole graph to arrive over the peer to peer gossip.
+pub(crate) struct RapidGossipSyncer {
+    settings: Arc<Settings>,
+    database: Arc<LdkDatabase>,
+    network_graph: Arc<NetworkGraph>,
+    rapid_gossip_sync: Arc<KldRapidGossipSync>,
+    client: reqwest::Client,
+}
+
+impl RapidGossipSyncer {
+    pub fn new(
+        settings: Arc<Settings>,
+        database: Arc<LdkDatabase>,
+        network_graph: Arc<NetworkGraph>,
+    ) -> Result<RapidGossipSyncer> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()?;
+        Ok(RapidGossipSyncer {
+            settings,
+            database,
+            network_graph: network_graph.clone(),
+            rapid_gossip_sync: Arc::new(RapidGossipSync::new(network_graph, KldLogger::global())),
+            client,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.settings.rgs_url.is_some() || self.settings.rgs_snapshot_path.is_some()
+    }
+
+    pub fn rapid_gossip_sync(&self) -> Arc<KldRapidGossipSync> {
+        self.rapid_gossip_sync.clone()
+    }
+
+    /// Apply the snapshot file when the graph has never been synced.
+    pub fn bootstrap(&self) -> Result<()> {
+        if let Some(path) = &self.settings.rgs_snapshot_path {
+            if self
+                .network_graph
+                .get_last_rapid_gossip_sync_timestamp()
+                .is_none()
+            {
+                let timestamp = sync_from_file(&self.rapid_gossip_sync, path)?;
+                info!("Network graph bootstrapped from snapshot {path} up to {timestamp}");
+                self.database.write_graph(&self.network_graph)?;
+            } else {
+                debug!("Network graph is already synced, skipping snapshot {path}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch the gossip that the server has seen since our last sync and prune the channels that
+    /// have not been updated for two weeks, which the gossip sync does not remove by itself.
+    pub async fn sync(&self) -> Result<()> {
+        let Some(url) = &self.settings.rgs_url else {
+            return Ok(());
+        };
+        let timestamp = sync_from_server(
+            &self.client,
+            &self.rapid_gossip_sync,
+            &self.network_graph,
+            url,
+        )
+        .await?;
+        info!("Network graph synced from {url} up to {timestamp}");
+        self.network_graph.remove_stale_channels_and_tracking();
+        // The sync timestamp is part of the graph, so they are always stored together.
+        self.database.write_graph(&self.network_graph)?;
+        Ok(())
+    }
+}
+
+async fn sync_from_server(
+    client: &reqwest::Client,
+    rapid_gossip_sync: &KldRapidGossipSync,
+    network_graph: &NetworkGraph,
+    url: &str,
+) -> Result<u32> {
+    let last_sync_timestamp = network_graph
+        .get_last_rapid_gossip_sync_timestamp()
+        .unwrap_or_default();
+    let update_data = client
+        .get(snapshot_url(url, last_sync_timestamp))
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    rapid_gossip_sync
+        .update_network_graph(&update_data)
+        .map_err(|e| anyhow!("Failed to apply rapid gossip sync data: {e:?}"))
+}
+
+/// Snapshot files are provided by the operator so they are applied even when they are old.
+fn sync_from_file(rapid_gossip_sync: &KldRapidGossipSync, path: &str) -> Result<u32> {
+    let update_data = std::fs::read(path)?;
+    rapid_gossip_sync
+        .update_network_graph_no_std(&update_data, None)
+        .map_err(|e| anyhow!("Failed to apply rapid gossip sync snapshot {path}: {e:?}"))
+}
+
+fn snapshot_url(url: &str, last_sync_timestamp: u32) -> String {
+    format!("{}/{last_sync_timestamp}", url.trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::Network;
+    use lightning_rapid_gossip_sync::RapidGossipSync;
+    use test_utils::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use crate::{ldk::NetworkGraph, logger::KldLogger};
+
+    use super::{snapshot_url, sync_from_file, sync_from_server};
+
+    // An empty snapshot: prefix, chain hash, timestamp and no nodes, announcements or updates.
+    fn empty_snapshot(timestamp: u32) -> Vec<u8> {
+        let mut snapshot = vec![76, 68, 75, 1];
+        snapshot.extend_from_slice(genesis_block(Network::Regtest).block_hash().as_ref());
+        snapshot.extend_from_slice(&timestamp.to_be_bytes());
+        snapshot.extend_from_slice(&[0; 12]);
+        snapshot
+    }
+
+    #[test]
+    fn test_snapshot_url() {
+        assert_eq!(
+            "https://rapidsync.lightningdevkit.org/snapshot/0",
+            snapshot_url("https://rapidsync.lightningdevkit.org/snapshot/", 0)
+        );
+        assert_eq!(
+            "http://127.0.0.1/1700000000",
+            snapshot_url("http://127.0.0.1", 1700000000)
+        );
+    }
+
+    #[test]
+    fn test_sync_from_file() {
+        KldLogger::init("test", log::LevelFilter::Info);
+        let network_graph = Arc::new(NetworkGraph::new(Network::Regtest, KldLogger::global()));
+        let rapid_gossip_sync = RapidGossipSync::new(network_graph, KldLogger::global());
+
+        let snapshot = empty_snapshot(1_700_000_000);
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("snapshot");
+        std::fs::write(&path, &snapshot).unwrap();
+        let timestamp = sync_from_file(&rapid_gossip_sync, path.to_str().unwrap()).unwrap();
+        assert_eq!(1_700_000_000, timestamp);
+
+        assert!(sync_from_file(&rapid_gossip_sync, "/does/not/exist").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_from_server() {
+        KldLogger::init("test", log::LevelFilter::Info);
+        let network_graph = Arc::new(NetworkGraph::new(Network::Regtest, KldLogger::global()));
+        let rapid_gossip_sync = RapidGossipSync::new(network_graph.clone(), KldLogger::global());
+
+        // The server data must be less than two weeks old.
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let (first, second) = (now - 7200, now - 3600);
+
+        // Serves one snapshot per request and reports the path that was requested.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/snapshot", listener.local_addr().unwrap());
+        let (path_sender, mut paths) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for timestamp in [first, second] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 1024];
+                let n = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..n]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap().to_string();
+                path_sender.send(path).unwrap();
+                let body = empty_snapshot(timestamp);
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(header.as_bytes()).await.unwrap();
+                stream.write_all(&body).await.unwrap();
+            }
+        });
+
+        let client = reqwest::Client::new();
+        let timestamp = sync_from_server(&client, &rapid_gossip_sync, &network_graph, &url)
+            .await
+            .unwrap();
+        assert_eq!(first, timestamp);
+        assert_eq!("/snapshot/0", paths.recv().await.unwrap());
+        assert_eq!(
+            Some(first),
+            network_graph.get_last_rapid_gossip_sync_timestamp()
+        );
+
+        // The next sync continues from the timestamp in the graph.
+        let timestamp = sync_from_server(&client, &rapid_gossip_sync, &network_graph, &url)
+            .await
+            .unwrap();
+        assert_eq!(second, timestamp);
+        assert_eq!(format!("/snapshot/{first}"), paths.recv().await.unwrap());
+    }
+}
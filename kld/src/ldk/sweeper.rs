@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use bitcoin::blockdata::locktime::PackedLockTime;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::Txid;
+use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use lightning::sign::KeysManager;
+use log::{debug, error, info};
+
+use crate::bitcoind::bitcoind_interface::BitcoindInterface;
+use crate::bitcoind::BitcoindClient;
+use crate::database::spendable_output::{SpendableOutput, SpendableOutputStatus};
+use crate::database::{LdkDatabase, WalletDatabase};
+use crate::settings::Settings;
+use crate::wallet::{Wallet, WalletInterface};
+
+/// Sweeps the outputs that LDK hands to us in SpendableOutputs events into the on-chain wallet.
+/// Sweep transactions are re-broadcast and fee bumped until they confirm, the outputs are only
+/// marked spent once their sweep has enough confirmations.
+pub(crate) struct Sweeper {
+    settings: Arc<Settings>,
+    database: Arc<LdkDatabase>,
+    bitcoind_client: Arc<BitcoindClient>,
+    keys_manager: Arc<KeysManager>,
+    wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+}
+
+impl Sweeper {
+    pub fn new(
+        settings: Arc<Settings>,
+        database: Arc<LdkDatabase>,
+        bitcoind_client: Arc<BitcoindClient>,
+        keys_manager: Arc<KeysManager>,
+        wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+    ) -> Sweeper {
+        Sweeper {
+            settings,
+            database,
+            bitcoind_client,
+            keys_manager,
+            wallet,
+        }
+    }
+
+    pub async fn sweep(&self) -> Result<()> {
+        let height = self.bitcoind_client.block_height().await? as u32;
+        let mut unspent = vec![];
+        let mut sweeps: HashMap<Txid, Vec<SpendableOutput>> = HashMap::new();
+        for output in self.database.fetch_spendable_outputs().await? {
+            match output.status {
+                SpendableOutputStatus::Unspent => unspent.push(output),
+                SpendableOutputStatus::Sweeping => {
+                    if let Some(txid) = output.sweep_txids.last() {
+                        sweeps.entry(*txid).or_default().push(output);
+                    }
+                }
+                SpendableOutputStatus::Spent => {}
+            }
+        }
+        // One sweep that fails must not hold back the others.
+        for (txid, outputs) in sweeps {
+            if let Err(e) = self.track_sweep(outputs, height).await {
+                error!("Failed to track sweep transaction {txid}: {e}");
+            }
+        }
+        self.sweep_unspent(unspent, height).await
+    }
+
+    // Mark the outputs spent once any version of their sweep is deeply confirmed, bump the fee when
+    // it has not confirmed for a while and re-broadcast it otherwise.
+    async fn track_sweep(&self, outputs: Vec<SpendableOutput>, height: u32) -> Result<()> {
+        let mut confirmation_height = None;
+        for txid in &outputs[0].sweep_txids {
+            if let Some(h) = self.wallet.confirmation_height(txid)? {
+                confirmation_height = Some(h);
+                break;
+            }
+        }
+        if let Some(confirmation_height) = confirmation_height {
+            let confirmations = height.saturating_sub(confirmation_height) + 1;
+            if confirmations >= self.settings.sweeper_confirmations {
+                for mut output in outputs {
+                    info!(
+                        "Spendable output {}:{} is swept with {confirmations} confirmations",
+                        output.txid, output.vout
+                    );
+                    output.status = SpendableOutputStatus::Spent;
+                    self.database.persist_spendable_output(output).await?;
+                }
+            }
+            return Ok(());
+        }
+        let outputs = self.resolve_spent_elsewhere(outputs).await?;
+        if outputs.is_empty() {
+            return Ok(());
+        }
+        let sweep_height = outputs[0].sweep_height.unwrap_or_default();
+        let bumped_feerate = (height >= sweep_height + self.settings.sweeper_bump_blocks)
+            .then(|| {
+                bump_feerate(
+                    outputs[0].sweep_feerate.unwrap_or_default(),
+                    self.sweep_feerate(),
+                    self.settings.sweeper_max_feerate_sat_per_kw,
+                )
+            })
+            .flatten();
+        if let Some(feerate) = bumped_feerate {
+            self.broadcast_sweep(outputs, feerate, height).await
+        } else {
+            if let Some(tx) = &outputs[0].sweep_tx {
+                self.bitcoind_client.broadcast_transactions(&[tx]);
+            }
+            Ok(())
+        }
+    }
+
+    // Outputs that a confirmed transaction other than our sweep has spent are resolved, there is
+    // nothing left to sweep. The other outputs of the sweep go back to unspent to be swept again
+    // without them, since the sweep transaction can not confirm anymore.
+    async fn resolve_spent_elsewhere(
+        &self,
+        outputs: Vec<SpendableOutput>,
+    ) -> Result<Vec<SpendableOutput>> {
+        let mut spent = vec![];
+        let mut unspent = vec![];
+        for output in outputs {
+            if self
+                .bitcoind_client
+                .is_spent(&output.txid, output.vout as u32)
+                .await?
+            {
+                spent.push(output);
+            } else {
+                unspent.push(output);
+            }
+        }
+        if spent.is_empty() {
+            return Ok(unspent);
+        }
+        for mut output in spent {
+            info!(
+                "Spendable output {}:{} was spent by another transaction",
+                output.txid, output.vout
+            );
+            output.status = SpendableOutputStatus::Spent;
+            self.database.persist_spendable_output(output).await?;
+        }
+        for mut output in unspent {
+            output.status = SpendableOutputStatus::Unspent;
+            self.database.persist_spendable_output(output).await?;
+        }
+        Ok(vec![])
+    }
+
+    async fn sweep_unspent(&self, mut outputs: Vec<SpendableOutput>, height: u32) -> Result<()> {
+        if outputs.is_empty() {
+            return Ok(());
+        }
+        for output in outputs.iter_mut() {
+            if output.first_seen_height.is_none() {
+                output.first_seen_height = Some(height);
+                self.database
+                    .persist_spendable_output(output.clone())
+                    .await?;
+            }
+        }
+        let feerate = self.sweep_feerate();
+        let first_seen = outputs
+            .iter()
+            .filter_map(|o| o.first_seen_height)
+            .min()
+            .unwrap_or(height);
+        if delay_sweep(
+            feerate,
+            self.settings.sweeper_high_feerate_sat_per_kw,
+            first_seen,
+            height,
+            self.settings.sweeper_max_delay_blocks,
+        ) {
+            debug!(
+                "Fee rate of {feerate} sat/kw is high, holding {} spendable outputs for a later batch",
+                outputs.len()
+            );
+            return Ok(());
+        }
+        self.broadcast_sweep(outputs, feerate, height).await
+    }
+
+    async fn broadcast_sweep(
+        &self,
+        outputs: Vec<SpendableOutput>,
+        feerate: u32,
+        height: u32,
+    ) -> Result<()> {
+        let descriptors: Vec<_> = outputs.iter().map(|o| &o.descriptor).collect();
+        let destination_address = self.wallet.new_internal_address()?;
+        let sweep_tx = self
+            .keys_manager
+            .spend_spendable_outputs(
+                &descriptors,
+                Vec::new(),
+                destination_address.script_pubkey(),
+                feerate,
+                Some(PackedLockTime(height)),
+                &Secp256k1::new(),
+            )
+            .map_err(|()| anyhow!("Failed to build sweep transaction"))?;
+        info!(
+            "Sweeping {} spendable outputs to {} in {} at {feerate} sat/kw",
+            outputs.len(),
+            destination_address.address,
+            sweep_tx.txid()
+        );
+        // Persist the sweep first so that we keep track of it if kld stops right after the broadcast.
+        for mut output in outputs {
+            output.status = SpendableOutputStatus::Sweeping;
+            output.sweep_txids.push(sweep_tx.txid());
+            output.sweep_tx = Some(sweep_tx.clone());
+            output.sweep_feerate = Some(feerate);
+            output.sweep_height = Some(height);
+            self.database.persist_spendable_output(output).await?;
+        }
+        self.bitcoind_client.broadcast_transactions(&[&sweep_tx]);
+        Ok(())
+    }
+
+    fn sweep_feerate(&self) -> u32 {
+        self.bitcoind_client
+            .get_est_sat_per_1000_weight(ConfirmationTarget::OnChainSweep)
+            .min(self.settings.sweeper_max_feerate_sat_per_kw)
+    }
+}
+
+/// Hold new outputs back while fees are high so that they are swept together in a later batch,
+/// but not for longer than the maximum delay.
+fn delay_sweep(
+    feerate: u32,
+    high_feerate: u32,
+    first_seen_height: u32,
+    height: u32,
+    max_delay_blocks: u32,
+) -> bool {
+    feerate > high_feerate && height < first_seen_height + max_delay_blocks
+}
+
+/// The fee rate (sat per 1000 weight) of a replacement sweep. It has to pay at least the minimum
+/// relay fee on top of the previous fee for bitcoind to accept the replacement. None when that
+/// would go above the maximum fee rate, the previous sweep is re-broadcast instead.
+fn bump_feerate(previous: u32, estimate: u32, max: u32) -> Option<u32> {
+    let feerate = estimate
+        .max(previous + previous / 4)
+        .max(previous + 253)
+        .min(max);
+    (feerate >= previous + 253).then_some(feerate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bump_feerate, delay_sweep};
+
+    #[test]
+    fn test_delay_sweep() {
+        assert!(!delay_sweep(1000, 5000, 100, 100, 144));
+        assert!(delay_sweep(6000, 5000, 100, 100, 144));
+        assert!(delay_sweep(6000, 5000, 100, 243, 144));
+        assert!(!delay_sweep(6000, 5000, 100, 244, 144));
+    }
+
+    #[test]
+    fn test_bump_feerate() {
+        assert_eq!(Some(3000), bump_feerate(1000, 3000, 25000));
+        assert_eq!(Some(1253), bump_feerate(1000, 500, 25000));
+        assert_eq!(Some(5000), bump_feerate(4000, 1000, 25000));
+        assert_eq!(Some(25000), bump_feerate(4000, 30000, 25000));
+        assert_eq!(Some(25000), bump_feerate(22000, 1000, 25000));
+        assert_eq!(None, bump_feerate(24800, 30000, 25000));
+        assert_eq!(None, bump_feerate(25000, 30000, 25000));
+    }
+}
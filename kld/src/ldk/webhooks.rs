@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use api::{NodeEvent, WebhookEvent, WebhookPayload};
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use futures::future::join_all;
+use log::{debug, warn};
+use reqwest::header::CONTENT_TYPE;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::bitcoind::BitcoindClient;
+use crate::database::microsecond_timestamp;
+use crate::database::webhook::{Webhook, WebhookDelivery, WebhookDeliveryStatus};
+use crate::database::{LdkDatabase, WalletDatabase};
+use crate::settings::Settings;
+use crate::wallet::{Wallet, WalletInterface};
+
+const MAX_DELIVERIES_PER_RUN: i64 = 100;
+
+/// Posts node events to the webhooks that operators registered. Deliveries are queued in the
+/// database and retried with exponential backoff, so they survive restarts.
+pub(crate) struct WebhookDispatcher {
+    settings: Arc<Settings>,
+    database: Arc<LdkDatabase>,
+    wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+    client: reqwest::Client,
+    // Number of consecutive balance checks that were below the threshold.
+    low_balance_checks: AtomicU32,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        settings: Arc<Settings>,
+        database: Arc<LdkDatabase>,
+        wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+    ) -> Result<WebhookDispatcher> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(WebhookDispatcher {
+            settings,
+            database,
+            wallet,
+            client,
+            low_balance_checks: AtomicU32::new(0),
+        })
+    }
+
+    /// Queue the event for the webhooks that are registered for it.
+    pub async fn notify(&self, event: &NodeEvent) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        for webhook_event in event.webhook_events() {
+            let payload = serde_json::to_string(&WebhookPayload {
+                event: webhook_event,
+                timestamp,
+                data: event.clone(),
+            })?;
+            let count = self
+                .database
+                .insert_webhook_deliveries(
+                    webhook_event,
+                    &event_id(webhook_event, event, timestamp)?,
+                    &payload,
+                )
+                .await?;
+            debug!("Queued {count} deliveries of {webhook_event}");
+        }
+        Ok(())
+    }
+
+    /// Returns an event when the confirmed balance has dropped below the threshold. The wallet
+    /// reports no balance while it is busy, so the balance has to be low at two checks in a row.
+    pub fn check_wallet_balance(&self) -> Result<Option<NodeEvent>> {
+        let threshold = self.settings.webhook_low_balance_sats;
+        if threshold == 0 {
+            return Ok(None);
+        }
+        let balance = self.wallet.balance()?.confirmed;
+        if balance >= threshold {
+            self.low_balance_checks.store(0, Ordering::Relaxed);
+            return Ok(None);
+        }
+        let checks = self.low_balance_checks.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok((checks == 2).then_some(NodeEvent::LowWalletBalance {
+            balance_sats: balance,
+            threshold_sats: threshold,
+        }))
+    }
+
+    /// Post the deliveries that are due and schedule a retry for the ones that fail. The webhooks
+    /// are posted to concurrently, the deliveries of one webhook in order.
+    pub async fn deliver(&self) -> Result<()> {
+        let now = microsecond_timestamp();
+        let deliveries = self
+            .database
+            .fetch_due_webhook_deliveries(&now, MAX_DELIVERIES_PER_RUN)
+            .await?;
+        if deliveries.is_empty() {
+            return Ok(());
+        }
+        let webhooks: HashMap<Uuid, Webhook> = self
+            .database
+            .fetch_webhooks()
+            .await?
+            .into_iter()
+            .map(|webhook| (webhook.id, webhook))
+            .collect();
+        let mut queues: HashMap<Uuid, Vec<WebhookDelivery>> = HashMap::new();
+        for delivery in deliveries {
+            queues
+                .entry(delivery.webhook_id)
+                .or_default()
+                .push(delivery);
+        }
+        let results = join_all(queues.into_iter().filter_map(|(webhook_id, deliveries)| {
+            // The webhook was deleted after the deliveries were fetched.
+            let webhook = webhooks.get(&webhook_id)?;
+            Some(self.deliver_to(webhook, deliveries, now))
+        }))
+        .await;
+        results.into_iter().collect()
+    }
+
+    // Once a delivery fails the webhook is backed off, its other deliveries wait for the retry
+    // without using up an attempt.
+    async fn deliver_to(
+        &self,
+        webhook: &Webhook,
+        deliveries: Vec<WebhookDelivery>,
+        now: OffsetDateTime,
+    ) -> Result<()> {
+        let mut retry_at: Option<OffsetDateTime> = None;
+        for mut delivery in deliveries {
+            if let Some(retry_at) = retry_at {
+                delivery.next_attempt = retry_at;
+                self.database.update_webhook_delivery(&delivery).await?;
+                continue;
+            }
+            delivery.attempts += 1;
+            match self.post(webhook, &delivery).await {
+                Ok(()) => {
+                    debug!("Delivered {} to {}", delivery.event, webhook.url);
+                    delivery.status = WebhookDeliveryStatus::Delivered;
+                    delivery.last_error = None;
+                }
+                Err(e) => {
+                    warn!(
+                        "Delivery {} of {} to {} failed (attempt {}): {e}",
+                        delivery.id, delivery.event, webhook.url, delivery.attempts
+                    );
+                    delivery.last_error = Some(e.to_string());
+                    delivery.next_attempt = now + backoff(delivery.attempts);
+                    if delivery.attempts >= self.settings.webhook_max_attempts {
+                        delivery.status = WebhookDeliveryStatus::Failed;
+                    }
+                    retry_at = Some(delivery.next_attempt);
+                }
+            }
+            self.database.update_webhook_delivery(&delivery).await?;
+        }
+        Ok(())
+    }
+
+    async fn post(&self, webhook: &Webhook, delivery: &WebhookDelivery) -> Result<()> {
+        self.client
+            .post(&webhook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header("X-Kld-Event", delivery.event.to_string())
+            .header("X-Kld-Delivery", delivery.id.to_string())
+            .header(
+                "X-Kld-Signature",
+                format!("sha256={}", signature(&webhook.secret, &delivery.payload)),
+            )
+            .body(delivery.payload.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// LDK hands over events again after a restart when the channel manager was not persisted after
+/// they were handled. A replay gets the same id so that it is only delivered once. The balance check is not replayed, so every low balance is a new event.
+fn event_id(webhook_event: WebhookEvent, event: &NodeEvent, timestamp: u64) -> Result<String> {
+    let mut engine = sha256::Hash::engine();
+    engine.input(webhook_event.to_string().as_bytes());
+    engine.input(serde_json::to_string(event)?.as_bytes());
+    if let NodeEvent::LowWalletBalance { .. } = event {
+        engine.input(&timestamp.to_be_bytes());
+    }
+    Ok(sha256::Hash::from_engine(engine).to_hex())
+}
+
+/// Hex encoded HMAC-SHA256 of the payload, receivers verify it with their copy of the secret.
+fn signature(secret: &str, payload: &str) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(payload.as_bytes());
+    Hmac::<sha256::Hash>::from_engine(engine).to_hex()
+}
+
+/// Wait 30 seconds after the first failure and double it after every attempt, up to 6 hours.
+fn backoff(attempts: u32) -> Duration {
+    let secs = 30u64 << attempts.saturating_sub(1).min(16);
+    Duration::from_secs(secs.min(6 * 60 * 60))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use api::{NodeEvent, WebhookEvent};
+
+    use super::{backoff, event_id, signature};
+
+    #[test]
+    fn test_event_id() {
+        let closed = NodeEvent::ChannelClosed {
+            channel_id: "00".repeat(32),
+            reason: "ProcessingError".to_string(),
+            force_closed: true,
+        };
+        // A replayed event has the same id for the same webhook event.
+        assert_eq!(
+            event_id(WebhookEvent::ChannelClosed, &closed, 1).unwrap(),
+            event_id(WebhookEvent::ChannelClosed, &closed, 2).unwrap()
+        );
+        assert_ne!(
+            event_id(WebhookEvent::ChannelClosed, &closed, 1).unwrap(),
+            event_id(WebhookEvent::ChannelForceClosed, &closed, 1).unwrap()
+        );
+        let low_balance = NodeEvent::LowWalletBalance {
+            balance_sats: 1000,
+            threshold_sats: 5000,
+        };
+        assert_ne!(
+            event_id(WebhookEvent::LowWalletBalance, &low_balance, 1).unwrap(),
+            event_id(WebhookEvent::LowWalletBalance, &low_balance, 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_signature() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            signature("Jefe", "what do ya want for nothing?")
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(Duration::from_secs(30), backoff(1));
+        assert_eq!(Duration::from_secs(60), backoff(2));
+        assert_eq!(Duration::from_secs(240), backoff(4));
+        assert_eq!(Duration::from_secs(15360), backoff(10));
+        assert_eq!(Duration::from_secs(6 * 60 * 60), backoff(11));
+        assert_eq!(Duration::from_secs(6 * 60 * 60), backoff(u32::MAX));
+    }
+}
@@ -1,7 +1,8 @@
 use anyhow::{Context, Result};
-use futures::FutureExt;
-use kld::api::{bind_api_server, MacaroonAuth};
+use futures::{future, FutureExt};
+use kld::api::{bind_api_server, bind_rpc_server, MacaroonAuth};
 use kld::bitcoind::BitcoindClient;
+use kld::database::cipher::Cipher;
 use kld::database::{DurableConnection, WalletDatabase};
 use kld::key_generator::KeyGenerator;
 use kld::ldk::Controller;
@@ -74,22 +75,68 @@ async fn run_kld(settings: Arc<Settings>) -> Result<()> {
         bitcoind_client.clone(),
         wallet.clone(),
         &key_generator.lightning_seed(),
+        Arc::new(Cipher::new(&key_generator.database_seed())),
         quit_signal.clone(),
     )
     .await
     .context("Failed to start ldk controller")?;
     let controller = Arc::new(controller);
 
-    let macaroon_auth = Arc::new(MacaroonAuth::init(
-        &key_generator.macaroon_seed(),
-        &settings.data_dir,
-    )?);
+    let macaroon_auth = Arc::new(
+        MacaroonAuth::init(
+            &key_generator.macaroon_seed(),
+            &settings.data_dir,
+            controller.as_ref(),
+        )
+        .await
+        .context("cannot initialize macaroon auth")?,
+    );
 
     let server = bind_api_server(
         settings.rest_api_address.clone(),
         settings.certs_dir.clone(),
     )
     .await?;
+    let lnd_server = match &settings.lnd_rest_api_address {
+        Some(address) => Some(bind_api_server(address.clone(), settings.certs_dir.clone()).await?),
+        None => None,
+    };
+    let lnd_api = async {
+        match lnd_server {
+            Some(server) => {
+                server
+                    .serve_lnd(
+                        bitcoind_client.clone(),
+                        controller.clone(),
+                        wallet.clone(),
+                        macaroon_auth.clone(),
+                        quit_signal.clone(),
+                    )
+                    .await
+            }
+            None => future::pending().await,
+        }
+    };
+    let rpc_server = settings
+        .rpc_socket
+        .as_ref()
+        .map(|rpc_socket| bind_rpc_server(format!("{}/{rpc_socket}", settings.data_dir)))
+        .transpose()?;
+    let rpc_api = async {
+        match rpc_server {
+            Some(server) => {
+                server
+                    .serve(
+                        bitcoind_client.clone(),
+                        controller.clone(),
+                        wallet.clone(),
+                        quit_signal.clone(),
+                    )
+                    .await
+            }
+            None => future::pending().await,
+        }
+    };
 
     tokio::select!(
         _ = quit_signal.clone() => {
@@ -99,8 +146,14 @@ async fn run_kld(settings: Arc<Settings>) -> Result<()> {
         result = start_prometheus_exporter(settings.exporter_address.clone(), controller.clone(), durable_connection.clone(), bitcoind_client.clone(), quit_signal.clone()) => {
             result.context("Prometheus exporter failed")
         },
-        result = server.serve(bitcoind_client.clone(), controller.clone(), wallet.clone(), macaroon_auth, quit_signal) => {
+        result = server.serve(bitcoind_client.clone(), controller.clone(), wallet.clone(), macaroon_auth.clone(), quit_signal.clone()) => {
             result.context("REST API failed")
         }
+        result = lnd_api => {
+            result.context("LND REST API failed")
+        }
+        result = rpc_api => {
+            result.context("JSON-RPC server failed")
+        }
     )
 }
@@ -1,8 +1,10 @@
 mod bitcoin_network;
 
 use crate::api::SocketAddress;
+use api::ChannelTypeFeature;
+use bitcoin::secp256k1::PublicKey;
 pub use bitcoin_network::Network;
-use clap::{builder::OsStr, Parser};
+use clap::{builder::OsStr, ArgAction, Parser};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -51,10 +53,173 @@ pub struct Settings {
     #[arg(long, value_delimiter = ',', env = "KLD_PUBLIC_ADDRESSES")]
     pub public_addresses: Vec<SocketAddress>,
 
+    /// Smallest inbound channel (in satoshis) that we accept.
+    #[arg(long, env = "KLD_INBOUND_CHANNEL_MIN_SATS")]
+    pub inbound_channel_min_sats: Option<u64>,
+    /// Largest inbound channel (in satoshis) that we accept.
+    #[arg(long, env = "KLD_INBOUND_CHANNEL_MAX_SATS")]
+    pub inbound_channel_max_sats: Option<u64>,
+    /// Only these node IDs may open channels with us. Empty allows every node.
+    #[arg(long, value_delimiter = ',', env = "KLD_INBOUND_CHANNEL_ALLOW_LIST")]
+    pub inbound_channel_allow_list: Vec<PublicKey>,
+    /// These node IDs may never open channels with us.
+    #[arg(long, value_delimiter = ',', env = "KLD_INBOUND_CHANNEL_DENY_LIST")]
+    pub inbound_channel_deny_list: Vec<PublicKey>,
+    /// Maximum number of channels that a single peer may have with us.
+    #[arg(long, env = "KLD_INBOUND_CHANNEL_MAX_PER_PEER")]
+    pub inbound_channel_max_per_peer: Option<usize>,
+    /// Channel type features that an inbound channel must negotiate [static_remote_key/anchors_zero_fee_htlc_tx/scid_privacy/zero_conf].
+    #[arg(
+        long,
+        value_delimiter = ',',
+        env = "KLD_INBOUND_CHANNEL_REQUIRED_FEATURES"
+    )]
+    pub inbound_channel_required_features: Vec<ChannelTypeFeature>,
+    /// Reason sent to the peer in the error message when an inbound channel is rejected.
+    #[arg(
+        long,
+        default_value = "Channel rejected by node policy",
+        env = "KLD_INBOUND_CHANNEL_REJECT_REASON"
+    )]
+    pub inbound_channel_reject_reason: String,
+    /// Negotiate anchor outputs (zero fee HTLC transactions) for new channels.
+    #[arg(long, default_value_t = true, action = ArgAction::Set, env = "KLD_ANCHOR_CHANNELS")]
+    pub anchor_channels: bool,
+    /// Amount (sats) kept in the on chain wallet per anchor channel to fee bump its commitment and HTLC transactions.
+    #[arg(long, default_value = "25000", env = "KLD_ANCHOR_CHANNEL_RESERVE_SATS")]
+    pub anchor_channel_reserve_sats: u64,
+    /// Sell just in time channels to LSPS2 clients. Needs both of the opening fee settings.
+    #[arg(long, default_value_t = false, action = ArgAction::Set, env = "KLD_LSPS2_ENABLED")]
+    pub lsps2_enabled: bool,
+    /// Minimum opening fee (msat) for a just in time channel.
+    #[arg(long, env = "KLD_LSPS2_MIN_FEE_MSAT")]
+    pub lsps2_min_fee_msat: Option<u64>,
+    /// Proportional opening fee (ppm of the payment size) for a just in time channel.
+    #[arg(long, env = "KLD_LSPS2_FEE_PROPORTIONAL")]
+    pub lsps2_fee_proportional: Option<u32>,
+    /// Number of blocks that a just in time channel is kept open.
+    #[arg(long, default_value = "4032", env = "KLD_LSPS2_MIN_LIFETIME")]
+    pub lsps2_min_lifetime: u32,
+    /// Maximum to_self_delay (blocks) that a just in time channel client has to wait for its funds.
+    #[arg(
+        long,
+        default_value = "2016",
+        env = "KLD_LSPS2_MAX_CLIENT_TO_SELF_DELAY"
+    )]
+    pub lsps2_max_client_to_self_delay: u32,
+    /// Smallest payment (msat) that may open a just in time channel.
+    #[arg(
+        long,
+        default_value = "10000000",
+        env = "KLD_LSPS2_MIN_PAYMENT_SIZE_MSAT"
+    )]
+    pub lsps2_min_payment_size_msat: u64,
+    /// Largest payment (msat) that may open a just in time channel.
+    #[arg(
+        long,
+        default_value = "1000000000",
+        env = "KLD_LSPS2_MAX_PAYMENT_SIZE_MSAT"
+    )]
+    pub lsps2_max_payment_size_msat: u64,
+    /// Minimum size (sats) of a just in time channel.
+    #[arg(long, default_value = "100000", env = "KLD_LSPS2_MIN_CHANNEL_SATS")]
+    pub lsps2_min_channel_sats: u64,
+    /// How long (seconds) the advertised opening fee parameters are valid.
+    #[arg(long, default_value = "3600", env = "KLD_LSPS2_OFFER_VALID_SECS")]
+    pub lsps2_offer_valid_secs: u64,
+    /// Periodically probe well connected nodes in the network graph to train the scorer.
+    #[arg(long, default_value_t = false, action = ArgAction::Set, env = "KLD_PROBING_ENABLED")]
+    pub probing_enabled: bool,
+    /// Time (seconds) between background probes.
+    #[arg(long, default_value = "60", env = "KLD_PROBING_INTERVAL_SECS")]
+    pub probing_interval_secs: u64,
+    /// Amount (msat) to send in each background probe.
+    #[arg(long, default_value = "50000000", env = "KLD_PROBING_AMOUNT_MSAT")]
+    pub probing_amount_msat: u64,
+    /// Number of the best connected nodes to choose background probe destinations from.
+    #[arg(long, default_value = "100", env = "KLD_PROBING_DESTINATIONS")]
+    pub probing_destinations: usize,
+    /// Default maximum routing fee of a payment as a percentage of the amount.
+    #[arg(long, default_value = "0.5", env = "KLD_PAYMENT_MAX_FEE_PERCENT")]
+    pub payment_max_fee_percent: f64,
+    /// Default routing fee (msat) that a payment may always pay, even above the maximum percentage.
+    #[arg(long, default_value = "5000", env = "KLD_PAYMENT_EXEMPT_FEE_MSAT")]
+    pub payment_exempt_fee_msat: u64,
+    /// Default time (seconds) to keep retrying a payment over other routes.
+    #[arg(long, default_value = "60", env = "KLD_PAYMENT_RETRY_FOR_SECS")]
+    pub payment_retry_for_secs: u64,
+    /// Default maximum total CLTV expiry delta (blocks) of a payment route.
+    #[arg(
+        long,
+        default_value = "1008",
+        env = "KLD_PAYMENT_MAX_CLTV_EXPIRY_DELTA"
+    )]
+    pub payment_max_cltv_expiry_delta: u32,
+    /// Number of confirmations of its sweep transaction before a spendable output counts as spent.
+    #[arg(long, default_value = "6", env = "KLD_SWEEPER_CONFIRMATIONS")]
+    pub sweeper_confirmations: u32,
+    /// Number of blocks to wait for a sweep transaction to confirm before its fee is bumped.
+    #[arg(long, default_value = "6", env = "KLD_SWEEPER_BUMP_BLOCKS")]
+    pub sweeper_bump_blocks: u32,
+    /// Above this fee rate (sat per 1000 weight) new spendable outputs are collected into a later batch.
+    #[arg(
+        long,
+        default_value = "5000",
+        env = "KLD_SWEEPER_HIGH_FEERATE_SAT_PER_KW"
+    )]
+    pub sweeper_high_feerate_sat_per_kw: u32,
+    /// Maximum number of blocks that high fees may delay the sweep of a spendable output.
+    #[arg(long, default_value = "144", env = "KLD_SWEEPER_MAX_DELAY_BLOCKS")]
+    pub sweeper_max_delay_blocks: u32,
+    /// Highest fee rate (sat per 1000 weight) that a sweep transaction pays, fee bumps stop there.
+    #[arg(
+        long,
+        default_value = "25000",
+        env = "KLD_SWEEPER_MAX_FEERATE_SAT_PER_KW"
+    )]
+    pub sweeper_max_feerate_sat_per_kw: u32,
+    /// Number of channel monitor updates that are stored on their own before the full monitor is written again.
+    #[arg(
+        long,
+        default_value = "100",
+        env = "KLD_CHANNEL_MONITOR_MAX_PENDING_UPDATES",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub channel_monitor_max_pending_updates: u64,
+    /// Rapid Gossip Sync server to bootstrap and refresh the network graph from, e.g. https://rapidsync.lightningdevkit.org/snapshot
+    #[arg(long, env = "KLD_RGS_URL")]
+    pub rgs_url: Option<String>,
+    /// Rapid Gossip Sync snapshot file to bootstrap the network graph from when it has never been synced.
+    #[arg(long, env = "KLD_RGS_SNAPSHOT_PATH")]
+    pub rgs_snapshot_path: Option<String>,
+    /// Time (seconds) between syncs with the Rapid Gossip Sync server.
+    #[arg(long, default_value = "3600", env = "KLD_RGS_SYNC_INTERVAL_SECS")]
+    pub rgs_sync_interval_secs: u64,
+    /// Start in recovery mode with a static channel backup exported by kld-cli. The node keeps connecting to the peers in the backup and asks them to force close the channels.
+    #[arg(long, env = "KLD_RECOVERY_CHANNEL_BACKUP_PATH")]
+    pub recovery_channel_backup_path: Option<String>,
+    /// Number of times that a webhook delivery is attempted before it is marked as failed.
+    #[arg(
+        long,
+        default_value = "10",
+        env = "KLD_WEBHOOK_MAX_ATTEMPTS",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub webhook_max_attempts: u32,
+    /// Notify the lowWalletBalance webhooks when the confirmed wallet balance (sats) drops below this, 0 disables it.
+    #[arg(long, default_value = "0", env = "KLD_WEBHOOK_LOW_BALANCE_SATS")]
+    pub webhook_low_balance_sats: u64,
+
     #[arg(long, default_value = "127.0.0.1:2233", env = "KLD_EXPORTER_ADDRESS")]
     pub exporter_address: String,
     #[arg(long, default_value = "127.0.0.1:2244", env = "KLD_REST_API_ADDRESS")]
     pub rest_api_address: String,
+    /// Also serve an LND compatible REST API on this address, for wallets and dashboards that only speak LND.
+    #[arg(long, env = "KLD_LND_REST_API_ADDRESS")]
+    pub lnd_rest_api_address: Option<String>,
+    /// File name of the JSON-RPC socket in the data directory, e.g. lightning-rpc. The JSON-RPC server only runs when it is set.
+    #[arg(long, env = "KLD_RPC_SOCKET")]
+    pub rpc_socket: Option<String>,
 
     #[arg(long, default_value = "127.0.0.1:60001", env = "KLD_ELECTRS_URL")]
     pub electrs_url: String,
@@ -67,13 +232,22 @@ pub struct Settings {
     pub database_user: String,
     #[arg(long, default_value = "postgres", env = "KLD_DATABASE_NAME")]
     pub database_name: String,
-
-    // #[arg(long, default_value = "", env = "KLD_DATABASE_CA_CERT_PATH")]
-    // pub database_ca_cert_path: String,
-    // #[arg(long, default_value = "", env = "KLD_DATABASE_CLIENT_CERT_PATH")]
-    // pub database_client_cert_path: String,
-    // #[arg(long, default_value = "", env = "KLD_DATABASE_CLIENT_KEY_PATH")]
-    // pub database_client_key_path: String,
+    /// Password of the database user, leave it unset to authenticate with the client certificate only.
+    #[arg(long, env = "KLD_DATABASE_PASSWORD")]
+    pub database_password: Option<String>,
+    /// TLS mode of the database connection [disable/prefer/require]. With prefer the connection is
+    /// only encrypted when the server supports TLS, set require to refuse plaintext connections.
+    #[arg(long, default_value = "prefer", env = "KLD_DATABASE_SSL_MODE")]
+    pub database_ssl_mode: String,
+    /// CA certificate that the database server certificate is verified with.
+    #[arg(long, default_value = "", env = "KLD_DATABASE_CA_CERT_PATH")]
+    pub database_ca_cert_path: String,
+    /// Client certificate for authenticating to the database. The certificates are reloaded when they change.
+    #[arg(long, default_value = "", env = "KLD_DATABASE_CLIENT_CERT_PATH")]
+    pub database_client_cert_path: String,
+    /// Private key of the database client certificate.
+    #[arg(long, default_value = "", env = "KLD_DATABASE_CLIENT_KEY_PATH")]
+    pub database_client_key_path: String,
 }
 
 impl Settings {
@@ -96,7 +270,14 @@ mod test {
     #[test]
     pub fn test_parse_settings() {
         set_var("KLD_PUBLIC_ADDRESSES", "127.0.0.1:2312,[2001:db8::1]:1212");
+        set_var(
+            "KLD_INBOUND_CHANNEL_REQUIRED_FEATURES",
+            "static_remote_key,anchors_zero_fee_htlc_tx",
+        );
         let settings = Settings::load();
         assert_eq!(settings.public_addresses.len(), 2);
+        assert_eq!(settings.inbound_channel_required_features.len(), 2);
+        assert!(settings.anchor_channels);
+        assert_eq!(settings.database_ssl_mode, "prefer");
     }
 }
@@ -1,5 +1,8 @@
 use std::{
-    sync::{Arc, Mutex, OnceLock},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
     time::Duration,
 };
 
@@ -12,11 +15,15 @@ use bdk::{
     database::{BatchDatabase, BatchOperations, Database},
     electrum_client::Client,
     template::Bip84,
-    wallet::AddressInfo,
+    wallet::{AddressIndex, AddressInfo},
     Balance, FeeRate, KeychainKind, LocalUtxo, SignOptions, SyncOptions, TransactionDetails,
 };
-use bitcoin::{Address, OutPoint, Script, Transaction};
+use bitcoin::{
+    hashes::Hash, util::psbt::PartiallySignedTransaction, Address, OutPoint, Script, Transaction,
+    Txid, WPubkeyHash,
+};
 use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use lightning::events::bump_transaction::{Utxo, WalletSource};
 use lightning_block_sync::BlockSource;
 use log::{error, info, warn};
 
@@ -33,6 +40,8 @@ pub struct Wallet<
     bitcoind_client: Arc<B>,
     settings: Arc<Settings>,
     blockchain: Arc<OnceLock<ElectrumBlockchain>>,
+    // Number of anchor channels that we may need to fee bump from this wallet.
+    anchor_channels: AtomicUsize,
 }
 
 #[async_trait]
@@ -87,6 +96,7 @@ impl<
                     tx_builder.fee_rate(self.to_bdk_fee_rate(fee_rate));
                 }
                 let (mut psbt, tx_details) = tx_builder.finish()?;
+                self.check_anchor_reserve(&wallet, &tx_details, self.anchor_reserve())?;
                 let _finalized = wallet.sign(&mut psbt, SignOptions::default())?;
                 let tx = psbt.extract_tx();
 
@@ -161,9 +171,60 @@ impl<
             bitcoind_client,
             settings,
             blockchain: Arc::new(OnceLock::new()),
+            anchor_channels: AtomicUsize::new(0),
         })
     }
 
+    /// Update the number of anchor channels so that enough funds are kept to fee bump them.
+    pub fn set_anchor_channels(&self, count: usize) {
+        self.anchor_channels.store(count, Ordering::Relaxed);
+    }
+
+    /// The amount (sats) that has to stay in the wallet for fee bumping anchor channel transactions.
+    pub fn anchor_reserve(&self) -> u64 {
+        self.anchor_channels.load(Ordering::Relaxed) as u64
+            * self.settings.anchor_channel_reserve_sats
+    }
+
+    /// Whether the wallet can cover the reserve of one more anchor channel.
+    pub fn has_reserve_for_anchor_channel(&self) -> Result<bool> {
+        let wallet = self
+            .wallet
+            .lock()
+            .map_err(|_| anyhow!("wallet lock is poisened"))?;
+        Ok(wallet.get_balance()?.get_spendable()
+            >= self.anchor_reserve() + self.settings.anchor_channel_reserve_sats)
+    }
+
+    /// Height of the block that confirmed one of our transactions, None while it is unconfirmed or unknown.
+    pub fn confirmation_height(&self, txid: &Txid) -> Result<Option<u32>> {
+        let wallet = self
+            .wallet
+            .lock()
+            .map_err(|_| anyhow!("wallet lock is poisened"))?;
+        Ok(wallet
+            .get_tx(txid, false)?
+            .and_then(|tx| tx.confirmation_time)
+            .map(|time| time.height))
+    }
+
+    fn check_anchor_reserve(
+        &self,
+        wallet: &bdk::Wallet<D>,
+        tx_details: &TransactionDetails,
+        reserve: u64,
+    ) -> Result<()> {
+        if reserve == 0 {
+            return Ok(());
+        }
+        let spent = tx_details.sent.saturating_sub(tx_details.received);
+        let remaining = wallet.get_balance()?.get_spendable().saturating_sub(spent);
+        if remaining < reserve {
+            bail!("Transaction would leave {remaining} sats in the wallet but {reserve} sats are reserved for fee bumping anchor channels");
+        }
+        Ok(())
+    }
+
     pub async fn synced(&self) -> bool {
         if let Ok((_, Some(height))) = self.bitcoind_client.get_best_block().await {
             if let Ok(wallet) = self.wallet.try_lock() {
@@ -236,7 +297,13 @@ impl<
             .fee_rate(self.to_bdk_fee_rate(fee_rate))
             .enable_rbf();
 
-        let (mut psbt, _tx_details) = tx_builder.finish()?;
+        let (mut psbt, tx_details) = tx_builder.finish()?;
+
+        let mut reserve = self.anchor_reserve();
+        if self.settings.anchor_channels {
+            reserve += self.settings.anchor_channel_reserve_sats;
+        }
+        self.check_anchor_reserve(&wallet, &tx_details, reserve)?;
 
         let _finalized = wallet.sign(&mut psbt, SignOptions::default())?;
 
@@ -267,17 +334,92 @@ impl<
     }
 }
 
+// Used by LDK to fund CPFP transactions that fee bump anchor channel commitment and HTLC transactions.
+impl<
+        D: Database + BatchDatabase + BatchOperations + Send + 'static,
+        B: BlockSource + FeeEstimator + Service,
+    > WalletSource for Wallet<D, B>
+{
+    fn list_confirmed_utxos(&self) -> Result<Vec<Utxo>, ()> {
+        let wallet = self
+            .wallet
+            .lock()
+            .map_err(|_| error!("wallet lock is poisened"))?;
+        let mut utxos = vec![];
+        for utxo in wallet
+            .list_unspent()
+            .map_err(|e| error!("Failed to list wallet utxos: {e}"))?
+        {
+            let confirmed = wallet
+                .get_tx(&utxo.outpoint.txid, false)
+                .map_err(|e| error!("Failed to get wallet transaction: {e}"))?
+                .is_some_and(|tx| tx.confirmation_time.is_some());
+            if !confirmed || !utxo.txout.script_pubkey.is_v0_p2wpkh() {
+                continue;
+            }
+            let pubkey_hash = WPubkeyHash::from_slice(&utxo.txout.script_pubkey.as_bytes()[2..])
+                .map_err(|e| error!("Invalid p2wpkh script: {e}"))?;
+            utxos.push(Utxo::new_v0_p2wpkh(
+                utxo.outpoint,
+                utxo.txout.value,
+                &pubkey_hash,
+            ));
+        }
+        Ok(utxos)
+    }
+
+    fn get_change_script(&self) -> Result<Script, ()> {
+        let wallet = self
+            .wallet
+            .lock()
+            .map_err(|_| error!("wallet lock is poisened"))?;
+        let address = wallet
+            .get_internal_address(AddressIndex::New)
+            .map_err(|e| error!("Failed to get change address: {e}"))?;
+        Ok(address.script_pubkey())
+    }
+
+    fn sign_tx(&self, tx: Transaction) -> Result<Transaction, ()> {
+        let wallet = self
+            .wallet
+            .lock()
+            .map_err(|_| error!("wallet lock is poisened"))?;
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx.clone())
+            .map_err(|e| error!("Failed to create PSBT: {e}"))?;
+        // Only sign the inputs that belong to our wallet. LDK signs the anchor and HTLC inputs.
+        for (index, input) in tx.input.iter().enumerate() {
+            if let Some(utxo) = wallet
+                .get_utxo(input.previous_output)
+                .map_err(|e| error!("Failed to get wallet utxo: {e}"))?
+            {
+                psbt.inputs[index] = wallet
+                    .get_psbt_input(utxo, None, true)
+                    .map_err(|e| error!("Failed to get PSBT input: {e}"))?;
+            }
+        }
+        let sign_options = SignOptions {
+            trust_witness_utxo: true,
+            ..Default::default()
+        };
+        let _finalized = wallet
+            .sign(&mut psbt, sign_options)
+            .map_err(|e| error!("Failed to sign transaction: {e}"))?;
+        Ok(psbt.extract_tx())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::{
         str::FromStr,
-        sync::{Arc, Mutex, OnceLock},
+        sync::{atomic::AtomicUsize, Arc, Mutex, OnceLock},
     };
 
     use crate::settings::Settings;
     use anyhow::Result;
     use bdk::{database::MemoryDatabase, wallet::get_funded_wallet, Balance};
     use bitcoin::Address;
+    use lightning::events::bump_transaction::WalletSource;
     use test_utils::{TEST_ADDRESS, TEST_WPKH};
 
     use crate::{bitcoind::MockBitcoindClient, wallet::WalletInterface};
@@ -325,6 +467,7 @@ mod test {
             wallet: Arc::new(Mutex::new(bdk_wallet)),
             settings: Arc::new(Settings::default()),
             blockchain: Arc::new(OnceLock::new()),
+            anchor_channels: AtomicUsize::new(0),
         };
 
         let res = wallet
@@ -350,6 +493,7 @@ mod test {
             wallet: Arc::new(Mutex::new(bdk_wallet)),
             settings: Arc::new(Settings::default()),
             blockchain: Arc::new(OnceLock::new()),
+            anchor_channels: AtomicUsize::new(0),
         };
 
         let (tx, tx_details) = wallet
@@ -371,4 +515,52 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_transfer_keeps_anchor_reserve() -> Result<()> {
+        let bitcoind_client = MockBitcoindClient::default();
+        let (bdk_wallet, _, _) = get_funded_wallet(TEST_WPKH);
+        let bitcoind_client = Arc::new(bitcoind_client);
+        let wallet = Wallet {
+            bitcoind_client: bitcoind_client.clone(),
+            wallet: Arc::new(Mutex::new(bdk_wallet)),
+            settings: Arc::new(Settings::default()),
+            blockchain: Arc::new(OnceLock::new()),
+            anchor_channels: AtomicUsize::new(0),
+        };
+        wallet.set_anchor_channels(2);
+        assert_eq!(
+            2 * Settings::default().anchor_channel_reserve_sats,
+            wallet.anchor_reserve()
+        );
+
+        let res = wallet
+            .transfer(
+                Address::from_str(TEST_ADDRESS)?,
+                u64::MAX,
+                None,
+                None,
+                vec![],
+            )
+            .await;
+        assert!(res.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_source() -> Result<()> {
+        let (bdk_wallet, _, _) = get_funded_wallet(TEST_WPKH);
+        let wallet = Wallet {
+            bitcoind_client: Arc::new(MockBitcoindClient::default()),
+            wallet: Arc::new(Mutex::new(bdk_wallet)),
+            settings: Arc::new(Settings::default()),
+            blockchain: Arc::new(OnceLock::new()),
+            anchor_channels: AtomicUsize::new(0),
+        };
+        let utxos = wallet.list_confirmed_utxos().unwrap();
+        assert_eq!(1, utxos.len());
+        assert!(utxos[0].satisfaction_weight > 0);
+        assert!(wallet.get_change_script().unwrap().is_v0_p2wpkh());
+        Ok(())
+    }
 }
@@ -1,18 +1,18 @@
-use std::{
-    process::{Command, Output},
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{bail, Result};
 use api::{
-    FeeRatesResponse, FundChannelResponse, GenerateInvoiceResponse, GetInfo, Invoice, ListFunds,
-    NetworkChannel, NetworkNode, PaymentResponse, Peer, SetChannelFeeResponse, SignResponse,
-    WalletBalance, WalletTransferResponse,
+    BakeMacaroonResponse, ChannelBackup, FeeRatesResponse, ForwardStats, FundChannelResponse,
+    GenerateInvoiceResponse, GetInfo, Invoice, InvoiceRequest, ListFunds, NetworkChannel,
+    NetworkNode, Offer, OfferResponse, PaymentDetailResponse, PaymentResponse,
+    PaymentStatusResponse, Peer, ProbeResponse, SetChannelFeeResponse, SignResponse, WalletBalance,
+    WalletTransferResponse,
 };
+use bitcoin::hashes::hex::ToHex;
 use kld::api::codegen::{
     get_v1_channel_history_response::GetV1ChannelHistoryResponseItem,
     get_v1_channel_list_forwards_response::GetV1ChannelListForwardsResponseItem,
-    get_v1_channel_list_peer_channels_response::GetV1ChannelListPeerChannelsResponse,
+    get_v1_channel_list_peer_channels_response::GetV1ChannelListPeerChannelsResponseItem,
     get_v1_channel_localremotebal_response::GetV1ChannelLocalremotebalResponse,
     get_v1_estimate_channel_liquidity_response::GetV1EstimateChannelLiquidityResponse,
     get_v1_get_fees_response::GetV1GetFeesResponse, get_v1_newaddr_response::GetV1NewaddrResponse,
@@ -22,34 +22,36 @@ use kld::api::codegen::{
 
 use super::rest::create_api_server;
 use crate::api::rest::mock_lightning;
+use clap::Parser;
+use kld_client::cli::{run_command, KldCliCommand};
 use serde::de;
-use test_utils::{TEST_ADDRESS, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID};
+use test_utils::{TempDir, TEST_ADDRESS, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID};
 
 #[tokio::test]
 async fn test_cli_get_info() -> Result<()> {
     let output = run_cli("get-info", &[]).await?;
-    let _: GetInfo = deserialize(&output.stdout)?;
+    let _: GetInfo = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_sign() -> Result<()> {
     let output = run_cli("sign", &["testmessage"]).await?;
-    let _: SignResponse = deserialize(&output.stdout)?;
+    let _: SignResponse = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_get_balance() -> Result<()> {
     let output = run_cli("get-balance", &[]).await?;
-    let _: WalletBalance = deserialize(&output.stdout)?;
+    let _: WalletBalance = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_new_address() -> Result<()> {
     let output = run_cli("new-address", &[]).await?;
-    let _: GetV1NewaddrResponse = deserialize(&output.stdout)?;
+    let _: GetV1NewaddrResponse = deserialize(&output)?;
     Ok(())
 }
 
@@ -60,35 +62,35 @@ async fn test_cli_withdraw() -> Result<()> {
         &[TEST_ADDRESS, "1000", "--fee-rate", "3000perkw"],
     )
     .await?;
-    let _: WalletTransferResponse = deserialize(&output.stdout)?;
+    let _: WalletTransferResponse = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_list_funds() -> Result<()> {
     let output = run_cli("list-funds", &[]).await?;
-    let _: ListFunds = deserialize(&output.stdout)?;
+    let _: ListFunds = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_list_peer_channels() -> Result<()> {
     let output = run_cli("list-peer-channels", &[]).await?;
-    let _: Vec<GetV1ChannelListPeerChannelsResponse> = deserialize(&output.stdout)?;
+    let _: Vec<GetV1ChannelListPeerChannelsResponseItem> = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_list_peers() -> Result<()> {
     let output = run_cli("list-peers", &[]).await?;
-    let _: Vec<Peer> = deserialize(&output.stdout)?;
+    let _: Vec<Peer> = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_connect_peer() -> Result<()> {
     let output = run_cli("connect-peer", &[TEST_PUBLIC_KEY]).await?;
-    let _: PostV1PeerConnectResponse = deserialize(&output.stdout)?;
+    let _: PostV1PeerConnectResponse = deserialize(&output)?;
     Ok(())
 }
 
@@ -96,7 +98,7 @@ async fn test_cli_connect_peer() -> Result<()> {
 async fn test_cli_disconnect_peer() -> Result<()> {
     let output = run_cli("disconnect-peer", &[TEST_PUBLIC_KEY]).await?;
 
-    assert!(&output.stdout.is_empty());
+    assert!(&output.is_empty());
     Ok(())
 }
 
@@ -114,7 +116,7 @@ async fn test_cli_open_channel() -> Result<()> {
         ],
     )
     .await?;
-    let _: FundChannelResponse = deserialize(&output.stdout)?;
+    let _: FundChannelResponse = deserialize(&output)?;
     Ok(())
 }
 
@@ -131,7 +133,7 @@ async fn test_cli_set_channel_fee() -> Result<()> {
         ],
     )
     .await?;
-    let _: SetChannelFeeResponse = deserialize(&output.stdout)?;
+    let _: SetChannelFeeResponse = deserialize(&output)?;
     Ok(())
 }
 
@@ -142,21 +144,21 @@ async fn test_cli_set_channel_fee_all() -> Result<()> {
         &["all", "--base-fee", "1000", "--ppm-fee", "200"],
     )
     .await?;
-    let _: SetChannelFeeResponse = deserialize(&output.stdout)?;
+    let _: SetChannelFeeResponse = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_close_channel() -> Result<()> {
     let output = run_cli("close-channel", &[&TEST_SHORT_CHANNEL_ID.to_string()]).await?;
-    assert!(output.stdout.is_empty());
+    assert!(output.is_empty());
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_get_network_node() -> Result<()> {
     let output = run_cli("network-nodes", &["--id", TEST_PUBLIC_KEY]).await?;
-    let nodes: Vec<NetworkNode> = deserialize(&output.stdout)?;
+    let nodes: Vec<NetworkNode> = deserialize(&output)?;
     assert!(!nodes.is_empty());
     Ok(())
 }
@@ -164,7 +166,7 @@ async fn test_cli_get_network_node() -> Result<()> {
 #[tokio::test]
 async fn test_cli_list_network_nodes() -> Result<()> {
     let output = run_cli("network-nodes", &[]).await?;
-    let nodes: Vec<NetworkNode> = deserialize(&output.stdout)?;
+    let nodes: Vec<NetworkNode> = deserialize(&output)?;
     assert!(!nodes.is_empty());
     Ok(())
 }
@@ -172,15 +174,14 @@ async fn test_cli_list_network_nodes() -> Result<()> {
 #[tokio::test]
 async fn test_cli_get_network_channel() -> Result<()> {
     let output = run_cli("network-channels", &["--id", "1234"]).await?;
-    let result = String::from_utf8(output.stdout)?;
-    assert!(result.contains("404"));
+    assert!(output.contains("404"));
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_list_network_channels() -> Result<()> {
     let output = run_cli("network-channels", &[]).await?;
-    let channels: Vec<NetworkChannel> = deserialize(&output.stdout)?;
+    let channels: Vec<NetworkChannel> = deserialize(&output)?;
     assert!(channels.is_empty());
     Ok(())
 }
@@ -188,7 +189,7 @@ async fn test_cli_list_network_channels() -> Result<()> {
 #[tokio::test]
 async fn test_cli_fee_rates() -> Result<()> {
     let output = run_cli("fee-rates", &[]).await?;
-    let fee_rates: FeeRatesResponse = deserialize(&output.stdout)?;
+    let fee_rates: FeeRatesResponse = deserialize(&output)?;
     assert!(fee_rates.perkb.is_some());
     Ok(())
 }
@@ -196,7 +197,7 @@ async fn test_cli_fee_rates() -> Result<()> {
 #[tokio::test]
 async fn test_cli_keysend() -> Result<()> {
     let output = run_cli("keysend", &[TEST_PUBLIC_KEY, "102000"]).await?;
-    let _: PaymentResponse = deserialize(&output.stdout)?;
+    let _: PaymentResponse = deserialize(&output)?;
     Ok(())
 }
 
@@ -213,14 +214,34 @@ async fn test_cli_generate_invoice() -> Result<()> {
         ],
     )
     .await?;
-    let _: GenerateInvoiceResponse = deserialize(&output.stdout)?;
+    let _: GenerateInvoiceResponse = deserialize(&output)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_generate_hold_invoice() -> Result<()> {
+    let payment_hash = mock_lightning().invoice.payment_hash.0.to_hex();
+    let output = run_cli(
+        "generate-hold-invoice",
+        &["200000", "test invoice", "test description", &payment_hash],
+    )
+    .await?;
+    let _: GenerateInvoiceResponse = deserialize(&output)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_cancel_hold_invoice() -> Result<()> {
+    let payment_hash = mock_lightning().invoice.payment_hash.0.to_hex();
+    let output = run_cli("cancel-hold-invoice", &[&payment_hash]).await?;
+    assert!(output.is_empty());
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_list_invoices() -> Result<()> {
     let output = run_cli("list-invoices", &["--label", "a label"]).await?;
-    let _: Vec<Invoice> = deserialize(&output.stdout)?;
+    let _: Vec<Invoice> = deserialize(&output)?;
     Ok(())
 }
 
@@ -228,7 +249,35 @@ async fn test_cli_list_invoices() -> Result<()> {
 async fn test_cli_pay_invoice() -> Result<()> {
     let bolt11 = mock_lightning().invoice.bolt11.to_string();
     let output = run_cli("pay-invoice", &[&bolt11, "-l", "a label"]).await?;
-    let _: PaymentResponse = deserialize(&output.stdout)?;
+    let _: PaymentResponse = deserialize(&output)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_create_offer() -> Result<()> {
+    let output = run_cli("create-offer", &["a description", "--amount", "1000"]).await?;
+    let _: OfferResponse = deserialize(&output)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_offers() -> Result<()> {
+    let output = run_cli("list-offers", &["--label", "a label"]).await?;
+    let _: Vec<Offer> = deserialize(&output)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_invoice_requests() -> Result<()> {
+    let output = run_cli("list-invoice-requests", &[]).await?;
+    let _: Vec<InvoiceRequest> = deserialize(&output)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_probe() -> Result<()> {
+    let output = run_cli("probe", &["--pubkey", TEST_PUBLIC_KEY, "--amount", "1000"]).await?;
+    let _: ProbeResponse = deserialize(&output)?;
     Ok(())
 }
 
@@ -239,7 +288,26 @@ async fn test_cli_list_payments() -> Result<()> {
         &["--bolt11", "bolt11", "--direction", "inbound"],
     )
     .await?;
-    let _: GetV1PayListPaymentsResponse = deserialize(&output.stdout)?;
+    let _: GetV1PayListPaymentsResponse = deserialize(&output)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_get_payment() -> Result<()> {
+    let payment_id = mock_lightning().payment.id.0.to_hex();
+    let output = run_cli("get-payment", &[&payment_id]).await?;
+    let response: PaymentStatusResponse = deserialize(&output)?;
+    assert_eq!(payment_id, response.id);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_payment_detail() -> Result<()> {
+    let payment_id = mock_lightning().payment.id.0.to_hex();
+    let output = run_cli("payment-detail", &[&payment_id]).await?;
+    let response: PaymentDetailResponse = deserialize(&output)?;
+    assert_eq!(payment_id, response.payment.id);
+    assert_eq!(2, response.attempts.len());
     Ok(())
 }
 
@@ -250,56 +318,96 @@ async fn test_cli_estimate_channel_liquidity() -> Result<()> {
         &[&TEST_SHORT_CHANNEL_ID.to_string(), TEST_PUBLIC_KEY],
     )
     .await?;
-    let _: GetV1EstimateChannelLiquidityResponse = deserialize(&output.stdout)?;
+    let _: GetV1EstimateChannelLiquidityResponse = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_local_remote_balance() -> Result<()> {
     let output = run_cli("local-remote-balance", &[]).await?;
-    let _: GetV1ChannelLocalremotebalResponse = deserialize(&output.stdout)?;
+    let _: GetV1ChannelLocalremotebalResponse = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_get_fees() -> Result<()> {
     let output = run_cli("get-fees", &[]).await?;
-    let _: GetV1GetFeesResponse = deserialize(&output.stdout)?;
+    let _: GetV1GetFeesResponse = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_list_forwards() -> Result<()> {
     let output = run_cli("list-forwards", &["--status", "settled"]).await?;
-    let _: Vec<GetV1ChannelListForwardsResponseItem> = deserialize(&output.stdout)?;
+    let _: Vec<GetV1ChannelListForwardsResponseItem> = deserialize(&output)?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cli_channel_history() -> Result<()> {
     let output = run_cli("list-channel-history", &[]).await?;
-    let _: Vec<GetV1ChannelHistoryResponseItem> = deserialize(&output.stdout)?;
+    let _: Vec<GetV1ChannelHistoryResponseItem> = deserialize(&output)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_forward_stats() -> Result<()> {
+    let output = run_cli("forward-stats", &["--since", "1700000000"]).await?;
+    let stats: ForwardStats = deserialize(&output)?;
+    assert_eq!(1, stats.peers.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_export_channel_backup() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let path = temp_dir.path().join("channel.backup");
+    let output = run_cli("export-channel-backup", &[path.to_str().unwrap()]).await?;
+    let response: ChannelBackup = deserialize(&output)?;
+    assert_eq!(1, response.channels);
+    assert_eq!(response.backup, std::fs::read_to_string(path)?);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cli_bake_macaroon() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let path = temp_dir.path().join("invoices.macaroon");
+    let output = run_cli(
+        "bake-macaroon",
+        &[
+            path.to_str().unwrap(),
+            "--permissions",
+            "invoices,payments",
+            "--max-payment-sats",
+            "1000",
+            "--expiry",
+            "3600",
+        ],
+    )
+    .await?;
+    let response: BakeMacaroonResponse = deserialize(&output)?;
+    assert!(response.expires_at.is_some());
+    assert_eq!(response.macaroon, std::fs::read_to_string(path)?);
     Ok(())
 }
 
-fn deserialize<'a, T>(bytes: &'a [u8]) -> Result<T>
+fn deserialize<'a, T>(s: &'a str) -> Result<T>
 where
     T: de::Deserialize<'a>,
 {
-    match serde_json::from_slice::<T>(bytes) {
+    match serde_json::from_str::<T>(s) {
         Ok(t) => Ok(t),
-        Err(_) => {
-            let s = String::from_utf8_lossy(bytes);
-            bail!("Expected json output, but got: {}", s)
-        }
+        Err(_) => bail!("Expected json output, but got: {}", s),
     }
 }
 
-async fn run_cli(command: &str, extra_args: &[&str]) -> Result<Output> {
+async fn run_cli(command: &str, extra_args: &[&str]) -> Result<String> {
     let context = create_api_server().await?;
 
-    let output = Command::new(env!("CARGO_BIN_EXE_kld-cli"))
-        .args([
+    let args = KldCliCommand::try_parse_from(
+        [
+            "kld-cli",
             "--target",
             &context.settings.rest_api_address,
             "--cert-path",
@@ -307,13 +415,9 @@ async fn run_cli(command: &str, extra_args: &[&str]) -> Result<Output> {
             "--macaroon-path",
             &format!("{}/macaroons/admin.macaroon", context.settings.data_dir),
             command,
-        ])
-        .args(extra_args)
-        .output()
-        .unwrap();
-
-    if !output.status.success() {
-        panic!("{}", String::from_utf8(output.stderr).unwrap());
-    }
-    Ok(output)
+        ]
+        .iter()
+        .chain(extra_args),
+    )?;
+    run_command(args).await
 }
@@ -1,3 +1,4 @@
 mod cli;
 mod prometheus;
 mod rest;
+mod rpc;
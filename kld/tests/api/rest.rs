@@ -1,20 +1,22 @@
 use std::assert_eq;
+use std::collections::BTreeSet;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::OnceLock;
 use std::thread::spawn;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fs, sync::Arc};
 
-use anyhow::{Context, Result};
-use bitcoin::hashes::hex::ToHex;
-use futures::FutureExt;
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose, Engine};
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use futures::{FutureExt, SinkExt, StreamExt};
 use hyper::Method;
 use kld::api::bind_api_server;
 use kld::api::codegen::get_v1_channel_history_response::GetV1ChannelHistoryResponseItem;
 use kld::api::codegen::get_v1_channel_list_forwards_response::GetV1ChannelListForwardsResponseItem;
 use kld::api::codegen::get_v1_channel_list_peer_channels_response::{
-    GetV1ChannelListPeerChannelsResponse, GetV1ChannelListPeerChannelsResponseState,
+    GetV1ChannelListPeerChannelsResponseItem, GetV1ChannelListPeerChannelsResponseItemState,
 };
 use kld::api::codegen::get_v1_channel_localremotebal_response::GetV1ChannelLocalremotebalResponse;
 use kld::api::codegen::get_v1_estimate_channel_liquidity_body::GetV1EstimateChannelLiquidityBody;
@@ -30,28 +32,43 @@ use kld::api::codegen::get_v1_utility_decode_invoice_string_response::{
 use kld::api::codegen::post_v1_peer_connect_body::PostV1PeerConnectBody;
 use kld::api::codegen::post_v1_peer_connect_response::PostV1PeerConnectResponse;
 use kld::api::MacaroonAuth;
+use kld::database::cipher::Cipher;
 use kld::database::payment::PaymentStatus;
+use kld::ldk::channel_backup::StaticChannelBackup;
 use kld::logger::KldLogger;
 use kld::settings::Settings;
+use kld_client::KldClient;
 use lightning::events::ClosureReason;
 use reqwest::RequestBuilder;
 use reqwest::StatusCode;
 use serde::Serialize;
 use test_utils::ports::get_available_port;
 use test_utils::{
-    https_client, poll, test_settings, TempDir, TEST_ADDRESS, TEST_ALIAS, TEST_PUBLIC_KEY,
-    TEST_SHORT_CHANNEL_ID, TEST_TX, TEST_TX_ID,
+    https_client, poll, random_public_key, test_settings, TempDir, TEST_ADDRESS, TEST_ALIAS,
+    TEST_BLOCK_HASH, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID, TEST_TX, TEST_TX_ID,
 };
 
 use api::{
-    routes, ChannelFee, ChannelState, FeeRate, FeeRatesResponse, FundChannel, FundChannelResponse,
-    GenerateInvoice, GenerateInvoiceResponse, GetInfo, Invoice, InvoiceStatus, KeysendRequest,
-    ListFunds, NetworkChannel, NetworkNode, OutputStatus, PayInvoice, PaymentResponse, Peer,
-    SetChannelFeeResponse, SignRequest, SignResponse, WalletBalance, WalletTransfer,
-    WalletTransferResponse,
+    lnd, routes, BakeMacaroon, BakeMacaroonResponse, CancelHoldInvoice, ChannelAcceptancePolicy,
+    ChannelBackup, ChannelFee, ChannelState, ChannelTypeFeature, CreateOffer, CreateRefund,
+    CreateWebhook, EventSubscription, EventTopic, FeeRate, FeeRatesResponse, ForwardStats,
+    FundChannel, FundChannelResponse, GenerateHoldInvoice, GenerateInvoice,
+    GenerateInvoiceResponse, GetInfo, Invoice, InvoiceRequest, InvoiceRequestStatus, InvoiceStatus,
+    JitChannelOffer, JitChannelSession, JitChannelState, KeysendRequest, ListFunds,
+    MacaroonPermission, MacaroonRootKey, NetworkChannel, NetworkNode, NodeEvent, Offer, OfferKind,
+    OfferResponse, OutputStatus, PayInvoice, PayOffer, PaymentDetailResponse, PaymentResponse,
+    PaymentStatusResponse, Peer, ProbeRequest, ProbeResponse, ProbeStatus, SetChannelFeeResponse,
+    SettleHoldInvoice, SignRequest, SignResponse, WalletBalance, WalletTransfer,
+    WalletTransferResponse, Webhook, WebhookEvent,
 };
+use tokio::net::TcpStream;
 use tokio::runtime::Runtime;
 use tokio::sync::RwLock;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue, Message};
+use tokio_tungstenite::{
+    connect_async_tls_with_config, Connector, MaybeTlsStream, WebSocketStream,
+};
 
 use crate::mocks::mock_bitcoind::MockBitcoind;
 use crate::mocks::mock_lightning::MockLightning;
@@ -61,7 +78,34 @@ use crate::quit_signal;
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_unauthorized() -> Result<()> {
     let context = create_api_server().await?;
-    let admin_functions = vec![
+    let admin_functions = admin_functions();
+    for (method, route) in &admin_functions {
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            readonly_request_with_body(&context, method.clone(), route, || ())?
+                .send()
+                .await?
+                .status()
+        );
+    }
+    let mut readonly_functions = readonly_functions();
+    readonly_functions.extend(admin_functions.into_iter());
+    for (method, route) in readonly_functions {
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            unauthorized_request(&context, method, route)
+                .unwrap()
+                .send()
+                .await?
+                .status()
+        );
+    }
+    Ok(())
+}
+
+/// The routes that need an admin macaroon.
+fn admin_functions() -> Vec<(Method, &'static str)> {
+    vec![
         (Method::POST, routes::SIGN),
         (Method::POST, routes::OPEN_CHANNEL),
         (Method::POST, routes::SET_CHANNEL_FEE),
@@ -78,19 +122,33 @@ pub async fn test_unauthorized() -> Result<()> {
         (Method::POST, routes::KEYSEND),
         (Method::POST, routes::GENERATE_INVOICE),
         (Method::POST, routes::PAY_INVOICE),
-    ];
-    for (method, route) in &admin_functions {
-        assert_eq!(
-            StatusCode::UNAUTHORIZED,
-            readonly_request_with_body(&context, method.clone(), route, || ())?
-                .send()
-                .await?
-                .status()
-        );
-    }
-    let mut readonly_functions = vec![
+        (Method::POST, routes::PROBE),
+        (Method::POST, routes::GENERATE_HOLD_INVOICE),
+        (Method::POST, routes::SETTLE_HOLD_INVOICE),
+        (Method::POST, routes::CANCEL_HOLD_INVOICE),
+        (Method::GET, routes::CHANNEL_ACCEPTANCE_POLICY),
+        (Method::POST, routes::CHANNEL_ACCEPTANCE_POLICY),
+        (Method::GET, routes::CHANNEL_BACKUP),
+        (Method::GET, routes::WEBHOOKS),
+        (Method::POST, routes::WEBHOOKS),
+        (Method::DELETE, routes::DELETE_WEBHOOK),
+        (Method::POST, routes::BAKE_MACAROON),
+        (Method::GET, routes::LIST_MACAROON_ROOT_KEYS),
+        (Method::DELETE, routes::DELETE_MACAROON_ROOT_KEY),
+        (Method::POST, routes::ROTATE_MACAROON_ROOT_KEYS),
+        (Method::POST, routes::CREATE_OFFER),
+        (Method::POST, routes::PAY_OFFER),
+        (Method::POST, routes::CREATE_REFUND),
+        (Method::POST, routes::REQUEST_REFUND_PAYMENT),
+    ]
+}
+
+/// The routes that a readonly macaroon can access.
+fn readonly_functions() -> Vec<(Method, &'static str)> {
+    vec![
         (Method::GET, routes::ROOT),
         (Method::GET, routes::GET_INFO),
+        (Method::GET, routes::OPENAPI),
         (Method::GET, routes::GET_BALANCE),
         (Method::GET, routes::LIST_FUNDS),
         (Method::GET, routes::LIST_PEERS),
@@ -101,26 +159,21 @@ pub async fn test_unauthorized() -> Result<()> {
         (Method::GET, routes::FEE_RATES),
         (Method::GET, routes::LIST_INVOICES),
         (Method::GET, routes::LIST_PAYMENTS),
+        (Method::GET, routes::GET_PAYMENT),
+        (Method::GET, routes::PAYMENT_DETAIL),
         (Method::GET, routes::ESTIMATE_CHANNEL_LIQUIDITY),
         (Method::GET, routes::LOCAL_REMOTE_BALANCE),
         (Method::GET, routes::GET_FEES),
         (Method::GET, routes::LIST_FORWARDS),
         (Method::GET, routes::LIST_CHANNEL_HISTORY),
+        (Method::GET, routes::FORWARD_STATS),
         (Method::GET, routes::LIST_PEER_CHANNELS),
         (Method::GET, routes::DECODE_INVOICE),
-    ];
-    readonly_functions.extend(admin_functions.into_iter());
-    for (method, route) in readonly_functions {
-        assert_eq!(
-            StatusCode::UNAUTHORIZED,
-            unauthorized_request(&context, method, route)
-                .unwrap()
-                .send()
-                .await?
-                .status()
-        );
-    }
-    Ok(())
+        (Method::GET, routes::JIT_CHANNEL_OFFER),
+        (Method::GET, routes::LIST_JIT_CHANNELS),
+        (Method::GET, routes::LIST_OFFERS),
+        (Method::GET, routes::LIST_INVOICE_REQUESTS),
+    ]
 }
 
 #[tokio::test(flavor = "multi_thread")]
@@ -158,6 +211,72 @@ async fn test_root_admin() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_openapi_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let spec: serde_json::Value = readonly_request(&context, Method::GET, routes::OPENAPI)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!("2.0", spec["swagger"]);
+
+    // Every route is documented, with the path parameters in either notation.
+    let normalise = |path: &str| {
+        path.split('/')
+            .map(|segment| {
+                if segment.starts_with(':') || segment.starts_with('{') {
+                    "{}"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+    let mut documented = BTreeSet::new();
+    for (path, operations) in spec["paths"].as_object().context("no paths")? {
+        for method in operations.as_object().context("no operations")?.keys() {
+            documented.insert((method.to_uppercase(), normalise(path)));
+        }
+    }
+    let served: BTreeSet<(String, String)> = admin_functions()
+        .into_iter()
+        .chain(readonly_functions())
+        .filter(|(_, route)| ![routes::ROOT, routes::OPENAPI].contains(route))
+        .map(|(method, route)| (method.to_string(), normalise(route)))
+        .collect();
+    assert_eq!(served, documented);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_kld_client() -> Result<()> {
+    let context = create_api_server().await?;
+    let client = KldClient::with_client(
+        format!("https://{}", context.settings.rest_api_address),
+        https_client(Some(context.readonly_macaroon.clone()))?,
+    );
+    let info = client.get_info().await?;
+    assert_eq!(mock_lightning().num_peers, info.num_peers);
+    let channels = client.list_peer_channels().await?;
+    assert_eq!(TEST_PUBLIC_KEY, channels[0].peer_id);
+
+    let result = client
+        .sign(&SignRequest {
+            message: "testmessage".to_string(),
+        })
+        .await;
+    assert!(matches!(
+        result,
+        Err(kld_client::Error::Api {
+            status: StatusCode::UNAUTHORIZED,
+            ..
+        })
+    ));
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_sign_admin() -> Result<()> {
     let context = create_api_server().await?;
@@ -237,7 +356,7 @@ async fn test_list_funds_readonly() -> Result<()> {
 #[tokio::test(flavor = "multi_thread")]
 async fn test_list_peer_channels_readonly() -> Result<()> {
     let context = create_api_server().await?;
-    let channels: Vec<GetV1ChannelListPeerChannelsResponse> =
+    let channels: Vec<GetV1ChannelListPeerChannelsResponseItem> =
         readonly_request(&context, Method::GET, routes::LIST_PEER_CHANNELS)?
             .send()
             .await?
@@ -254,7 +373,7 @@ async fn test_list_peer_channels_readonly() -> Result<()> {
     assert!(!channel.private);
     assert!(matches!(
         channel.state,
-        GetV1ChannelListPeerChannelsResponseState::ChanneldNormal
+        GetV1ChannelListPeerChannelsResponseItemState::ChanneldNormal
     ));
     assert_eq!(100000, channel.to_us_msat);
     assert_eq!(1000000000, channel.total_msat);
@@ -627,6 +746,81 @@ async fn test_generate_invoice() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_hold_invoice() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice_request = GenerateHoldInvoice {
+        amount: 200000,
+        label: "test label".to_string(),
+        description: "test description".to_string(),
+        expiry: None,
+        payment_hash: [1u8; 32].to_hex(),
+    };
+    let response: GenerateInvoiceResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::GENERATE_HOLD_INVOICE,
+        || invoice_request.clone(),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!([1u8; 32].to_hex(), response.payment_hash);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_hold_invoice_malformed_hash() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice_request = GenerateHoldInvoice {
+        payment_hash: "not a hash".to_string(),
+        ..Default::default()
+    };
+    let response = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::GENERATE_HOLD_INVOICE,
+        || invoice_request.clone(),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_settle_hold_invoice_unknown_preimage() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = SettleHoldInvoice {
+        preimage: [5u8; 32].to_hex(),
+    };
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::SETTLE_HOLD_INVOICE, || {
+            request
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cancel_hold_invoice() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = CancelHoldInvoice {
+        payment_hash: [1u8; 32].to_hex(),
+    };
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::CANCEL_HOLD_INVOICE, || {
+            request
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, response.status());
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_list_invoice_unpaid() -> Result<()> {
     let context = create_api_server().await?;
@@ -683,8 +877,66 @@ async fn test_list_payments() -> Result<()> {
     ));
     assert!(payment_response.payment_preimage.is_none());
     assert_eq!(payment.amount as i64, payment_response.amount_sent_msat);
+    assert_eq!(1, payment_response.parts);
+    assert_eq!(2, payment_response.attempts);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_payment() -> Result<()> {
+    let context = create_api_server().await?;
+    let payment = &mock_lightning().payment;
+    let response: PaymentStatusResponse = readonly_request(
+        &context,
+        Method::GET,
+        &routes::GET_PAYMENT.replace(":id", &payment.id.0.to_hex()),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(payment.id.0.to_hex(), response.id);
+    assert_eq!(payment.status.to_string(), response.status);
+    assert_eq!(payment.amount, response.amount_msat);
+    assert_eq!(payment.fee, response.fee_msat);
+    assert_eq!(payment.attempts, response.attempts);
+    assert_eq!(1, response.parts);
+
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &routes::GET_PAYMENT.replace(":id", &[7u8; 32].to_hex()),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payment_detail() -> Result<()> {
+    let context = create_api_server().await?;
+    let payment = &mock_lightning().payment;
+    let response: PaymentDetailResponse = readonly_request(
+        &context,
+        Method::GET,
+        &routes::PAYMENT_DETAIL.replace(":id", &payment.id.0.to_hex()),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(payment.id.0.to_hex(), response.payment.id);
+    assert_eq!(2, response.attempts.len());
+    let failed = &response.attempts[0];
+    assert_eq!("failed", failed.status);
+    assert_eq!(Some(TEST_SHORT_CHANNEL_ID), failed.failed_short_channel_id);
+    assert_eq!(TEST_PUBLIC_KEY, failed.hops[0].node_id);
+    assert_eq!(1000, failed.fee_msat);
+    assert_eq!("succeeded", response.attempts[1].status);
     Ok(())
 }
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_pay_invoice() -> Result<()> {
     let context = create_api_server().await?;
@@ -692,6 +944,7 @@ async fn test_pay_invoice() -> Result<()> {
     let request = PayInvoice {
         label: Some("test label".to_string()),
         invoice: invoice.to_string(),
+        ..Default::default()
     };
     let response: PaymentResponse =
         admin_request_with_body(&context, Method::POST, routes::PAY_INVOICE, || request)?
@@ -706,10 +959,96 @@ async fn test_pay_invoice() -> Result<()> {
         response.created_at,
         SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()
     );
-    assert_eq!(1, response.parts);
     assert_eq!(Some(200000), response.amount_msat);
     assert_eq!(200000, response.amount_sent_msat);
     assert_eq!("succeeded", response.status);
+
+    // The invoice has an amount already.
+    let request = PayInvoice {
+        invoice: invoice.to_string(),
+        amount_msat: Some(300000),
+        ..Default::default()
+    };
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::PAY_INVOICE, || request)?
+            .send()
+            .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_probe_node_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = ProbeRequest {
+        pubkey: Some(TEST_PUBLIC_KEY.to_string()),
+        amount_msat: Some(100000),
+        ..Default::default()
+    };
+    let response: ProbeResponse =
+        admin_request_with_body(&context, Method::POST, routes::PROBE, || request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(ProbeStatus::Succeeded, response.status);
+    assert_eq!(1, response.paths);
+    assert_eq!(None, response.failed_short_channel_id);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_probe_invoice_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = ProbeRequest {
+        invoice: Some(mock_lightning().invoice.bolt11.to_string()),
+        ..Default::default()
+    };
+    let response: ProbeResponse =
+        admin_request_with_body(&context, Method::POST, routes::PROBE, || request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(ProbeStatus::Succeeded, response.status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_probe_failed_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let destination = random_public_key().to_string();
+    let request = ProbeRequest {
+        pubkey: Some(destination.clone()),
+        amount_msat: Some(100000),
+        ..Default::default()
+    };
+    let response: ProbeResponse =
+        admin_request_with_body(&context, Method::POST, routes::PROBE, || request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(ProbeStatus::Failed, response.status);
+    assert_eq!(
+        Some(TEST_SHORT_CHANNEL_ID),
+        response.failed_short_channel_id
+    );
+    assert_eq!(Some(destination), response.failed_node_id);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_probe_missing_amount() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = ProbeRequest {
+        pubkey: Some(TEST_PUBLIC_KEY.to_string()),
+        ..Default::default()
+    };
+    let response = admin_request_with_body(&context, Method::POST, routes::PROBE, || request)?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
     Ok(())
 }
 
@@ -726,13 +1065,26 @@ async fn test_keysend_admin() -> Result<()> {
     assert_eq!(64, response.payment_hash.len());
     assert!(response.payment_preimage.is_empty());
     assert!(response.created_at > 0);
-    assert_eq!(1, response.parts);
     assert_eq!(Some(1000), response.amount_msat);
     assert_eq!(1000000, response.amount_sent_msat);
     assert_eq!(PaymentStatus::Pending.to_string(), response.status);
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keysend_invalid_fee_limit() -> Result<()> {
+    let context = create_api_server().await?;
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::KEYSEND, || KeysendRequest {
+            maxfeepercent: Some(150.0),
+            ..keysend_request()
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_estimate_liquidity() -> Result<()> {
     let context = create_api_server().await?;
@@ -813,6 +1165,35 @@ async fn test_fetch_forwards() -> Result<()> {
     assert!(forward.resolved_time.is_some());
     assert_eq!(None, forward.failcode);
     assert_eq!(None, forward.failreason);
+    assert_eq!(Some(mock_lightning().forward.id.to_string()), forward.id);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fetch_forwards_page() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = readonly_request(&context, Method::GET, routes::LIST_FORWARDS)?
+        .query(&[
+            ("peer", TEST_PUBLIC_KEY),
+            ("since", "1700000000"),
+            ("limit", "10"),
+            ("cursor", &mock_lightning().forward.id.to_string()),
+        ])
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = readonly_request(&context, Method::GET, routes::LIST_FORWARDS)?
+        .query(&[("cursor", "not a uuid")])
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = readonly_request(&context, Method::GET, routes::LIST_CHANNEL_HISTORY)?
+        .query(&[("channel", "abcd")])
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
     Ok(())
 }
 
@@ -847,6 +1228,39 @@ async fn test_channel_history() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_forward_stats() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: ForwardStats = readonly_request(&context, Method::GET, routes::FORWARD_STATS)?
+        .query(&[("since", "1700000000")])
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(Some(1700000000), response.since);
+    assert_eq!(None, response.until);
+    assert_eq!(1, response.total.succeeded_in);
+    assert_eq!(5003000, response.total.volume_in_msat);
+    assert_eq!(5000000, response.total.volume_out_msat);
+    assert_eq!(3000, response.total.fees_earned_msat);
+    assert_eq!(Some(600), response.total.effective_fee_rate_ppm);
+    assert_eq!(2, response.channels.len());
+    let outbound = response.channels.first().context("expected channel")?;
+    assert_eq!(
+        mock_lightning()
+            .forward
+            .outbound_channel_id
+            .map(|c| c.to_hex()),
+        Some(outbound.channel_id.clone())
+    );
+    assert_eq!(Some(TEST_PUBLIC_KEY.to_string()), outbound.peer_id);
+    assert_eq!(3000, outbound.stats.fees_earned_msat);
+    let peer = response.peers.first().context("expected peer")?;
+    assert_eq!(TEST_PUBLIC_KEY, peer.peer_id);
+    assert_eq!(1, peer.stats.succeeded_out);
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_decode_invoice() -> Result<()> {
     let context = create_api_server().await?;
@@ -876,20 +1290,867 @@ async fn test_decode_invoice() -> Result<()> {
     Ok(())
 }
 
-fn withdraw_request() -> WalletTransfer {
-    WalletTransfer {
-        address: TEST_ADDRESS.to_string(),
-        satoshis: "all".to_string(),
-        fee_rate: Some(FeeRate::PerKw(4000)),
-        min_conf: Some("3".to_string()),
-        utxos: vec![],
-    }
+#[tokio::test(flavor = "multi_thread")]
+async fn test_channel_backup_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: ChannelBackup = admin_request(&context, Method::GET, routes::CHANNEL_BACKUP)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(1, response.channels);
+    assert_eq!(1_700_000_000, response.timestamp);
+
+    let backup =
+        StaticChannelBackup::decrypt(&Cipher::new(&[0; 32]), &hex::decode(response.backup)?)?;
+    let channel = backup.channels.first().context("expected channel")?;
+    assert_eq!(
+        mock_lightning().channel.channel_id.to_hex(),
+        channel.channel_id
+    );
+    assert_eq!(TEST_PUBLIC_KEY, channel.counterparty);
+    assert_eq!(Some(format!("{TEST_TX_ID}:2")), channel.funding_txo);
+    assert_eq!(1000000, channel.value_sats);
+    assert_eq!(Some("01".repeat(32)), channel.channel_keys_id);
+    Ok(())
 }
 
-fn fund_channel_request() -> FundChannel {
-    FundChannel {
-        id: TEST_PUBLIC_KEY.to_string() + "@1.2.3.4:1234",
-        satoshis: "2100000".to_string(),
+#[tokio::test(flavor = "multi_thread")]
+async fn test_create_webhook_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = CreateWebhook {
+        url: "https://example.com/hook".to_string(),
+        secret: "secret".to_string(),
+        events: vec![
+            WebhookEvent::InvoiceSettled,
+            WebhookEvent::ChannelForceClosed,
+        ],
+    };
+    let response: Webhook =
+        admin_request_with_body(&context, Method::POST, routes::WEBHOOKS, || request.clone())?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert!(!response.id.is_empty());
+    assert_eq!(request.url, response.url);
+    assert_eq!(request.events, response.events);
+    assert!(response.created_at > 0);
+
+    let request = CreateWebhook {
+        url: "ftp://example.com/hook".to_string(),
+        ..request
+    };
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::WEBHOOKS, || request.clone())?
+            .send()
+            .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_webhooks_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: Vec<Webhook> = admin_request(&context, Method::GET, routes::WEBHOOKS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let webhook = response.first().context("expected webhook")?;
+    assert_eq!(mock_lightning().webhook.id.to_string(), webhook.id);
+    assert_eq!("https://example.com/kld", webhook.url);
+    assert_eq!(vec![WebhookEvent::InvoiceSettled], webhook.events);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delete_webhook_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let id = mock_lightning().webhook.id.to_string();
+    let response = admin_request(
+        &context,
+        Method::DELETE,
+        &routes::DELETE_WEBHOOK.replace(":id", &id),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = admin_request(
+        &context,
+        Method::DELETE,
+        &routes::DELETE_WEBHOOK.replace(":id", "00000000-0000-0000-0000-000000000000"),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_websocket_topics_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let mut socket = websocket(&context, "payments").await?;
+    let events = &mock_lightning().events;
+
+    // The channel event is sent first, so it would be received first if it was not filtered.
+    let channel_ready = NodeEvent::ChannelReady {
+        channel_id: "ab".repeat(32),
+        counterparty: TEST_PUBLIC_KEY.to_string(),
+    };
+    let payment_sent = NodeEvent::PaymentSent {
+        payment_id: "cd".repeat(32),
+        payment_hash: "ef".repeat(32),
+        fee_msat: Some(1000),
+    };
+    events.send(channel_ready.clone())?;
+    events.send(payment_sent.clone())?;
+    assert_eq!(payment_sent, next_event(&mut socket).await?);
+
+    // The subscription is changed asynchronously, so send the channel event until it gets through.
+    let subscribe = EventSubscription::Subscribe(vec![EventTopic::Channels]);
+    socket
+        .send(Message::Text(serde_json::to_string(&subscribe)?))
+        .await?;
+    poll!(
+        5,
+        events.send(channel_ready.clone()).is_ok()
+            && matches!(
+                timeout(Duration::from_secs(1), next_event(&mut socket)).await,
+                Ok(Ok(event)) if event == channel_ready
+            )
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bake_macaroon_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let bake = BakeMacaroon {
+        permissions: vec![MacaroonPermission::Payments],
+        max_payment_sats: Some(100),
+        expiry: Some(3600),
+        ..Default::default()
+    };
+    let response: BakeMacaroonResponse =
+        admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || bake)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert!(response.expires_at.is_some());
+
+    let address = &context.settings.rest_api_address;
+    let client = https_client(Some(response.macaroon.into_bytes()))?;
+    let response = client
+        .get(format!("https://{address}{}", routes::LIST_PAYMENTS))
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    // Routes outside of the permissions are rejected.
+    let response = client
+        .get(format!("https://{address}{}", routes::LIST_INVOICES))
+        .send()
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // The invoice is for 200 sats.
+    let request = PayInvoice {
+        invoice: mock_lightning().invoice.bolt11.to_string(),
+        ..Default::default()
+    };
+    let response = client
+        .post(format!("https://{address}{}", routes::PAY_INVOICE))
+        .body(serde_json::to_string(&request)?)
+        .send()
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // A restricted macaroon can not bake one without its restrictions.
+    let response = client
+        .post(format!("https://{address}{}", routes::BAKE_MACAROON))
+        .body(serde_json::to_string(&BakeMacaroon::default())?)
+        .send()
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_macaroon_max_payment_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let bake = BakeMacaroon {
+        max_payment_sats: Some(5),
+        ..Default::default()
+    };
+    let baked: BakeMacaroonResponse =
+        admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || bake)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    let macaroon = baked.macaroon.into_bytes();
+    let address = &context.settings.rest_api_address;
+    let client = https_client(Some(macaroon.clone()))?;
+    let post = |route: &str, body: String| {
+        client
+            .post(format!("https://{address}{route}"))
+            .body(body)
+            .send()
+    };
+
+    // The mock allows a 5 sat routing fee unless the request sets exemptfee.
+    let response = post(routes::KEYSEND, serde_json::to_string(&keysend_request())?).await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let request = KeysendRequest {
+        exemptfee: Some(4000),
+        ..keysend_request()
+    };
+    let response = post(routes::KEYSEND, serde_json::to_string(&request)?).await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let request = PayInvoice {
+        invoice: mock_lightning().invoice.bolt11.to_string(),
+        ..Default::default()
+    };
+    let response = post(routes::PAY_INVOICE, serde_json::to_string(&request)?).await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let offer: OfferResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::CREATE_OFFER,
+        create_offer_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let request = PayOffer {
+        offer: offer.bolt12,
+        amount_msat: Some(1000),
+        ..Default::default()
+    };
+    let response = post(routes::PAY_OFFER, serde_json::to_string(&request)?).await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let request = CreateRefund {
+        description: "test refund".to_string(),
+        amount_msat: 1000,
+        expiry: None,
+        label: None,
+    };
+    let response = post(routes::CREATE_REFUND, serde_json::to_string(&request)?).await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let request = FundChannel {
+        satoshis: "4".to_string(),
+        push_msat: Some("2000".to_string()),
+        ..fund_channel_request()
+    };
+    let response = post(routes::OPEN_CHANNEL, serde_json::to_string(&request)?).await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let response = post(
+        routes::WITHDRAW,
+        serde_json::to_string(&withdraw_request())?,
+    )
+    .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let request = WalletTransfer {
+        satoshis: "6".to_string(),
+        ..withdraw_request()
+    };
+    let response = post(routes::WITHDRAW, serde_json::to_string(&request)?).await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let response = lnd_request(&context, Method::POST, routes::lnd::SEND_COINS, &macaroon)?
+        .json(&lnd::SendCoins {
+            addr: TEST_ADDRESS.to_string(),
+            amount: 6,
+            ..Default::default()
+        })
+        .send()
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let response = lnd_request(&context, Method::POST, routes::lnd::SEND_PAYMENT, &macaroon)?
+        .json(&lnd::SendPayment {
+            dest: general_purpose::STANDARD.encode(Vec::<u8>::from_hex(TEST_PUBLIC_KEY)?),
+            amt_msat: 1000,
+            ..Default::default()
+        })
+        .send()
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_macaroon_root_keys_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let bake = BakeMacaroon {
+        root_key: Some("monitoring".to_string()),
+        ..Default::default()
+    };
+    let baked: BakeMacaroonResponse =
+        admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || bake)?
+            .send()
+            .await?
+            .json()
+            .await?;
+
+    let root_keys: Vec<MacaroonRootKey> =
+        admin_request(&context, Method::GET, routes::LIST_MACAROON_ROOT_KEYS)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    let admin_root_key = root_keys
+        .iter()
+        .find(|root_key| root_key.name == "admin")
+        .context("expected admin root key")?;
+    assert!(root_keys.iter().any(|root_key| root_key.name == "readonly"));
+    let root_key = root_keys
+        .iter()
+        .find(|root_key| root_key.id == baked.root_key_id)
+        .context("expected baked root key")?;
+    assert_eq!("monitoring", root_key.name);
+
+    let address = &context.settings.rest_api_address;
+    let client = https_client(Some(baked.macaroon.into_bytes()))?;
+    let response = client
+        .get(format!("https://{address}{}", routes::GET_INFO))
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let route = routes::DELETE_MACAROON_ROOT_KEY.replace(":id", &baked.root_key_id);
+    let response = admin_request(&context, Method::DELETE, &route)?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, response.status());
+    let response = client
+        .get(format!("https://{address}{}", routes::GET_INFO))
+        .send()
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let response = admin_request(&context, Method::DELETE, &route)?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    // A root key that another node deleted from the database.
+    let bake = BakeMacaroon {
+        root_key: Some("other-node".to_string()),
+        ..Default::default()
+    };
+    let baked: BakeMacaroonResponse =
+        admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || bake)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    mock_lightning()
+        .macaroon_root_keys
+        .lock()
+        .unwrap()
+        .retain(|root_key| root_key.id.to_string() != baked.root_key_id);
+    let response = https_client(Some(baked.macaroon.into_bytes()))?
+        .get(format!("https://{address}{}", routes::GET_INFO))
+        .send()
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // The default root keys can only be rotated.
+    let route = routes::DELETE_MACAROON_ROOT_KEY.replace(":id", &admin_root_key.id);
+    let response = admin_request(&context, Method::DELETE, &route)?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_channel_acceptance_policy_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: ChannelAcceptancePolicy =
+        admin_request(&context, Method::GET, routes::CHANNEL_ACCEPTANCE_POLICY)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(Some(20000), response.min_channel_sats);
+    assert_eq!(None, response.max_channel_sats);
+    assert!(response.allow_list.is_empty());
+    assert_eq!("Channel rejected by node policy", response.reject_reason);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_channel_acceptance_policy_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = ChannelAcceptancePolicy {
+        min_channel_sats: Some(100000),
+        max_channel_sats: Some(5000000),
+        allow_list: vec![],
+        deny_list: vec![TEST_PUBLIC_KEY.to_string()],
+        max_channels_per_peer: Some(2),
+        required_features: vec![ChannelTypeFeature::AnchorsZeroFeeHtlcTx],
+        reject_reason: "Too small".to_string(),
+    };
+    let response: ChannelAcceptancePolicy = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::CHANNEL_ACCEPTANCE_POLICY,
+        || request.clone(),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(request, response);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_channel_acceptance_policy_malformed() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = ChannelAcceptancePolicy {
+        min_channel_sats: Some(100000),
+        max_channel_sats: Some(1000),
+        reject_reason: "Too small".to_string(),
+        ..Default::default()
+    };
+    let response = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::CHANNEL_ACCEPTANCE_POLICY,
+        || request,
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_jit_channel_offer_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let offer: JitChannelOffer =
+        readonly_request(&context, Method::GET, routes::JIT_CHANNEL_OFFER)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(2000000, offer.min_fee_msat);
+    assert_eq!(1000, offer.proportional);
+    assert_eq!(10000000, offer.min_payment_size_msat);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_jit_channels_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let sessions: Vec<JitChannelSession> =
+        readonly_request(&context, Method::GET, routes::LIST_JIT_CHANNELS)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    let session = sessions.get(0).context("expected a session")?;
+    assert_eq!(3, session.user_channel_id);
+    assert_eq!(TEST_PUBLIC_KEY, session.counterparty_node_id);
+    assert_eq!(Some(2000000), session.opening_fee_msat);
+    assert_eq!(None, session.channel_id);
+    assert_eq!(JitChannelState::AwaitingPayment, session.state);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_create_offer_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: OfferResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::CREATE_OFFER,
+        create_offer_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert!(response.bolt12.starts_with("lno"));
+    assert_eq!(64, response.id.len());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_offers_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let offers: Vec<Offer> = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?label=test", routes::LIST_OFFERS),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let offer = offers.get(0).context("expected an offer")?;
+    assert_eq!(OfferKind::Offer, offer.kind);
+    assert_eq!("test offer", offer.description);
+    assert_eq!(Some("test".to_string()), offer.label);
+    assert_eq!(Some(1000), offer.amount_msat);
+    assert_eq!("inbound", offer.direction);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pay_offer_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let offer: OfferResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::CREATE_OFFER,
+        create_offer_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let request = PayOffer {
+        offer: offer.bolt12,
+        label: Some("test label".to_string()),
+        ..Default::default()
+    };
+    let response: PaymentResponse =
+        admin_request_with_body(&context, Method::POST, routes::PAY_OFFER, || request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(TEST_PUBLIC_KEY, response.destination);
+    assert_eq!(64, response.payment_preimage.len());
+    assert_eq!(Some(5000), response.amount_msat);
+    assert_eq!(5012, response.amount_sent_msat);
+    assert_eq!("succeeded", response.status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pay_offer_malformed() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = PayOffer {
+        offer: "lno1notanoffer".to_string(),
+        ..Default::default()
+    };
+    let response = admin_request_with_body(&context, Method::POST, routes::PAY_OFFER, || request)?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_create_refund_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = CreateRefund {
+        description: "test refund".to_string(),
+        amount_msat: 3000,
+        expiry: None,
+        label: None,
+    };
+    let response: OfferResponse =
+        admin_request_with_body(&context, Method::POST, routes::CREATE_REFUND, || request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert!(response.bolt12.starts_with("lnr"));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_invoice_requests_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let requests: Vec<InvoiceRequest> =
+        readonly_request(&context, Method::GET, routes::LIST_INVOICE_REQUESTS)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    let request = requests.get(0).context("expected an invoice request")?;
+    assert_eq!(Some(1000), request.amount_msat);
+    assert_eq!(Some("thanks".to_string()), request.payer_note);
+    assert_eq!(InvoiceRequestStatus::Pending, request.status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lnd_unauthorized() -> Result<()> {
+    let context = create_api_server().await?;
+    let address = context
+        .settings
+        .lnd_rest_api_address
+        .as_ref()
+        .context("expected LND REST API")?;
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        https_client(None)?
+            .get(format!("https://{address}{}", routes::lnd::GET_INFO))
+            .send()
+            .await?
+            .status()
+    );
+    for (method, route) in [
+        (Method::GET, routes::lnd::NEW_ADDRESS),
+        (Method::POST, routes::lnd::SEND_COINS),
+        (Method::POST, routes::lnd::INVOICES),
+        (Method::POST, routes::lnd::SEND_PAYMENT),
+    ] {
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            lnd_request(&context, method, route, &context.readonly_macaroon)?
+                .send()
+                .await?
+                .status()
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lnd_get_info() -> Result<()> {
+    let context = create_api_server().await?;
+    let info: lnd::GetInfo = lnd_request(
+        &context,
+        Method::GET,
+        routes::lnd::GET_INFO,
+        &context.readonly_macaroon,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let pubkey = mock_lightning().public_key.to_string();
+    assert_eq!(pubkey, info.identity_pubkey);
+    assert_eq!("#6e2cf7", info.color);
+    assert_eq!(800000, info.block_height);
+    assert_eq!(TEST_BLOCK_HASH, info.block_hash);
+    assert_eq!("mainnet", info.chains[0].network);
+    assert_eq!(format!("{pubkey}@127.0.0.1:2312"), info.uris[0]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lnd_balances() -> Result<()> {
+    let context = create_api_server().await?;
+    let balance: serde_json::Value = lnd_request(
+        &context,
+        Method::GET,
+        routes::lnd::WALLET_BALANCE,
+        &context.readonly_macaroon,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    // LND encodes 64 bit integers as strings.
+    assert_eq!("9", balance["total_balance"]);
+    assert_eq!("4", balance["confirmed_balance"]);
+    assert_eq!("5", balance["unconfirmed_balance"]);
+
+    let balance: lnd::ChannelBalance = lnd_request(
+        &context,
+        Method::GET,
+        routes::lnd::CHANNEL_BALANCE,
+        &context.readonly_macaroon,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(100, balance.balance);
+    assert_eq!(100000, balance.local_balance.msat);
+    assert_eq!(999900, balance.remote_balance.sat);
+    assert_eq!(0, balance.pending_open_local_balance.msat);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lnd_list_channels_and_peers() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: lnd::ListChannels = lnd_request(
+        &context,
+        Method::GET,
+        routes::lnd::LIST_CHANNELS,
+        &context.readonly_macaroon,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let channel = response.channels.get(0).context("expected channel")?;
+    assert!(channel.active);
+    assert_eq!(TEST_SHORT_CHANNEL_ID, channel.chan_id);
+    assert_eq!(1000000, channel.capacity);
+    assert_eq!(100, channel.local_balance);
+    assert_eq!(999900, channel.remote_balance);
+
+    let response: lnd::ListPeers = lnd_request(
+        &context,
+        Method::GET,
+        routes::lnd::LIST_PEERS,
+        &context.readonly_macaroon,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let peer = response.peers.get(0).context("expected peer")?;
+    assert_eq!(TEST_PUBLIC_KEY, peer.pub_key);
+    assert_eq!("127.0.0.1:5555", peer.address);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lnd_invoices() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice = &mock_lightning().invoice;
+    let response: lnd::AddInvoiceResponse = lnd_request(
+        &context,
+        Method::POST,
+        routes::lnd::INVOICES,
+        &context.admin_macaroon,
+    )?
+    .json(&lnd::AddInvoice {
+        memo: "test".to_string(),
+        value: 200,
+        ..Default::default()
+    })
+    .send()
+    .await?
+    .json()
+    .await?;
+    let r_hash = general_purpose::STANDARD.encode(invoice.payment_hash.0);
+    assert_eq!(r_hash, response.r_hash);
+    assert_eq!(invoice.bolt11.to_string(), response.payment_request);
+
+    let response: lnd::ListInvoices = lnd_request(
+        &context,
+        Method::GET,
+        routes::lnd::INVOICES,
+        &context.readonly_macaroon,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let lnd_invoice = response.invoices.get(0).context("expected invoice")?;
+    assert_eq!(r_hash, lnd_invoice.r_hash);
+    assert_eq!(200000, lnd_invoice.value_msat);
+    assert_eq!(200, lnd_invoice.value);
+
+    let pay_req: lnd::PayReq = lnd_request(
+        &context,
+        Method::GET,
+        &routes::lnd::DECODE_PAY_REQ.replace(":pay_req", &invoice.bolt11.to_string()),
+        &context.readonly_macaroon,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(TEST_PUBLIC_KEY, pay_req.destination);
+    assert_eq!(invoice.payment_hash.0.to_hex(), pay_req.payment_hash);
+    assert_eq!(200, pay_req.num_satoshis);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lnd_payments() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice = mock_lightning().invoice.bolt11.to_string();
+    let response: lnd::PaymentUpdate = lnd_request(
+        &context,
+        Method::POST,
+        routes::lnd::SEND_PAYMENT,
+        &context.admin_macaroon,
+    )?
+    .json(&lnd::SendPayment {
+        payment_request: invoice.clone(),
+        fee_limit_sat: 10,
+        ..Default::default()
+    })
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(lnd::PaymentStatus::Succeeded, response.result.status);
+    assert_eq!(200000, response.result.value_msat);
+    assert_eq!(invoice, response.result.payment_request);
+
+    // The invoice already has an amount.
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        lnd_request(
+            &context,
+            Method::POST,
+            routes::lnd::SEND_PAYMENT,
+            &context.admin_macaroon,
+        )?
+        .json(&lnd::SendPayment {
+            payment_request: invoice,
+            amt: 100,
+            ..Default::default()
+        })
+        .send()
+        .await?
+        .status()
+    );
+
+    let payment = &mock_lightning().payment;
+    let response: lnd::ListPayments = lnd_request(
+        &context,
+        Method::GET,
+        routes::lnd::LIST_PAYMENTS,
+        &context.readonly_macaroon,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let lnd_payment = response.payments.get(0).context("expected payment")?;
+    assert_eq!(lnd::PaymentStatus::InFlight, lnd_payment.status);
+    assert_eq!(payment.amount, lnd_payment.value_msat);
+    Ok(())
+}
+
+fn create_offer_request() -> CreateOffer {
+    CreateOffer {
+        description: "test offer".to_string(),
+        amount_msat: Some(5000),
+        label: Some("test".to_string()),
+        ..Default::default()
+    }
+}
+
+fn withdraw_request() -> WalletTransfer {
+    WalletTransfer {
+        address: TEST_ADDRESS.to_string(),
+        satoshis: "all".to_string(),
+        fee_rate: Some(FeeRate::PerKw(4000)),
+        min_conf: Some("3".to_string()),
+        utxos: vec![],
+    }
+}
+
+fn fund_channel_request() -> FundChannel {
+    FundChannel {
+        id: TEST_PUBLIC_KEY.to_string() + "@1.2.3.4:1234",
+        satoshis: "2100000".to_string(),
         fee_rate: Some(api::FeeRate::Urgent),
         announce: Some(false),
         push_msat: Some("10000".to_string()),
@@ -918,6 +2179,7 @@ fn keysend_request() -> KeysendRequest {
         retry_for: None,
         maxdelay: None,
         exemptfee: None,
+        nowait: None,
     }
 }
 
@@ -957,32 +2219,49 @@ pub async fn create_api_server() -> Result<Arc<TestContext>> {
     let tmp_dir = TempDir::new()?;
     let rest_api_port = get_available_port().context("no port available")?;
     let rest_api_address = format!("127.0.0.1:{rest_api_port}");
+    let lnd_rest_api_port = get_available_port().context("no port available")?;
+    let lnd_rest_api_address = format!("127.0.0.1:{lnd_rest_api_port}");
     let mut settings = test_settings(&tmp_dir, "api");
     settings.rest_api_address = rest_api_address.clone();
+    settings.lnd_rest_api_address = Some(lnd_rest_api_address.clone());
     let certs_dir = settings.certs_dir.clone();
     let macaroon_auth = Arc::new(
-        MacaroonAuth::init(&[0u8; 32], &settings.data_dir)
+        MacaroonAuth::init(&[0u8; 32], &settings.data_dir, mock_lightning().as_ref())
+            .await
             .context("cannot initialize macaroon auth")?,
     );
     let admin_macaroon = admin_macaroon(&settings)?;
     let readonly_macaroon = readonly_macaroon(&settings)?;
 
     // Run the API with its own runtime in its own thread.
+    let lnd_certs_dir = certs_dir.clone();
+    let lnd_macaroon_auth = macaroon_auth.clone();
     spawn(move || {
-        API_RUNTIME
-            .get_or_init(|| Runtime::new().unwrap())
-            .spawn(async {
-                bind_api_server(rest_api_address, certs_dir)
-                    .await?
-                    .serve(
-                        Arc::new(MockBitcoind),
-                        mock_lightning(),
-                        Arc::new(MockWallet::default()),
-                        macaroon_auth,
-                        quit_signal().shared(),
-                    )
-                    .await
-            })
+        let runtime = API_RUNTIME.get_or_init(|| Runtime::new().unwrap());
+        runtime.spawn(async {
+            bind_api_server(lnd_rest_api_address, lnd_certs_dir)
+                .await?
+                .serve_lnd(
+                    Arc::new(MockBitcoind),
+                    mock_lightning(),
+                    Arc::new(MockWallet::default()),
+                    lnd_macaroon_auth,
+                    quit_signal().shared(),
+                )
+                .await
+        });
+        runtime.spawn(async {
+            bind_api_server(rest_api_address, certs_dir)
+                .await?
+                .serve(
+                    Arc::new(MockBitcoind),
+                    mock_lightning(),
+                    Arc::new(MockWallet::default()),
+                    macaroon_auth,
+                    quit_signal().shared(),
+                )
+                .await
+        })
     });
 
     let new_context = TestContext {
@@ -1000,6 +2279,19 @@ pub async fn create_api_server() -> Result<Arc<TestContext>> {
             .map(|r| r.status().is_success())
             .unwrap_or_default()
     );
+    poll!(
+        3,
+        lnd_request(
+            &new_context,
+            Method::GET,
+            routes::lnd::GET_INFO,
+            &new_context.readonly_macaroon
+        )?
+        .send()
+        .await
+        .map(|r| r.status().is_success())
+        .unwrap_or_default()
+    );
 
     *context = Some(Arc::new(new_context));
     drop(context); // release lock
@@ -1038,6 +2330,41 @@ fn admin_request(context: &TestContext, method: Method, route: &str) -> Result<R
         .request(method, format!("https://{address}{route}")))
 }
 
+type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Websocket clients send the macaroon as the first protocol, the server answers with the second.
+async fn websocket(context: &TestContext, topics: &str) -> Result<WebSocket> {
+    let address = &context.settings.rest_api_address;
+    let mut request =
+        format!("wss://{address}{}?topics={topics}", routes::WEBSOCKET).into_client_request()?;
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_str(&format!("{}, hex", hex::encode(&context.admin_macaroon)))?,
+    );
+    let certificate = native_tls::Certificate::from_pem(&fs::read(format!(
+        "{}/kld.crt",
+        context.settings.certs_dir
+    ))?)?;
+    let connector = native_tls::TlsConnector::builder()
+        .add_root_certificate(certificate)
+        .use_sni(false)
+        .build()?;
+    let (socket, _) =
+        connect_async_tls_with_config(request, None, false, Some(Connector::NativeTls(connector)))
+            .await?;
+    Ok(socket)
+}
+
+// The next event on the websocket, skipping the pings of the server.
+async fn next_event(socket: &mut WebSocket) -> Result<NodeEvent> {
+    while let Some(message) = socket.next().await {
+        if let Message::Text(json) = message? {
+            return Ok(serde_json::from_str(&json)?);
+        }
+    }
+    bail!("Websocket closed")
+}
+
 fn admin_request_with_body<T: Serialize, F: FnOnce() -> T>(
     context: &TestContext,
     method: Method,
@@ -1054,6 +2381,23 @@ fn readonly_request(context: &TestContext, method: Method, route: &str) -> Resul
         .request(method, format!("https://{address}{route}")))
 }
 
+// LND clients send the hex encoded macaroon file in this header.
+fn lnd_request(
+    context: &TestContext,
+    method: Method,
+    route: &str,
+    macaroon: &[u8],
+) -> Result<RequestBuilder> {
+    let address = context
+        .settings
+        .lnd_rest_api_address
+        .as_ref()
+        .context("expected LND REST API")?;
+    Ok(https_client(None)?
+        .request(method, format!("https://{address}{route}"))
+        .header("Grpc-Metadata-macaroon", macaroon.to_hex()))
+}
+
 fn readonly_request_with_body<T: Serialize, F: FnOnce() -> T>(
     context: &TestContext,
     method: Method,
@@ -0,0 +1,126 @@
+use std::{fs, os::unix::fs::PermissionsExt, sync::Arc};
+
+use anyhow::{Context, Result};
+use futures::FutureExt;
+use kld::api::bind_rpc_server;
+use serde_json::{json, Value};
+use test_utils::{TempDir, TEST_PUBLIC_KEY};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+use crate::{
+    mocks::{mock_bitcoind::MockBitcoind, mock_lightning::MockLightning, mock_wallet::MockWallet},
+    quit_signal,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_json_rpc() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let path = format!("{}/lightning-rpc", tmp_dir.path().display());
+    let lightning = Arc::new(MockLightning::default());
+    let server = bind_rpc_server(path.clone())?;
+    assert_eq!(0o600, fs::metadata(&path)?.permissions().mode() & 0o777);
+    assert!(fs::metadata(format!("{path}.tmp")).is_err());
+    tokio::spawn(server.serve(
+        Arc::new(MockBitcoind),
+        lightning.clone(),
+        Arc::new(MockWallet::default()),
+        quit_signal().shared(),
+    ));
+    let mut stream = UnixStream::connect(&path).await?;
+
+    let response = call(
+        &mut stream,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getinfo"}),
+    )
+    .await?;
+    assert_eq!(1, response["id"]);
+    assert_eq!(lightning.num_peers, response["result"]["num_peers"]);
+
+    let response = call(
+        &mut stream,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "listpeerchannels", "params": {}}),
+    )
+    .await?;
+    let channel = &response["result"]["channels"][0];
+    assert_eq!(TEST_PUBLIC_KEY, channel["peer_id"]);
+    assert_eq!("CHANNELD_NORMAL", channel["state"]);
+    let short_channel_id = lightning
+        .channel
+        .short_channel_id
+        .context("expected scid")?;
+    assert_eq!(
+        format!(
+            "{}x{}x{}",
+            short_channel_id >> 40,
+            (short_channel_id >> 16) & 0xffffff,
+            short_channel_id & 0xffff
+        ),
+        channel["short_channel_id"]
+    );
+
+    let response = call(
+        &mut stream,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "invoice", "params": ["1000msat", "rpc label", "rpc description"]}),
+    )
+    .await?;
+    assert_eq!(
+        lightning.invoice.bolt11.to_string(),
+        response["result"]["bolt11"]
+    );
+    assert!(response["result"]["payment_hash"].is_string());
+
+    let response = call(
+        &mut stream,
+        json!({"jsonrpc": "2.0", "id": 4, "method": "pay", "params": {"bolt11": lightning.invoice.bolt11.to_string()}}),
+    )
+    .await?;
+    assert_eq!("complete", response["result"]["status"]);
+    assert_eq!(200000, response["result"]["amount_sent_msat"]);
+    assert!(response["result"].get("amountSentMsat").is_none());
+
+    let response = call(
+        &mut stream,
+        json!({"jsonrpc": "2.0", "id": 4, "method": "pay", "params": [lightning.invoice.bolt11.to_string(), "300000msat"]}),
+    )
+    .await?;
+    assert_eq!(-32602, response["error"]["code"]);
+
+    let response = call(
+        &mut stream,
+        json!({"jsonrpc": "2.0", "id": 5, "method": "pay", "params": {"bolt11": "lnbc1", "riskfactor": 10}}),
+    )
+    .await?;
+    assert_eq!(-32602, response["error"]["code"]);
+
+    let response = call(
+        &mut stream,
+        json!({"jsonrpc": "2.0", "id": 6, "method": "pay", "params": {"bolt11": "lnbc1"}}),
+    )
+    .await?;
+    assert_eq!(-32602, response["error"]["code"]);
+
+    let response = call(
+        &mut stream,
+        json!({"jsonrpc": "2.0", "id": 7, "method": "stop"}),
+    )
+    .await?;
+    assert_eq!(-32601, response["error"]["code"]);
+    Ok(())
+}
+
+async fn call(stream: &mut UnixStream, request: Value) -> Result<Value> {
+    stream.write_all(request.to_string().as_bytes()).await?;
+    let mut response = vec![];
+    while !response.ends_with(b"\n\n") {
+        let mut buf = [0u8; 4096];
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+    }
+    serde_json::from_slice(&response).context("invalid response")
+}
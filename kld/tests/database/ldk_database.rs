@@ -3,19 +3,28 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
+use api::{ChannelAcceptancePolicy, ChannelTypeFeature, JitChannelState, WebhookEvent};
 use bitcoin::hashes::hex::FromHex;
 use bitcoin::hashes::{sha256, Hash};
 use bitcoin::secp256k1::{Secp256k1, SecretKey};
-use bitcoin::{Network, TxOut, Txid};
-use kld::database::channel::Channel;
-use kld::database::forward::{Forward, ForwardStatus};
-use kld::database::invoice::Invoice;
-use kld::database::payment::{Payment, PaymentDirection};
+use bitcoin::{Network, PackedLockTime, Transaction, TxOut, Txid};
+use kld::database::channel::{Channel, ChannelFilter};
+use kld::database::cipher::Cipher;
+use kld::database::forward::{
+    ChannelForwardTotals, FailedForwards, Forward, ForwardFilter, ForwardStatus,
+};
+use kld::database::invoice::{HoldInvoiceStatus, Invoice, InvoiceFilter};
+use kld::database::jit_channel::JitChannel;
+use kld::database::macaroon_root_key::MacaroonRootKey;
+use kld::database::offer::{InvoiceRequest, InvoiceRequestStatus, Offer};
+use kld::database::payment::{Payment, PaymentDirection, PaymentFilter, PaymentStatus};
+use kld::database::payment_attempt::{PaymentAttempt, PaymentAttemptStatus, PaymentHop};
 use kld::database::peer::Peer;
-use kld::database::{microsecond_timestamp, LdkDatabase};
+use kld::database::{microsecond_timestamp, LdkDatabase, Page, UnknownCursor};
 use kld::ldk::Scorer;
 
 use kld::database::spendable_output::{SpendableOutput, SpendableOutputStatus};
+use kld::database::webhook::{Webhook, WebhookDeliveryStatus};
 use kld::logger::KldLogger;
 use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
 use lightning::chain::chainmonitor::ChainMonitor;
@@ -23,10 +32,12 @@ use lightning::chain::transaction::OutPoint;
 use lightning::chain::Filter;
 
 use lightning::events::ClosureReason;
+use lightning::ln::channelmanager::{InterceptId, PaymentId};
 use lightning::ln::features::ChannelTypeFeatures;
 use lightning::ln::msgs::SocketAddress;
 use lightning::ln::ChannelId;
 use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
+use lightning::offers::offer::OfferBuilder;
 use lightning::routing::gossip::{NetworkGraph, NodeId};
 use lightning::routing::router::DefaultRouter;
 use lightning::routing::scoring::{
@@ -40,13 +51,14 @@ use test_utils::{
     init_db_test_context, poll, random_public_key, TempDir, TEST_PRIVATE_KEY, TEST_PUBLIC_KEY,
     TEST_TX_ID,
 };
+use uuid::Uuid;
 
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_peers() -> Result<()> {
     let temp_dir = TempDir::new()?;
     let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
 
-    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
 
     let peer = Peer {
         public_key: random_public_key(),
@@ -78,7 +90,7 @@ pub async fn test_forwards() -> Result<()> {
     let temp_dir = TempDir::new()?;
     let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
 
-    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
 
     let amount = 1000000;
     let fee = 100;
@@ -103,7 +115,7 @@ pub async fn test_forwards() -> Result<()> {
     assert_eq!(amount, total.amount);
     assert_eq!(fee, total.fee);
 
-    let forwards = database.fetch_forwards(None).await?;
+    let forwards = database.fetch_forwards(&ForwardFilter::default()).await?;
     assert_eq!(
         forwards.first().context("expected success forward")?,
         &forward_success
@@ -114,10 +126,96 @@ pub async fn test_forwards() -> Result<()> {
     );
 
     let forwards = database
-        .fetch_forwards(Some(ForwardStatus::Succeeded))
+        .fetch_forwards(&ForwardFilter {
+            status: Some(ForwardStatus::Succeeded),
+            ..Default::default()
+        })
         .await?;
     assert_eq!(1, forwards.len());
 
+    let forwards = database
+        .fetch_forwards(&ForwardFilter {
+            channel_id: Some(ChannelId::from_bytes([1u8; 32])),
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(vec![forward_success.clone()], forwards);
+
+    let first_page = database
+        .fetch_forwards(&ForwardFilter {
+            page: Page {
+                limit: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(vec![forward_success.clone()], first_page);
+    let second_page = database
+        .fetch_forwards(&ForwardFilter {
+            page: Page {
+                limit: Some(1),
+                ..Default::default()
+            },
+            cursor: Some(forward_success.id),
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(vec![forward_fail.clone()], second_page);
+    let unknown_cursor = database
+        .fetch_forwards(&ForwardFilter {
+            cursor: Some(Uuid::new_v4()),
+            ..Default::default()
+        })
+        .await;
+    assert!(matches!(unknown_cursor, Err(e) if e.is::<UnknownCursor>()));
+
+    let mut totals = database
+        .fetch_channel_forward_totals(&Page::default())
+        .await?;
+    totals.sort_by_key(|t| t.channel_id.0);
+    assert_eq!(
+        vec![
+            ChannelForwardTotals {
+                channel_id: ChannelId::from_bytes([0u8; 32]),
+                succeeded_in: 1,
+                succeeded_out: 0,
+                volume_in: amount + fee,
+                volume_out: 0,
+                fees: 0,
+            },
+            ChannelForwardTotals {
+                channel_id: ChannelId::from_bytes([1u8; 32]),
+                succeeded_in: 0,
+                succeeded_out: 1,
+                volume_in: 0,
+                volume_out: amount,
+                fees: fee,
+            },
+        ],
+        totals
+    );
+    let failed = database.fetch_failed_forwards(&Page::default()).await?;
+    assert_eq!(
+        vec![FailedForwards {
+            inbound_channel_id: ChannelId::from_bytes([3u8; 32]),
+            htlc_destination: forward_fail.htlc_destination.clone(),
+            count: 1,
+        }],
+        failed
+    );
+
+    let forwards = database
+        .fetch_forwards(&ForwardFilter {
+            page: Page {
+                until: Some(forward_fail.timestamp),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(vec![forward_success], forwards);
+
     Ok(())
 }
 
@@ -126,7 +224,7 @@ pub async fn test_invoice_payments() -> Result<()> {
     let temp_dir = TempDir::new()?;
     let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
 
-    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
 
     let private_key = SecretKey::from_slice(&TEST_PRIVATE_KEY)?;
     let payment_hash = sha256::Hash::from_slice(&[1u8; 32]).unwrap();
@@ -146,8 +244,12 @@ pub async fn test_invoice_payments() -> Result<()> {
     let invoice = Invoice::new(Some(label.clone()), bolt11)?;
     database.persist_invoice(&invoice).await?;
 
+    let label_filter = InvoiceFilter {
+        label: Some(label.clone()),
+        ..Default::default()
+    };
     let result = database
-        .fetch_invoices(Some(label.clone()))
+        .fetch_invoices(&label_filter)
         .await?
         .into_iter()
         .last()
@@ -158,18 +260,26 @@ pub async fn test_invoice_payments() -> Result<()> {
     database.persist_payment(&payment).await?;
 
     let result = database
-        .fetch_invoices(Some(label.clone()))
+        .fetch_invoices(&label_filter)
         .await?
         .into_iter()
         .last()
         .context("expected invoice")?;
     assert_eq!(1, result.payments.len());
 
-    let result = database.fetch_invoices(None).await?;
+    let result = database.fetch_invoices(&InvoiceFilter::default()).await?;
     assert_eq!(1, result.len());
 
+    let result = database
+        .fetch_invoices(&InvoiceFilter {
+            cursor: Some(invoice.payment_hash),
+            ..Default::default()
+        })
+        .await?;
+    assert!(result.is_empty());
+
     let stored_payments = database
-        .fetch_payments(None, None)
+        .fetch_payments(&PaymentFilter::default())
         .await?
         .into_iter()
         .find(|p| p.id == payment.id)
@@ -180,7 +290,12 @@ pub async fn test_invoice_payments() -> Result<()> {
     database.persist_payment(&payment).await?;
 
     let stored_payments = database
-        .fetch_payments(payment.hash, Some(PaymentDirection::Outbound))
+        .fetch_payments(&PaymentFilter {
+            payment_hash: payment.hash,
+            direction: Some(PaymentDirection::Outbound),
+            statuses: vec![PaymentStatus::Succeeded],
+            ..Default::default()
+        })
         .await?;
     assert_eq!(1, stored_payments.len());
     assert_eq!(
@@ -188,6 +303,194 @@ pub async fn test_invoice_payments() -> Result<()> {
         &payment
     );
 
+    let hop = PaymentHop {
+        node_id: private_key.public_key(&Secp256k1::new()),
+        short_channel_id: 42,
+    };
+    let failed_attempt = PaymentAttempt {
+        id: Uuid::new_v4(),
+        payment_id: payment.id,
+        status: PaymentAttemptStatus::Failed,
+        hops: vec![hop.clone()],
+        amount: 1000,
+        fee: 10,
+        failed_short_channel_id: Some(42),
+        failed_permanently: false,
+        timestamp: microsecond_timestamp(),
+    };
+    let successful_attempt = PaymentAttempt {
+        id: Uuid::new_v4(),
+        status: PaymentAttemptStatus::Succeeded,
+        failed_short_channel_id: None,
+        timestamp: failed_attempt.timestamp + Duration::from_secs(1),
+        ..failed_attempt.clone()
+    };
+    database.persist_payment_attempt(&failed_attempt).await?;
+    database
+        .persist_payment_attempt(&successful_attempt)
+        .await?;
+    // A replayed event does not count the attempt twice.
+    database.persist_payment_attempt(&failed_attempt).await?;
+    assert_eq!(
+        vec![failed_attempt, successful_attempt],
+        database.fetch_payment_attempts(&payment.id).await?
+    );
+
+    let stored_payment = database
+        .fetch_payment(&payment.id)
+        .await?
+        .context("expected payment")?;
+    payment.attempts = 2;
+    payment.successful_paths = 1;
+    assert_eq!(stored_payment, payment);
+    assert!(database
+        .fetch_payment(&PaymentId([9u8; 32]))
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_hold_invoices() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
+
+    let private_key = SecretKey::from_slice(&TEST_PRIVATE_KEY)?;
+    let payment_hash = sha256::Hash::from_slice(&[3u8; 32]).unwrap();
+
+    let bolt11 = InvoiceBuilder::new(Currency::Regtest)
+        .description("test".into())
+        .amount_milli_satoshis(1000)
+        .payment_hash(payment_hash)
+        .payment_secret(PaymentSecret([2u8; 32]))
+        .current_timestamp()
+        .expiry_time(Duration::from_secs(3600))
+        .min_final_cltv_expiry_delta(144)
+        .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &private_key))?;
+
+    let mut invoice = Invoice::hold(Some("hold".to_string()), bolt11)?;
+    database.persist_invoice(&invoice).await?;
+
+    let result = database
+        .fetch_invoice(&invoice.payment_hash)
+        .await?
+        .context("expected invoice")?;
+    assert_eq!(result, invoice);
+    assert_eq!(Some(HoldInvoiceStatus::Open), result.hold_status);
+
+    assert!(!invoice.claim_deadline_reached(u32::MAX));
+
+    invoice.hold_status = Some(HoldInvoiceStatus::Accepted);
+    invoice.claim_deadline = Some(1000);
+    database.persist_invoice(&invoice).await?;
+    assert!(!invoice.claim_deadline_reached(999));
+    assert!(invoice.claim_deadline_reached(1000));
+    assert!(invoice.claim_deadline_reached(1001));
+
+    let result = database
+        .fetch_hold_invoices(HoldInvoiceStatus::Accepted)
+        .await?;
+    assert_eq!(vec![invoice.clone()], result);
+    assert!(database
+        .fetch_hold_invoices(HoldInvoiceStatus::Open)
+        .await?
+        .is_empty());
+    assert!(database
+        .fetch_invoice(&PaymentHash([4u8; 32]))
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_offers() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
+
+    let bolt12 = OfferBuilder::new("test".to_string(), random_public_key())
+        .amount_msats(1000)
+        .build()
+        .map_err(|e| anyhow!("{e:?}"))?;
+    let label = "test label".to_owned();
+    let offer = Offer::of_offer(&bolt12, Some(label.clone()), PaymentDirection::Outbound);
+    database.persist_offer(&offer).await?;
+
+    let result = database
+        .fetch_offers(Some(label))
+        .await?
+        .into_iter()
+        .last()
+        .context("expected offer")?;
+    assert_eq!(result, offer);
+
+    let payment = Payment::of_offer_outbound(Payment::new_id(), 1000, None);
+    database.persist_payment(&payment).await?;
+    let invoice_request = InvoiceRequest::new(
+        payment.id,
+        offer.id,
+        Some(1000),
+        Some(1),
+        Some("note".to_string()),
+    );
+    database.persist_invoice_request(&invoice_request).await?;
+
+    let result = database.fetch_invoice_requests(Some(offer.id)).await?;
+    assert_eq!(vec![invoice_request.clone()], result);
+
+    database
+        .update_invoice_request_status(&payment.id, InvoiceRequestStatus::Failed)
+        .await?;
+    let result = database
+        .fetch_invoice_requests(None)
+        .await?
+        .into_iter()
+        .find(|r| r.payment_id == payment.id)
+        .context("expected invoice request")?;
+    assert_eq!(InvoiceRequestStatus::Failed, result.status);
+
+    // The payment fails with the invoice request when nobody is waiting for the invoice.
+    let payment = Payment::of_offer_outbound(Payment::new_id(), 1000, None);
+    database.persist_payment(&payment).await?;
+    database
+        .persist_invoice_request(&InvoiceRequest::new(
+            payment.id,
+            offer.id,
+            Some(1000),
+            None,
+            None,
+        ))
+        .await?;
+    database.fail_invoice_request(&payment.id).await?;
+    let result = database
+        .fetch_invoice_requests(Some(offer.id))
+        .await?
+        .into_iter()
+        .find(|r| r.payment_id == payment.id)
+        .context("expected invoice request")?;
+    assert_eq!(InvoiceRequestStatus::Failed, result.status);
+    let result = database
+        .fetch_payment(&payment.id)
+        .await?
+        .context("expected payment")?;
+    assert_eq!(PaymentStatus::Error, result.status);
+
+    // A payment that has already been resolved keeps its status.
+    let mut payment = Payment::of_offer_outbound(Payment::new_id(), 1000, None);
+    payment.status = PaymentStatus::Succeeded;
+    database.persist_payment(&payment).await?;
+    database.fail_invoice_request(&payment.id).await?;
+    let result = database
+        .fetch_payment(&payment.id)
+        .await?
+        .context("expected payment")?;
+    assert_eq!(PaymentStatus::Succeeded, result.status);
+
     Ok(())
 }
 
@@ -197,7 +500,7 @@ pub async fn test_network_graph() -> Result<()> {
     let temp_dir = TempDir::new()?;
     let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
 
-    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
 
     let network_graph = Arc::new(NetworkGraph::new(Network::Regtest, KldLogger::global()));
     // how to make this less verbose?
@@ -230,6 +533,12 @@ pub async fn test_network_graph() -> Result<()> {
     persist(&database, &network_graph)?;
     assert!(database.fetch_graph().await.unwrap().is_some());
 
+    // The rapid gossip sync timestamp is stored with the graph.
+    network_graph.set_last_rapid_gossip_sync_timestamp(20);
+    database.write_graph(&network_graph)?;
+    let graph = database.fetch_graph().await?.context("expected graph")?;
+    assert_eq!(Some(20), graph.get_last_rapid_gossip_sync_timestamp());
+
     Ok(())
 }
 
@@ -239,7 +548,9 @@ pub async fn test_scorer() -> Result<()> {
     let temp_dir = TempDir::new()?;
     let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
 
-    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+    let settings = Arc::new(settings);
+    let durable_connection = Arc::new(durable_connection);
+    let database = LdkDatabase::new(settings.clone(), durable_connection.clone(), test_cipher());
 
     let network_graph = Arc::new(NetworkGraph::new(Network::Regtest, KldLogger::global()));
     let scorer = Mutex::new(ProbabilisticScorer::new(
@@ -304,6 +615,217 @@ pub async fn test_scorer() -> Result<()> {
             .filter(|t| t > &timestamp)
             .is_some()
     );
+
+    // Running it again, e.g. after an interrupted start, leaves the blobs readable.
+    database.encrypt_blobs().await?;
+    database.encrypt_blobs().await?;
+    assert!(database
+        .fetch_scorer(
+            ProbabilisticScoringDecayParameters::default(),
+            network_graph.clone()
+        )
+        .await?
+        .is_some());
+
+    // The scorer is encrypted so it can not be read with another key.
+    let database = LdkDatabase::new(settings, durable_connection, test_cipher());
+    assert!(database
+        .fetch_scorer(
+            ProbabilisticScoringDecayParameters::default(),
+            network_graph
+        )
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_channel_acceptance_policy() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
+    assert_eq!(None, database.fetch_channel_acceptance_policy().await?);
+
+    let mut policy = ChannelAcceptancePolicy {
+        min_channel_sats: Some(20000),
+        deny_list: vec![TEST_PUBLIC_KEY.to_string()],
+        required_features: vec![ChannelTypeFeature::AnchorsZeroFeeHtlcTx],
+        reject_reason: "no thanks".to_string(),
+        ..Default::default()
+    };
+    database.persist_channel_acceptance_policy(&policy).await?;
+    assert_eq!(
+        Some(policy.clone()),
+        database.fetch_channel_acceptance_policy().await?
+    );
+
+    policy.max_channels_per_peer = Some(1);
+    database.persist_channel_acceptance_policy(&policy).await?;
+    assert_eq!(
+        Some(policy),
+        database.fetch_channel_acceptance_policy().await?
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_jit_channels() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
+
+    let user_channel_id = random::<u64>() / 2;
+    let mut channel = JitChannel::bought(
+        user_channel_id,
+        random_public_key(),
+        42,
+        Some(10_000_000),
+        2000,
+        1000,
+    );
+    database.persist_jit_channel(&channel).await?;
+    let intercept_id = InterceptId([3u8; 32]);
+    database
+        .persist_jit_channel_htlc(user_channel_id, &intercept_id)
+        .await?;
+
+    channel.opening_fee_msat = Some(10_000);
+    channel.channel_id = Some(ChannelId::from_bytes([1u8; 32]));
+    channel.state = JitChannelState::ChannelReady;
+    database.persist_jit_channel(&channel).await?;
+    let channels = database.fetch_jit_channels().await?;
+    assert_eq!(
+        Some(&channel),
+        channels
+            .iter()
+            .find(|c| c.user_channel_id == user_channel_id)
+    );
+
+    assert_eq!(
+        vec![intercept_id],
+        database.fetch_jit_channel_htlcs(user_channel_id).await?
+    );
+    database.delete_jit_channel_htlcs(user_channel_id).await?;
+    assert!(database
+        .fetch_jit_channel_htlcs(user_channel_id)
+        .await?
+        .is_empty());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_webhooks() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
+
+    let settled = Webhook::new(
+        "https://example.com/settled".to_string(),
+        "secret".to_string(),
+        vec![WebhookEvent::InvoiceSettled],
+    );
+    let closed = Webhook::new(
+        "https://example.com/closed".to_string(),
+        "other secret".to_string(),
+        vec![
+            WebhookEvent::ChannelClosed,
+            WebhookEvent::ChannelForceClosed,
+        ],
+    );
+    database.persist_webhook(&settled).await?;
+    database.persist_webhook(&closed).await?;
+    assert_eq!(
+        vec![settled.clone(), closed.clone()],
+        database.fetch_webhooks().await?
+    );
+
+    assert_eq!(
+        1,
+        database
+            .insert_webhook_deliveries(WebhookEvent::InvoiceSettled, "settled", "{}")
+            .await?
+    );
+    // A replayed event is not delivered again.
+    assert_eq!(
+        0,
+        database
+            .insert_webhook_deliveries(WebhookEvent::InvoiceSettled, "settled", "{}")
+            .await?
+    );
+    assert_eq!(
+        0,
+        database
+            .insert_webhook_deliveries(WebhookEvent::LowWalletBalance, "low balance", "{}")
+            .await?
+    );
+    let now = microsecond_timestamp() + Duration::from_secs(1);
+    let mut deliveries = database.fetch_due_webhook_deliveries(&now, 10).await?;
+    assert_eq!(1, deliveries.len());
+    let mut delivery = deliveries.remove(0);
+    assert_eq!(settled.id, delivery.webhook_id);
+    assert_eq!(WebhookEvent::InvoiceSettled, delivery.event);
+    assert_eq!("{}", delivery.payload);
+    assert_eq!(WebhookDeliveryStatus::Pending, delivery.status);
+    assert_eq!(0, delivery.attempts);
+
+    // A failed attempt is retried later.
+    delivery.attempts = 1;
+    delivery.next_attempt = now + Duration::from_secs(60);
+    delivery.last_error = Some("connection refused".to_string());
+    database.update_webhook_delivery(&delivery).await?;
+    assert!(database
+        .fetch_due_webhook_deliveries(&now, 10)
+        .await?
+        .is_empty());
+    let later = now + Duration::from_secs(61);
+    assert_eq!(
+        vec![delivery.clone()],
+        database.fetch_due_webhook_deliveries(&later, 10).await?
+    );
+
+    delivery.status = WebhookDeliveryStatus::Delivered;
+    database.update_webhook_delivery(&delivery).await?;
+    assert!(database
+        .fetch_due_webhook_deliveries(&later, 10)
+        .await?
+        .is_empty());
+
+    // Deleting a webhook drops its pending deliveries.
+    database
+        .insert_webhook_deliveries(WebhookEvent::ChannelForceClosed, "force closed", "{}")
+        .await?;
+    assert!(database.delete_webhook(&closed.id).await?);
+    assert!(!database.delete_webhook(&closed.id).await?);
+    assert!(database
+        .fetch_due_webhook_deliveries(&later, 10)
+        .await?
+        .is_empty());
+    assert_eq!(vec![settled], database.fetch_webhooks().await?);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_macaroon_root_keys() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
+
+    let admin = MacaroonRootKey::new("admin".to_string());
+    let readonly = MacaroonRootKey::new("readonly".to_string());
+    database.persist_macaroon_root_key(&admin).await?;
+    database.persist_macaroon_root_key(&readonly).await?;
+    assert_eq!(
+        vec![admin.clone(), readonly.clone()],
+        database.fetch_macaroon_root_keys().await?
+    );
+
+    assert!(database.delete_macaroon_root_key(&admin.id).await?);
+    assert!(!database.delete_macaroon_root_key(&admin.id).await?);
+    assert_eq!(vec![readonly], database.fetch_macaroon_root_keys().await?);
     Ok(())
 }
 
@@ -312,7 +834,7 @@ pub async fn test_spendable_outputs() -> Result<()> {
     let temp_dir = TempDir::new()?;
     let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
 
-    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
 
     let output = TxOut::default();
     let outpoint = OutPoint {
@@ -321,15 +843,145 @@ pub async fn test_spendable_outputs() -> Result<()> {
     };
     let descriptor = SpendableOutputDescriptor::StaticOutput { outpoint, output };
     let mut spendable_output = SpendableOutput::new(descriptor);
+    database
+        .insert_spendable_output(spendable_output.clone())
+        .await?;
+
+    let sweep_tx = Transaction {
+        version: 2,
+        lock_time: PackedLockTime(100),
+        input: vec![],
+        output: vec![TxOut::default()],
+    };
+    spendable_output.status = SpendableOutputStatus::Sweeping;
+    spendable_output.first_seen_height = Some(90);
+    spendable_output.sweep_txids.push(sweep_tx.txid());
+    spendable_output.sweep_tx = Some(sweep_tx.clone());
+    spendable_output.sweep_feerate = Some(2500);
+    spendable_output.sweep_height = Some(100);
     database
         .persist_spendable_output(spendable_output.clone())
         .await?;
 
+    // A replayed event must not reset the sweep.
+    database
+        .insert_spendable_output(SpendableOutput::new(spendable_output.descriptor.clone()))
+        .await?;
+
+    let spendable_outputs = database.fetch_spendable_outputs().await?;
+    assert_eq!(1, spendable_outputs.len());
+    let saved = &spendable_outputs[0];
+    assert_eq!(SpendableOutputStatus::Sweeping, saved.status);
+    assert_eq!(Some(90), saved.first_seen_height);
+    assert_eq!(vec![sweep_tx.txid()], saved.sweep_txids);
+    assert_eq!(Some(sweep_tx), saved.sweep_tx);
+    assert_eq!(Some(2500), saved.sweep_feerate);
+    assert_eq!(Some(100), saved.sweep_height);
+
     spendable_output.status = SpendableOutputStatus::Spent;
     database.persist_spendable_output(spendable_output).await?;
 
     let spendable_outputs = database.fetch_spendable_outputs().await?;
     assert_eq!(1, spendable_outputs.len());
+    assert_eq!(SpendableOutputStatus::Spent, spendable_outputs[0].status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_channel_monitor_persistence() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
+
+    let funding_txo = OutPoint {
+        txid: Txid::from_hex(TEST_TX_ID)?,
+        index: 1,
+    };
+    // A serialized ChannelMonitorUpdate without any steps.
+    let update = |update_id: u64| {
+        let mut bytes = vec![1, 1];
+        bytes.extend_from_slice(&update_id.to_be_bytes());
+        bytes.extend_from_slice(&0u64.to_be_bytes());
+        bytes.push(0);
+        bytes
+    };
+
+    // A write that completes after a newer one must not replace it.
+    assert!(
+        database
+            .persist_channel_monitor(&funding_txo, b"monitor 10", 10)
+            .await?
+    );
+    for update_id in [11, 12, 13] {
+        database
+            .persist_channel_monitor_update(&funding_txo, &update(update_id), update_id)
+            .await?;
+    }
+    assert!(
+        database
+            .persist_channel_monitor(&funding_txo, b"monitor 12", 12)
+            .await?
+    );
+    assert!(
+        !database
+            .persist_channel_monitor(&funding_txo, b"monitor 10", 10)
+            .await?
+    );
+    // Chain sync writes the monitor again without a new update id.
+    assert!(
+        database
+            .persist_channel_monitor(&funding_txo, b"monitor 12", 12)
+            .await?
+    );
+
+    // The updates that the monitor contains are pruned, the rest are replayed in order.
+    let updates = database
+        .fetch_channel_monitor_updates(&funding_txo, 0)
+        .await?;
+    assert_eq!(
+        vec![13],
+        updates.iter().map(|u| u.update_id).collect::<Vec<_>>()
+    );
+    assert!(database
+        .fetch_channel_monitor_updates(&funding_txo, 13)
+        .await?
+        .is_empty());
+
+    // Updates past the stored monitor are replayed after it.
+    database
+        .persist_channel_monitor_update(&funding_txo, &update(14), 14)
+        .await?;
+    let updates = database
+        .fetch_channel_monitor_updates(&funding_txo, 12)
+        .await?;
+    assert_eq!(
+        vec![13, 14],
+        updates.iter().map(|u| u.update_id).collect::<Vec<_>>()
+    );
+
+    database.encrypt_blobs().await?;
+    database.encrypt_blobs().await?;
+    let updates = database
+        .fetch_channel_monitor_updates(&funding_txo, 12)
+        .await?;
+    assert_eq!(2, updates.len());
+
+    // The monitor of a closed channel replaces every other monitor and prunes every update.
+    assert!(
+        database
+            .persist_channel_monitor(&funding_txo, b"closed", u64::MAX)
+            .await?
+    );
+    assert!(
+        !database
+            .persist_channel_monitor(&funding_txo, b"monitor 14", 14)
+            .await?
+    );
+    assert!(database
+        .fetch_channel_monitor_updates(&funding_txo, 0)
+        .await?
+        .is_empty());
     Ok(())
 }
 
@@ -338,7 +990,7 @@ pub async fn test_channels() -> Result<()> {
     let temp_dir = TempDir::new()?;
     let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
 
-    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+    let database = LdkDatabase::new(settings.into(), durable_connection.into(), test_cipher());
 
     let mut type_features = ChannelTypeFeatures::empty();
     type_features.set_zero_conf_optional();
@@ -363,10 +1015,18 @@ pub async fn test_channels() -> Result<()> {
     };
     database.persist_channel(channel.clone()).await?;
 
+    let counterparties = database.fetch_channel_counterparties().await?;
+    assert_eq!(Some(&channel.counterparty), counterparties.get(&channel.id));
+
     let reason = ClosureReason::CooperativeClosure;
     database.close_channel(&channel.id, &reason).await?;
 
-    let channels = database.fetch_channel_history().await?;
+    let channels = database
+        .fetch_channel_history(&ChannelFilter {
+            peer: Some(channel.counterparty),
+            ..Default::default()
+        })
+        .await?;
     assert_eq!(1, channels.len());
     let persisted_channel = channels.first().context("expected channel")?;
     assert!(persisted_channel
@@ -377,6 +1037,10 @@ pub async fn test_channels() -> Result<()> {
     Ok(())
 }
 
+fn test_cipher() -> Arc<Cipher> {
+    Arc::new(Cipher::new(&random()))
+}
+
 // XXX
 // Why do we make a crate `test-utils`?
 // Test utils should be collect.
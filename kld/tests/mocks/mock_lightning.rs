@@ -1,43 +1,68 @@
 use std::{
+    collections::HashMap,
     net::{SocketAddrV4, SocketAddrV6},
     str::FromStr,
+    sync::Mutex,
     time::Duration,
 };
 
-use anyhow::{Context, Result};
-use api::FeeRate;
+use anyhow::{anyhow, bail, Context, Result};
+use api::{FeeRate, JitChannelOffer, JitChannelState, NodeEvent, WebhookEvent};
 use async_trait::async_trait;
 use bitcoin::{
     consensus::deserialize,
-    hashes::{hex::FromHex, sha256, Hash},
+    hashes::{
+        hex::{FromHex, ToHex},
+        sha256, Hash,
+    },
     secp256k1::{PublicKey, Secp256k1, SecretKey},
     Network, Txid,
 };
 use kld::{
     api::SocketAddress,
     database::{
-        channel::Channel,
-        forward::{Forward, ForwardStatus, TotalForwards},
+        channel::{Channel, ChannelFilter},
+        cipher::Cipher,
+        forward::{ChannelForwardTotals, Forward, ForwardFilter, TotalForwards},
         microsecond_timestamp,
     },
 };
 use kld::{
     database::{
-        invoice::Invoice,
-        payment::{Payment, PaymentDirection},
+        invoice::{HoldInvoiceStatus, Invoice, InvoiceFilter},
+        jit_channel::JitChannel,
+        macaroon_root_key::MacaroonRootKey,
+        offer::{InvoiceRequest, Offer},
+        payment::{Payment, PaymentDirection, PaymentFilter},
+        payment_attempt::{PaymentAttempt, PaymentAttemptStatus, PaymentHop},
+        webhook::Webhook,
+        Page,
+    },
+    ldk::{
+        analytics::ForwardStats,
+        channel_acceptor::InboundChannelPolicy,
+        channel_backup::{BackupChannel, StaticChannelBackup},
+        payment_limits::PaymentLimits,
+        LightningInterface, OpenChannelResult, Peer, PeerStatus, ProbeResult,
     },
-    ldk::{LightningInterface, OpenChannelResult, Peer, PeerStatus},
     MillisatAmount,
 };
 use lightning::{
     chain::transaction::OutPoint,
     events::ClosureReason,
     ln::{
-        channelmanager::{ChannelCounterparty, ChannelDetails},
+        channelmanager::{ChannelCounterparty, ChannelDetails, PaymentId},
         features::{ChannelTypeFeatures, Features, InitFeatures},
-        ChannelId, PaymentPreimage, PaymentSecret,
+        ChannelId, PaymentHash, PaymentPreimage, PaymentSecret,
+    },
+    offers::{
+        offer::{Offer as Bolt12Offer, OfferBuilder},
+        refund::{Refund, RefundBuilder},
+    },
+    routing::{
+        gossip::{ChannelInfo, NodeAlias, NodeAnnouncementInfo, NodeId, NodeInfo},
+        router::RouteHint,
     },
-    routing::gossip::{ChannelInfo, NodeAlias, NodeAnnouncementInfo, NodeId, NodeInfo},
     util::{
         config::{ChannelConfig, UserConfig},
         indexed_map::IndexedMap,
@@ -49,6 +74,8 @@ use lightning_invoice::{Currency, InvoiceBuilder};
 use test_utils::{
     TEST_ALIAS, TEST_PRIVATE_KEY, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID, TEST_TX, TEST_TX_ID,
 };
+use tokio::sync::broadcast;
+use uuid::Uuid;
 
 pub struct MockLightning {
     pub num_peers: usize,
@@ -61,6 +88,9 @@ pub struct MockLightning {
     pub invoice: Invoice,
     pub payment: Payment,
     pub forward: Forward,
+    pub events: broadcast::Sender<NodeEvent>,
+    pub webhook: Webhook,
+    pub macaroon_root_keys: Mutex<Vec<MacaroonRootKey>>,
 }
 
 impl Default for MockLightning {
@@ -127,7 +157,9 @@ impl Default for MockLightning {
             .unwrap();
         let invoice =
             kld::database::invoice::Invoice::new(Some("label".to_string()), invoice).unwrap();
-        let payment = Payment::of_invoice_outbound(&invoice, Some("label".to_string()));
+        let mut payment = Payment::of_invoice_outbound(&invoice, Some("label".to_string()));
+        payment.attempts = 2;
+        payment.successful_paths = 1;
         let forward = Forward::success(
             ChannelId::from_bytes([3u8; 32]),
             ChannelId::from_bytes([4u8; 32]),
@@ -146,6 +178,13 @@ impl Default for MockLightning {
             invoice,
             payment,
             forward,
+            events: broadcast::channel(16).0,
+            webhook: Webhook::new(
+                "https://example.com/kld".to_string(),
+                "secret".to_string(),
+                vec![WebhookEvent::InvoiceSettled],
+            ),
+            macaroon_root_keys: Mutex::new(vec![]),
         }
     }
 }
@@ -316,6 +355,10 @@ impl LightningInterface for MockLightning {
         UserConfig::default()
     }
 
+    fn max_fee_msat(&self, limits: &PaymentLimits, _amount: MillisatAmount) -> MillisatAmount {
+        limits.exempt_fee_msat.unwrap_or(5000)
+    }
+
     async fn generate_invoice(
         &self,
         _label: String,
@@ -326,28 +369,183 @@ impl LightningInterface for MockLightning {
         Ok(self.invoice.clone())
     }
 
-    async fn pay_invoice(&self, invoice: Invoice, label: Option<String>) -> Result<Payment> {
+    async fn generate_hold_invoice(
+        &self,
+        _label: String,
+        _amount: u64,
+        _description: String,
+        _expiry: Option<u32>,
+        payment_hash: PaymentHash,
+    ) -> Result<Invoice> {
+        if payment_hash != self.invoice.payment_hash {
+            bail!("unexpected payment hash");
+        }
+        let mut invoice = self.invoice.clone();
+        invoice.hold_status = Some(HoldInvoiceStatus::Open);
+        Ok(invoice)
+    }
+
+    async fn settle_hold_invoice(&self, preimage: PaymentPreimage) -> Result<()> {
+        let payment_hash = PaymentHash(sha256::Hash::hash(&preimage.0).into_inner());
+        self.cancel_hold_invoice(payment_hash).await
+    }
+
+    async fn cancel_hold_invoice(&self, payment_hash: PaymentHash) -> Result<()> {
+        if payment_hash != self.invoice.payment_hash {
+            bail!("Hold invoice not found");
+        }
+        Ok(())
+    }
+
+    async fn pay_invoice(
+        &self,
+        invoice: Invoice,
+        label: Option<String>,
+        _limits: PaymentLimits,
+        _wait: bool,
+    ) -> Result<Payment> {
         let mut payment = Payment::of_invoice_outbound(&invoice, label);
         payment.succeeded(invoice.payment_hash, PaymentPreimage([1u8; 32]), Some(2323));
         Ok(payment)
     }
 
-    async fn list_payments(
-        &self,
-        _bolt11: Option<Invoice>,
-        _direction: Option<PaymentDirection>,
-    ) -> Result<Vec<Payment>> {
+    async fn list_payments(&self, _filter: PaymentFilter) -> Result<Vec<Payment>> {
         Ok(vec![self.payment.clone()])
     }
 
-    async fn list_invoices(&self, _label: Option<String>) -> Result<Vec<Invoice>> {
+    async fn get_payment(&self, id: PaymentId) -> Result<Option<Payment>> {
+        Ok(Some(self.payment.clone()).filter(|p| p.id == id))
+    }
+
+    async fn get_payment_attempts(&self, id: PaymentId) -> Result<Vec<PaymentAttempt>> {
+        let attempt = PaymentAttempt {
+            id: Uuid::new_v4(),
+            payment_id: id,
+            status: PaymentAttemptStatus::Failed,
+            hops: vec![PaymentHop {
+                node_id: self.public_key,
+                short_channel_id: TEST_SHORT_CHANNEL_ID,
+            }],
+            amount: self.payment.amount,
+            fee: 1000,
+            failed_short_channel_id: Some(TEST_SHORT_CHANNEL_ID),
+            failed_permanently: false,
+            timestamp: microsecond_timestamp(),
+        };
+        Ok(vec![
+            attempt.clone(),
+            PaymentAttempt {
+                id: Uuid::new_v4(),
+                status: PaymentAttemptStatus::Succeeded,
+                failed_short_channel_id: None,
+                ..attempt
+            },
+        ])
+    }
+
+    async fn list_invoices(&self, _filter: InvoiceFilter) -> Result<Vec<Invoice>> {
         Ok(vec![self.invoice.clone()])
     }
 
-    async fn keysend_payment(&self, _payee: NodeId, _amount: MillisatAmount) -> Result<Payment> {
+    async fn keysend_payment(
+        &self,
+        _payee: NodeId,
+        _amount: MillisatAmount,
+        _limits: PaymentLimits,
+        _wait: bool,
+    ) -> Result<Payment> {
         Ok(self.payment.clone())
     }
 
+    async fn probe(
+        &self,
+        payee: PublicKey,
+        _amount: MillisatAmount,
+        _route_hints: Vec<RouteHint>,
+    ) -> Result<ProbeResult> {
+        if payee == self.public_key {
+            Ok(ProbeResult::Succeeded { paths: 1 })
+        } else {
+            Ok(ProbeResult::Failed {
+                paths: 1,
+                short_channel_id: Some(TEST_SHORT_CHANNEL_ID),
+                node_id: Some(payee),
+            })
+        }
+    }
+
+    async fn create_offer(
+        &self,
+        description: String,
+        amount: Option<MillisatAmount>,
+        _expiry: Option<Duration>,
+        _issuer: Option<String>,
+        label: Option<String>,
+    ) -> Result<Offer> {
+        let mut builder = OfferBuilder::new(description, self.public_key);
+        if let Some(amount) = amount {
+            builder = builder.amount_msats(amount);
+        }
+        let offer = builder
+            .build()
+            .map_err(|e| anyhow!("Bolt12 error: {e:?}"))?;
+        Ok(Offer::of_offer(&offer, label, PaymentDirection::Inbound))
+    }
+
+    async fn create_refund(
+        &self,
+        description: String,
+        amount: MillisatAmount,
+        _expiry: Option<Duration>,
+        label: Option<String>,
+    ) -> Result<Offer> {
+        let refund = RefundBuilder::new(description, vec![1; 32], self.public_key, amount)
+            .and_then(|builder| builder.build())
+            .map_err(|e| anyhow!("Bolt12 error: {e:?}"))?;
+        Ok(Offer::of_refund(&refund, label, PaymentDirection::Outbound))
+    }
+
+    async fn list_offers(&self, label: Option<String>) -> Result<Vec<Offer>> {
+        let offer = self
+            .create_offer("test offer".to_string(), Some(1000), None, None, label)
+            .await?;
+        Ok(vec![offer])
+    }
+
+    async fn pay_offer(
+        &self,
+        offer: Bolt12Offer,
+        amount: Option<MillisatAmount>,
+        _quantity: Option<u64>,
+        _payer_note: Option<String>,
+        label: Option<String>,
+    ) -> Result<Payment> {
+        let stored_offer = Offer::of_offer(&offer, None, PaymentDirection::Outbound);
+        let amount = amount
+            .or(stored_offer.amount)
+            .context("amount is required for an offer without an amount")?;
+        let mut payment = Payment::of_offer_outbound(Payment::new_id(), amount, label);
+        payment.succeeded(PaymentHash([3u8; 32]), PaymentPreimage([1u8; 32]), Some(12));
+        Ok(payment)
+    }
+
+    async fn request_refund_payment(&self, _refund: Refund) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_invoice_requests(
+        &self,
+        offer_id: Option<sha256::Hash>,
+    ) -> Result<Vec<InvoiceRequest>> {
+        Ok(vec![InvoiceRequest::new(
+            Payment::new_id(),
+            offer_id.unwrap_or(sha256::Hash::hash(&[2u8; 32])),
+            Some(1000),
+            None,
+            Some("thanks".to_string()),
+        )])
+    }
+
     async fn estimated_channel_liquidity_range(
         &self,
         _scid: u64,
@@ -364,14 +562,135 @@ impl LightningInterface for MockLightning {
         })
     }
 
-    async fn fetch_forwards(&self, _status: Option<ForwardStatus>) -> Result<Vec<Forward>> {
+    async fn fetch_forwards(&self, _filter: ForwardFilter) -> Result<Vec<Forward>> {
         Ok(vec![self.forward.clone()])
     }
 
-    async fn channel_history(&self) -> Result<Vec<Channel>> {
+    async fn forward_stats(&self, _window: Page) -> Result<ForwardStats> {
+        let outbound_channel_id = self
+            .forward
+            .outbound_channel_id
+            .context("expected outbound channel")?;
+        let amount = self.forward.amount.context("expected amount")?;
+        let fee = self.forward.fee.context("expected fee")?;
+        let totals = [
+            ChannelForwardTotals {
+                channel_id: self.forward.inbound_channel_id,
+                succeeded_in: 1,
+                succeeded_out: 0,
+                volume_in: amount + fee,
+                volume_out: 0,
+                fees: 0,
+            },
+            ChannelForwardTotals {
+                channel_id: outbound_channel_id,
+                succeeded_in: 0,
+                succeeded_out: 1,
+                volume_in: 0,
+                volume_out: amount,
+                fees: fee,
+            },
+        ];
+        let counterparties =
+            HashMap::from([(outbound_channel_id, NodeId::from_pubkey(&self.public_key))]);
+        Ok(ForwardStats::new(&totals, &[], &counterparties))
+    }
+
+    async fn channel_history(&self, _filter: ChannelFilter) -> Result<Vec<Channel>> {
         let mut channel: Channel = self.channel.clone().try_into()?;
         channel.close_timestamp = Some(microsecond_timestamp());
         channel.closure_reason = Some(ClosureReason::CooperativeClosure);
         Ok(vec![channel])
     }
+
+    async fn channel_backup(&self) -> Result<StaticChannelBackup> {
+        Ok(StaticChannelBackup {
+            node_id: self.public_key.to_string(),
+            timestamp: 1_700_000_000,
+            channels: vec![BackupChannel {
+                channel_id: self.channel.channel_id.to_hex(),
+                counterparty: self.channel.counterparty.node_id.to_string(),
+                address: Some("127.0.0.1:9735".to_string()),
+                funding_txo: self
+                    .channel
+                    .funding_txo
+                    .map(|txo| format!("{}:{}", txo.txid, txo.index)),
+                value_sats: self.channel.channel_value_satoshis,
+                channel_keys_id: Some("01".repeat(32)),
+            }],
+        })
+    }
+
+    fn encrypt_channel_backup(&self, backup: &StaticChannelBackup) -> Result<Vec<u8>> {
+        backup.encrypt(&Cipher::new(&[0; 32]))
+    }
+
+    fn inbound_channel_policy(&self) -> Result<InboundChannelPolicy> {
+        Ok(InboundChannelPolicy {
+            min_channel_sats: Some(20000),
+            reject_reason: "Channel rejected by node policy".to_string(),
+            ..Default::default()
+        })
+    }
+
+    async fn set_inbound_channel_policy(&self, _policy: InboundChannelPolicy) -> Result<()> {
+        Ok(())
+    }
+
+    fn jit_channel_offer(&self) -> Option<JitChannelOffer> {
+        Some(JitChannelOffer {
+            min_fee_msat: 2000000,
+            proportional: 1000,
+            valid_until: "2035-01-01T00:00:00.000Z".to_string(),
+            min_lifetime: 4032,
+            max_client_to_self_delay: 2016,
+            min_payment_size_msat: 10000000,
+            max_payment_size_msat: 1000000000,
+        })
+    }
+
+    fn list_jit_channels(&self) -> Result<Vec<JitChannel>> {
+        Ok(vec![JitChannel {
+            user_channel_id: 3,
+            counterparty_node_id: self.public_key,
+            intercept_scid: 0x0100000100010001,
+            payment_size_msat: Some(50000000),
+            opening_fee_msat: Some(2000000),
+            channel_id: None,
+            state: JitChannelState::AwaitingPayment,
+            created_at: microsecond_timestamp(),
+        }])
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<NodeEvent> {
+        self.events.subscribe()
+    }
+
+    async fn create_webhook(&self, _webhook: Webhook) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        Ok(vec![self.webhook.clone()])
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> Result<bool> {
+        Ok(id == self.webhook.id)
+    }
+
+    async fn create_macaroon_root_key(&self, root_key: MacaroonRootKey) -> Result<()> {
+        self.macaroon_root_keys.lock().unwrap().push(root_key);
+        Ok(())
+    }
+
+    async fn list_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>> {
+        Ok(self.macaroon_root_keys.lock().unwrap().clone())
+    }
+
+    async fn delete_macaroon_root_key(&self, id: Uuid) -> Result<bool> {
+        let mut root_keys = self.macaroon_root_keys.lock().unwrap();
+        let len = root_keys.len();
+        root_keys.retain(|root_key| root_key.id != id);
+        Ok(root_keys.len() < len)
+    }
 }
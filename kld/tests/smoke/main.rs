@@ -1,3 +1,4 @@
+mod recovery;
 mod start;
 
 pub const START_N_BLOCKS: u64 = 10;
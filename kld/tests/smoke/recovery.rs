@@ -0,0 +1,162 @@
+use std::str::FromStr;
+
+use crate::START_N_BLOCKS;
+use anyhow::{Context, Result};
+use api::{routes, ChannelBackup, FundChannel, FundChannelResponse, GetInfo, WalletBalance};
+use bitcoin::Address;
+use hyper::Method;
+use kld::api::codegen::{
+    get_v1_channel_history_response::GetV1ChannelHistoryResponseItem,
+    get_v1_channel_list_peer_channels_response::{
+        GetV1ChannelListPeerChannelsResponseItem, GetV1ChannelListPeerChannelsResponseItemState,
+    },
+    get_v1_newaddr_response::GetV1NewaddrResponse,
+};
+use test_utils::{
+    poll, test_settings, BitcoinManager, CockroachManager, ElectrsManager, KldManager, TempDir,
+    TEST_ADDRESS,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+pub async fn test_recovery() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+
+    let mut settings_0 = test_settings(&tmp_dir, "recovery");
+    let cockroach = CockroachManager::builder(&tmp_dir, &mut settings_0)
+        .await?
+        .build()
+        .await?;
+    let bitcoin = BitcoinManager::new(&tmp_dir, &mut settings_0).await?;
+    bitcoin
+        .generate_blocks(START_N_BLOCKS, &Address::from_str(TEST_ADDRESS)?, false)
+        .await?;
+
+    settings_0.node_id = "recovery0".to_owned();
+    settings_0.database_name = "recovery0".to_owned();
+    let electrs_0 = ElectrsManager::new(&tmp_dir, &bitcoin, &mut settings_0).await?;
+    let kld_0 = KldManager::new(
+        &tmp_dir,
+        env!("CARGO_BIN_EXE_kld"),
+        &cockroach,
+        &electrs_0,
+        &mut settings_0,
+    )
+    .await?;
+
+    let mut settings_1 = settings_0.clone();
+    settings_1.node_id = "recovery1".to_owned();
+    settings_1.database_name = "recovery1".to_owned();
+    let electrs_1 = ElectrsManager::new(&tmp_dir, &bitcoin, &mut settings_1).await?;
+    let kld_1 = KldManager::new(
+        &tmp_dir,
+        env!("CARGO_BIN_EXE_kld"),
+        &cockroach,
+        &electrs_1,
+        &mut settings_1,
+    )
+    .await?;
+
+    let address: GetV1NewaddrResponse = kld_0
+        .call_rest_api(Method::GET, routes::NEW_ADDR, ())
+        .await?;
+    bitcoin
+        .generate_blocks(1, &bitcoin::Address::from_str(&address.address)?, false)
+        .await?;
+    bitcoin
+        .generate_blocks(
+            100, // Coinbase not spendable for 100 blocks.
+            &Address::from_str(TEST_ADDRESS)?,
+            false,
+        )
+        .await?;
+    poll!(
+        7,
+        kld_0
+            .call_rest_api::<WalletBalance, ()>(Method::GET, routes::GET_BALANCE, ())
+            .await?
+            .conf_balance
+            > 0
+    );
+
+    let info_1: GetInfo = kld_1
+        .call_rest_api(Method::GET, routes::GET_INFO, ())
+        .await?;
+    poll!(
+        7,
+        kld_0
+            .call_rest_api::<FundChannelResponse, FundChannel>(
+                Method::POST,
+                routes::OPEN_CHANNEL,
+                FundChannel {
+                    id: format!("{}@127.0.0.1:{}", info_1.id, settings_1.peer_port),
+                    satoshis: "1000000".to_string(),
+                    push_msat: Some("1000000".to_string()),
+                    ..Default::default()
+                }
+            )
+            .await
+            .is_ok()
+    );
+    bitcoin
+        .generate_blocks(10, &bitcoin::Address::from_str(TEST_ADDRESS)?, true)
+        .await?;
+    poll!(
+        7,
+        matches!(
+            kld_1
+                .call_rest_api::<Vec<GetV1ChannelListPeerChannelsResponseItem>, ()>(
+                    Method::GET,
+                    routes::LIST_PEER_CHANNELS,
+                    ()
+                )
+                .await?
+                .get(0)
+                .map(|c| &c.state),
+            Some(&GetV1ChannelListPeerChannelsResponseItemState::ChanneldNormal)
+        )
+    );
+
+    let backup: ChannelBackup = kld_0
+        .call_rest_api(Method::GET, routes::CHANNEL_BACKUP, ())
+        .await?;
+    assert_eq!(1, backup.channels);
+    let backup_path = tmp_dir.path().join("channel.backup");
+    std::fs::write(&backup_path, backup.backup)?;
+
+    // Lose the database of kld_0 and start it again from the same mnemonic and the backup.
+    drop(kld_0);
+    settings_0.database_name = "recovery0_restored".to_owned();
+    settings_0.recovery_channel_backup_path = Some(
+        backup_path
+            .to_str()
+            .context("expected UTF-8 backup path")?
+            .to_string(),
+    );
+    let _kld_0 = KldManager::new(
+        &tmp_dir,
+        env!("CARGO_BIN_EXE_kld"),
+        &cockroach,
+        &electrs_0,
+        &mut settings_0,
+    )
+    .await?;
+
+    // kld_1 force closes the channel when kld_0 reconnects.
+    poll!(
+        7,
+        kld_1
+            .call_rest_api::<Vec<GetV1ChannelListPeerChannelsResponseItem>, ()>(
+                Method::GET,
+                routes::LIST_PEER_CHANNELS,
+                ()
+            )
+            .await?
+            .is_empty()
+    );
+    let closed: Vec<GetV1ChannelHistoryResponseItem> = kld_1
+        .call_rest_api(Method::GET, routes::LIST_CHANNEL_HISTORY, ())
+        .await?;
+    let channel = closed.first().context("expected closed channel")?;
+    assert!(channel.close_timestamp > 0);
+    Ok(())
+}
@@ -3,15 +3,16 @@ use std::{str::FromStr, time::Duration};
 use crate::START_N_BLOCKS;
 use anyhow::{Context, Result};
 use api::{
-    routes, FundChannel, FundChannelResponse, GenerateInvoice, GenerateInvoiceResponse, GetInfo,
-    Invoice, KeysendRequest, PayInvoice, PaymentResponse, WalletBalance,
+    routes, ChannelAcceptancePolicy, FundChannel, FundChannelResponse, GenerateInvoice,
+    GenerateInvoiceResponse, GetInfo, Invoice, KeysendRequest, PayInvoice, PaymentResponse,
+    WalletBalance,
 };
 use bitcoin::Address;
 use hyper::Method;
 use kld::{
     api::codegen::{
         get_v1_channel_list_peer_channels_response::{
-            GetV1ChannelListPeerChannelsResponse, GetV1ChannelListPeerChannelsResponseState,
+            GetV1ChannelListPeerChannelsResponseItem, GetV1ChannelListPeerChannelsResponseItemState,
         },
         get_v1_newaddr_response::GetV1NewaddrResponse,
     },
@@ -110,19 +111,57 @@ pub async fn test_start() -> Result<()> {
         .call_rest_api(Method::GET, routes::GET_INFO, ())
         .await?;
 
+    let fund_channel = || FundChannel {
+        id: format!("{}@127.0.0.1:{}", info_1.id, settings_1.peer_port),
+        satoshis: channel_amount.to_string(),
+        push_msat: Some(push_amount_msat.to_string()),
+        fee_rate: Some(api::FeeRate::PerKb(fee_rate_kb as u32)),
+        ..Default::default()
+    };
+
+    // kld_1 rejects the channel with the reason from its policy.
+    let _: ChannelAcceptancePolicy = kld_1
+        .call_rest_api(
+            Method::POST,
+            routes::CHANNEL_ACCEPTANCE_POLICY,
+            ChannelAcceptancePolicy {
+                min_channel_sats: Some(channel_amount + 1),
+                reject_reason: "Channel too small".to_string(),
+                ..Default::default()
+            },
+        )
+        .await?;
+    poll!(
+        7,
+        matches!(
+            kld_0
+                .call_rest_api::<FundChannelResponse, FundChannel>(
+                    Method::POST,
+                    routes::OPEN_CHANNEL,
+                    fund_channel()
+                )
+                .await,
+            Err(e) if e.to_string().contains("Channel too small")
+        )
+    );
+    let _: ChannelAcceptancePolicy = kld_1
+        .call_rest_api(
+            Method::POST,
+            routes::CHANNEL_ACCEPTANCE_POLICY,
+            ChannelAcceptancePolicy {
+                reject_reason: "Channel rejected by node policy".to_string(),
+                ..Default::default()
+            },
+        )
+        .await?;
+
     poll!(
         7,
         kld_0
             .call_rest_api::<FundChannelResponse, FundChannel>(
                 Method::POST,
                 routes::OPEN_CHANNEL,
-                FundChannel {
-                    id: format!("{}@127.0.0.1:{}", info_1.id, settings_1.peer_port),
-                    satoshis: channel_amount.to_string(),
-                    push_msat: Some(push_amount_msat.to_string()),
-                    fee_rate: Some(api::FeeRate::PerKb(fee_rate_kb as u32)),
-                    ..Default::default()
-                }
+                fund_channel()
             )
             .await
             .is_ok()
@@ -144,7 +183,7 @@ pub async fn test_start() -> Result<()> {
         7,
         matches!(
             kld_1
-                .call_rest_api::<Vec<GetV1ChannelListPeerChannelsResponse>, ()>(
+                .call_rest_api::<Vec<GetV1ChannelListPeerChannelsResponseItem>, ()>(
                     Method::GET,
                     routes::LIST_PEER_CHANNELS,
                     ()
@@ -152,11 +191,11 @@ pub async fn test_start() -> Result<()> {
                 .await?
                 .get(0)
                 .map(|c| &c.state),
-            Some(&GetV1ChannelListPeerChannelsResponseState::ChanneldNormal)
+            Some(&GetV1ChannelListPeerChannelsResponseItemState::ChanneldNormal)
         )
     );
     let channels = kld_1
-        .call_rest_api::<Vec<GetV1ChannelListPeerChannelsResponse>, ()>(
+        .call_rest_api::<Vec<GetV1ChannelListPeerChannelsResponseItem>, ()>(
             Method::GET,
             routes::LIST_PEER_CHANNELS,
             (),
@@ -189,6 +228,7 @@ pub async fn test_start() -> Result<()> {
     let pay_invoice = PayInvoice {
         label: Some("payment".to_string()),
         invoice: invoice.bolt11,
+        ..Default::default()
     };
     let payment: PaymentResponse = kld_0
         .call_rest_api(Method::POST, routes::PAY_INVOICE, pay_invoice)
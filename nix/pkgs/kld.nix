@@ -14,6 +14,7 @@ let
     "Cargo.lock"
     "api"
     "kld"
+    "kld-client"
     "benches"
     "settings"
     "test-utils"
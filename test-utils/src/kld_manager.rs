@@ -2,12 +2,13 @@ use crate::cockroach_manager::{create_database, CockroachManager};
 use crate::electrs_manager::ElectrsManager;
 use crate::https_client;
 use crate::ports::get_available_port;
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{bail, Context, Result};
 use kld::settings::Settings;
+use kld_client::KldClient;
 use reqwest::Method;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::env::set_var;
+use std::env::{remove_var, set_var};
 use std::fs;
 use std::marker::PhantomData;
 use std::process::{Child, Command, Stdio};
@@ -17,8 +18,7 @@ use tokio::time::{sleep_until, Duration, Instant};
 pub struct KldManager<'a> {
     process: Child,
     exporter_port: u16,
-    rest_port: u16,
-    rest_client: reqwest::Client,
+    rest_client: KldClient,
     _electrs: PhantomData<&'a ElectrsManager<'a, 'a>>,
 }
 
@@ -35,8 +35,9 @@ impl<'a> KldManager<'a> {
         settings.rest_api_address = format!("127.0.0.1:{rest_port}");
         settings.exporter_address = format!("127.0.0.1:{exporter_port}");
         settings.peer_port = get_available_port()?;
+        // A restarted node keeps its data directory, and with it the mnemonic.
         let storage_dir = output_dir.path().join(format!("kld_{}", settings.node_id));
-        std::fs::create_dir(&storage_dir)?;
+        std::fs::create_dir_all(&storage_dir)?;
 
         let certs_dir = format!("{}/certs", env!("CARGO_MANIFEST_DIR"));
 
@@ -68,6 +69,7 @@ impl<'a> KldManager<'a> {
         );
         set_var("KLD_DATABASE_PORT", cockroach.sql_port.to_string());
         set_var("KLD_DATABASE_NAME", settings.database_name.clone());
+        set_var("KLD_DATABASE_SSL_MODE", settings.database_ssl_mode.clone());
         set_var("KLD_NODE_ID", settings.node_id.clone());
         set_var(
             "KLD_DATABASE_CA_CERT_PATH",
@@ -84,6 +86,10 @@ impl<'a> KldManager<'a> {
         set_var("KLD_LOG_LEVEL", "debug");
         set_var("KLD_NODE_ALIAS", "kld-00-alias");
         set_var("KLD_ELECTRS_URL", settings.electrs_url.clone());
+        match &settings.recovery_channel_backup_path {
+            Some(path) => set_var("KLD_RECOVERY_CHANNEL_BACKUP_PATH", path),
+            None => remove_var("KLD_RECOVERY_CHANNEL_BACKUP_PATH"),
+        }
 
         let mut process = if std::env::var("KEEP_TEST_ARTIFACTS_IN").is_ok() {
             Command::new(kld_bin)
@@ -119,10 +125,12 @@ impl<'a> KldManager<'a> {
 
         Ok(KldManager {
             process,
-            rest_client: https_client(Some(fs::read(macaroon_path)?))?,
+            rest_client: KldClient::with_client(
+                format!("https://127.0.0.1:{rest_port}"),
+                https_client(Some(fs::read(macaroon_path)?))?,
+            ),
             _electrs: PhantomData,
             exporter_port,
-            rest_port,
         })
     }
 
@@ -146,27 +154,13 @@ impl<'a> KldManager<'a> {
         route: &str,
         body: B,
     ) -> Result<T> {
-        let res = self
-            .rest_client
-            .request(
-                method,
-                format!("https://127.0.0.1:{}{}", self.rest_port, route),
-            )
-            .body(serde_json::to_string(&body).unwrap())
-            .send()
-            .await?;
-        let status = res.status();
-        let text = res.text().await?;
-        match serde_json::from_str::<T>(&text) {
-            Ok(t) => {
-                println!("API result: {text}");
-                Ok(t)
-            }
-            Err(e) => {
-                println!("Error from API: {status} {text}");
-                Err(anyhow!(e))
-            }
-        }
+        self.rest_client
+            .call(method, route, &body)
+            .await
+            .map_err(|e| {
+                println!("Error from API: {e}");
+                e.into()
+            })
     }
 }
 